@@ -0,0 +1,115 @@
+# llm-tui shell hook: records the last failed command (and, optionally, its
+# stderr) so that `/explain-last` in llm-tui can send them to a model.
+#
+# Enable it from ~/.bashrc or ~/.zshrc with:
+#   eval "$(llm-tui --shell-hook)"
+#
+# Set LLM_TUI_CAPTURE_STDERR=1 before that line to also record stderr. While a
+# command runs its stderr then goes through a pipe, so programs that check for a
+# terminal there (colors, progress bars, password prompts) behave as if piped.
+
+__llm_tui_dir="${XDG_DATA_HOME:-$HOME/.local/share}/llm-tui"
+# One log per shell, so shells running side by side don't clobber each other's.
+__llm_tui_stderr="$__llm_tui_dir/stderr.$$.log"
+mkdir -p "$__llm_tui_dir"
+
+# Drops the logs of shells that have exited.
+__llm_tui_clean_logs() {
+    [ -n "$ZSH_VERSION" ] && setopt localoptions nullglob
+    local log pid
+    for log in "$__llm_tui_dir"/stderr.*.log; do
+        pid="${log##*/stderr.}"
+        pid="${pid%.log}"
+        if [ -f "$log" ] && ! kill -0 "$pid" 2>/dev/null; then
+            rm -f "$log"
+        fi
+    done
+}
+__llm_tui_clean_logs
+
+if [ "${LLM_TUI_CAPTURE_STDERR:-0}" = 1 ] && [ -z "$__llm_tui_installed" ]; then
+    # Commands' stderr is mirrored into the log through fd 9. Only they write to
+    # it, so the line editor's output isn't recorded.
+    : >> "$__llm_tui_stderr"
+    exec 9> >(tee -a "$__llm_tui_stderr" >&2)
+fi
+
+__llm_tui_preexec() {
+    __llm_tui_command="$1"
+    if [ "${LLM_TUI_CAPTURE_STDERR:-0}" = 1 ]; then
+        __llm_tui_offset=$(wc -c < "$__llm_tui_stderr")
+        exec 8>&2 2>&9
+        __llm_tui_capturing=1
+    fi
+}
+
+__llm_tui_precmd() {
+    local exit_status=$?
+    if [ -n "$__llm_tui_capturing" ]; then
+        exec 2>&8 8>&-
+        __llm_tui_capturing=
+        # Give tee a moment to flush the command's last lines.
+        sleep 0.05
+    fi
+    if [ "$exit_status" -ne 0 ] && [ -n "$__llm_tui_command" ]; then
+        {
+            printf 'command: %s\n' "$__llm_tui_command"
+            printf 'exit_status: %s\n' "$exit_status"
+            printf 'cwd: %s\n' "$PWD"
+            if [ -n "$__llm_tui_offset" ]; then
+                printf -- '--- stderr ---\n'
+                tail -c +"$((__llm_tui_offset + 1))" "$__llm_tui_stderr" | tail -n 200
+            fi
+        } > "$__llm_tui_dir/last_failure.txt"
+    fi
+    __llm_tui_command=
+    __llm_tui_offset=
+    if [ -f "$__llm_tui_stderr" ] && [ "$(wc -c < "$__llm_tui_stderr")" -gt 1000000 ]; then
+        : > "$__llm_tui_stderr"
+    fi
+}
+
+if [ -n "$ZSH_VERSION" ]; then
+    autoload -Uz add-zsh-hook
+    add-zsh-hook preexec __llm_tui_preexec
+    add-zsh-hook precmd __llm_tui_precmd
+    __llm_tui_installed=1
+elif [ -n "$BASH_VERSION" ] && [ -z "$__llm_tui_installed" ]; then
+    __llm_tui_installed=1
+    # Keep a DEBUG trap that was already set and run it first. bash hides it from
+    # sourced files, so this needs the hook loaded with eval as above.
+    __llm_tui_previous_debug=
+    __llm_tui_trap=$(trap -p DEBUG)
+    if [ -n "$__llm_tui_trap" ]; then
+        trap() { __llm_tui_previous_debug=$2; }
+        eval "$__llm_tui_trap"
+        unset -f trap
+    fi
+    unset __llm_tui_trap
+
+    __llm_tui_debug() {
+        if [ -n "$__llm_tui_previous_debug" ]; then
+            eval "$__llm_tui_previous_debug"
+        fi
+        case "$BASH_COMMAND" in
+            __llm_tui_*) return ;;
+        esac
+        # Only the first command after the prompt is the one typed; the rest are
+        # its later parts or PROMPT_COMMAND's. Pipelines run the trap in their
+        # subshells too, which mustn't redirect anything.
+        if [ -n "$__llm_tui_armed" ] && [ "$BASHPID" = "$$" ]; then
+            __llm_tui_armed=
+            __llm_tui_preexec "$BASH_COMMAND"
+        fi
+    }
+
+    __llm_tui_arm() {
+        __llm_tui_armed=1
+    }
+
+    trap '__llm_tui_debug' DEBUG
+    # precmd comes first to see the command's exit status, and arming comes last
+    # so the rest of PROMPT_COMMAND isn't taken for a typed command.
+    PROMPT_COMMAND="__llm_tui_precmd${PROMPT_COMMAND:+; $PROMPT_COMMAND}; __llm_tui_arm"
+    __llm_tui_armed=1
+fi
@@ -0,0 +1,35 @@
+// Anki reads these header lines when importing a text file (Anki 2.1.54+).
+const HEADER: &str = "#separator:tab\n#html:true\n#columns:Front\tBack\n";
+
+pub const CONDENSE_PROMPT: &str = "Rewrite this answer as the back of a flashcard: \
+    keep only the essential facts in at most three short sentences or a small code \
+    snippet. Reply with only the flashcard text.";
+
+// A tab-separated file of (question, answer) notes, importable with File > Import.
+pub fn to_tsv(cards: &[(String, String)]) -> String {
+    let mut tsv = HEADER.to_string();
+    for (question, answer) in cards {
+        tsv.push_str(&field(question));
+        tsv.push('\t');
+        tsv.push_str(&field(answer));
+        tsv.push('\n');
+    }
+    tsv
+}
+
+pub fn condense_prompt(instructions: &str, question: &str, answer: &str) -> String {
+    format!(
+        "{}\n\nQuestion:\n{}\n\nAnswer:\n{}",
+        instructions, question, answer
+    )
+}
+
+// Escapes HTML and keeps line breaks, since fields can't contain tabs or newlines.
+fn field(text: &str) -> String {
+    text.trim()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\t', "    ")
+        .replace('\n', "<br>")
+}
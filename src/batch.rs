@@ -0,0 +1,191 @@
+use crate::config::{self, Config};
+use crate::{find_logged, llm_command, run_llm_checked, save_link, LlmRequest};
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "Usage: llm-tui batch --template FILE --input FILE... [--model MODEL] \
+                     [--output DIR] [--rate N]";
+const GAUGE_WIDTH: usize = 30;
+
+// `llm-tui batch`: sends a templated prompt once per input file, one at a time. The
+// results go to `--output` as `<input>.md` files, or otherwise into one new
+// conversation in the conversation list. Each input is sent on its own, so llm
+// doesn't replay the earlier ones; their logged conversations are linked to the
+// first one's instead.
+pub fn run(args: Vec<String>) -> io::Result<()> {
+    let mut template = None;
+    let mut inputs = Vec::new();
+    let mut model = None;
+    let mut output = None;
+    let mut rate = None;
+
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--template" | "-t" => template = args.next(),
+            "--model" | "-m" => model = args.next(),
+            "--output" | "-o" => output = args.next().map(|dir| config::expand_home(&dir)),
+            "--rate" => rate = args.next(),
+            // The shell expands `--input files/*.py` into many arguments.
+            "--input" | "-i" => {
+                while let Some(input) = args.next_if(|arg| !arg.starts_with('-')) {
+                    inputs.push(PathBuf::from(input));
+                }
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => return Err(invalid(&format!("Unknown argument '{}'\n{}", arg, USAGE))),
+        }
+    }
+
+    let template = template.ok_or_else(|| invalid(USAGE))?;
+    let template = fs::read_to_string(config::expand_home(&template))?;
+    if inputs.is_empty() {
+        return Err(invalid(USAGE));
+    }
+    // Requests per minute.
+    let interval = match rate {
+        Some(rate) => match rate.parse::<f64>() {
+            Ok(rate) if rate > 0.0 => Some(Duration::from_secs_f64(60.0 / rate)),
+            _ => return Err(invalid(&format!("Not a rate: {}", rate))),
+        },
+        None => None,
+    };
+    let config = Config::load();
+    let model = match model.or(config.model.clone()) {
+        Some(model) => model,
+        None => default_model()?,
+    };
+    if let Some(dir) = &output {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut conversation_id: Option<String> = None;
+    let mut failures = Vec::new();
+    let mut last_request: Option<Instant> = None;
+    for (done, input) in inputs.iter().enumerate() {
+        show_progress(done, inputs.len(), input);
+        if let (Some(interval), Some(last)) = (interval, last_request) {
+            thread::sleep(interval.saturating_sub(last.elapsed()));
+        }
+        last_request = Some(Instant::now());
+
+        let result = fs::read_to_string(input)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| {
+                let request = LlmRequest {
+                    // Results kept in files would only clutter the conversation list.
+                    no_log: output.is_some(),
+                    ..LlmRequest::new(
+                        config.redact(&build_prompt(&template, input, &contents)),
+                        model.clone(),
+                    )
+                };
+                run_llm_checked(&request)
+            });
+        let result = result.and_then(|response| match &output {
+            Some(dir) => fs::write(dir.join(output_name(input)), format!("{}\n", response))
+                .map_err(|e| e.to_string()),
+            None => match (find_logged(&response), &conversation_id) {
+                (Some((logged_id, _)), Some(first)) => {
+                    save_link(&logged_id, first).map_err(|e| e.to_string())
+                }
+                (Some((logged_id, _)), None) => {
+                    conversation_id = Some(logged_id);
+                    Ok(())
+                }
+                (None, _) => Err("the response wasn't found in llm's log".to_string()),
+            },
+        });
+        if let Err(e) = result {
+            failures.push(format!("{}: {}", input.display(), e));
+        }
+    }
+    show_progress(inputs.len(), inputs.len(), Path::new(""));
+
+    for failure in &failures {
+        eprintln!("Failed: {}", failure);
+    }
+    let succeeded = inputs.len() - failures.len();
+    match &output {
+        Some(dir) => eprintln!("Wrote {} result(s) to {}", succeeded, dir.display()),
+        None => eprintln!("Added {} exchange(s) to a new conversation", succeeded),
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{} of {} input(s) failed",
+            failures.len(),
+            inputs.len()
+        )))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+// Fills in `{input}` (the file's contents) and `{path}`; the contents are appended
+// if the template doesn't mention them.
+fn build_prompt(template: &str, path: &Path, contents: &str) -> String {
+    let prompt = template.replace("{path}", &path.display().to_string());
+    if prompt.contains("{input}") {
+        prompt.replace("{input}", contents)
+    } else {
+        format!("{}\n\n{}", prompt.trim_end(), contents)
+    }
+}
+
+// `src/app.py` becomes `src_app.py.md`, so inputs with the same name don't clash.
+fn output_name(input: &Path) -> String {
+    let name: String = input
+        .to_string_lossy()
+        .trim_start_matches("./")
+        .chars()
+        .map(|c| {
+            if matches!(c, '/' | '\\' | ':') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!("{}.md", name)
+}
+
+// A gauge on stderr, redrawn in place when it's a terminal.
+fn show_progress(done: usize, total: usize, current: &Path) {
+    let filled = GAUGE_WIDTH * done / total.max(1);
+    let line = format!(
+        "[{}{}] {}/{} {}",
+        "#".repeat(filled),
+        "-".repeat(GAUGE_WIDTH - filled),
+        done,
+        total,
+        current.display()
+    );
+    let mut stderr = io::stderr();
+    if stderr.is_terminal() {
+        let end = if done == total { "\n" } else { "" };
+        let _ = write!(stderr, "\r\x1b[2K{}{}", line, end);
+        let _ = stderr.flush();
+    } else if done < total {
+        let _ = writeln!(stderr, "{}", line);
+    }
+}
+
+fn default_model() -> io::Result<String> {
+    let output = llm_command().args(["models", "default"]).output()?;
+    let model = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if model.is_empty() {
+        Err(invalid("No model: pass --model or set model in the config"))
+    } else {
+        Ok(model)
+    }
+}
@@ -0,0 +1,180 @@
+use crate::config;
+use crate::store;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+const USAGE: &str = "Usage: llm-tui config export [PATH] | llm-tui config import PATH";
+const FORMAT_KEY: &str = "llm_tui_bundle";
+const FORMAT_VERSION: u64 = 1;
+// Crash recovery for this machine only.
+const SKIPPED_FILES: &[&str] = &["draft.json"];
+// The local prompt library's subdirectories (see `library::Library`).
+const LIBRARY_DIRS: [&str; 2] = ["prompts", "personas"];
+
+// `llm-tui config export|import`: moves the config file, the local state kept next
+// to llm's logs (pins, tags, reactions, ...) and the local prompt library between
+// machines as one JSON file.
+pub fn run(args: Vec<String>) -> io::Result<()> {
+    match args.first().map(String::as_str) {
+        Some("export") => export(args.get(1).map(Path::new)),
+        Some("import") => match args.get(1) {
+            Some(path) => import(Path::new(path)),
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, USAGE)),
+        },
+        Some("--help" | "-h") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, USAGE)),
+    }
+}
+
+// Writes the bundle to `path`, or to stdout without one.
+fn export(path: Option<&Path>) -> io::Result<()> {
+    let config = config::config_path().and_then(|path| fs::read_to_string(path).ok());
+    let mut state = Map::new();
+    if let Some(entries) = store::data_dir().and_then(|dir| fs::read_dir(dir).ok()) {
+        for entry in entries.filter_map(Result::ok) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.ends_with(".json") || SKIPPED_FILES.contains(&name.as_str()) {
+                continue;
+            }
+            let value = store::load(&name);
+            if !value.is_null() {
+                state.insert(name, value);
+            }
+        }
+    }
+    let count = state.len();
+    let library = export_library();
+    let bundle = json!({
+        FORMAT_KEY: FORMAT_VERSION,
+        "config": config,
+        "state": state,
+        "library": library,
+    });
+    let contents = serde_json::to_string_pretty(&bundle).map_err(io::Error::other)?;
+    match path {
+        Some(path) => {
+            fs::write(path, contents)?;
+            eprintln!(
+                "Exported the config, {} state file(s) and {} library file(s) to {}",
+                count,
+                library.len(),
+                path.display()
+            );
+        }
+        None => writeln!(io::stdout(), "{}", contents)?,
+    }
+    Ok(())
+}
+
+// The local library's prompts and personas, keyed by their path under it
+// (`prompts/review.md`). The shared repository's are synced separately.
+fn export_library() -> Map<String, Value> {
+    let mut files = Map::new();
+    let Some(dir) = config::Config::load().library_dir else {
+        return files;
+    };
+    for subdir in LIBRARY_DIRS {
+        let Ok(entries) = fs::read_dir(dir.join(subdir)) else {
+            continue;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') || !entry.path().is_file() {
+                continue;
+            }
+            if let Ok(text) = fs::read_to_string(entry.path()) {
+                files.insert(format!("{}/{}", subdir, name), Value::String(text));
+            }
+        }
+    }
+    files
+}
+
+// Reads a bundle (`-` for stdin). The config file is replaced, keeping the old one
+// as `config.toml.bak`; state maps are merged so local entries the bundle doesn't
+// mention survive, with the bundle winning on conflicts. Library files are written
+// into the library directory of the imported config, replacing ones of the same name.
+fn import(path: &Path) -> io::Result<()> {
+    let contents = if path == Path::new("-") {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents)?;
+        contents
+    } else {
+        fs::read_to_string(path)?
+    };
+    let bundle: Value = serde_json::from_str(&contents)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    match bundle[FORMAT_KEY].as_u64() {
+        Some(version) if version <= FORMAT_VERSION => {}
+        Some(version) => {
+            return Err(io::Error::other(format!(
+                "Bundle version {} is newer than this llm-tui supports",
+                version
+            )))
+        }
+        None => return Err(io::Error::other("Not an llm-tui config bundle")),
+    }
+
+    if let Some(config) = bundle["config"].as_str() {
+        let path = config::config_path().ok_or_else(|| io::Error::other("No home directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        if path.exists() {
+            fs::copy(&path, path.with_extension("toml.bak"))?;
+        }
+        fs::write(&path, config)?;
+        eprintln!("Imported config to {}", path.display());
+    }
+
+    let mut count = 0;
+    for (name, imported) in bundle["state"].as_object().into_iter().flatten() {
+        // Only plain file names, so a bundle can't write outside the data directory.
+        if !name.ends_with(".json") || name.contains(['/', '\\']) || name.starts_with('.') {
+            continue;
+        }
+        let merged = match (store::load(name), imported) {
+            (Value::Object(mut local), Value::Object(imported)) => {
+                local.extend(imported.clone());
+                Value::Object(local)
+            }
+            (_, imported) => imported.clone(),
+        };
+        store::save(name, &merged)?;
+        count += 1;
+    }
+    eprintln!("Imported {} state file(s)", count);
+
+    if let Some(files) = bundle["library"]
+        .as_object()
+        .filter(|files| !files.is_empty())
+    {
+        let dir = config::Config::load()
+            .library_dir
+            .ok_or_else(|| io::Error::other("No library directory to import prompts into"))?;
+        let mut count = 0;
+        for (name, text) in files {
+            // Only files directly under `prompts/` or `personas/`.
+            let (Some((subdir, file)), Some(text)) = (name.split_once('/'), text.as_str()) else {
+                continue;
+            };
+            if !LIBRARY_DIRS.contains(&subdir)
+                || file.is_empty()
+                || file.contains(['/', '\\'])
+                || file.starts_with('.')
+            {
+                continue;
+            }
+            fs::create_dir_all(dir.join(subdir))?;
+            fs::write(dir.join(subdir).join(file), text)?;
+            count += 1;
+        }
+        eprintln!("Imported {} library file(s) to {}", count, dir.display());
+    }
+    Ok(())
+}
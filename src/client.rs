@@ -0,0 +1,171 @@
+use crate::config::Config;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::Command;
+
+const DEFAULT_CAPTURE_LINES: usize = 200;
+const CAPTURE_USAGE: &str =
+    "Usage: llm-tui capture-pane [--lines N] [--target PANE] [--conversation NAME] \
+     [--no-wait] [PROMPT...]";
+const USAGE: &str = "Usage: llm-tui send [--selection] [--file PATH] [--lines A-B] \
+                     [--filetype FT] [--conversation NAME] [--no-wait] [PROMPT...]";
+
+// `llm-tui send`: a small client for the remote protocol, meant for editor
+// keybindings. With `--selection` the code to ask about is read from stdin and
+// annotated with where it came from; the response is printed to stdout.
+pub fn send(args: Vec<String>) -> io::Result<()> {
+    let mut selection = false;
+    let mut wait = true;
+    let mut file = None;
+    let mut lines = None;
+    let mut filetype = None;
+    let mut conversation = None;
+    let mut words = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--selection" => selection = true,
+            "--no-wait" => wait = false,
+            "--file" => file = args.next(),
+            "--lines" => lines = args.next(),
+            "--filetype" => filetype = args.next(),
+            "--conversation" | "-c" => conversation = args.next(),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => words.push(arg),
+        }
+    }
+
+    let mut prompt = words.join(" ");
+    if selection {
+        let mut code = String::new();
+        io::stdin().read_to_string(&mut code)?;
+        prompt = annotate_selection(
+            &prompt,
+            &code,
+            file.as_deref(),
+            lines.as_deref(),
+            filetype.as_deref(),
+        );
+    }
+    if prompt.trim().is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, USAGE));
+    }
+    send_prompt(&prompt, wait, conversation.as_deref())
+}
+
+// `llm-tui capture-pane`: sends the scrollback of the current tmux pane along with
+// a question, for "what went wrong in this terminal?" keybindings.
+pub fn capture_pane(args: Vec<String>) -> io::Result<()> {
+    let mut wait = true;
+    let mut lines = DEFAULT_CAPTURE_LINES;
+    let mut target = None;
+    let mut conversation = None;
+    let mut words = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-wait" => wait = false,
+            "--lines" => {
+                lines = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, CAPTURE_USAGE))?
+            }
+            "--target" | "-t" => target = args.next(),
+            "--conversation" | "-c" => conversation = args.next(),
+            "--help" | "-h" => {
+                println!("{}", CAPTURE_USAGE);
+                return Ok(());
+            }
+            _ => words.push(arg),
+        }
+    }
+
+    let start = format!("-{}", lines);
+    let mut command = Command::new("tmux");
+    command.args(["capture-pane", "-p", "-J", "-S", &start]);
+    if let Some(target) = &target {
+        command.args(["-t", target]);
+    }
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    let scrollback = trim_scrollback(&String::from_utf8_lossy(&output.stdout), lines);
+    let question = if words.is_empty() {
+        "What went wrong in this terminal?".to_string()
+    } else {
+        words.join(" ")
+    };
+    let prompt = format!("{}\n\nTerminal output:\n```\n{}\n```", question, scrollback);
+    send_prompt(&prompt, wait, conversation.as_deref())
+}
+
+// Drops trailing whitespace and the empty lines below the cursor, keeping at most
+// the last `max_lines` lines.
+fn trim_scrollback(text: &str, max_lines: usize) -> String {
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    let end = lines
+        .iter()
+        .rposition(|line| !line.is_empty())
+        .map_or(0, |i| i + 1);
+    let start = end.saturating_sub(max_lines);
+    lines[start..end].join("\n")
+}
+
+fn send_prompt(prompt: &str, wait: bool, conversation: Option<&str>) -> io::Result<()> {
+    let mut request = json!({"prompt": prompt, "wait": wait});
+    if let Some(conversation) = conversation {
+        request["conversation"] = json!(conversation);
+    }
+    let mut stream = TcpStream::connect(Config::load().server_address())?;
+    writeln!(stream, "{}", request)?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    let reply: Value = serde_json::from_str(&reply).map_err(io::Error::other)?;
+    if let Some(error) = reply["error"].as_str() {
+        return Err(io::Error::other(error.to_string()));
+    }
+    if let Some(response) = reply["response"].as_str() {
+        print!("{}", response);
+    }
+    Ok(())
+}
+
+// Builds e.g. "rust code from foo.rs:20-60:" followed by the fenced selection.
+fn annotate_selection(
+    prompt: &str,
+    code: &str,
+    file: Option<&str>,
+    lines: Option<&str>,
+    filetype: Option<&str>,
+) -> String {
+    let mut source = format!("{} code", filetype.unwrap_or("Selected"));
+    if let Some(file) = file {
+        source.push_str(" from ");
+        source.push_str(file);
+        if let Some(lines) = lines {
+            source.push(':');
+            source.push_str(lines);
+        }
+    }
+    format!(
+        "{}\n\n{}:\n```{}\n{}\n```",
+        prompt.trim(),
+        source,
+        filetype.unwrap_or_default(),
+        code.trim_end()
+    )
+    .trim_start()
+    .to_string()
+}
@@ -0,0 +1,256 @@
+use crate::language;
+
+pub enum Segment {
+    Text(String),
+    Code(CodeBlock),
+}
+
+pub struct CodeBlock {
+    pub info: String,
+    pub lines: Vec<String>,
+}
+
+impl CodeBlock {
+    pub fn tagged_language(&self) -> Option<&str> {
+        self.info.split_whitespace().next()
+    }
+
+    // The fence's language tag, or a heuristic guess when the fence is bare.
+    pub fn language(&self) -> Option<&str> {
+        self.tagged_language()
+            .or_else(|| language::detect(&self.lines))
+    }
+}
+
+// Splits message content into prose and ``` fenced code blocks. An unterminated
+// fence (e.g. a truncated response) still counts as a code block up to the end.
+pub fn parse_segments(content: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut code: Option<CodeBlock> = None;
+
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        match code.take() {
+            Some(block) if trimmed.starts_with("```") => {
+                segments.push(Segment::Code(block));
+            }
+            Some(mut block) => {
+                block.lines.push(line.to_string());
+                code = Some(block);
+            }
+            None if trimmed.starts_with("```") => {
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                code = Some(CodeBlock {
+                    info: trimmed.trim_start_matches('`').trim().to_string(),
+                    lines: Vec::new(),
+                });
+            }
+            None => {
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(line);
+            }
+        }
+    }
+
+    if let Some(block) = code {
+        segments.push(Segment::Code(block));
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    segments
+}
+
+pub fn code_blocks(content: &str) -> Vec<CodeBlock> {
+    parse_segments(content)
+        .into_iter()
+        .filter_map(|segment| match segment {
+            Segment::Code(block) => Some(block),
+            Segment::Text(_) => None,
+        })
+        .collect()
+}
+
+// A 1-based, inclusive range of lines within one code block of a message.
+pub struct LineRange {
+    pub block: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+// Parses `[block:]start[-end]`, e.g. `2:10-25`, `10-25` (block 1) or `7`.
+pub fn parse_line_range(spec: &str) -> Result<LineRange, String> {
+    let spec = spec.trim();
+    let (block, lines) = match spec.split_once(':') {
+        Some((block, lines)) => (parse_number(block, "block")?, lines),
+        None => (1, spec),
+    };
+    let (start, end) = match lines.split_once('-') {
+        Some((start, end)) => (parse_number(start, "line")?, parse_number(end, "line")?),
+        None => {
+            let line = parse_number(lines, "line")?;
+            (line, line)
+        }
+    };
+    if start > end {
+        return Err(format!("Start line {} is after end line {}", start, end));
+    }
+    Ok(LineRange { block, start, end })
+}
+
+fn parse_number(s: &str, what: &str) -> Result<usize, String> {
+    match s.trim().parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("Invalid {} number: '{}'", what, s.trim())),
+    }
+}
+
+pub fn extract_lines(content: &str, range: &LineRange) -> Result<String, String> {
+    let blocks = code_blocks(content);
+    let block = blocks.get(range.block - 1).ok_or_else(|| {
+        format!(
+            "Block {} not found ({} code blocks in message)",
+            range.block,
+            blocks.len()
+        )
+    })?;
+    if range.start > block.lines.len() {
+        return Err(format!(
+            "Line {} is past the end of block {} ({} lines)",
+            range.start,
+            range.block,
+            block.lines.len()
+        ));
+    }
+    let end = range.end.min(block.lines.len());
+    Ok(block.lines[range.start - 1..end].join("\n"))
+}
+
+const KNOWN_FILENAMES: &[&str] = &["Makefile", "Dockerfile", "Justfile", "Procfile", "Gemfile"];
+
+// Finds the file a code block is meant to be saved as, looking at (in order) the
+// fence info (`python title=utils.py`, `rust src/main.rs`), a leading comment in the
+// block (`// src/app.rs`), and the prose line right before the fence (`**src/app.rs**`).
+fn filename_hint(block: &CodeBlock, preceding_line: Option<&str>) -> Option<String> {
+    for (i, token) in block.info.split_whitespace().enumerate() {
+        if let Some((key, value)) = token.split_once('=') {
+            if matches!(key, "title" | "file" | "filename" | "path") {
+                return as_path(value);
+            }
+        } else if let Some((_, path)) = token.split_once(':') {
+            if let Some(path) = as_path(path) {
+                return Some(path);
+            }
+        } else if i > 0 {
+            if let Some(path) = as_path(token) {
+                return Some(path);
+            }
+        }
+    }
+
+    let first_line = block.lines.iter().find(|line| !line.trim().is_empty());
+    if let Some(comment) = first_line.and_then(|line| comment_body(line.trim())) {
+        if let Some(path) = as_path(strip_label(comment)) {
+            return Some(path);
+        }
+    }
+
+    preceding_line.and_then(|line| as_path(strip_label(line.trim())))
+}
+
+fn comment_body(line: &str) -> Option<&str> {
+    for (open, close) in [
+        ("<!--", "-->"),
+        ("/*", "*/"),
+        ("//", ""),
+        ("#", ""),
+        ("--", ""),
+    ] {
+        if let Some(rest) = line.strip_prefix(open) {
+            return Some(rest.trim_end_matches(close).trim());
+        }
+    }
+    None
+}
+
+fn strip_label(s: &str) -> &str {
+    for label in ["File:", "file:", "Filename:", "filename:", "Path:", "path:"] {
+        if let Some(rest) = s.strip_prefix(label) {
+            return rest.trim();
+        }
+    }
+    s
+}
+
+fn as_path(candidate: &str) -> Option<String> {
+    let path = candidate
+        .trim()
+        .trim_end_matches(':')
+        .trim_matches(|c| matches!(c, '`' | '*' | '"' | '\'' | '_'))
+        .trim_end_matches(':');
+    if path.is_empty() || path.contains(char::is_whitespace) || path.contains("://") {
+        return None;
+    }
+    let name = path.rsplit('/').next()?;
+    if KNOWN_FILENAMES.contains(&name) {
+        return Some(path.to_string());
+    }
+    let (stem, extension) = name.rsplit_once('.')?;
+    let valid_extension =
+        (1..=10).contains(&extension.len()) && extension.chars().all(|c| c.is_ascii_alphanumeric());
+    if (stem.is_empty() && !name.starts_with('.')) || !valid_extension {
+        return None;
+    }
+    Some(path.to_string())
+}
+
+pub struct PlannedFile {
+    pub path: String,
+    pub contents: String,
+}
+
+// Pairs each code block of a message with the file it should be written to. Blocks
+// with filename hints are used when there are any; otherwise every block is saved as
+// `block-N.<ext>` based on its (possibly detected) language.
+pub fn planned_files(content: &str) -> Vec<PlannedFile> {
+    let mut hinted = Vec::new();
+    let mut all = Vec::new();
+    let mut preceding_line: Option<String> = None;
+
+    for segment in parse_segments(content) {
+        match segment {
+            Segment::Text(text) => {
+                preceding_line = text
+                    .lines()
+                    .rev()
+                    .find(|line| !line.trim().is_empty())
+                    .map(str::to_string);
+            }
+            Segment::Code(block) => {
+                let mut contents = block.lines.join("\n");
+                contents.push('\n');
+                let hint = filename_hint(&block, preceding_line.as_deref());
+                let extension = block.language().map(language::extension).unwrap_or("txt");
+                all.push(PlannedFile {
+                    path: format!("block-{}.{}", all.len() + 1, extension),
+                    contents: contents.clone(),
+                });
+                if let Some(path) = hint {
+                    hinted.push(PlannedFile { path, contents });
+                }
+                preceding_line = None;
+            }
+        }
+    }
+
+    if hinted.is_empty() {
+        all
+    } else {
+        hinted
+    }
+}
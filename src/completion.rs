@@ -0,0 +1,50 @@
+use unicode_width::UnicodeWidthChar;
+
+pub const DEFAULT_DELAY_MS: u64 = 600;
+// Suggestions are a single line of ghost text, so longer ones are cut.
+const MAX_CHARS: usize = 120;
+
+// Asks for what comes next after `text`, the way a fill-in-the-middle model would
+// with an empty suffix.
+pub fn build_prompt(text: &str) -> String {
+    format!(
+        "Continue the text below, which someone is typing as a prompt to an AI \
+         assistant. Reply with only the next few words, without repeating the text \
+         and without quotes. If it's already complete, reply with nothing.\n\n\
+         <text>{}</text>",
+        text
+    )
+}
+
+// The first line of the reply, minus any echo of the text itself; `None` if
+// nothing useful is left.
+pub fn clean(text: &str, reply: &str) -> Option<String> {
+    let reply = reply.trim_end();
+    let reply = reply.strip_prefix(text).unwrap_or(reply);
+    let line = reply.lines().find(|line| !line.trim().is_empty())?;
+    let line = line.trim_end().trim_matches('"');
+    let suggestion = if text.ends_with(char::is_whitespace) {
+        line.trim_start().to_string()
+    } else if text.ends_with(|c: char| ",.;:!?".contains(c))
+        && line.starts_with(char::is_alphanumeric)
+    {
+        // The space the model left out after punctuation.
+        format!(" {}", line)
+    } else {
+        line.to_string()
+    };
+    let suggestion: String = suggestion.chars().take(MAX_CHARS).collect();
+    (!suggestion.trim().is_empty()).then_some(suggestion)
+}
+
+// The start of `text` that fits in `width` columns.
+pub fn fit(text: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in text.char_indices() {
+        used += c.width().unwrap_or(0);
+        if used > width {
+            return &text[..i];
+        }
+    }
+    text
+}
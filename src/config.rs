@@ -0,0 +1,642 @@
+use crate::completion;
+use crate::filters::Filters;
+use crate::gen_tests;
+use crate::highlight;
+use crate::improve;
+use crate::keys::Keymap;
+use crate::listing::{self, Column, SortKey};
+use crate::paste;
+use crate::remote;
+use crate::sandbox::{self, Sandbox};
+use crate::theme::Theme;
+use serde_json::{Map, Value};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PROJECT_CONFIG_FILE: &str = ".llmtui.toml";
+// All a project file may set. A cloned repository picks the model and what goes
+// along with prompts; it doesn't get to run code, read files outside it, write
+// anywhere or take input from elsewhere.
+const PROJECT_KEYS: [&str; 4] = ["model", "system_prompt", "fragments", "redact"];
+const DEFAULT_DUPLICATE_THRESHOLD: f64 = 0.7;
+const DEFAULT_LOG_PAGE_SIZE: usize = 500;
+const DEFAULT_SUMMARY_PROMPT: &str = "Summarize this conversation in two or three sentences: \
+     what was asked, what was decided and anything left open.";
+const DEFAULT_TRANSLATE_PROMPT: &str = "Translate the following text into {language}. \
+     Keep code blocks, names and formatting unchanged and reply with only the translation.\n\n{text}";
+
+pub struct Config {
+    pub wrap_code: bool,
+    pub line_numbers: bool,
+    // The syntect theme for code blocks; `None` turns highlighting off.
+    pub highlight_theme: Option<String>,
+    // Overrides the context window guessed from the model name.
+    pub context_tokens: Option<usize>,
+    // Messages longer than this many lines are cut short in the chat.
+    pub max_display_lines: Option<usize>,
+    // What the conversation list shows and its initial order.
+    pub list_columns: Vec<Column>,
+    pub list_sort: SortKey,
+    // How many `llm logs` entries are loaded at startup and each time the list is
+    // scrolled past its end; `None` loads them all at once.
+    pub log_page_size: Option<usize>,
+    // Retry context-length errors with less context without asking first.
+    pub auto_retry_context: bool,
+    // When set, prompts at least this similar (0-1) to an answered one ask first.
+    pub duplicate_threshold: Option<f64>,
+    // Model name substrings mapped to HuggingFace tokenizer.json files.
+    pub tokenizers: Vec<(String, PathBuf)>,
+    // Model aliases or names mapped to USD per million input and output tokens,
+    // for the cost in the quit summary.
+    pub prices: Vec<(String, (f64, f64))>,
+    // Model aliases or names mapped to how many requests may run at once; the
+    // rest wait for one to end.
+    pub concurrency: Vec<(String, usize)>,
+    // Print what the session sent (prompts, tokens, cost) after quitting.
+    pub quit_summary: bool,
+    pub filters: Filters,
+    pub project_mode: bool,
+    pub project_top_k: usize,
+    pub model: Option<String>,
+    pub system_prompt: Option<String>,
+    pub fragments: Vec<String>,
+    pub redact: Vec<String>,
+    // Offer to fence pasted stack traces and logs under one of `paste_prompts`.
+    pub smart_paste: bool,
+    pub paste_prompts: Vec<String>,
+    pub digest_feeds: Vec<String>,
+    pub digest_interval_minutes: u64,
+    pub digest_model: Option<String>,
+    pub digest_conversation: String,
+    pub summary_model: Option<String>,
+    pub summary_prompt: String,
+    // Markdown file `/note` appends to; `{date}` makes it a daily note.
+    pub notes_path: Option<PathBuf>,
+    pub notes_template: String,
+    // Ask for follow-up questions after each response.
+    pub suggestions: bool,
+    pub suggestions_model: Option<String>,
+    // Picking a suggestion sends it instead of putting it in the input.
+    pub suggestions_send: bool,
+    // `--kiosk` settings: the one conversation and model, and words refused in input.
+    pub kiosk_conversation: String,
+    pub kiosk_model: Option<String>,
+    pub kiosk_banned_words: Vec<String>,
+    // Git repository with the team's shared prompts and personas.
+    pub library_repo: Option<String>,
+    // Local prompts and personas, which win over shared ones of the same name.
+    pub library_dir: Option<PathBuf>,
+    pub improve_model: Option<String>,
+    // The "prompt doctor" template used by `/improve`; `{prompt}` is the draft.
+    pub improve_prompt: String,
+    pub gen_tests_model: Option<String>,
+    // The llm model options a conversation's stop sequences, response prefix and
+    // seed are passed as; plugins name them differently.
+    pub stop_option: String,
+    pub prefix_option: String,
+    pub seed_option: String,
+    // Ghost-text completion of the input is on when a model is set.
+    pub completion_model: Option<String>,
+    // How long typing has to pause before a completion is asked for.
+    pub completion_delay_ms: u64,
+    // Used by `/gen-tests`; `{language}`, `{path}` and `{code}` are filled in.
+    pub gen_tests_prompt: String,
+    // Shorten answers with a model before exporting them as flashcards.
+    pub anki_condense: bool,
+    pub anki_model: Option<String>,
+    pub translate_model: Option<String>,
+    // `{language}` and `{text}` are filled in.
+    pub translate_prompt: String,
+    pub translate_language: Option<String>,
+    // What code run from the chat is wrapped in; it runs directly when unset.
+    pub sandbox: Option<Sandbox>,
+    pub run_timeout_seconds: u64,
+    // The command "Run in Python" starts, e.g. a virtualenv's python or `uv run python`.
+    pub python_interpreter: Vec<String>,
+    pub fifo: Option<PathBuf>,
+    // The TCP server for remote commands, which `llm-tui send` connects to.
+    pub server_enabled: bool,
+    pub server_host: String,
+    pub server_port: u16,
+    // Listen on an address other machines can reach. Prompts aren't authenticated.
+    pub server_allow_network: bool,
+    pub keys: Keymap,
+    pub show_conversation_list: bool,
+    // Flash the list entry of a conversation that gets a response while another is
+    // open, and offer Ctrl+O to jump to it.
+    pub follow_activity: bool,
+    pub theme: Theme,
+    // Start with remote prompts queued for review instead of sent.
+    pub do_not_disturb: bool,
+    // Start with background jobs (digests, follow-up suggestions, inline
+    // completions, library sync) paused.
+    pub pause_background: bool,
+    // The `.llmtui.toml` that was merged over the global config, if any.
+    pub project_file: Option<PathBuf>,
+    // Settings and fragments of the project file that were left out.
+    pub project_ignored: Vec<String>,
+    // Config files that couldn't be read, and why.
+    pub load_errors: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            wrap_code: true,
+            line_numbers: false,
+            highlight_theme: Some(highlight::DEFAULT_THEME.to_string()),
+            context_tokens: None,
+            max_display_lines: Some(500),
+            list_columns: listing::DEFAULT_COLUMNS.to_vec(),
+            list_sort: SortKey::Log,
+            log_page_size: Some(DEFAULT_LOG_PAGE_SIZE),
+            auto_retry_context: false,
+            duplicate_threshold: None,
+            tokenizers: Vec::new(),
+            prices: Vec::new(),
+            concurrency: Vec::new(),
+            quit_summary: false,
+            filters: Filters::default(),
+            project_mode: false,
+            project_top_k: 3,
+            model: None,
+            system_prompt: None,
+            fragments: Vec::new(),
+            redact: Vec::new(),
+            smart_paste: true,
+            paste_prompts: paste::DEFAULT_PROMPTS.map(str::to_string).to_vec(),
+            digest_feeds: Vec::new(),
+            digest_interval_minutes: 360,
+            digest_model: None,
+            digest_conversation: "Digest".to_string(),
+            summary_model: None,
+            summary_prompt: DEFAULT_SUMMARY_PROMPT.to_string(),
+            notes_path: None,
+            notes_template: crate::notes::DEFAULT_TEMPLATE.to_string(),
+            suggestions: false,
+            suggestions_model: None,
+            suggestions_send: false,
+            kiosk_conversation: "Kiosk".to_string(),
+            kiosk_model: None,
+            kiosk_banned_words: Vec::new(),
+            library_repo: None,
+            library_dir: config_dir().map(|dir| dir.join("library")),
+            improve_model: None,
+            improve_prompt: improve::DEFAULT_TEMPLATE.to_string(),
+            gen_tests_model: None,
+            stop_option: "stop".to_string(),
+            prefix_option: "prefill".to_string(),
+            seed_option: "seed".to_string(),
+            completion_model: None,
+            completion_delay_ms: completion::DEFAULT_DELAY_MS,
+            gen_tests_prompt: gen_tests::DEFAULT_TEMPLATE.to_string(),
+            anki_condense: false,
+            anki_model: None,
+            translate_model: None,
+            translate_prompt: DEFAULT_TRANSLATE_PROMPT.to_string(),
+            translate_language: None,
+            sandbox: None,
+            run_timeout_seconds: sandbox::DEFAULT_TIMEOUT_SECONDS,
+            python_interpreter: vec![if cfg!(windows) { "python" } else { "python3" }.to_string()],
+            fifo: None,
+            server_enabled: true,
+            server_host: remote::DEFAULT_HOST.to_string(),
+            server_port: remote::DEFAULT_PORT,
+            server_allow_network: false,
+            keys: Keymap::default(),
+            show_conversation_list: false,
+            follow_activity: false,
+            theme: Theme::default(),
+            do_not_disturb: false,
+            pause_background: false,
+            project_file: None,
+            project_ignored: Vec::new(),
+            load_errors: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Self {
+        let mut load_errors = Vec::new();
+        let mut value = config_path()
+            .and_then(|path| read_toml(&path, &mut load_errors))
+            .unwrap_or_else(|| Value::Object(Map::new()));
+
+        let project_file = env::current_dir()
+            .ok()
+            .and_then(|cwd| find_project_config(&cwd));
+        let mut project_ignored = Vec::new();
+        if let Some(path) = &project_file {
+            if let Some(project) = read_toml(path, &mut load_errors) {
+                let dir = path.parent().unwrap_or(Path::new("."));
+                let project = project_settings(project, dir, &value, &mut project_ignored);
+                merge(&mut value, project);
+            }
+        }
+
+        let mut config = Config::default();
+        config.apply(&value);
+        config.project_file = project_file;
+        config.project_ignored = project_ignored;
+        config.load_errors = load_errors;
+        config
+    }
+
+    pub fn server_address(&self) -> String {
+        format!("{}:{}", self.server_host, self.server_port)
+    }
+
+    // Replaces known secrets in outgoing text with a placeholder.
+    pub fn redact(&self, text: &str) -> String {
+        redact(&self.redact, text)
+    }
+
+    fn apply(&mut self, value: &Value) {
+        if let Some(wrap_code) = value["chat"]["wrap_code"].as_bool() {
+            self.wrap_code = wrap_code;
+        }
+        if let Some(line_numbers) = value["chat"]["line_numbers"].as_bool() {
+            self.line_numbers = line_numbers;
+        }
+        if let Some(theme) = value["chat"]["syntax_theme"].as_str() {
+            self.highlight_theme = Some(theme.to_string());
+        }
+        if value["chat"]["syntax_highlighting"].as_bool() == Some(false) {
+            self.highlight_theme = None;
+        }
+        if let Some(tokens) = value["chat"]["context_tokens"].as_u64() {
+            self.context_tokens = Some(tokens as usize);
+        }
+        if let Some(auto) = value["chat"]["auto_retry_context"].as_bool() {
+            self.auto_retry_context = auto;
+        }
+        if let Some(check) = value["chat"]["duplicate_check"].as_bool() {
+            self.duplicate_threshold = check.then_some(DEFAULT_DUPLICATE_THRESHOLD);
+        }
+        if let Some(threshold) = value["chat"]["duplicate_threshold"].as_f64() {
+            if self.duplicate_threshold.is_some() {
+                self.duplicate_threshold = Some(threshold.clamp(0.0, 1.0));
+            }
+        }
+        // 0 shows messages in full however long they are.
+        if let Some(lines) = value["chat"]["max_display_lines"].as_u64() {
+            self.max_display_lines = Some(lines as usize).filter(|&lines| lines > 0);
+        }
+        if let Some(columns) = string_list(&value["conversation_list"]["columns"]) {
+            self.list_columns = columns.iter().filter_map(|c| Column::parse(c)).collect();
+        }
+        if let Some(sort) = value["conversation_list"]["sort"]
+            .as_str()
+            .and_then(SortKey::parse)
+        {
+            self.list_sort = sort;
+        }
+        // 0 loads the whole log at startup.
+        if let Some(size) = value["conversation_list"]["page_size"].as_u64() {
+            self.log_page_size = Some(size as usize).filter(|&size| size > 0);
+        }
+        if let Some(tokenizers) = value["tokenizers"].as_object() {
+            self.tokenizers = tokenizers
+                .iter()
+                .filter_map(|(model, path)| Some((model.clone(), expand_home(path.as_str()?))))
+                .collect();
+        }
+        if let Some(prices) = value["prices"].as_object() {
+            self.prices = prices
+                .iter()
+                .filter_map(|(model, price)| {
+                    let price = price.as_array()?;
+                    Some((
+                        model.clone(),
+                        (price.first()?.as_f64()?, price.get(1)?.as_f64()?),
+                    ))
+                })
+                .collect();
+        }
+        if let Some(concurrency) = value["concurrency"].as_object() {
+            self.concurrency = concurrency
+                .iter()
+                .filter_map(|(model, limit)| Some((model.clone(), limit.as_u64()?.max(1) as usize)))
+                .collect();
+        }
+        if let Some(summary) = value["session"]["summary_on_quit"].as_bool() {
+            self.quit_summary = summary;
+        }
+        self.filters.apply(&value["filters"]);
+        if let Some(enabled) = value["project"]["enabled"].as_bool() {
+            self.project_mode = enabled;
+        }
+        if let Some(top_k) = value["project"]["top_k"].as_u64() {
+            self.project_top_k = top_k as usize;
+        }
+        if let Some(paused) = value["pause_background"].as_bool() {
+            self.pause_background = paused;
+        }
+        if let Some(model) = value["model"].as_str() {
+            self.model = Some(model.to_string());
+        }
+        if let Some(system_prompt) = value["system_prompt"].as_str() {
+            self.system_prompt = Some(system_prompt.to_string());
+        }
+        if let Some(fragments) = string_list(&value["fragments"]) {
+            self.fragments = fragments;
+        }
+        if let Some(redact) = string_list(&value["redact"]) {
+            self.redact = redact;
+        }
+        if let Some(detect) = value["paste"]["detect"].as_bool() {
+            self.smart_paste = detect;
+        }
+        if let Some(prompts) = string_list(&value["paste"]["prompts"]) {
+            self.paste_prompts = prompts;
+        }
+        if let Some(feeds) = string_list(&value["digest"]["feeds"]) {
+            self.digest_feeds = feeds;
+        }
+        if let Some(minutes) = value["digest"]["interval_minutes"].as_u64() {
+            self.digest_interval_minutes = minutes.max(1);
+        }
+        if let Some(model) = value["digest"]["model"].as_str() {
+            self.digest_model = Some(model.to_string());
+        }
+        if let Some(conversation) = value["digest"]["conversation"].as_str() {
+            self.digest_conversation = conversation.to_string();
+        }
+        if let Some(model) = value["summarize"]["model"].as_str() {
+            self.summary_model = Some(model.to_string());
+        }
+        if let Some(prompt) = value["summarize"]["prompt"].as_str() {
+            self.summary_prompt = prompt.to_string();
+        }
+        if let Some(path) = value["notes"]["path"].as_str() {
+            self.notes_path = Some(expand_home(path));
+        }
+        if let Some(template) = value["notes"]["template"].as_str() {
+            self.notes_template = template.to_string();
+        }
+        if let Some(enabled) = value["suggestions"]["enabled"].as_bool() {
+            self.suggestions = enabled;
+        }
+        if let Some(model) = value["suggestions"]["model"].as_str() {
+            self.suggestions_model = Some(model.to_string());
+        }
+        if let Some(send) = value["suggestions"]["send"].as_bool() {
+            self.suggestions_send = send;
+        }
+        if let Some(name) = value["kiosk"]["conversation"].as_str() {
+            self.kiosk_conversation = name.to_string();
+        }
+        if let Some(model) = value["kiosk"]["model"].as_str() {
+            self.kiosk_model = Some(model.to_string());
+        }
+        if let Some(words) = string_list(&value["kiosk"]["banned_words"]) {
+            self.kiosk_banned_words = words.iter().map(|word| word.to_lowercase()).collect();
+        }
+        if let Some(repo) = value["library"]["repo"].as_str() {
+            self.library_repo = Some(repo.to_string());
+        }
+        if let Some(dir) = value["library"]["local"].as_str() {
+            self.library_dir = Some(expand_home(dir));
+        }
+        if let Some(model) = value["improve"]["model"].as_str() {
+            self.improve_model = Some(model.to_string());
+        }
+        if let Some(prompt) = value["improve"]["prompt"].as_str() {
+            self.improve_prompt = prompt.to_string();
+        }
+        if let Some(model) = value["completion"]["model"].as_str() {
+            self.completion_model = Some(model.to_string()).filter(|model| !model.is_empty());
+        }
+        if let Some(delay) = value["completion"]["delay_ms"].as_u64() {
+            self.completion_delay_ms = delay;
+        }
+        if let Some(name) = value["model_options"]["stop"].as_str() {
+            self.stop_option = name.to_string();
+        }
+        if let Some(name) = value["model_options"]["prefix"].as_str() {
+            self.prefix_option = name.to_string();
+        }
+        if let Some(name) = value["model_options"]["seed"].as_str() {
+            self.seed_option = name.to_string();
+        }
+        if let Some(model) = value["gen_tests"]["model"].as_str() {
+            self.gen_tests_model = Some(model.to_string());
+        }
+        if let Some(prompt) = value["gen_tests"]["prompt"].as_str() {
+            self.gen_tests_prompt = prompt.to_string();
+        }
+        if let Some(condense) = value["anki"]["condense"].as_bool() {
+            self.anki_condense = condense;
+        }
+        if let Some(model) = value["anki"]["model"].as_str() {
+            self.anki_model = Some(model.to_string());
+        }
+        if let Some(model) = value["translate"]["model"].as_str() {
+            self.translate_model = Some(model.to_string());
+        }
+        if let Some(prompt) = value["translate"]["prompt"].as_str() {
+            self.translate_prompt = prompt.to_string();
+        }
+        if let Some(language) = value["translate"]["language"].as_str() {
+            self.translate_language = Some(language.to_string());
+        }
+        if let Some(preset) = value["sandbox"]["preset"].as_str() {
+            let image = value["sandbox"]["image"]
+                .as_str()
+                .unwrap_or(sandbox::DEFAULT_IMAGE);
+            self.sandbox = Some(Sandbox::preset(preset, image));
+        }
+        if let Some(command) = value["sandbox"]["command"].as_str() {
+            self.sandbox = Some(Sandbox::custom(command));
+        }
+        if let Some(seconds) = value["sandbox"]["timeout_seconds"].as_u64() {
+            self.run_timeout_seconds = seconds.max(1);
+        }
+        if let Some(interpreter) = value["python"]["interpreter"].as_str() {
+            let mut words = interpreter.split_whitespace();
+            if let Some(program) = words.next() {
+                self.python_interpreter = std::iter::once(expand_home(program))
+                    .map(|program| program.to_string_lossy().into_owned())
+                    .chain(words.map(str::to_string))
+                    .collect();
+            }
+        }
+        if let Some(fifo) = value["remote"]["fifo"].as_str() {
+            self.fifo = Some(expand_home(fifo));
+        }
+        if let Some(dnd) = value["remote"]["do_not_disturb"].as_bool() {
+            self.do_not_disturb = dnd;
+        }
+        if let Some(enabled) = value["server"]["enabled"].as_bool() {
+            self.server_enabled = enabled;
+        }
+        if let Some(host) = value["server"]["address"].as_str() {
+            self.server_host = host.to_string();
+        }
+        if let Some(allow) = value["server"]["allow_network"].as_bool() {
+            self.server_allow_network = allow;
+        }
+        if let Some(port) = value["server"]["port"]
+            .as_u64()
+            .and_then(|port| u16::try_from(port).ok())
+        {
+            self.server_port = port;
+        }
+        self.keys.apply(&value["keys"]);
+        if let Some(show) = value["ui"]["show_conversation_list"].as_bool() {
+            self.show_conversation_list = show;
+        }
+        if let Some(follow) = value["ui"]["follow_activity"].as_bool() {
+            self.follow_activity = follow;
+        }
+        self.theme.apply(&value["theme"]);
+    }
+}
+
+// `HOME`, or `USERPROFILE` on Windows where `HOME` is usually unset.
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    let rest = path
+        .strip_prefix("~/")
+        .or_else(|| path.strip_prefix("~\\").filter(|_| cfg!(windows)));
+    match (rest, home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+// `Config::redact`, for threads that only keep the secrets.
+pub fn redact(secrets: &[String], text: &str) -> String {
+    secrets
+        .iter()
+        .filter(|secret| !secret.is_empty())
+        .fold(text.to_string(), |text, secret| {
+            text.replace(secret.as_str(), "[REDACTED]")
+        })
+}
+
+fn string_list(value: &Value) -> Option<Vec<String>> {
+    value.as_array().map(|items| {
+        items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect()
+    })
+}
+
+// Walks up from `start` looking for a per-project config file.
+fn find_project_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+// The part of a project file that's merged over the global config: only the
+// `PROJECT_KEYS`, fragments that are files inside `dir` (they're written relative
+// to it, not the cwd), and `redact` added to the global list rather than replacing
+// it. What's left out is named in `ignored`.
+fn project_settings(
+    project: Value,
+    dir: &Path,
+    global: &Value,
+    ignored: &mut Vec<String>,
+) -> Value {
+    let Value::Object(mut table) = project else {
+        return Value::Object(Map::new());
+    };
+    ignored.extend(
+        table
+            .keys()
+            .filter(|key| !PROJECT_KEYS.contains(&key.as_str()))
+            .cloned(),
+    );
+    table.retain(|key, _| PROJECT_KEYS.contains(&key.as_str()));
+    if let Some(fragments) = table.get("fragments").and_then(string_list) {
+        let root = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        let inside: Vec<Value> = fragments
+            .into_iter()
+            .filter_map(|fragment| {
+                let path = root.join(&fragment).canonicalize().ok();
+                match path.filter(|path| path.starts_with(&root) && path.is_file()) {
+                    Some(path) => Some(Value::String(path.to_string_lossy().into_owned())),
+                    None => {
+                        ignored.push(fragment);
+                        None
+                    }
+                }
+            })
+            .collect();
+        table.insert("fragments".to_string(), Value::Array(inside));
+    }
+    if let Some(redact) = table.get("redact").and_then(string_list) {
+        let mut all = string_list(&global["redact"]).unwrap_or_default();
+        all.extend(redact);
+        table.insert(
+            "redact".to_string(),
+            Value::Array(all.into_iter().map(Value::String).collect()),
+        );
+    }
+    Value::Object(table)
+}
+
+// Recursively merges `overlay` into `base`: tables are merged key by key, anything
+// else in `overlay` replaces the value in `base`.
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("llm-tui"));
+    }
+    if cfg!(windows) {
+        if let Some(dir) = env::var_os("APPDATA").filter(|d| !d.is_empty()) {
+            return Some(PathBuf::from(dir).join("llm-tui"));
+        }
+    }
+    home_dir().map(|home| home.join(".config").join("llm-tui"))
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+// Reads a TOML file into a JSON value. A file that doesn't parse is reported in
+// `errors`, with the line it failed on, rather than partly applied.
+fn read_toml(path: &Path, errors: &mut Vec<String>) -> Option<Value> {
+    let contents = fs::read_to_string(path).ok()?;
+    match toml::from_str(&contents) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            let line = e
+                .span()
+                .map(|span| contents[..span.start].lines().count().max(1));
+            errors.push(match line {
+                Some(line) => format!("{} line {}: {}", path.display(), line, e.message()),
+                None => format!("{}: {}", path.display(), e.message()),
+            });
+            None
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use crate::tokens::TokenCounter;
+use crate::Message;
+
+// Provider error messages (lowercased) that mean the request didn't fit.
+const LENGTH_ERRORS: &[&str] = &[
+    "context_length_exceeded",
+    "maximum context length",
+    "context length",
+    "context window",
+    "prompt is too long",
+    "input is too long",
+    "too many tokens",
+    "reduce the length",
+];
+
+// Used when neither the config nor the model name says otherwise.
+const DEFAULT_LIMIT: usize = 8_192;
+
+// Known context windows, matched as substrings of the model name. More specific
+// names come first.
+const MODEL_LIMITS: &[(&str, usize)] = &[
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4.1", 1_000_000),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4", 8_192),
+    ("gpt-3.5", 16_385),
+    ("o1", 128_000),
+    ("o3", 200_000),
+    ("claude", 200_000),
+    ("gemini", 1_000_000),
+    ("mistral", 32_000),
+    ("llama3", 8_192),
+    ("llama-3", 8_192),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Inclusion {
+    Pinned,
+    Included,
+    Hidden,
+    // Doesn't fit in what's left of the context window.
+    Dropped,
+}
+
+pub struct ContextPlan {
+    // One entry per message, in conversation order.
+    pub inclusion: Vec<Inclusion>,
+    pub tokens: Vec<usize>,
+    pub used: usize,
+    pub limit: usize,
+}
+
+pub fn model_limit(model: &str) -> usize {
+    let model = model.to_lowercase();
+    MODEL_LIMITS
+        .iter()
+        .find(|(name, _)| model.contains(name))
+        .map_or(DEFAULT_LIMIT, |(_, limit)| *limit)
+}
+
+// Whether the error a request failed with says the context was too long.
+pub fn is_length_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    LENGTH_ERRORS.iter().any(|pattern| error.contains(pattern))
+}
+
+// How many of the oldest context parts to leave out so the prompt fits in `limit`
+// with a quarter of it to spare for the answer. At least one is dropped, since the
+// provider has already said the estimate was too low.
+pub fn parts_to_drop(prompt_tokens: usize, part_tokens: &[usize], limit: usize) -> usize {
+    let budget = limit - limit / 4;
+    let mut total = prompt_tokens + part_tokens.iter().sum::<usize>();
+    let mut drop = 0;
+    while drop < part_tokens.len() && (drop == 0 || total > budget) {
+        total -= part_tokens[drop];
+        drop += 1;
+    }
+    drop
+}
+
+// Earlier messages written out as one text, for the fragment sent before a prompt.
+pub fn transcript<'a>(messages: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    let turns: Vec<String> = messages
+        .into_iter()
+        .map(|(role, content)| {
+            let speaker = if role == "user" { "User" } else { "Assistant" };
+            format!("{}:\n{}", speaker, content)
+        })
+        .collect();
+    format!(
+        "Earlier messages in this conversation, oldest first:\n\n{}",
+        turns.join("\n\n")
+    )
+}
+
+// Decides which messages go into the next request: pinned messages first, in pin
+// order, then the most recent unhidden history until the budget runs out. Older
+// messages are dropped as a block so the history sent stays contiguous.
+pub fn plan(messages: &[Message], limit: usize, counter: &TokenCounter) -> ContextPlan {
+    let tokens: Vec<usize> = messages.iter().map(|m| counter.count(&m.content)).collect();
+    let mut inclusion = vec![Inclusion::Dropped; messages.len()];
+    let mut used = 0;
+
+    let mut pinned: Vec<usize> = (0..messages.len())
+        .filter(|&i| messages[i].pin.is_some())
+        .collect();
+    pinned.sort_by_key(|&i| messages[i].pin);
+    for i in pinned {
+        if used + tokens[i] <= limit {
+            used += tokens[i];
+            inclusion[i] = Inclusion::Pinned;
+        }
+    }
+
+    let mut full = false;
+    for i in (0..messages.len()).rev() {
+        if messages[i].pin.is_some() {
+            continue;
+        }
+        if messages[i].hidden {
+            inclusion[i] = Inclusion::Hidden;
+        } else if !full && used + tokens[i] <= limit {
+            used += tokens[i];
+            inclusion[i] = Inclusion::Included;
+        } else {
+            full = true;
+        }
+    }
+
+    ContextPlan {
+        inclusion,
+        tokens,
+        used,
+        limit,
+    }
+}
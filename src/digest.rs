@@ -0,0 +1,154 @@
+use crate::fetch;
+use crate::store;
+use serde_json::Value;
+
+const SEEN_FILE: &str = "digest_seen.json";
+const MAX_SEEN: usize = 2000;
+const MAX_SUMMARY_CHARS: usize = 600;
+
+pub struct FeedItem {
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    pub summary: String,
+}
+
+// Fetches every feed and returns the items that haven't been included in a digest
+// yet. Feeds that fail to download are reported, not fatal.
+pub fn collect_new_items(feeds: &[String]) -> (Vec<FeedItem>, Vec<String>) {
+    let seen = load_seen();
+    let mut items: Vec<FeedItem> = Vec::new();
+    let mut errors = Vec::new();
+    for feed in feeds {
+        match fetch::fetch_raw(feed) {
+            Ok(xml) => {
+                for item in parse_feed(&xml) {
+                    if !seen.contains(&item.id) && !items.iter().any(|i| i.id == item.id) {
+                        items.push(item);
+                    }
+                }
+            }
+            Err(e) => errors.push(format!("{}: {}", feed, e)),
+        }
+    }
+    (items, errors)
+}
+
+// Records `items` as included in a digest, once it was built.
+pub fn mark_seen(items: &[FeedItem]) -> Result<(), String> {
+    let mut seen = load_seen();
+    seen.extend(items.iter().map(|item| item.id.clone()));
+    let skip = seen.len().saturating_sub(MAX_SEEN);
+    let seen: Vec<Value> = seen.into_iter().skip(skip).map(Value::String).collect();
+    store::save(SEEN_FILE, &Value::Array(seen))
+        .map_err(|e| format!("Failed to save seen items: {}", e))
+}
+
+fn load_seen() -> Vec<String> {
+    store::load(SEEN_FILE)
+        .as_array()
+        .map(|ids| {
+            ids.iter()
+                .filter_map(|id| id.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn build_prompt(items: &[FeedItem]) -> String {
+    let mut prompt = String::from(
+        "Summarize the following new feed items as a short daily briefing. Group related \
+         items, keep one or two sentences per item, and include each item's link.\n",
+    );
+    for item in items {
+        prompt.push_str(&format!(
+            "\n## {}\n{}\n{}\n",
+            item.title, item.link, item.summary
+        ));
+    }
+    prompt
+}
+
+// Parses RSS `<item>` and Atom `<entry>` elements. This is a tolerant scan rather
+// than a full XML parser, which is enough for the feeds seen in practice.
+pub fn parse_feed(xml: &str) -> Vec<FeedItem> {
+    let (open, close) = if xml.contains("<entry") {
+        ("<entry", "</entry>")
+    } else {
+        ("<item", "</item>")
+    };
+
+    let mut items = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(open) {
+        let Some(end) = rest[start..].find(close) else {
+            break;
+        };
+        let element = &rest[start..start + end];
+        rest = &rest[start + end + close.len()..];
+
+        let title = element_text(element, "title").unwrap_or_default();
+        let link = element_text(element, "link")
+            .filter(|link| !link.is_empty())
+            .or_else(|| attribute(element, "<link", "href"))
+            .unwrap_or_default();
+        let id = element_text(element, "guid")
+            .or_else(|| element_text(element, "id"))
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| link.clone());
+        let summary = ["description", "summary", "content"]
+            .iter()
+            .find_map(|tag| element_text(element, tag))
+            .map(|html| fetch::html_to_text(&html))
+            .map(|text| text.chars().take(MAX_SUMMARY_CHARS).collect())
+            .unwrap_or_default();
+
+        if !id.is_empty() {
+            items.push(FeedItem {
+                id,
+                title,
+                link,
+                summary,
+            });
+        }
+    }
+    items
+}
+
+fn element_text(element: &str, tag: &str) -> Option<String> {
+    let open = element.find(&format!("<{}", tag))?;
+    let after_open = &element[open + tag.len() + 1..];
+    // Make sure we matched `<tag>` or `<tag attr..>`, not a longer tag name.
+    if !after_open.starts_with(['>', ' ', '\t', '\n']) {
+        return None;
+    }
+    let content_start = after_open.find('>')? + 1;
+    if after_open[..content_start].ends_with("/>") {
+        return Some(String::new());
+    }
+    let content = &after_open[content_start..];
+    let end = content.find(&format!("</{}", tag))?;
+    let text = content[..end].trim();
+    let text = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|t| t.strip_suffix("]]>"))
+        .unwrap_or(text);
+    Some(decode_xml(text.trim()))
+}
+
+fn attribute(element: &str, tag: &str, name: &str) -> Option<String> {
+    let start = element.find(tag)?;
+    let tag_body = &element[start..start + element[start..].find('>')?];
+    let key = format!("{}=\"", name);
+    let value_start = tag_body.find(&key)? + key.len();
+    let value_end = tag_body[value_start..].find('"')?;
+    Some(decode_xml(&tag_body[value_start..value_start + value_end]))
+}
+
+fn decode_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,47 @@
+use crate::project;
+
+// Prompts with fewer keywords than this ("thanks!", "go on") are never duplicates.
+const MIN_KEYWORDS: usize = 3;
+
+pub struct Duplicate {
+    pub conversation: usize,
+    // The earlier prompt; its answer is the message after it.
+    pub message: usize,
+    pub score: f64,
+}
+
+// Finds the earlier prompt most similar to `prompt`, if it scores at least
+// `threshold`. Candidates are (conversation, message, text) triples and similarity
+// is the overlap of their keywords (Jaccard index).
+pub fn find<'a>(
+    prompt: &str,
+    candidates: impl Iterator<Item = (usize, usize, &'a str)>,
+    threshold: f64,
+) -> Option<Duplicate> {
+    let wanted = project::keywords(prompt);
+    if wanted.len() < MIN_KEYWORDS {
+        return None;
+    }
+    candidates
+        .map(|(conversation, message, text)| Duplicate {
+            conversation,
+            message,
+            score: similarity(&wanted, &project::keywords(text)),
+        })
+        .filter(|duplicate| duplicate.score >= threshold)
+        .max_by(|a, b| a.score.total_cmp(&b.score))
+}
+
+// Both lists are sorted and deduplicated, as returned by `project::keywords`.
+fn similarity(a: &[String], b: &[String]) -> f64 {
+    let shared = a
+        .iter()
+        .filter(|word| b.binary_search(word).is_ok())
+        .count();
+    let total = a.len() + b.len() - shared;
+    if total == 0 {
+        0.0
+    } else {
+        shared as f64 / total as f64
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{preview, run_llm_checked, LlmRequest};
+use regex::Regex;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use unicode_width::UnicodeWidthStr;
+
+const JUDGE_PROMPT: &str = "You are grading an answer against the expected one. Reply with \
+     PASS if the answer is correct and agrees with the expected answer in substance \
+     (wording may differ), otherwise FAIL. Reply with only PASS or FAIL.\n\n\
+     Question:\n{prompt}\n\nExpected answer:\n{expected}\n\nAnswer:\n{answer}";
+const PROMPT_COLUMN: usize = 32;
+
+// How an answer is compared to the expected one.
+#[derive(Clone, Copy)]
+pub enum Matcher {
+    // Equal once surrounding whitespace is trimmed.
+    Exact,
+    Contains,
+    // `expected` is a pattern the answer has to match.
+    Regex,
+    // A judge model decides.
+    Judge,
+}
+
+impl Matcher {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "exact" => Ok(Matcher::Exact),
+            "contains" => Ok(Matcher::Contains),
+            "regex" => Ok(Matcher::Regex),
+            "judge" => Ok(Matcher::Judge),
+            _ => Err(format!(
+                "Unknown matcher '{}' (exact, contains, regex or judge)",
+                name
+            )),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Matcher::Exact => "exact",
+            Matcher::Contains => "contains",
+            Matcher::Regex => "regex",
+            Matcher::Judge => "judge",
+        }
+    }
+}
+
+pub struct Case {
+    pub prompt: String,
+    pub expected: String,
+    pub matcher: Matcher,
+}
+
+// A YAML file of cases, e.g.
+//
+//   models: [4o-mini, claude-3.5-haiku]
+//   judge: 4o
+//   cases:
+//     - prompt: What is the capital of France?
+//       expected: Paris
+//       match: contains
+pub struct Suite {
+    pub name: String,
+    pub models: Vec<String>,
+    pub judge: Option<String>,
+    pub cases: Vec<Case>,
+}
+
+impl Suite {
+    pub fn load(path: &Path) -> Result<Suite, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let value: Value = serde_yaml::from_str(&text)
+            .map_err(|e| format!("Invalid YAML in {}: {}", path.display(), e))?;
+        let strings = |value: &Value| -> Vec<String> {
+            match value {
+                Value::String(text) => vec![text.clone()],
+                Value::Array(items) => items
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect(),
+                _ => Vec::new(),
+            }
+        };
+        let mut cases = Vec::new();
+        for (n, case) in value["cases"].as_array().into_iter().flatten().enumerate() {
+            let field = |key: &str| match &case[key] {
+                Value::String(text) => Some(text.clone()),
+                Value::Number(number) => Some(number.to_string()),
+                Value::Bool(flag) => Some(flag.to_string()),
+                _ => None,
+            };
+            let (Some(prompt), Some(expected)) = (field("prompt"), field("expected")) else {
+                return Err(format!(
+                    "Case {} needs a prompt and an expected answer",
+                    n + 1
+                ));
+            };
+            let matcher = Matcher::parse(case["match"].as_str().unwrap_or("exact"))?;
+            if let Matcher::Regex = matcher {
+                Regex::new(&expected).map_err(|e| format!("Case {}: {}", n + 1, e))?;
+            }
+            cases.push(Case {
+                prompt,
+                expected,
+                matcher,
+            });
+        }
+        if cases.is_empty() {
+            return Err(format!("No cases in {}", path.display()));
+        }
+        Ok(Suite {
+            name: path
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned()),
+            models: strings(&value["models"]),
+            judge: value["judge"].as_str().map(str::to_string),
+            cases,
+        })
+    }
+}
+
+// One case answered by one model.
+pub struct Outcome {
+    pub passed: bool,
+    // The answer, or why there's none.
+    pub answer: Result<String, String>,
+}
+
+pub struct Report {
+    pub name: String,
+    pub models: Vec<String>,
+    pub cases: Vec<Case>,
+    // By case, then by model.
+    pub outcomes: Vec<Vec<Outcome>>,
+}
+
+// Runs every case on every model, one request at a time. `judge` grades `judge`
+// cases; `progress` is told how many of the runs are done.
+pub fn run(
+    suite: Suite,
+    models: Vec<String>,
+    judge: &str,
+    mut progress: impl FnMut(usize, usize),
+) -> Report {
+    let total = suite.cases.len() * models.len();
+    let mut outcomes = Vec::new();
+    for (n, case) in suite.cases.iter().enumerate() {
+        let mut row = Vec::new();
+        for (m, model) in models.iter().enumerate() {
+            let answer = ask(model, &case.prompt);
+            let passed = match &answer {
+                Ok(answer) => passes(case, answer, judge),
+                Err(_) => false,
+            };
+            row.push(Outcome { passed, answer });
+            progress(n * models.len() + m + 1, total);
+        }
+        outcomes.push(row);
+    }
+    Report {
+        name: suite.name,
+        models,
+        cases: suite.cases,
+        outcomes,
+    }
+}
+
+fn ask(model: &str, prompt: &str) -> Result<String, String> {
+    run_llm_checked(&LlmRequest {
+        no_log: true,
+        ..LlmRequest::new(prompt.to_string(), model.to_string())
+    })
+}
+
+fn passes(case: &Case, answer: &str, judge: &str) -> bool {
+    match case.matcher {
+        Matcher::Exact => answer.trim() == case.expected.trim(),
+        Matcher::Contains => answer.contains(case.expected.trim()),
+        Matcher::Regex => Regex::new(&case.expected).is_ok_and(|regex| regex.is_match(answer)),
+        Matcher::Judge => {
+            let prompt = JUDGE_PROMPT
+                .replace("{prompt}", &case.prompt)
+                .replace("{expected}", &case.expected)
+                .replace("{answer}", answer);
+            ask(judge, &prompt)
+                .is_ok_and(|verdict| verdict.trim().to_uppercase().starts_with("PASS"))
+        }
+    }
+}
+
+impl Report {
+    pub fn passed(&self) -> usize {
+        self.outcomes.iter().flatten().filter(|o| o.passed).count()
+    }
+
+    pub fn total(&self) -> usize {
+        self.outcomes.iter().map(Vec::len).sum()
+    }
+
+    // A case-by-model table of results, totals per model, then what the failing
+    // runs answered.
+    pub fn matrix_lines(&self) -> Vec<String> {
+        let widths: Vec<usize> = self
+            .models
+            .iter()
+            .map(|model| model.width().max(5))
+            .collect();
+        let row = |label: String, cells: Vec<String>| {
+            let mut line = format!("{:<width$}", label, width = PROMPT_COLUMN + 5);
+            for (cell, width) in cells.iter().zip(&widths) {
+                line.push_str(&format!(" {:<width$}", cell, width = width));
+            }
+            line.trim_end().to_string()
+        };
+
+        let mut lines = vec![row("Case".to_string(), self.models.clone())];
+        for (n, (case, outcomes)) in self.cases.iter().zip(&self.outcomes).enumerate() {
+            let cells = outcomes
+                .iter()
+                .map(|outcome| match (&outcome.answer, outcome.passed) {
+                    (Err(_), _) => "error",
+                    (Ok(_), true) => "pass",
+                    (Ok(_), false) => "FAIL",
+                })
+                .map(str::to_string)
+                .collect();
+            lines.push(row(
+                format!("{:>3}. {}", n + 1, preview(&case.prompt, PROMPT_COLUMN)),
+                cells,
+            ));
+        }
+        let totals = (0..self.models.len())
+            .map(|m| {
+                let passed = self.outcomes.iter().filter(|row| row[m].passed).count();
+                format!("{}/{}", passed, self.cases.len())
+            })
+            .collect();
+        lines.push(row("Passed".to_string(), totals));
+
+        let mut failures = Vec::new();
+        for (n, (case, outcomes)) in self.cases.iter().zip(&self.outcomes).enumerate() {
+            for (model, outcome) in self.models.iter().zip(outcomes) {
+                if outcome.passed {
+                    continue;
+                }
+                let answer = match &outcome.answer {
+                    Ok(answer) => preview(answer, 60),
+                    Err(e) => format!("error: {}", preview(e, 60)),
+                };
+                failures.push(format!(
+                    "{:>3}. {} ({} '{}'): {}",
+                    n + 1,
+                    model,
+                    case.matcher.name(),
+                    preview(&case.expected, 30),
+                    answer
+                ));
+            }
+        }
+        if !failures.is_empty() {
+            lines.push(String::new());
+            lines.push("Failed runs:".to_string());
+            lines.extend(failures);
+        }
+        lines
+    }
+
+    // One row per case and model.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("case,prompt,expected,matcher,model,passed,answer\n");
+        for (n, (case, outcomes)) in self.cases.iter().zip(&self.outcomes).enumerate() {
+            for (model, outcome) in self.models.iter().zip(outcomes) {
+                let answer = match &outcome.answer {
+                    Ok(answer) => answer.clone(),
+                    Err(e) => format!("Error: {}", e),
+                };
+                let fields = [
+                    (n + 1).to_string(),
+                    case.prompt.clone(),
+                    case.expected.clone(),
+                    case.matcher.name().to_string(),
+                    model.clone(),
+                    outcome.passed.to_string(),
+                    answer,
+                ];
+                let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                csv.push_str(&fields.join(","));
+                csv.push('\n');
+            }
+        }
+        csv
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,231 @@
+use std::process::Command;
+
+const MAX_BYTES: &str = "5000000";
+const SKIPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "svg", "nav", "header", "footer", "aside", "form", "template",
+];
+
+// Downloads `url` with curl and returns the page as readable markdown-ish text.
+pub fn fetch_readable(url: &str) -> Result<String, String> {
+    let body = fetch_raw(url)?;
+    let text = if looks_like_html(&body) {
+        html_to_text(&body)
+    } else {
+        body
+    };
+    if text.trim().is_empty() {
+        return Err("Page has no readable text".to_string());
+    }
+    Ok(text)
+}
+
+pub fn fetch_raw(url: &str) -> Result<String, String> {
+    // Only http(s), redirects included, so a URL can't read local files or talk to
+    // other services; `--` keeps a URL starting with `-` from being taken as an option.
+    let output = Command::new("curl")
+        .args([
+            "--proto",
+            "=http,https",
+            "--proto-redir",
+            "=http,https",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--fail",
+            "--max-time",
+            "30",
+            "--max-filesize",
+            MAX_BYTES,
+            "--",
+            url,
+        ])
+        .output()
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn looks_like_html(body: &str) -> bool {
+    let start = body.trim_start().get(..512).unwrap_or(body).to_lowercase();
+    start.starts_with("<!doctype html") || start.contains("<html") || start.contains("<body")
+}
+
+// A readability-style extraction: keep the <article> or <main> element when the page
+// has one, drop boilerplate elements, and map block-level tags to markdown structure.
+pub fn html_to_text(html: &str) -> String {
+    let lower = html.to_ascii_lowercase();
+    let content = ["article", "main", "body"]
+        .iter()
+        .find_map(|tag| element_body(html, &lower, tag))
+        .unwrap_or(html);
+
+    let mut out = String::new();
+    let mut skip_depth: Option<(String, usize)> = None;
+    let mut in_pre = false;
+    let mut rest = content;
+
+    while let Some(start) = rest.find('<') {
+        if skip_depth.is_none() {
+            if in_pre {
+                out.push_str(&decode_entities(&rest[..start]));
+            } else {
+                push_text(&mut out, &rest[..start]);
+            }
+        }
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+
+        if tag.starts_with("!--") {
+            if let Some(close) = rest.find("-->") {
+                rest = &rest[close + 3..];
+            }
+            continue;
+        }
+
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        if let Some((skipped, depth)) = skip_depth.as_mut() {
+            if *skipped == name {
+                if closing {
+                    *depth -= 1;
+                    if *depth == 0 {
+                        skip_depth = None;
+                    }
+                } else if !tag.ends_with('/') {
+                    *depth += 1;
+                }
+            }
+            continue;
+        }
+        if !closing && SKIPPED_TAGS.contains(&name.as_str()) && !tag.ends_with('/') {
+            skip_depth = Some((name, 1));
+            continue;
+        }
+
+        match (name.as_str(), closing) {
+            ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", false) => {
+                let level = name[1..].parse::<usize>().unwrap_or(1);
+                out.push_str("\n\n");
+                out.push_str(&"#".repeat(level));
+                out.push(' ');
+            }
+            ("li", false) => out.push_str("\n- "),
+            ("pre", false) => {
+                in_pre = true;
+                out.push_str("\n\n```\n");
+            }
+            ("pre", true) => {
+                in_pre = false;
+                out.push_str("\n```\n\n");
+            }
+            ("br", _) => out.push('\n'),
+            ("p" | "div" | "section" | "ul" | "ol" | "table" | "tr" | "blockquote", _)
+            | ("h1" | "h2" | "h3" | "h4" | "h5" | "h6", true) => out.push_str("\n\n"),
+            _ => {}
+        }
+    }
+    if skip_depth.is_none() {
+        push_text(&mut out, rest);
+    }
+
+    collapse_blank_lines(&out)
+}
+
+fn element_body<'a>(html: &'a str, lower: &str, tag: &str) -> Option<&'a str> {
+    let open = lower.find(&format!("<{}", tag))?;
+    let content_start = open + lower[open..].find('>')? + 1;
+    let close = lower[content_start..]
+        .rfind(&format!("</{}", tag))
+        .map(|i| content_start + i)
+        .unwrap_or(html.len());
+    html.get(content_start..close)
+}
+
+fn push_text(out: &mut String, text: &str) {
+    let decoded = decode_entities(text);
+    let mut last_was_space = out.ends_with(char::is_whitespace);
+    for c in decoded.chars() {
+        if c.is_whitespace() {
+            if !last_was_space {
+                out.push(' ');
+                last_was_space = true;
+            }
+        } else {
+            out.push(c);
+            last_was_space = false;
+        }
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" | "#39" => Some('\''),
+                "nbsp" => Some(' '),
+                "mdash" => Some('—'),
+                "ndash" => Some('–'),
+                "hellip" => Some('…'),
+                _ => entity
+                    .strip_prefix("#x")
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                    .and_then(char::from_u32),
+            }?;
+            Some((c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn collapse_blank_lines(text: &str) -> String {
+    let mut out = String::new();
+    let mut blank_run = 0;
+    for line in text.lines().map(str::trim_end) {
+        if line.trim().is_empty() {
+            blank_run += 1;
+            if blank_run > 1 {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.trim().to_string()
+}
@@ -0,0 +1,136 @@
+use crate::code_blocks::{self, Segment};
+use serde_json::Value;
+
+const APOLOGY_STARTS: &[&str] = &[
+    "i apologize",
+    "i apologise",
+    "i'm sorry",
+    "i am sorry",
+    "sorry for",
+    "sorry about",
+    "apologies",
+    "my apologies",
+];
+
+// Post-processing applied to responses before they're stored, configured in the
+// `[filters]` table and overridable per conversation with `/filters`.
+#[derive(Clone, Default)]
+pub struct Filters {
+    pub strip_apologies: bool,
+    // Plain-text mode: drop ``` fence lines but keep the code.
+    pub strip_fences: bool,
+    // Re-wraps prose (not code) to at most this many columns.
+    pub max_width: Option<usize>,
+}
+
+enum Filter {
+    StripApologies,
+    StripFences,
+    MaxWidth(usize),
+}
+
+impl Filters {
+    // Reads the keys present in `value`, leaving the others unchanged. A
+    // `max_width` of 0 turns wrapping off.
+    pub fn apply(&mut self, value: &Value) {
+        if let Some(strip) = value["strip_apologies"].as_bool() {
+            self.strip_apologies = strip;
+        }
+        if let Some(strip) = value["strip_fences"].as_bool() {
+            self.strip_fences = strip;
+        }
+        if let Some(width) = value["max_width"].as_u64() {
+            self.max_width = Some(width as usize).filter(|&w| w > 0);
+        }
+    }
+
+    pub fn run(&self, text: &str) -> String {
+        self.chain()
+            .iter()
+            .fold(text.to_string(), |text, filter| filter.run(&text))
+    }
+
+    pub fn describe(&self) -> String {
+        let mut active = Vec::new();
+        if self.strip_apologies {
+            active.push("strip_apologies".to_string());
+        }
+        if self.strip_fences {
+            active.push("strip_fences".to_string());
+        }
+        if let Some(width) = self.max_width {
+            active.push(format!("max_width={}", width));
+        }
+        if active.is_empty() {
+            "none".to_string()
+        } else {
+            active.join(", ")
+        }
+    }
+
+    // Fences are stripped after wrapping so code is still recognised as code.
+    fn chain(&self) -> Vec<Filter> {
+        let mut chain = Vec::new();
+        if self.strip_apologies {
+            chain.push(Filter::StripApologies);
+        }
+        if let Some(width) = self.max_width {
+            chain.push(Filter::MaxWidth(width));
+        }
+        if self.strip_fences {
+            chain.push(Filter::StripFences);
+        }
+        chain
+    }
+}
+
+impl Filter {
+    fn run(&self, text: &str) -> String {
+        match self {
+            Filter::StripApologies => strip_apologies(text),
+            Filter::StripFences => text
+                .lines()
+                .filter(|line| !line.trim_start().starts_with("```"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Filter::MaxWidth(width) => wrap_prose(text, *width),
+        }
+    }
+}
+
+// Drops trailing paragraphs that are only an apology.
+fn strip_apologies(text: &str) -> String {
+    let mut paragraphs: Vec<&str> = text.trim_end().split("\n\n").collect();
+    while paragraphs.len() > 1 {
+        let last = paragraphs[paragraphs.len() - 1].trim().to_lowercase();
+        if APOLOGY_STARTS.iter().any(|start| last.starts_with(start)) {
+            paragraphs.pop();
+        } else {
+            break;
+        }
+    }
+    paragraphs.join("\n\n")
+}
+
+fn wrap_prose(text: &str, width: usize) -> String {
+    let mut out = Vec::new();
+    for segment in code_blocks::parse_segments(text) {
+        match segment {
+            Segment::Text(text) => {
+                for line in text.lines() {
+                    if line.chars().count() <= width {
+                        out.push(line.to_string());
+                    } else {
+                        out.extend(textwrap::wrap(line, width).into_iter().map(String::from));
+                    }
+                }
+            }
+            Segment::Code(block) => {
+                out.push(format!("```{}", block.info));
+                out.extend(block.lines);
+                out.push("```".to_string());
+            }
+        }
+    }
+    out.join("\n")
+}
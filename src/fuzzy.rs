@@ -0,0 +1,45 @@
+// Points for each matched character, and extra for runs and word starts, so
+// "rlq" ranks "Rust lifetimes question" above a name that merely contains the
+// letters somewhere.
+const MATCH: i64 = 1;
+const CONSECUTIVE: i64 = 4;
+const WORD_START: i64 = 6;
+// Taken off for each character skipped between matches.
+const GAP: i64 = 1;
+
+// How well `query` matches `text` as a subsequence, ignoring case; `None` if some
+// of its characters don't appear in order. Whitespace in the query is ignored.
+pub fn score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let mut score = 0;
+    let mut wanted = query.iter().peekable();
+    let mut last_match: Option<usize> = None;
+    let mut previous = ' ';
+    for (position, c) in text.chars().enumerate() {
+        let Some(&&next) = wanted.peek() else {
+            break;
+        };
+        if c.to_lowercase().eq(std::iter::once(next)) {
+            score += MATCH;
+            match last_match {
+                Some(last) if last + 1 == position => score += CONSECUTIVE,
+                Some(last) => score -= GAP * (position - last - 1).min(10) as i64,
+                None => {}
+            }
+            if !previous.is_alphanumeric() {
+                score += WORD_START;
+            }
+            last_match = Some(position);
+            wanted.next();
+        }
+        previous = c;
+    }
+    wanted.peek().is_none().then_some(score)
+}
@@ -0,0 +1,55 @@
+use crate::code_blocks;
+use std::path::Path;
+
+pub const DEFAULT_TEMPLATE: &str = "Write unit tests for the {language} code below, taken from \
+     {path}. Use the language's usual test framework, cover edge cases and failure paths, and \
+     import the code under test rather than copying it. Reply with the complete test file in a \
+     single code block.\n\n{code}";
+
+// Fills in `{language}`, `{path}` and `{code}`; the code is appended if the template
+// doesn't mention it.
+pub fn build_prompt(template: &str, language: &str, path: &str, code: &str) -> String {
+    let fenced = format!("```{}\n{}\n```", language, code.trim_end());
+    let prompt = template
+        .replace("{language}", language)
+        .replace("{path}", path);
+    if prompt.contains("{code}") {
+        prompt.replace("{code}", &fenced)
+    } else {
+        format!("{}\n\n{}", prompt, fenced)
+    }
+}
+
+// Where tests for `source` (a file with the given extension) usually live.
+pub fn suggested_path(source: &Path, extension: &str) -> String {
+    let stem = source
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("snippet");
+    let beside = |name: String| {
+        source
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map_or_else(|| name.clone(), |dir| dir.join(&name).display().to_string())
+    };
+    match extension {
+        "rs" => format!("tests/{}.rs", stem),
+        "py" => format!("tests/test_{}.py", stem),
+        "js" | "ts" | "jsx" | "tsx" => beside(format!("{}.test.{}", stem, extension)),
+        "go" => beside(format!("{}_test.go", stem)),
+        "rb" => format!("spec/{}_spec.rb", stem),
+        "java" => beside(format!("{}Test.java", stem)),
+        _ => format!("tests/test_{}.{}", stem, extension),
+    }
+}
+
+// The test file from a response: its first code block, or the whole reply when
+// there's none.
+pub fn extract(response: &str) -> String {
+    let mut contents = code_blocks::code_blocks(response).first().map_or_else(
+        || response.trim().to_string(),
+        |block| block.lines.join("\n"),
+    );
+    contents.push('\n');
+    contents
+}
@@ -0,0 +1,50 @@
+// Share of the context window past which answers tend to lose track of the start.
+const LONG_RATIO: f64 = 0.6;
+const FULL_RATIO: f64 = 0.9;
+const STALE_DAYS: u64 = 30;
+
+// Describes why a conversation might be getting unreliable, if it is: its history
+// takes up most of the context window or it was started long ago.
+pub fn warning(tokens: usize, limit: usize, age_days: Option<u64>) -> Option<String> {
+    let mut reasons = Vec::new();
+    let ratio = tokens as f64 / limit.max(1) as f64;
+    if ratio >= FULL_RATIO {
+        reasons.push(format!("{:.0}% of context, nearly full", ratio * 100.0));
+    } else if ratio >= LONG_RATIO {
+        reasons.push(format!("{:.0}% of context", ratio * 100.0));
+    }
+    if let Some(days) = age_days.filter(|&days| days >= STALE_DAYS) {
+        reasons.push(format!("{} days old", days));
+    }
+    if reasons.is_empty() {
+        None
+    } else {
+        Some(reasons.join(", "))
+    }
+}
+
+// Days between a `YYYY-MM-DD...` timestamp and `now_secs` (Unix time).
+pub fn age_days(datetime: &str, now_secs: u64) -> Option<u64> {
+    let start = days_since_epoch(datetime)?;
+    let today = (now_secs / 86_400) as i64;
+    u64::try_from(today - start).ok()
+}
+
+// The day of a `YYYY-MM-DD...` timestamp, counted from 1970-01-01.
+pub fn days_since_epoch(datetime: &str) -> Option<i64> {
+    let mut parts = datetime.get(..10)?.split('-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    Some(days_from_civil(year, month, day))
+}
+
+// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
@@ -0,0 +1,98 @@
+use ratatui::style::{Color, Modifier, Style};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{FontStyle, Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+
+pub const DEFAULT_THEME: &str = "base16-ocean.dark";
+// Highlighted blocks kept between frames; the chat is redrawn on every streamed
+// chunk, so re-highlighting everything each time would be too slow.
+const CACHE_SIZE: usize = 256;
+
+// Each line of a block as (style, text) runs.
+pub type StyledLines = Arc<Vec<Vec<(Style, String)>>>;
+
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn themes() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+fn cache() -> &'static Mutex<HashMap<u64, StyledLines>> {
+    static CACHE: OnceLock<Mutex<HashMap<u64, StyledLines>>> = OnceLock::new();
+    CACHE.get_or_init(Mutex::default)
+}
+
+// Colors a code block, or `None` when the language is unknown (or has no syntax
+// bundled), in which case it's shown plain.
+pub fn highlight(lines: &[String], language: Option<&str>, theme: &str) -> Option<StyledLines> {
+    let syntaxes = syntaxes();
+    let syntax = syntaxes.find_syntax_by_token(language?)?;
+    let theme = themes()
+        .themes
+        .get(theme)
+        .or_else(|| themes().themes.get(DEFAULT_THEME))?;
+
+    let mut hasher = DefaultHasher::new();
+    (&syntax.name, &theme.name, lines).hash(&mut hasher);
+    let key = hasher.finish();
+    if let Some(styled) = cache().lock().unwrap().get(&key) {
+        return Some(Arc::clone(styled));
+    }
+
+    let styled = Arc::new(highlight_lines(lines, syntax, theme, syntaxes)?);
+    let mut cache = cache().lock().unwrap();
+    if cache.len() >= CACHE_SIZE {
+        cache.clear();
+    }
+    cache.insert(key, Arc::clone(&styled));
+    Some(styled)
+}
+
+fn highlight_lines(
+    lines: &[String],
+    syntax: &syntect::parsing::SyntaxReference,
+    theme: &Theme,
+    syntaxes: &SyntaxSet,
+) -> Option<Vec<Vec<(Style, String)>>> {
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    lines
+        .iter()
+        .map(|line| {
+            let line = format!("{}\n", line.replace('\t', "    "));
+            let ranges = highlighter.highlight_line(&line, syntaxes).ok()?;
+            Some(
+                ranges
+                    .into_iter()
+                    .map(|(style, text)| {
+                        (to_ratatui(style), text.trim_end_matches('\n').to_string())
+                    })
+                    .filter(|(_, text)| !text.is_empty())
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+// Only the foreground and font style; the chat picks the background.
+fn to_ratatui(style: syntect::highlighting::Style) -> Style {
+    let fg = style.foreground;
+    let mut converted = Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b));
+    if style.font_style.contains(FontStyle::BOLD) {
+        converted = converted.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        converted = converted.add_modifier(Modifier::ITALIC);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        converted = converted.add_modifier(Modifier::UNDERLINED);
+    }
+    converted
+}
@@ -0,0 +1,127 @@
+use serde_json::Value;
+
+// One prompt/response pair exactly as llm logged it, before any local hiding,
+// deletion or response filtering.
+pub struct Exchange {
+    pub log_id: Option<String>,
+    pub datetime: String,
+    pub model: String,
+    pub system: Option<String>,
+    pub prompt: String,
+    pub response: String,
+    // Model options such as temperature, as logged.
+    pub options: Value,
+    pub fragments: Vec<String>,
+    pub attachments: Vec<String>,
+    // Tools the model called, as `name(arguments)`.
+    pub tool_calls: Vec<String>,
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+    pub duration_ms: Option<u64>,
+}
+
+// Reads every logged exchange of the given llm conversations, oldest first.
+pub fn load(conversation_ids: &[String]) -> Result<Vec<Exchange>, String> {
+    let mut command = crate::llm_command();
+    command.args(["logs", "list", "--json", "-n", "0"]);
+    // llm takes one `--cid`; for several, the whole log is filtered.
+    if let [conversation_id] = conversation_ids {
+        command.args(["--cid", conversation_id]);
+    }
+    let output = command
+        .output()
+        .map_err(|e| format!("Failed to run llm: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let logs: Value = serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
+    let mut exchanges: Vec<Exchange> = logs
+        .as_array()
+        .map(|logs| {
+            logs.iter()
+                .filter(|log| {
+                    log["conversation_id"]
+                        .as_str()
+                        .is_some_and(|id| conversation_ids.iter().any(|wanted| wanted == id))
+                })
+                .map(parse_exchange)
+                .collect()
+        })
+        .unwrap_or_default();
+    exchanges.sort_by(|a, b| a.datetime.cmp(&b.datetime));
+    Ok(exchanges)
+}
+
+// Finds the exchange logged as `log_id`.
+pub fn find(conversation_ids: &[String], log_id: &str) -> Result<Option<Exchange>, String> {
+    Ok(load(conversation_ids)?
+        .into_iter()
+        .find(|exchange| exchange.log_id.as_deref() == Some(log_id)))
+}
+
+fn parse_exchange(log: &Value) -> Exchange {
+    let text = |key: &str| log[key].as_str().unwrap_or_default().to_string();
+    Exchange {
+        log_id: log["id"].as_str().map(str::to_string),
+        datetime: text("datetime_utc"),
+        model: text("model"),
+        system: log["system"]
+            .as_str()
+            .filter(|system| !system.is_empty())
+            .map(str::to_string),
+        prompt: text("prompt"),
+        response: text("response"),
+        options: match &log["options_json"] {
+            // Older llm versions log the options as a JSON string.
+            Value::String(json) => serde_json::from_str(json).unwrap_or(Value::Null),
+            options => options.clone(),
+        },
+        fragments: describe_each(&log["fragments"], &["source", "hash"]),
+        attachments: describe_each(&log["attachments"], &["path", "url", "type"]),
+        tool_calls: log["tool_calls"]
+            .as_array()
+            .map(|calls| {
+                calls
+                    .iter()
+                    .map(|call| {
+                        format!(
+                            "{}({})",
+                            call["name"].as_str().unwrap_or("?"),
+                            compact(&call["arguments"])
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        input_tokens: log["input_tokens"].as_u64(),
+        output_tokens: log["output_tokens"].as_u64(),
+        duration_ms: log["duration_ms"].as_u64(),
+    }
+}
+
+// Names each item of a logged list by the first of `keys` it has.
+fn describe_each(items: &Value, keys: &[&str]) -> Vec<String> {
+    items
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .map(|item| match item {
+                    Value::String(text) => text.clone(),
+                    item => keys
+                        .iter()
+                        .find_map(|key| item[*key].as_str())
+                        .map_or_else(|| compact(item), str::to_string),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn compact(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
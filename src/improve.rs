@@ -0,0 +1,43 @@
+use unicode_width::UnicodeWidthStr;
+
+pub const DEFAULT_TEMPLATE: &str = "You are a prompt engineer. Rewrite the prompt below so a \
+     language model answers it better: make the goal, context, constraints and expected output \
+     format explicit, and fix ambiguities. Keep the user's intent and language. Reply with only \
+     the rewritten prompt.\n\n{prompt}";
+
+// Fills in `{prompt}`, or appends the draft if the template doesn't mention it.
+pub fn build_prompt(template: &str, draft: &str) -> String {
+    if template.contains("{prompt}") {
+        template.replace("{prompt}", draft)
+    } else {
+        format!("{}\n\n{}", template, draft)
+    }
+}
+
+// Lays out two texts as columns of `width` characters separated by a bar.
+pub fn side_by_side(left: &str, right: &str, width: usize) -> Vec<String> {
+    let wrap = |text: &str| -> Vec<String> {
+        text.lines()
+            .flat_map(|line| {
+                let wrapped = textwrap::wrap(line, width);
+                if wrapped.is_empty() {
+                    vec![String::new()]
+                } else {
+                    wrapped.into_iter().map(String::from).collect()
+                }
+            })
+            .collect()
+    };
+    let (left, right) = (wrap(left), wrap(right));
+    (0..left.len().max(right.len()))
+        .map(|i| {
+            let cell = left.get(i).map_or("", String::as_str);
+            format!(
+                "{}{} │ {}",
+                cell,
+                " ".repeat(width.saturating_sub(cell.width())),
+                right.get(i).map_or("", String::as_str)
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,120 @@
+use std::ops::Deref;
+
+enum Edit {
+    Insert { at: usize, text: String },
+    Delete { at: usize, text: String },
+    // Wholesale changes such as clearing the input or a search-and-replace.
+    Replace { before: String, after: String },
+}
+
+// The prompt being composed, kept as a history of edits so they can be undone.
+// Typing is grouped roughly by word so one undo doesn't remove a single character.
+#[derive(Default)]
+pub struct InputBuffer {
+    text: String,
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl InputBuffer {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn push(&mut self, c: char) {
+        let at = self.text.len();
+        self.text.push(c);
+        self.redo.clear();
+        if let Some(Edit::Insert { at: start, text }) = self.undo.last_mut() {
+            let word_boundary = c.is_whitespace() && !text.ends_with(char::is_whitespace);
+            if *start + text.len() == at && c != '\n' && !word_boundary {
+                text.push(c);
+                return;
+            }
+        }
+        self.undo.push(Edit::Insert {
+            at,
+            text: c.to_string(),
+        });
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        self.undo.push(Edit::Insert {
+            at: self.text.len(),
+            text: s.to_string(),
+        });
+        self.redo.clear();
+        self.text.push_str(s);
+    }
+
+    pub fn pop(&mut self) -> Option<char> {
+        let c = self.text.pop()?;
+        let at = self.text.len();
+        self.redo.clear();
+        if let Some(Edit::Delete { at: start, text }) = self.undo.last_mut() {
+            if *start == at + c.len_utf8() {
+                text.insert(0, c);
+                *start = at;
+                return Some(c);
+            }
+        }
+        self.undo.push(Edit::Delete {
+            at,
+            text: c.to_string(),
+        });
+        Some(c)
+    }
+
+    pub fn set(&mut self, text: String) {
+        if text == self.text {
+            return;
+        }
+        let before = std::mem::replace(&mut self.text, text);
+        self.undo.push(Edit::Replace {
+            before,
+            after: self.text.clone(),
+        });
+        self.redo.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.set(String::new());
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let Some(edit) = self.undo.pop() else {
+            return false;
+        };
+        match &edit {
+            Edit::Insert { at, text } => self.text.replace_range(*at..*at + text.len(), ""),
+            Edit::Delete { at, text } => self.text.insert_str(*at, text),
+            Edit::Replace { before, .. } => self.text = before.clone(),
+        }
+        self.redo.push(edit);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(edit) = self.redo.pop() else {
+            return false;
+        };
+        match &edit {
+            Edit::Insert { at, text } => self.text.insert_str(*at, text),
+            Edit::Delete { at, text } => self.text.replace_range(*at..*at + text.len(), ""),
+            Edit::Replace { after, .. } => self.text = after.clone(),
+        }
+        self.undo.push(edit);
+        true
+    }
+}
+
+impl Deref for InputBuffer {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
@@ -0,0 +1,172 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde_json::Value;
+
+// The keys that do the same thing wherever the focus is (outside text input), and
+// can be rebound in the `[keys]` table.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Quit,
+    NextFocus,
+    ToggleList,
+    EditInput,
+    ProjectMode,
+    DoNotDisturb,
+    ReviewRemote,
+    PauseBackground,
+}
+
+const ACTIONS: &[(Action, &str, KeyCode)] = &[
+    (Action::Quit, "quit", KeyCode::Char('q')),
+    (Action::NextFocus, "next_focus", KeyCode::Tab),
+    (Action::ToggleList, "toggle_list", KeyCode::Char('h')),
+    (Action::EditInput, "edit_input", KeyCode::Char('i')),
+    (Action::ProjectMode, "project_mode", KeyCode::Char('P')),
+    (Action::DoNotDisturb, "do_not_disturb", KeyCode::Char('D')),
+    (Action::ReviewRemote, "review_remote", KeyCode::Char('A')),
+    (
+        Action::PauseBackground,
+        "pause_background",
+        KeyCode::Char('Z'),
+    ),
+];
+
+#[derive(Clone, Copy, PartialEq)]
+struct Key {
+    code: KeyCode,
+    // Only Ctrl and Alt; Shift is part of the character.
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    fn of(event: &KeyEvent) -> Key {
+        Key {
+            code: event.code,
+            modifiers: event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Keymap {
+    // Rebound actions and their new keys.
+    bindings: Vec<(Action, Key)>,
+}
+
+impl Keymap {
+    // Reads `action = "key"` pairs such as `quit = "ctrl+q"`, skipping unknown
+    // actions and keys that don't parse.
+    pub fn apply(&mut self, value: &Value) {
+        let Some(table) = value.as_object() else {
+            return;
+        };
+        for (name, key) in table {
+            let action = ACTIONS.iter().find(|(_, n, _)| n == name);
+            let key = key.as_str().and_then(parse);
+            if let (Some((action, _, _)), Some(key)) = (action, key) {
+                self.bindings.retain(|(bound, _)| bound != action);
+                self.bindings.push((*action, key));
+            }
+        }
+    }
+
+    // The key event as the built-in bindings know it: a rebound key becomes its
+    // action's default key, and the default key of a rebound action does nothing.
+    pub fn translate(&self, event: KeyEvent) -> Option<KeyEvent> {
+        let key = Key::of(&event);
+        if let Some((action, _)) = self.bindings.iter().find(|(_, bound)| *bound == key) {
+            return Some(KeyEvent::new(default_key(*action), KeyModifiers::NONE));
+        }
+        let freed = self
+            .bindings
+            .iter()
+            .any(|(action, _)| key.modifiers.is_empty() && key.code == default_key(*action));
+        (!freed).then_some(event)
+    }
+
+    // How the key for `action` is shown in hints, e.g. `Ctrl+Q`.
+    pub fn label(&self, action: Action) -> String {
+        let key = self
+            .bindings
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .map_or(
+                Key {
+                    code: default_key(action),
+                    modifiers: KeyModifiers::NONE,
+                },
+                |(_, key)| *key,
+            );
+        let mut label = String::new();
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            label.push_str("Ctrl+");
+        }
+        if key.modifiers.contains(KeyModifiers::ALT) {
+            label.push_str("Alt+");
+        }
+        match key.code {
+            KeyCode::Char(' ') => label.push_str("Space"),
+            KeyCode::Char(c) if !key.modifiers.is_empty() => label.push(c.to_ascii_uppercase()),
+            KeyCode::Char(c) => label.push(c),
+            KeyCode::F(n) => label.push_str(&format!("F{}", n)),
+            KeyCode::BackTab => label.push_str("Shift+Tab"),
+            code => label.push_str(&format!("{:?}", code)),
+        }
+        label
+    }
+}
+
+fn default_key(action: Action) -> KeyCode {
+    ACTIONS
+        .iter()
+        .find(|(a, _, _)| *a == action)
+        .map_or(KeyCode::Null, |(_, _, code)| *code)
+}
+
+// "q", "Q", "ctrl+q", "alt+enter", "f2", "shift+tab", ...
+fn parse(text: &str) -> Option<Key> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = text.trim();
+    let mut shift = false;
+    loop {
+        let lower = rest.to_lowercase();
+        if lower.starts_with("ctrl+") {
+            modifiers |= KeyModifiers::CONTROL;
+        } else if lower.starts_with("alt+") {
+            modifiers |= KeyModifiers::ALT;
+        } else if lower.starts_with("shift+") {
+            shift = true;
+        } else {
+            break;
+        }
+        rest = &rest[rest.find('+')? + 1..];
+    }
+    let code = match rest.to_lowercase().as_str() {
+        "tab" if shift => KeyCode::BackTab,
+        "tab" => KeyCode::Tab,
+        "enter" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "space" => KeyCode::Char(' '),
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        name => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if shift => KeyCode::Char(c.to_ascii_uppercase()),
+                // Terminals report Ctrl+letter as the lowercase letter.
+                (Some(c), None) if !modifiers.is_empty() => KeyCode::Char(c.to_ascii_lowercase()),
+                (Some(c), None) => KeyCode::Char(c),
+                _ => KeyCode::F(name.strip_prefix('f')?.parse().ok()?),
+            }
+        }
+    };
+    Some(Key { code, modifiers })
+}
@@ -0,0 +1,216 @@
+// Heuristic language guessing for code blocks whose fence has no language tag.
+// Each language has a handful of telltale substrings; the best-scoring language
+// wins as long as it clears a minimum score, otherwise the block stays unlabeled.
+
+const MIN_SCORE: usize = 2;
+
+const SIGNATURES: &[(&str, &[&str])] = &[
+    (
+        "rust",
+        &[
+            "fn ", "let mut ", "impl ", "pub fn", "::", "-> ", "println!", "use std", "&self",
+            "match ", "Some(", "Ok(",
+        ],
+    ),
+    (
+        "python",
+        &[
+            "def ", "import ", "from ", "self.", "elif ", "print(", "__init__", "None", "True",
+            "False", "lambda ",
+        ],
+    ),
+    (
+        "javascript",
+        &[
+            "const ",
+            "function ",
+            "=> ",
+            "console.log",
+            "require(",
+            "let ",
+            "===",
+            "export ",
+            "document.",
+        ],
+    ),
+    (
+        "typescript",
+        &[
+            "interface ",
+            ": string",
+            ": number",
+            ": boolean",
+            "export type ",
+            "as const",
+            "<T>",
+        ],
+    ),
+    (
+        "go",
+        &[
+            "package ",
+            "func ",
+            ":= ",
+            "fmt.",
+            "err != nil",
+            "import (",
+            "go func",
+        ],
+    ),
+    (
+        "c",
+        &[
+            "#include <",
+            "int main(",
+            "printf(",
+            "malloc(",
+            "->",
+            "NULL",
+        ],
+    ),
+    (
+        "cpp",
+        &[
+            "#include <",
+            "std::",
+            "cout <<",
+            "template <",
+            "nullptr",
+            "namespace ",
+        ],
+    ),
+    (
+        "java",
+        &[
+            "public class ",
+            "public static void",
+            "System.out",
+            "private ",
+            "@Override",
+            "new ",
+        ],
+    ),
+    (
+        "ruby",
+        &[
+            "def ",
+            "\nend",
+            "puts ",
+            "require '",
+            "do |",
+            ".each ",
+            "attr_",
+        ],
+    ),
+    (
+        "bash",
+        &[
+            "#!/bin/", "echo ", "sudo ", "export ", "\nfi", "; then", "$(", "${", "apt ", "| grep",
+        ],
+    ),
+    (
+        "sql",
+        &[
+            "SELECT ",
+            "FROM ",
+            "WHERE ",
+            "INSERT INTO",
+            "CREATE TABLE",
+            "JOIN ",
+            "GROUP BY",
+        ],
+    ),
+    (
+        "html",
+        &[
+            "<html",
+            "<div",
+            "</",
+            "<body",
+            "<head",
+            "class=\"",
+            "<!DOCTYPE",
+        ],
+    ),
+    (
+        "css",
+        &[
+            "px;", "color:", "margin", "padding", "display:", "font-", "em;",
+        ],
+    ),
+    ("diff", &["@@ ", "+++ ", "--- ", "diff --git"]),
+];
+
+pub fn detect(lines: &[String]) -> Option<&'static str> {
+    let first = lines.iter().find(|line| !line.trim().is_empty())?.trim();
+
+    if let Some(interpreter) = first.strip_prefix("#!") {
+        return shebang_language(interpreter);
+    }
+    if looks_like_json(lines) {
+        return Some("json");
+    }
+
+    let text = lines.join("\n");
+    SIGNATURES
+        .iter()
+        .map(|(language, patterns)| {
+            let score = patterns
+                .iter()
+                .filter(|pattern| text.contains(*pattern))
+                .count();
+            (score, *language)
+        })
+        .filter(|(score, _)| *score >= MIN_SCORE)
+        // `max_by_key` keeps the last maximum, so reverse to prefer earlier entries on ties.
+        .rev()
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, language)| language)
+}
+
+fn shebang_language(interpreter: &str) -> Option<&'static str> {
+    let program = interpreter.split_whitespace().last()?;
+    let program = program.rsplit('/').next()?;
+    match program {
+        "bash" | "sh" | "zsh" => Some("bash"),
+        p if p.starts_with("python") => Some("python"),
+        "node" => Some("javascript"),
+        "ruby" => Some("ruby"),
+        "perl" => Some("perl"),
+        _ => None,
+    }
+}
+
+fn looks_like_json(lines: &[String]) -> bool {
+    let text = lines.join("\n");
+    let text = text.trim();
+    (text.starts_with('{') && text.ends_with('}') || text.starts_with('[') && text.ends_with(']'))
+        && serde_json::from_str::<serde_json::Value>(text).is_ok()
+}
+
+pub fn extension(language: &str) -> &'static str {
+    match language.to_ascii_lowercase().as_str() {
+        "rust" | "rs" => "rs",
+        "python" | "py" => "py",
+        "javascript" | "js" | "node" => "js",
+        "typescript" | "ts" => "ts",
+        "tsx" => "tsx",
+        "jsx" => "jsx",
+        "go" | "golang" => "go",
+        "c" => "c",
+        "cpp" | "c++" | "cxx" => "cpp",
+        "java" => "java",
+        "ruby" | "rb" => "rb",
+        "bash" | "sh" | "shell" | "zsh" | "console" => "sh",
+        "sql" => "sql",
+        "html" => "html",
+        "css" => "css",
+        "json" => "json",
+        "yaml" | "yml" => "yml",
+        "toml" => "toml",
+        "markdown" | "md" => "md",
+        "diff" | "patch" => "diff",
+        "perl" | "pl" => "pl",
+        _ => "txt",
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// Prompt snippets and system personas: text files under `prompts/` and
+// `personas/`, named after the file without its extension.
+#[derive(Default)]
+pub struct Library {
+    pub prompts: BTreeMap<String, Entry>,
+    pub personas: BTreeMap<String, Entry>,
+}
+
+pub struct Entry {
+    pub text: String,
+    // From the local directory rather than the shared repository.
+    pub local: bool,
+}
+
+impl Library {
+    // Reads the shared checkout, then the local directory over it, so local files
+    // replace shared ones of the same name.
+    pub fn load(shared: Option<&Path>, local: Option<&Path>) -> Self {
+        let mut library = Library::default();
+        for (dir, is_local) in [(shared, false), (local, true)] {
+            let Some(dir) = dir else {
+                continue;
+            };
+            read_entries(&dir.join("prompts"), is_local, &mut library.prompts);
+            read_entries(&dir.join("personas"), is_local, &mut library.personas);
+        }
+        library
+    }
+}
+
+fn read_entries(dir: &Path, local: bool, entries: &mut BTreeMap<String, Entry>) {
+    let Ok(files) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in files.filter_map(Result::ok).map(|entry| entry.path()) {
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        if name.starts_with('.') || !path.is_file() {
+            continue;
+        }
+        if let Ok(text) = fs::read_to_string(&path) {
+            entries.insert(
+                name.to_string(),
+                Entry {
+                    text: text.trim().to_string(),
+                    local,
+                },
+            );
+        }
+    }
+}
+
+// Clones `repo` into `dir`, or fast-forwards an existing clone of it. A clone of
+// another repository (the setting changed) is replaced.
+pub fn sync(repo: &str, dir: &Path) -> Result<(), String> {
+    let mut command = Command::new("git");
+    if dir.join(".git").exists() && origin(dir).as_deref() != Some(repo) {
+        fs::remove_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    if dir.join(".git").exists() {
+        command
+            .arg("-C")
+            .arg(dir)
+            .args(["pull", "--ff-only", "--quiet"]);
+    } else {
+        if let Some(parent) = dir.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        command
+            .args(["clone", "--depth", "1", "--quiet", "--", repo])
+            .arg(dir);
+    }
+    let output = command
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+fn origin(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["remote", "get-url", "origin"])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,169 @@
+use crate::health;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// Below this the name column is too cramped to read, so trailing columns are
+// dropped instead.
+const MIN_NAME_WIDTH: usize = 12;
+const MODEL_WIDTH: usize = 10;
+
+// What the conversation list shows for each conversation, set with
+// `[conversation_list] columns`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Column {
+    // The name followed by the tags; it takes whatever width is left.
+    Name,
+    // The model of the latest exchange.
+    Model,
+    Messages,
+    // Time since the latest exchange, e.g. `5m` or `3d`.
+    Activity,
+    // Pinned context and unread responses.
+    Markers,
+}
+
+pub const DEFAULT_COLUMNS: &[Column] = &[
+    Column::Name,
+    Column::Model,
+    Column::Messages,
+    Column::Activity,
+    Column::Markers,
+];
+
+impl Column {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(Column::Name),
+            "model" => Some(Column::Model),
+            "messages" => Some(Column::Messages),
+            "activity" => Some(Column::Activity),
+            "markers" => Some(Column::Markers),
+            _ => None,
+        }
+    }
+
+    // `None` for the name, which is as wide as it can be.
+    pub fn width(self) -> Option<usize> {
+        match self {
+            Column::Name => None,
+            Column::Model => Some(MODEL_WIDTH),
+            Column::Messages => Some(4),
+            Column::Activity => Some(4),
+            Column::Markers => Some(4),
+        }
+    }
+}
+
+// The columns that fit in `width`, in order, and how wide the name can be. A
+// single space separates columns.
+pub fn fit_columns(columns: &[Column], width: usize) -> (Vec<Column>, usize) {
+    let mut columns = columns.to_vec();
+    if !columns.contains(&Column::Name) {
+        columns.insert(0, Column::Name);
+    }
+    loop {
+        let fixed: usize = columns
+            .iter()
+            .filter_map(|c| c.width())
+            .map(|w| w + 1)
+            .sum();
+        let name_width = width.saturating_sub(fixed);
+        if name_width >= MIN_NAME_WIDTH || columns.len() == 1 {
+            return (columns, name_width);
+        }
+        if let Some(last) = columns.iter().rposition(|&c| c != Column::Name) {
+            columns.remove(last);
+        }
+    }
+}
+
+// The order of the conversation list, set with `[conversation_list] sort` and
+// cycled with `o`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortKey {
+    // The order llm's logs list them in.
+    Log,
+    // Most recently active first.
+    Recent,
+    Name,
+    // Most messages first.
+    Messages,
+    Model,
+}
+
+impl SortKey {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "log" => Some(SortKey::Log),
+            "recent" => Some(SortKey::Recent),
+            "name" => Some(SortKey::Name),
+            "messages" => Some(SortKey::Messages),
+            "model" => Some(SortKey::Model),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::Log => "log",
+            SortKey::Recent => "recent",
+            SortKey::Name => "name",
+            SortKey::Messages => "messages",
+            SortKey::Model => "model",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            SortKey::Log => SortKey::Recent,
+            SortKey::Recent => SortKey::Name,
+            SortKey::Name => SortKey::Messages,
+            SortKey::Messages => SortKey::Model,
+            SortKey::Model => SortKey::Log,
+        }
+    }
+}
+
+// Unix time of a `YYYY-MM-DDTHH:MM:SS...` UTC timestamp, as llm logs them.
+pub fn timestamp_secs(datetime: &str) -> Option<u64> {
+    let days = health::days_since_epoch(datetime)?;
+    let mut time = datetime.get(11..19)?.split(':');
+    let hours: u64 = time.next()?.parse().ok()?;
+    let minutes: u64 = time.next()?.parse().ok()?;
+    let seconds: u64 = time.next()?.parse().ok()?;
+    Some(u64::try_from(days).ok()? * 86_400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+// How long ago `secs` was, in its largest unit.
+pub fn ago(secs: u64, now: u64) -> String {
+    let elapsed = now.saturating_sub(secs);
+    match elapsed {
+        0..60 => "now".to_string(),
+        60..3600 => format!("{}m", elapsed / 60),
+        3600..86_400 => format!("{}h", elapsed / 3600),
+        86_400..604_800 => format!("{}d", elapsed / 86_400),
+        604_800..31_536_000 => format!("{}w", elapsed / 604_800),
+        _ => format!("{}y", elapsed / 31_536_000),
+    }
+}
+
+// `text` cut to `width` columns (with an ellipsis) or padded to it.
+pub fn pad(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return format!("{}{}", text, " ".repeat(width - text.width()));
+    }
+    let mut cut = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        cut.push(c);
+        used += w;
+    }
+    if width > 0 {
+        cut.push('…');
+        used += 1;
+    }
+    cut + &" ".repeat(width.saturating_sub(used))
+}
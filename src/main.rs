@@ -1,32 +1,99 @@
 use copypasta::{ClipboardContext, ClipboardProvider};
-use ratatui::widgets::Gauge;
-use std::io::{BufRead, BufReader, Read};
-use std::process::{Command, Stdio};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::env;
+use std::fs;
+use std::io::{BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Child, Command, Stdio};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    cursor::Show,
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{
+        self, disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+    },
 };
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
     Frame, Terminal,
 };
 use serde_json::Value;
 use std::io;
-use std::io::Write;
-use std::net::{TcpListener, TcpStream};
+use std::net::TcpListener;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crossbeam_channel::{unbounded, Receiver as CrossbeamReceiver, Sender as CrossbeamSender};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+mod anki;
+mod batch;
+mod bundle;
+mod client;
+mod code_blocks;
+mod completion;
+mod config;
+mod context;
+mod digest;
+mod duplicates;
+mod eval;
+mod fetch;
+mod filters;
+mod fuzzy;
+mod gen_tests;
+mod health;
+mod highlight;
+mod history;
+mod improve;
+mod input;
+mod keys;
+mod language;
+mod library;
+mod listing;
+mod markdown;
+mod mentions;
+mod notes;
+mod paste;
+mod preferences;
+mod profile;
+mod project;
+mod python;
+mod remote;
+mod rpc;
+mod sandbox;
+mod search;
+mod session;
+mod store;
+mod suggestions;
+mod summary;
+mod templates;
+mod theme;
+mod tokens;
+
+use code_blocks::Segment;
+use config::Config;
+use input::InputBuffer;
+use keys::Action;
+use library::Library;
+use listing::{Column, SortKey};
+use preferences::Preferences;
+use project::ProjectIndex;
+use remote::{RemoteCommand, RemoteError};
+use session::SessionStats;
+use theme::Theme;
 
 // Modify your AppState enum
 #[derive(Clone, PartialEq)]
@@ -53,12 +120,96 @@ struct Conversation {
     id: String,
     name: String,
     messages: Vec<Message>,
+    // Working directory the conversation was started from.
+    directory: Option<PathBuf>,
+    // Free-form text pinned with `/pin`, sent along with every prompt.
+    snippets: Vec<String>,
+    // `/filters` settings layered over the `[filters]` config table.
+    filter_overrides: Value,
+    // Set with `/env` and exported to commands run for this conversation.
+    env: BTreeMap<String, String>,
+    // Response language and style, added to the system prompt.
+    preferences: Preferences,
+    // Written by the summarize action and shown under the name in the list.
+    summary: Option<String>,
+    // Topic tags from the summary, for filtering the list.
+    tags: Vec<String>,
+    // When the first logged exchange happened (`datetime_utc`), if known.
+    started: Option<String>,
+    // The model and Unix time of the latest exchange.
+    model: Option<String>,
+    last_active: Option<u64>,
+    // Responses that arrived while another conversation was open.
+    unread: usize,
 }
 
 #[derive(Clone)]
 struct Message {
     role: String,
     content: String,
+    // Per-message override of `Config::wrap_code`; `None` follows the config.
+    wrap_code: Option<bool>,
+    code_scroll: usize,
+    // Project files attached to this prompt as `llm -f` fragments.
+    fragments: Vec<String>,
+    // The `llm logs` entry this message came from; `None` until the logs are reloaded.
+    log_id: Option<String>,
+    // Hidden messages stay visible here but are left out of the conversation context.
+    hidden: bool,
+    // Position among the pinned messages, which are always sent first.
+    pin: Option<usize>,
+    // The response exactly as the model returned it, when filters changed it.
+    raw: Option<String>,
+    // Per-message override of `App::show_raw`.
+    show_raw: Option<bool>,
+    // Still receiving chunks from `llm`.
+    streaming: bool,
+    // Names from `REACTIONS`, in that order.
+    reactions: Vec<String>,
+    // The seed a response was generated with, if one was set.
+    seed: Option<u64>,
+    // A response that is the error `llm` failed with rather than an answer.
+    failed: bool,
+}
+
+impl Message {
+    fn new(role: &str, content: String) -> Self {
+        Message {
+            role: role.to_string(),
+            content,
+            wrap_code: None,
+            code_scroll: 0,
+            fragments: Vec::new(),
+            log_id: None,
+            hidden: false,
+            pin: None,
+            raw: None,
+            show_raw: None,
+            streaming: false,
+            reactions: Vec::new(),
+            seed: None,
+            failed: false,
+        }
+    }
+
+    fn raw_content(&self) -> &str {
+        self.raw.as_deref().unwrap_or(&self.content)
+    }
+
+    fn reaction_emojis(&self) -> String {
+        REACTIONS
+            .iter()
+            .filter(|(_, name, _)| self.reactions.iter().any(|r| r == name))
+            .map(|(_, _, emoji)| *emoji)
+            .collect()
+    }
+
+    // Key for this message in `MESSAGE_OVERRIDES_FILE`.
+    fn override_key(&self) -> Option<String> {
+        self.log_id
+            .as_ref()
+            .map(|log_id| format!("{}/{}", log_id, self.role))
+    }
 }
 
 enum InputMode {
@@ -72,13 +223,88 @@ enum FocusedBlock {
     Chat,
     Input,
 }
+const CODE_PAN_STEP: isize = 4;
+const CONVERSATION_DIRS_FILE: &str = "conversation_dirs.json";
+const DIGEST_ID: &str = "digest";
+const DIGEST_FILE: &str = "digest.json";
+// Messages hidden from context or deleted locally, as `"<log id>/<role>": "hidden" | "deleted"`.
+const MESSAGE_OVERRIDES_FILE: &str = "message_overrides.json";
+// Ids of conversations deleted from the list; llm's logs keep them.
+const DELETED_CONVERSATIONS_FILE: &str = "deleted_conversations.json";
+// Personal reactions to messages, as `"<log id>/<role>": ["star", ...]`.
+const MESSAGE_REACTIONS_FILE: &str = "message_reactions.json";
+// Chat key, name and emoji of each reaction.
+const REACTIONS: &[(char, &str, &str)] = &[
+    ('+', "up", "👍"),
+    ('-', "down", "👎"),
+    ('*', "star", "⭐"),
+    ('!', "flag", "🚩"),
+];
+// Pinned context messages, as `"<log id>/<role>": position`.
+const CONTEXT_PINS_FILE: &str = "context_pins.json";
+// Text pinned to conversations with `/pin`, as `"<conversation id>": [text, ...]`.
+const PINNED_SNIPPETS_FILE: &str = "pinned_snippets.json";
+// Per-conversation `/filters` overrides, as `"<conversation id>": {"max_width": 72, ...}`.
+const CONVERSATION_FILTERS_FILE: &str = "conversation_filters.json";
+// Per-conversation `/env` variables, as `"<conversation id>": {"KEY": "value", ...}`.
+const CONVERSATION_ENV_FILE: &str = "conversation_env.json";
+// Per-conversation response preferences, as
+// `"<conversation id>": {"language": "French", "max_words": 200, ...}`.
+const CONVERSATION_PREFERENCES_FILE: &str = "conversation_preferences.json";
+// Conversation summaries, as `"<conversation id>": "summary"`.
+const CONVERSATION_SUMMARIES_FILE: &str = "conversation_summaries.json";
+// Names given with `r` in the conversation list, by conversation id.
+const CONVERSATION_NAMES_FILE: &str = "conversation_names.json";
+// Topic tags, as `"<conversation id>": ["tag", ...]`.
+const CONVERSATION_TAGS_FILE: &str = "conversation_tags.json";
+// The conversation each exchange sent from here belongs to, as
+// `"<llm conversation id>": "<conversation id>"`. The app sends the history itself
+// rather than continuing llm's conversation, so llm logs every exchange as a
+// conversation of its own; this puts them back together.
+const CONVERSATION_LINKS_FILE: &str = "conversation_links.json";
+// Pinned snippets starting with this are replaced when the conversation is
+// summarized again.
+const SUMMARY_PREFIX: &str = "Conversation summary: ";
+const LAST_FAILURE_FILE: &str = "last_failure.txt";
+// The unsent input, saved periodically so it survives a crash.
+const DRAFT_FILE: &str = "draft.json";
+const DRAFT_SAVE_INTERVAL: Duration = Duration::from_secs(3);
+const CLIPBOARD_TIMEOUT: Duration = Duration::from_secs(5);
+const REMOTE_PREVIEW_LINES: usize = 12;
+const RUN_PREVIEW_LINES: usize = 12;
+const TESTS_PREVIEW_LINES: usize = 20;
+const CODE_BACKGROUND: Color = Color::Indexed(236);
+const SUMMARY_TOOLTIP_LINES: usize = 3;
+// Messages shown when previewing a conversation highlighted in the list.
+const PREVIEW_MESSAGES: usize = 6;
+const MAX_INPUT_LINES: usize = 10;
+const EARLIER_ANSWER_LINES: usize = 15;
+// File names listed when Tab after `@` matches several.
+const MENTION_MATCHES_SHOWN: usize = 8;
+// Ctrl+F lists at most this many messages, with excerpts about this wide.
+const SEARCH_MAX_HITS: usize = 200;
+const SEARCH_EXCERPT_CHARS: usize = 90;
+// Redraws are capped at ~30fps; without changes the screen is still refreshed
+// periodically for clocks and status that change on their own.
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+const IDLE_REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+// With no input for this long and nothing in flight the app is idle: the loop
+// wakes once a second, the screen is refreshed only for changes and the odd clock
+// tick, and scheduled digests wait for the next key.
+const IDLE_AFTER: Duration = Duration::from_secs(30);
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const IDLE_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+// How long a list entry flashes (on and off every idle redraw) after a response.
+const ACTIVITY_FLASH: Duration = Duration::from_millis(1500);
+const SHELL_HOOK: &str = include_str!("../shell/llm-tui-hook.sh");
+
 struct ModelInfo {
     alias: String,
     full_name: String,
 }
 
 struct App {
-    input: String,
+    input: InputBuffer,
     input_mode: InputMode,
     focused_block: FocusedBlock,
     conversations: Vec<Conversation>,
@@ -89,58 +315,1044 @@ struct App {
     show_conversation_list: bool, // New field to control conversation list visibility
     chat_state: ChatState,
     feedback: Option<FeedbackMessage>,
-    tx: Sender<String>,
-    rx: Receiver<String>,
-    remote_command_rx: CrossbeamReceiver<String>,
-    remote_command_tx: CrossbeamSender<String>,
+    tx: Sender<ResponseEvent>,
+    rx: Receiver<ResponseEvent>,
+    background_tx: Sender<BackgroundEvent>,
+    background_rx: Receiver<BackgroundEvent>,
+    // Fragments queued for the next prompt, as (label, path) pairs.
+    pending_fragments: Vec<(String, String)>,
+    // Files or URLs for the next prompt's `-a` attachments (`/attach`).
+    pending_attachments: Vec<String>,
+    // Seed for the next prompt only, over the conversation's own (replaying a
+    // response).
+    pending_seed: Option<u64>,
+    // The llm template the next prompt runs, once its variables are filled in.
+    pending_template: Option<templates::TemplateCall>,
+    // Templates from `llm templates list` and the selected one, while picking.
+    template_picker: Option<(Vec<(String, String)>, usize)>,
+    global_search: Option<GlobalSearch>,
+    // Text searched for with `/` in the chat, highlighted and stepped through with
+    // n/N; `chat_searching` while it's still being typed.
+    chat_query: String,
+    chat_searching: bool,
+    // The picked template and the values of its variables asked for so far.
+    template_fill: Option<(templates::Template, Vec<(String, String)>)>,
+    remote_command_rx: CrossbeamReceiver<RemoteCommand>,
+    remote_command_tx: CrossbeamSender<RemoteCommand>,
+    // In do-not-disturb mode remote prompts wait here until accepted with `A`.
+    do_not_disturb: bool,
+    pending_remote: VecDeque<RemoteCommand>,
     state: AppState,
     server_running: Arc<AtomicBool>,
+    server_stats: Arc<remote::ServerStats>,
+    // Set with `Z`: nothing is sent to a model unless asked for, e.g. no scheduled
+    // digests or follow-up suggestions. Shared with the digest thread.
+    background_paused: Arc<AtomicBool>,
+    // Set while the app is idle (see `IDLE_AFTER`). Shared with the digest thread.
+    idle: Arc<AtomicBool>,
+    last_input: Instant,
+    config: Config,
+    show_line_numbers: bool,
+    prompt: Option<Prompt>,
+    confirm: Option<Confirm>,
+    action_menu: Option<ActionMenu>,
+    // Cursor in the context composer, when it's open.
+    composer: Option<ListState>,
+    time_travel: Option<TimeTravel>,
+    panel: Option<Panel>,
+    // Text to show in `$PAGER`; the main loop suspends the TUI for it.
+    pager_request: Option<String>,
+    // The latest prompt sent in each conversation, for retrying it with less context.
+    last_sent: BTreeMap<usize, SentPrompt>,
+    session: SessionStats,
+    // Shared and local prompts and personas.
+    library: Library,
+    // In-flight requests by conversation, for cancelling them.
+    running: BTreeMap<usize, RunningChild>,
+    // Requests held back by a `[concurrency]` limit, oldest first; they're in
+    // `running` too.
+    waiting: VecDeque<WaitingRequest>,
+    // Prompts to conversations that were still being answered, sent in order once
+    // they're free.
+    held: VecDeque<HeldPrompt>,
+    // Locked down by `--kiosk`: one conversation and model, no server or commands.
+    kiosk: bool,
+    // Selected row of the response preferences popup, while it's open.
+    preferences_row: Option<usize>,
+    // Selected row of the model options popup, while it's open.
+    model_options_row: Option<usize>,
+    // Persistent Python processes for "Run in Python", by conversation id.
+    python_sessions: Arc<Mutex<BTreeMap<String, python::Session>>>,
+    // Selected code block of the picker `c` opens for messages with several.
+    code_picker: Option<usize>,
+    // Follow-up questions shown under the latest answer of a conversation.
+    suggestions: Option<(usize, Vec<String>)>,
+    // Only conversations with this tag are listed.
+    tag_filter: Option<String>,
+    // Fuzzy filter typed after `/` in the list, matched against names and first
+    // messages; `list_searching` while it's still being typed.
+    list_query: String,
+    list_searching: bool,
+    // The same for the model selector, matched against full names and aliases.
+    model_query: String,
+    model_searching: bool,
+    list_sort: listing::SortKey,
+    // How many log entries were asked for so far, whether older ones are left and
+    // whether they're being loaded.
+    logs_requested: usize,
+    older_logs: bool,
+    loading_older: bool,
+    // Progress of `/autotag` as (done, total), and how many failed so far.
+    tagging: Option<(usize, usize)>,
+    tagging_errors: usize,
+    // Progress of `/eval` as (done, total), and the results of the last one.
+    evaluating: Option<(usize, usize)>,
+    eval_report: Option<eval::Report>,
+    // The health warning for a conversation and what it was computed from.
+    health: Option<(HealthKey, usize, Option<String>)>,
+    pinned_expanded: bool,
+    // The input as last written to `DRAFT_FILE`, and when that was checked.
+    saved_draft: String,
+    draft_checked_at: Instant,
+    completion: Completion,
+    // Something changed since the last frame was drawn.
+    needs_redraw: bool,
+    last_draw: Instant,
+    // Follow mode keeps the chat at the newest message; scrolling up turns it off
+    // until the bottom is reached again or `G` is pressed.
+    follow: bool,
+    // Messages added and the last message growing while follow mode was off.
+    unseen_messages: usize,
+    new_content_below: bool,
+    // The conversation (by id) that last got a response while another was open, and
+    // when; Ctrl+O jumps to it.
+    activity: Option<(String, Instant)>,
+    // Where Ctrl+O jumped from, for Ctrl+B to return to.
+    jump_back: Option<String>,
+    // Show messages as the raw text the model returned instead of rendered.
+    show_raw: bool,
+    // Collapse user prompts to one line, except the selected one.
+    compact: bool,
+    // Scroll offset of the plain transcript (`/plain`), while it's shown.
+    plain: Option<u16>,
+    project: Option<ProjectIndex>,
+    // The directory being indexed for project context mode, on a helper thread.
+    indexing: Option<PathBuf>,
+    project_filter: bool,
 
     remote_message_received: bool,
 }
 
+enum PromptKind {
+    CopyLines,
+    SaveCodeBlocks,
+    ExportSelection,
+    ExportFlashcards,
+    SendSelection,
+    Translate,
+    TagFilter,
+    RenameConversation(usize),
+    // Where to write the test file generated by `/gen-tests`, holding its contents.
+    SaveTests(String),
+    Preference(preferences::Field),
+    // A model option, replacing the named one when editing it.
+    ModelOption(Option<String>),
+    // A variable of the template being filled in.
+    TemplateVariable(String),
+    // Search-and-replace in the input: the pattern first, then its replacement.
+    Find {
+        regex: bool,
+    },
+    Replace {
+        pattern: String,
+        regex: bool,
+        matches: usize,
+    },
+}
+
+impl PromptKind {
+    fn title(&self) -> String {
+        match self {
+            PromptKind::CopyLines => "Copy lines ([block:]start-end, e.g. 2:10-25)".to_string(),
+            PromptKind::SaveCodeBlocks => "Save code blocks under directory".to_string(),
+            PromptKind::ExportSelection => "Export selected messages to file".to_string(),
+            PromptKind::ExportFlashcards => "Export flashcards (Anki TSV) to file".to_string(),
+            PromptKind::SendSelection => {
+                "Send selection to conversation (name or number)".to_string()
+            }
+            PromptKind::Translate => "Translate into (e.g. Spanish)".to_string(),
+            PromptKind::TagFilter => "Show conversations tagged (empty: all)".to_string(),
+            PromptKind::SaveTests(_) => "Save generated tests to".to_string(),
+            PromptKind::RenameConversation(_) => "Rename conversation".to_string(),
+            PromptKind::Preference(preferences::Field::Stop) => {
+                "Stop sequences, comma-separated (empty: none)".to_string()
+            }
+            PromptKind::Preference(field) => format!("{} (empty: none)", field.label()),
+            PromptKind::TemplateVariable(name) => format!("Template: value for ${}", name),
+            PromptKind::ModelOption(_) => {
+                "Model option (name value, e.g. temperature 0.7; name alone: remove)".to_string()
+            }
+            PromptKind::Find { regex: false } => "Find in input (Ctrl+R: regex)".to_string(),
+            PromptKind::Find { regex: true } => "Find regex in input (Ctrl+R: literal)".to_string(),
+            PromptKind::Replace {
+                pattern, matches, ..
+            } => format!("Replace {} match(es) of '{}' with", matches, pattern),
+        }
+    }
+}
+
+// A single-line text prompt shown as a popup; it captures all keys while open.
+struct Prompt {
+    kind: PromptKind,
+    input: String,
+}
+
+enum ConfirmAction {
+    SaveFiles(Vec<(PathBuf, String)>),
+    DeleteMessages(usize, Vec<usize>),
+    DeleteConversation(usize),
+    RestoreDraft(String),
+    SendRemote(RemoteCommand),
+    // Replace the input with a rewritten prompt.
+    UseImprovedPrompt(String),
+    // Pasted text that looks like a stack trace or log, to fence (under a prompt).
+    SmartPaste(String),
+    // Send the input even though a similar prompt was answered before.
+    SendAnyway(usize),
+    // Conversation, prompt, the context to keep and labels of what's left out.
+    RetryWithLessContext(usize, String, Vec<ContextPart>, Vec<String>),
+    RunCode(sandbox::Run),
+    // The conversation id and the Python cells to run in its session.
+    RunPython(String, Vec<String>),
+}
+
+// A yes/no popup listing what is about to happen; y/Enter confirms, n/Esc cancels.
+struct Confirm {
+    title: String,
+    lines: Vec<String>,
+    action: ConfirmAction,
+}
+
+// Conversation, message count, length of the last message and selected model.
+type HealthKey = (usize, usize, usize, Option<usize>);
+
+// Something sent alongside a prompt: pinned text, a project file or an attachment.
+#[derive(Clone)]
+struct ContextPart {
+    label: String,
+    source: PartSource,
+}
+
+#[derive(Clone)]
+enum PartSource {
+    // Earlier messages (role and content) are sent together as a transcript.
+    History(String, String),
+    // Pinned messages and snippets are sent together as one fragment.
+    Pinned(String),
+    File(String),
+}
+
+fn part_tokens(part: &ContextPart, counter: &tokens::TokenCounter) -> usize {
+    match &part.source {
+        PartSource::History(_, text) | PartSource::Pinned(text) => counter.count(text),
+        PartSource::File(path) => fs::read_to_string(path)
+            .map(|text| counter.count(&text))
+            .unwrap_or(0),
+    }
+}
+
+struct SentPrompt {
+    prompt: String,
+    parts: Vec<ContextPart>,
+    seed: Option<u64>,
+}
+
+// Ghost-text completion of the input, asked for once typing pauses.
+#[derive(Default)]
+struct Completion {
+    // The input as of the last edit, and when that was (until a request is made).
+    seen: String,
+    edited_at: Option<Instant>,
+    // The request in flight, always for `seen`.
+    pending: Option<RunningChild>,
+    // What might follow `seen`.
+    suggestion: Option<String>,
+}
+
+// Ctrl+F: the messages of every conversation that contain all the words typed,
+// newest first, and the highlighted one.
+struct GlobalSearch {
+    query: String,
+    hits: Vec<SearchHit>,
+    selected: usize,
+}
+
+struct SearchHit {
+    conversation: usize,
+    message: usize,
+    excerpt: String,
+    // Where the first word of the query is in the excerpt.
+    matched: std::ops::Range<usize>,
+}
+
+// Read-only text shown in a popup, e.g. a response's provenance.
+struct Panel {
+    title: String,
+    lines: Vec<String>,
+    scroll: u16,
+}
+
+// A read-only view of a conversation as it stood after an earlier exchange, built
+// from llm's logs rather than the (possibly edited) messages on screen.
+struct TimeTravel {
+    name: String,
+    exchanges: Vec<history::Exchange>,
+    // How many exchanges are shown, from 1 to all of them.
+    position: usize,
+    // Lines scrolled up from the bottom of the view.
+    scroll: usize,
+    // Local overrides, to flag exchanges hidden or deleted since.
+    overrides: Value,
+}
+
+// Everything needed to build one digest off the main thread.
+struct DigestJob {
+    feeds: Vec<String>,
+    // llm's default model if not configured.
+    model: Option<String>,
+    // `redact` from the config.
+    secrets: Vec<String>,
+    background_tx: Sender<BackgroundEvent>,
+    paused: Arc<AtomicBool>,
+    idle: Arc<AtomicBool>,
+}
+
+impl DigestJob {
+    fn run(&self) {
+        let (items, errors) = digest::collect_new_items(&self.feeds);
+        if items.is_empty() {
+            if !errors.is_empty() {
+                let _ = self
+                    .background_tx
+                    .send(BackgroundEvent::Digest(Err(errors.join("; "))));
+            }
+            return;
+        }
+        let result = self.model().and_then(|model| {
+            let prompt = config::redact(&self.secrets, &digest::build_prompt(&items));
+            run_llm_checked(&LlmRequest::new(prompt, model))
+        });
+        // Items stay new until a digest including them was built, so a failed one
+        // is retried next time.
+        let saved = match &result {
+            Ok(_) => digest::mark_seen(&items),
+            Err(_) => Ok(()),
+        };
+        let result =
+            result.map(|summary| format!("Digest: {} new item(s)\n\n{}", items.len(), summary));
+        let _ = self.background_tx.send(BackgroundEvent::Digest(result));
+        if let Err(e) = saved {
+            let _ = self.background_tx.send(BackgroundEvent::Digest(Err(e)));
+        }
+    }
+
+    fn model(&self) -> Result<String, String> {
+        if let Some(model) = &self.model {
+            return Ok(model.clone());
+        }
+        let output = llm_output(&["models", "default"])?;
+        let model = String::from_utf8_lossy(&output).trim().to_string();
+        if model.is_empty() {
+            return Err("No model: set model under [digest] in the config".to_string());
+        }
+        Ok(model)
+    }
+
+    // Builds a digest now and then every `interval` for the life of the app,
+    // skipping the times background jobs are paused. One that falls due while the
+    // app is idle is built once it isn't.
+    fn spawn_periodic(self, interval: Duration) {
+        thread::spawn(move || loop {
+            if !self.paused.load(Ordering::SeqCst) {
+                self.run();
+            }
+            thread::sleep(interval);
+            while self.idle.load(Ordering::SeqCst) {
+                thread::sleep(IDLE_POLL_INTERVAL);
+            }
+        });
+    }
+}
+
+// Output of a running `llm` request, tagged with the conversation it belongs to.
+enum ResponseEvent {
+    Chunk(usize, String),
+    Done(usize, Result<String, LlmFailure>),
+    // The ids llm logged an answered exchange under: its conversation and entry.
+    Logged(usize, String, String),
+    // The request was cancelled and its `llm` process killed.
+    Cancelled(usize),
+}
+
+// The `llm` process answering a prompt, shared so the UI can kill it. Empty once
+// it has finished or been cancelled.
+type RunningChild = Arc<Mutex<Option<Child>>>;
+
+// A prompt that waits for a request to its model to end before it's sent.
+struct WaitingRequest {
+    index: usize,
+    request: LlmRequest,
+    reply: Option<Sender<String>>,
+    running: RunningChild,
+    temporary: TempFiles,
+}
+
+// A prompt sent while its conversation was still being answered, kept as it was
+// passed to `send_prompt`.
+struct HeldPrompt {
+    index: usize,
+    prompt: String,
+    attachments: Vec<(String, String)>,
+    reply: Option<Sender<String>>,
+    template: Option<templates::TemplateCall>,
+}
+
+// Files written for one request, such as the history sent along, removed once
+// it's done with them.
+struct TempFiles(Vec<PathBuf>);
+
+impl Drop for TempFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// Results of work done on helper threads, drained on each tick of the main loop.
+enum BackgroundEvent {
+    Fetched(Result<(String, String), String>),
+    // The shared prompt library was cloned or pulled.
+    LibrarySynced(Result<(), String>),
+    Digest(Result<String, String>),
+    // The feedback message to show on success, or the error.
+    Copied(Result<String, String>),
+    // Clipboard contents to paste into the input (Ctrl+V).
+    ClipboardRead(Result<String, String>),
+    // Follow-up questions for the conversation with this id.
+    Suggested(String, Vec<String>),
+    // The feedback message for a finished export, or the error.
+    Exported(Result<String, String>),
+    // The conversation id and its summary.
+    Summarized(String, Result<String, String>),
+    // The same, from the `/autotag` batch job.
+    AutoTagged(String, Result<String, String>),
+    // The target language and the translation.
+    Translated(String, Result<String, String>),
+    // The draft and its rewrite by the `[improve]` prompt.
+    Improved(String, Result<String, String>),
+    // The language of a code block that was run, how it ended and its output.
+    Ran(String, Result<(String, String), String>),
+    // The conversation id and the output of its Python cells.
+    PythonRan(String, Result<String, String>),
+    // The suggested path for generated tests and the response.
+    TestsGenerated(String, Result<String, String>),
+    // The input a completion was asked for and the model's reply, `None` if the
+    // request failed.
+    Completed(String, Option<String>),
+    // Runs of `/eval` done so far, out of how many.
+    EvalProgress(usize, usize),
+    Evaluated(eval::Report),
+    // A page of older logs: how many entries were asked for, and the conversations
+    // (or parts of them) older than the ones loaded with how many entries llm returned.
+    OlderLogs(usize, Result<(Vec<Conversation>, usize), String>),
+    // The directory indexed for project context mode and its index.
+    ProjectIndexed(PathBuf, Result<ProjectIndex, String>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MessageAction {
+    Copy,
+    CopyCode,
+    CopyLines,
+    SaveCode,
+    RunCode,
+    RunPython,
+    Quote,
+    Regenerate,
+    ReplaySeed,
+    ToggleWrap,
+    ToggleRaw,
+    TogglePin,
+    ToggleHidden,
+    Delete,
+    Provenance,
+    OpenInPager,
+    Translate,
+    AppendNote,
+    CopySelection,
+    ExportSelection,
+    ExportFlashcards,
+    HideSelection,
+    DeleteSelection,
+    SendSelection,
+    ClearSelection,
+}
+
+impl MessageAction {
+    fn label(&self) -> &'static str {
+        match self {
+            MessageAction::Copy => "Copy message",
+            MessageAction::CopyCode => "Copy code blocks",
+            MessageAction::CopyLines => "Copy code lines...",
+            MessageAction::SaveCode => "Save code blocks as files...",
+            MessageAction::RunCode => "Run code block...",
+            MessageAction::RunPython => "Run in Python session...",
+            MessageAction::Quote => "Quote in input",
+            MessageAction::Regenerate => "Regenerate response",
+            MessageAction::ReplaySeed => "Replay with same seed",
+            MessageAction::ToggleWrap => "Toggle code wrapping",
+            MessageAction::ToggleRaw => "Toggle raw / rendered view",
+            MessageAction::TogglePin => "Pin / unpin as context",
+            MessageAction::ToggleHidden => "Hide from / restore to context",
+            MessageAction::Delete => "Delete message (local)",
+            MessageAction::Provenance => "Show provenance",
+            MessageAction::OpenInPager => "Open in pager",
+            MessageAction::Translate => "Translate...",
+            MessageAction::AppendNote => "Append to notes",
+            MessageAction::CopySelection => "Copy selected messages",
+            MessageAction::ExportSelection => "Export selected messages...",
+            MessageAction::ExportFlashcards => "Export as Anki flashcards...",
+            MessageAction::HideSelection => "Hide selected messages from context",
+            MessageAction::DeleteSelection => "Delete selected messages (local)",
+            MessageAction::SendSelection => "Send selection to conversation...",
+            MessageAction::ClearSelection => "Clear selection",
+        }
+    }
+
+    fn key(&self) -> char {
+        match self {
+            MessageAction::Copy => 'y',
+            MessageAction::CopyCode => 'c',
+            MessageAction::CopyLines => 'Y',
+            MessageAction::SaveCode => 'S',
+            MessageAction::RunCode => 'x',
+            MessageAction::RunPython => 'e',
+            MessageAction::Quote => '>',
+            MessageAction::Regenerate => 'r',
+            MessageAction::ReplaySeed => 'g',
+            MessageAction::ToggleWrap => 'w',
+            MessageAction::ToggleRaw => 'R',
+            MessageAction::TogglePin => 'p',
+            MessageAction::ToggleHidden => 'h',
+            MessageAction::Delete => 'd',
+            MessageAction::Provenance => 'i',
+            MessageAction::OpenInPager => 'o',
+            MessageAction::Translate => 't',
+            MessageAction::AppendNote => 'n',
+            MessageAction::CopySelection => 'y',
+            MessageAction::ExportSelection => 'e',
+            MessageAction::ExportFlashcards => 'a',
+            MessageAction::HideSelection => 'h',
+            MessageAction::DeleteSelection => 'd',
+            MessageAction::SendSelection => 's',
+            MessageAction::ClearSelection => 'x',
+        }
+    }
+}
+
+// The context menu of actions for the selected message.
+struct ActionMenu {
+    actions: Vec<MessageAction>,
+    state: ListState,
+}
+
+impl ActionMenu {
+    fn next(&mut self) {
+        let i = self
+            .state
+            .selected()
+            .map_or(0, |i| (i + 1) % self.actions.len());
+        self.state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        let i = self.state.selected().map_or(0, |i| {
+            if i == 0 {
+                self.actions.len() - 1
+            } else {
+                i - 1
+            }
+        });
+        self.state.select(Some(i));
+    }
+
+    fn selected(&self) -> MessageAction {
+        self.actions[self.state.selected().unwrap_or(0)]
+    }
+}
+
 struct ChatState {
     list_state: ListState,
+    // Messages of the current conversation marked for bulk actions.
+    marked: BTreeSet<usize>,
 }
 
 impl ChatState {
     fn new() -> Self {
         Self {
             list_state: ListState::default(),
+            marked: BTreeSet::new(),
         }
     }
 }
 
 impl App {
-    fn new() -> Self {
+    // Fails when llm can't be run or its logs can't be read.
+    fn new() -> Result<Self, String> {
         let (tx, rx) = channel();
+        let (background_tx, background_rx) = channel();
         let (remote_command_tx, remote_command_rx) = unbounded();
         let server_running = Arc::new(AtomicBool::new(false));
 
-        let conversations = load_conversations();
-        let models = load_models();
-        let app = App {
-            input: String::new(),
+        let config = Config::load();
+        let (conversations, entries) = load_logs(config.log_page_size, None)?;
+        let logs_requested = config.log_page_size.unwrap_or(0);
+        let older_logs = config.log_page_size.is_some_and(|size| entries >= size);
+        let (models, default_model) = load_models()?;
+        let mut app = App {
+            input: InputBuffer::default(),
             input_mode: InputMode::Normal,
-            focused_block: FocusedBlock::ConversationList,
+            focused_block: if config.show_conversation_list {
+                FocusedBlock::ConversationList
+            } else {
+                FocusedBlock::Input
+            },
             conversations,
             conversation_list_state: ListState::default(),
             current_conversation_index: None,
             models,
-            model_list_state: ListState::default(),
-            show_conversation_list: false,
+            model_list_state: {
+                let mut state = ListState::default();
+                state.select(default_model);
+                state
+            },
+            show_conversation_list: config.show_conversation_list,
             chat_state: ChatState::new(),
             feedback: None,
             state: AppState::Normal,
             server_running,
+            server_stats: Arc::default(),
+            background_paused: Arc::new(AtomicBool::new(config.pause_background)),
+            idle: Arc::default(),
+            last_input: Instant::now(),
+            show_line_numbers: config.line_numbers,
+            do_not_disturb: config.do_not_disturb,
+            list_sort: config.list_sort,
+            config,
+            prompt: None,
+            confirm: None,
+            pending_remote: VecDeque::new(),
+            action_menu: None,
+            composer: None,
+            time_travel: None,
+            panel: None,
+            pager_request: None,
+            last_sent: BTreeMap::new(),
+            session: SessionStats::new(),
+            running: BTreeMap::new(),
+            held: VecDeque::new(),
+            waiting: VecDeque::new(),
+            library: Library::default(),
+            kiosk: false,
+            preferences_row: None,
+            model_options_row: None,
+            python_sessions: Arc::default(),
+            code_picker: None,
+            suggestions: None,
+            tag_filter: None,
+            list_query: String::new(),
+            logs_requested,
+            older_logs,
+            loading_older: false,
+            list_searching: false,
+            model_query: String::new(),
+            model_searching: false,
+            tagging: None,
+            tagging_errors: 0,
+            evaluating: None,
+            eval_report: None,
+            health: None,
+            pinned_expanded: false,
+            saved_draft: String::new(),
+            completion: Completion::default(),
+            draft_checked_at: Instant::now(),
+            needs_redraw: true,
+            last_draw: Instant::now(),
+            follow: true,
+            unseen_messages: 0,
+            new_content_below: false,
+            activity: None,
+            jump_back: None,
+            show_raw: false,
+            compact: false,
+            plain: None,
+            project: None,
+            indexing: None,
+            project_filter: false,
             remote_message_received: false,
             tx,
             rx,
+            background_tx,
+            background_rx,
+            pending_fragments: Vec::new(),
+            pending_attachments: Vec::new(),
+            pending_seed: None,
+            pending_template: None,
+            template_picker: None,
+            global_search: None,
+            chat_query: String::new(),
+            chat_searching: false,
+            template_fill: None,
             remote_command_rx,
             remote_command_tx,
         };
-        app
+        if let Some(digests) = store::load(DIGEST_FILE).as_array() {
+            for digest in digests.iter().filter_map(Value::as_str) {
+                app.push_digest_message(digest.to_string());
+            }
+        }
+        if let Some(model) = &app.config.model {
+            let position = app
+                .models
+                .iter()
+                .position(|m| &m.alias == model || &m.full_name == model);
+            if position.is_some() {
+                app.model_list_state.select(position);
+            }
+        }
+        if let Some(path) = &app.config.project_file {
+            let mut message = format!("Loaded project config from {}", path.display());
+            if !app.config.project_ignored.is_empty() {
+                message.push_str(&format!(
+                    " (ignored: {})",
+                    app.config.project_ignored.join(", ")
+                ));
+            }
+            app.set_feedback(message, FeedbackType::Positive);
+        }
+        if !app.config.load_errors.is_empty() {
+            app.set_feedback(
+                format!("Config file ignored: {}", app.config.load_errors.join("; ")),
+                FeedbackType::Negative,
+            );
+        }
+        if app.config.project_mode {
+            app.toggle_project_mode();
+        }
+        app.reload_library();
+        Ok(app)
+    }
+
+    // Where the shared checkout lives (with a repository configured) and the local
+    // library directory.
+    fn library_dirs(&self) -> (Option<PathBuf>, Option<PathBuf>) {
+        let shared = self
+            .config
+            .library_repo
+            .as_ref()
+            .and(store::base_dir())
+            .map(|dir| dir.join("library"));
+        (shared, self.config.library_dir.clone())
+    }
+
+    fn reload_library(&mut self) {
+        let (shared, local) = self.library_dirs();
+        self.library = Library::load(shared.as_deref(), local.as_deref());
+    }
+
+    // Clones or pulls the `[library]` repository in the background.
+    fn sync_library(&mut self) {
+        let (Some(repo), (Some(dir), _)) = (self.config.library_repo.clone(), self.library_dirs())
+        else {
+            return;
+        };
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let result = library::sync(&repo, &dir);
+            let _ = background_tx.send(BackgroundEvent::LibrarySynced(result));
+        });
+    }
+
+    // `/library` lists the prompts and personas, `/library sync` pulls the shared ones.
+    fn show_library(&mut self, argument: &str) {
+        if argument == "sync" {
+            if self.config.library_repo.is_none() {
+                self.set_feedback(
+                    "No [library] repo configured".to_string(),
+                    FeedbackType::Negative,
+                );
+                return;
+            }
+            self.set_feedback("Syncing library...".to_string(), FeedbackType::Positive);
+            self.sync_library();
+            return;
+        }
+        let mut lines = Vec::new();
+        for (heading, entries) in [
+            ("Prompts (/snippet <name>)", &self.library.prompts),
+            ("Personas (/persona <name>)", &self.library.personas),
+        ] {
+            lines.push(heading.to_string());
+            if entries.is_empty() {
+                lines.push("  none".to_string());
+            }
+            for (name, entry) in entries {
+                lines.push(format!(
+                    "  {}{}: {}",
+                    name,
+                    if entry.local { " (local)" } else { "" },
+                    preview(&entry.text, 60)
+                ));
+            }
+            lines.push(String::new());
+        }
+        self.panel = Some(Panel {
+            title: "Library | j/k: Scroll | Esc: Close".to_string(),
+            lines,
+            scroll: 0,
+        });
+    }
+
+    // Puts a library prompt in the input to edit before sending.
+    fn insert_snippet(&mut self, name: &str) {
+        if name.is_empty() {
+            self.show_library("");
+            return;
+        }
+        match self.library.prompts.get(name) {
+            Some(entry) => {
+                self.input.set(entry.text.clone());
+                self.focused_block = FocusedBlock::Input;
+                self.input_mode = InputMode::Editing;
+            }
+            None => self.set_feedback(
+                format!("No prompt named '{}' (see /library)", name),
+                FeedbackType::Negative,
+            ),
+        }
+    }
+
+    // `/template` picks one of llm's templates, `/template <name> [text]` uses one:
+    // its variables (and `$input`, unless the text is given) are asked for, then it
+    // runs.
+    fn use_template(&mut self, argument: &str) {
+        if self.current_conversation_index.is_none() {
+            self.set_feedback("No conversation open".to_string(), FeedbackType::Negative);
+            return;
+        }
+        if !argument.is_empty() {
+            self.template_picker = None;
+            let (name, input) = argument
+                .split_once(char::is_whitespace)
+                .unwrap_or((argument, ""));
+            match templates::load(name) {
+                Ok(template) => {
+                    let mut values = Vec::new();
+                    if !input.trim().is_empty() {
+                        values.push(("input".to_string(), input.trim().to_string()));
+                    }
+                    self.template_fill = Some((template, values));
+                    self.fill_template();
+                }
+                Err(e) => self.set_feedback(e, FeedbackType::Negative),
+            }
+            return;
+        }
+        match templates::list() {
+            Ok(list) if list.is_empty() => self.set_feedback(
+                "No templates yet (see `llm templates --help`)".to_string(),
+                FeedbackType::Negative,
+            ),
+            Ok(list) => self.template_picker = Some((list, 0)),
+            Err(e) => self.set_feedback(e, FeedbackType::Negative),
+        }
+    }
+
+    // Asks for the next variable of the template being filled in, or sends it once
+    // they all have values.
+    fn fill_template(&mut self) {
+        let Some((template, values)) = &self.template_fill else {
+            return;
+        };
+        let mut wanted = template.variables();
+        if template.takes_input() {
+            wanted.push("input".to_string());
+        }
+        let missing = wanted
+            .into_iter()
+            .find(|name| !values.iter().any(|(known, _)| known == name));
+        if let Some(name) = missing {
+            let prefill = template
+                .default_value(&name)
+                .unwrap_or_default()
+                .to_string();
+            self.open_prompt(PromptKind::TemplateVariable(name), prefill);
+            return;
+        }
+        let (Some((template, values)), Some(index)) =
+            (self.template_fill.take(), self.current_conversation_index)
+        else {
+            return;
+        };
+        let (input, params): (Vec<_>, Vec<_>) =
+            values.into_iter().partition(|(name, _)| name == "input");
+        let call = templates::TemplateCall {
+            name: template.name.clone(),
+            params,
+            input: input
+                .into_iter()
+                .next()
+                .map(|(_, text)| self.config.redact(&text))
+                .unwrap_or_default(),
+        };
+        // The conversation shows the prompt as llm expands it.
+        let prompt = template.expand(&call);
+        self.pending_template = Some(call);
+        let attachments = std::mem::take(&mut self.pending_fragments);
+        self.send_prompt(index, prompt, attachments, None);
+    }
+
+    // Sets (or with `off`, clears) the open conversation's persona.
+    fn set_persona(&mut self, name: &str) {
+        if name.is_empty() {
+            self.show_library("");
+            return;
+        }
+        if name != "off" && !self.library.personas.contains_key(name) {
+            self.set_feedback(
+                format!("No persona named '{}' (see /library)", name),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        let value = if name == "off" { "" } else { name };
+        self.set_preference(preferences::Field::Persona, value);
+    }
+
+    // The configured system prompt, then the conversation's own, then its persona,
+    // then its response preferences.
+    fn system_prompt(&self, index: usize) -> Option<String> {
+        let preferences = &self.conversations[index].preferences;
+        let persona = preferences
+            .persona
+            .as_ref()
+            .and_then(|name| self.library.personas.get(name))
+            .map(|entry| entry.text.clone());
+        let parts: Vec<String> = [
+            self.config.system_prompt.clone(),
+            preferences.system.clone(),
+            persona,
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        preferences.apply(Some(parts.join("\n\n")).filter(|system| !system.is_empty()))
+    }
+
+    // `/system <text>` sets the open conversation's system prompt, `/system off`
+    // clears it and a bare `/system` opens it for editing.
+    fn set_system_prompt(&mut self, text: &str) {
+        let Some(index) = self.current_conversation_index else {
+            self.set_feedback("No conversation open".to_string(), FeedbackType::Negative);
+            return;
+        };
+        match text {
+            "" => {
+                let value = self.conversations[index]
+                    .preferences
+                    .value(preferences::Field::System);
+                self.open_prompt(PromptKind::Preference(preferences::Field::System), value);
+            }
+            "off" => {
+                self.set_preference(preferences::Field::System, "");
+                self.set_feedback("System prompt cleared".to_string(), FeedbackType::Positive);
+            }
+            text => {
+                self.set_preference(preferences::Field::System, text);
+                self.set_feedback(
+                    "System prompt set for this conversation".to_string(),
+                    FeedbackType::Positive,
+                );
+            }
+        }
+    }
+    // Switches to the `[kiosk]` conversation and model and hides everything else.
+    fn enter_kiosk_mode(&mut self) {
+        self.kiosk = true;
+        self.show_conversation_list = false;
+        if self.project.is_some() || self.indexing.is_some() {
+            self.toggle_project_mode();
+        }
+        if let Some(model) = &self.config.kiosk_model {
+            match self
+                .models
+                .iter()
+                .position(|m| &m.alias == model || &m.full_name == model)
+            {
+                Some(position) => self.model_list_state.select(Some(position)),
+                None => self.set_feedback(
+                    format!("Kiosk model '{}' not found", model),
+                    FeedbackType::Negative,
+                ),
+            }
+        }
+        let name = self.config.kiosk_conversation.clone();
+        let index = match self.conversations.iter().position(|c| c.name == name) {
+            Some(index) => index,
+            None => self.create_conversation(name),
+        };
+        self.current_conversation_index = Some(index);
+        self.focused_block = FocusedBlock::Input;
+        self.input_mode = InputMode::Editing;
+    }
+
+    // In kiosk mode only scrolling and copying in the chat and typing in the input
+    // are allowed.
+    fn kiosk_allows(&self, key: &KeyEvent) -> bool {
+        match (&self.focused_block, &self.input_mode) {
+            (FocusedBlock::Input, InputMode::Editing) => true,
+            (FocusedBlock::Input, InputMode::Normal) => {
+                matches!(key.code, KeyCode::Char('i' | 'u') | KeyCode::Tab)
+            }
+            (FocusedBlock::Chat, _) => match key.code {
+                KeyCode::Char('h' | 'l') | KeyCode::Left | KeyCode::Right => self.is_panning_code(),
+                KeyCode::Char(
+                    'j' | 'k' | 'G' | 'w' | '#' | 'y' | 'c' | 'R' | 'M' | 'z' | '1'..='9',
+                )
+                | KeyCode::Up
+                | KeyCode::Down
+                | KeyCode::Tab => true,
+                _ => false,
+            },
+            _ => key.code == KeyCode::Tab,
+        }
+    }
+
+    // The first `[kiosk]` banned word in the input, matched as a whole word.
+    fn banned_word(&self) -> Option<&str> {
+        let input = self.input.to_lowercase();
+        let words: BTreeSet<&str> = input
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        self.config
+            .kiosk_banned_words
+            .iter()
+            .find(|banned| words.contains(banned.as_str()))
+            .map(String::as_str)
     }
+
     fn exit_edit_mode(&mut self) {
         if let InputMode::Editing = self.input_mode {
             self.input_mode = InputMode::Normal;
@@ -149,6 +1361,10 @@ impl App {
 
     fn next_focus(&mut self) {
         self.exit_edit_mode();
+        // A conversation that was only previewed stays closed.
+        if matches!(self.focused_block, FocusedBlock::ConversationList) {
+            self.sync_conversation_list();
+        }
         self.focused_block = match self.focused_block {
             FocusedBlock::ConversationList => {
                 if self.show_conversation_list {
@@ -175,10 +1391,135 @@ impl App {
         };
     }
 
+    // Indices into `conversations` of the entries shown in the list, in display order.
+    fn visible_conversations(&self) -> Vec<usize> {
+        let cwd = env::current_dir().ok();
+        let mut visible = self
+            .conversations
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| {
+                !self.project_filter
+                    || c.directory
+                        .as_ref()
+                        .zip(cwd.as_ref())
+                        .is_some_and(|(dir, cwd)| dir.starts_with(cwd))
+            })
+            .filter(|(_, c)| {
+                self.tag_filter
+                    .as_ref()
+                    .is_none_or(|tag| c.tags.contains(tag))
+            })
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let conversations = &self.conversations;
+        if !self.list_query.is_empty() {
+            // Best matches first; the sort below doesn't apply while filtering.
+            let mut scored: Vec<(i64, usize)> = visible
+                .into_iter()
+                .filter_map(|i| Some((self.list_score(i)?, i)))
+                .collect();
+            scored.sort_by_key(|&(score, _)| Reverse(score));
+            return scored.into_iter().map(|(_, i)| i).collect();
+        }
+        match self.list_sort {
+            SortKey::Log => {}
+            SortKey::Recent => visible.sort_by_key(|&i| Reverse(conversations[i].last_active)),
+            SortKey::Name => visible.sort_by_cached_key(|&i| conversations[i].name.to_lowercase()),
+            SortKey::Messages => visible.sort_by_key(|&i| Reverse(conversations[i].messages.len())),
+            // Conversations without a known model go last.
+            SortKey::Model => visible
+                .sort_by_key(|&i| (conversations[i].model.is_none(), &conversations[i].model)),
+        }
+        visible
+    }
+
+    // How well the list filter matches conversation `index`: its name, or failing
+    // that its first message, which counts for less.
+    fn list_score(&self, index: usize) -> Option<i64> {
+        let conversation = &self.conversations[index];
+        let name = fuzzy::score(&self.list_query, &conversation.name).map(|score| score * 2);
+        let first = conversation
+            .messages
+            .first()
+            .and_then(|message| fuzzy::score(&self.list_query, &message.content));
+        name.max(first)
+    }
+
+    // Narrows the list to `query` and highlights the best match.
+    fn set_list_query(&mut self, query: String) {
+        self.list_query = query;
+        let any = !self.visible_conversations().is_empty();
+        self.conversation_list_state.select(any.then_some(0));
+    }
+
+    // Moves the list's highlight to the open conversation, after the list was
+    // filtered or reordered.
+    fn sync_conversation_list(&mut self) {
+        let position = self.current_conversation_index.and_then(|index| {
+            self.visible_conversations()
+                .iter()
+                .position(|&visible| visible == index)
+        });
+        self.conversation_list_state.select(position);
+    }
+
+    fn cycle_list_sort(&mut self) {
+        self.list_sort = self.list_sort.next();
+        self.sync_conversation_list();
+        self.set_feedback(
+            format!("Sorted by {}", self.list_sort.name()),
+            FeedbackType::Positive,
+        );
+    }
+
+    // The conversation highlighted in the list, which j/k move through; it's only
+    // opened with Enter.
+    fn highlighted_conversation(&self) -> Option<usize> {
+        self.conversation_list_state
+            .selected()
+            .and_then(|position| self.visible_conversations().get(position).copied())
+    }
+
+    // The highlighted conversation while the list has focus, if it isn't the open one.
+    fn previewed_conversation(&self) -> Option<usize> {
+        if !matches!(self.focused_block, FocusedBlock::ConversationList) {
+            return None;
+        }
+        self.highlighted_conversation()
+            .filter(|&index| Some(index) != self.current_conversation_index)
+    }
+
+    fn open_highlighted_conversation(&mut self) {
+        if self.previewed_conversation().is_some() {
+            self.select_visible_conversation(self.conversation_list_state.selected());
+        }
+    }
+
+    fn select_visible_conversation(&mut self, position: Option<usize>) {
+        self.chat_state.marked.clear();
+        self.resume_follow();
+        self.conversation_list_state.select(position);
+        self.current_conversation_index =
+            position.and_then(|p| self.visible_conversations().get(p).copied());
+        if let Some(index) = self.current_conversation_index {
+            self.conversations[index].unread = 0;
+        }
+    }
+
     fn next_conversation(&mut self) {
+        let len = self.visible_conversations().len();
+        if len == 0 {
+            return;
+        }
         let i = match self.conversation_list_state.selected() {
+            // Past the end, older conversations are loaded rather than wrapping around.
+            Some(i) if i >= len - 1 && self.older_logs => {
+                self.load_older_conversations();
+                return;
+            }
             Some(i) => {
-                if i >= self.conversations.len() - 1 {
+                if i >= len - 1 {
                     0
                 } else {
                     i + 1
@@ -187,190 +1528,4592 @@ impl App {
             None => 0,
         };
         self.conversation_list_state.select(Some(i));
-        self.current_conversation_index = Some(i);
     }
 
-    fn previous_conversation(&mut self) {
-        let i = match self.conversation_list_state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.conversations.len() - 1
-                } else {
-                    i - 1
-                }
-            }
-            None => 0,
+    // Fetches the next page of llm's logs in the background.
+    fn load_older_conversations(&mut self) {
+        let Some(page) = self.config.log_page_size else {
+            return;
         };
-        self.conversation_list_state.select(Some(i));
-        self.current_conversation_index = Some(i);
+        if self.loading_older {
+            return;
+        }
+        self.loading_older = true;
+        let count = self.logs_requested + page;
+        // Entries logged since startup push the loaded ones down the list too; only
+        // those older than everything loaded are new.
+        let oldest = self
+            .conversations
+            .iter()
+            .filter_map(|c| c.started.clone())
+            .min();
+        let tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let older = load_logs(Some(count), oldest.as_deref());
+            let _ = tx.send(BackgroundEvent::OlderLogs(count, older));
+        });
     }
 
-    fn next_model(&mut self) {
-        let i = match self.model_list_state.selected() {
-            Some(i) => {
-                if i >= self.models.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
+    // Adds a page of older logs: earlier messages of conversations already listed,
+    // and conversations at the end of the list.
+    fn add_older_conversations(&mut self, count: usize, older: Vec<Conversation>, entries: usize) {
+        self.loading_older = false;
+        self.logs_requested = count;
+        self.older_logs = entries >= count;
+        let highlighted = self.highlighted_conversation();
+        let mut added = 0;
+        for conversation in older {
+            let Some(index) = self
+                .conversations
+                .iter()
+                .position(|c| c.id == conversation.id)
+            else {
+                self.conversations.push(conversation);
+                added += 1;
+                continue;
+            };
+            let inserted = conversation.messages.len();
+            let existing = &mut self.conversations[index];
+            existing.messages.splice(0..0, conversation.messages);
+            existing.started = conversation.started.or(existing.started.take());
+            if Some(index) == self.current_conversation_index {
+                self.chat_state.marked.clear();
+                let selected = self.chat_state.list_state.selected();
+                self.chat_state
+                    .list_state
+                    .select(selected.map(|selected| selected + inserted));
             }
-            None => 0,
+        }
+        let position = highlighted.and_then(|index| {
+            self.visible_conversations()
+                .iter()
+                .position(|&visible| visible == index)
+        });
+        self.conversation_list_state.select(position);
+        let message = match (added, self.older_logs) {
+            (0, false) => "No older conversations".to_string(),
+            (added, false) => format!("Loaded {} older conversation(s), that's all", added),
+            (added, true) => format!("Loaded {} older conversation(s)", added),
         };
-        self.model_list_state.select(Some(i));
+        self.set_feedback(message, FeedbackType::Positive);
     }
 
-    fn previous_model(&mut self) {
-        let i = match self.model_list_state.selected() {
+    fn previous_conversation(&mut self) {
+        let len = self.visible_conversations().len();
+        if len == 0 {
+            return;
+        }
+        let i = match self.conversation_list_state.selected() {
             Some(i) => {
                 if i == 0 {
-                    self.models.len() - 1
+                    len - 1
                 } else {
                     i - 1
                 }
             }
             None => 0,
         };
-        self.model_list_state.select(Some(i));
+        self.conversation_list_state.select(Some(i));
     }
 
-    fn send_message(&mut self) {
-        if let Some(index) = self.current_conversation_index {
-            let conversation = &mut self.conversations[index];
-            let prompt = self.input.clone();
-            conversation.messages.push(Message {
-                role: "user".to_string(),
-                content: prompt.clone(),
-            });
+    fn toggle_project_filter(&mut self) {
+        self.project_filter = !self.project_filter;
+        self.sync_conversation_list();
+    }
 
-            self.input.clear();
-            self.state = AppState::Thinking;
+    // Switches the working directory to the one the selected conversation was started
+    // from, so commands and file references resolve relative to that project.
+    fn enter_conversation_directory(&mut self) {
+        let Some(index) = self.current_conversation_index else {
+            return;
+        };
+        let Some(directory) = self.conversations[index].directory.clone() else {
+            self.set_feedback(
+                "Conversation has no recorded directory".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        match env::set_current_dir(&directory) {
+            Ok(()) => {
+                if self.project.take().is_some() || self.indexing.take().is_some() {
+                    self.toggle_project_mode();
+                }
+                self.set_feedback(
+                    format!("Working directory: {}", directory.display()),
+                    FeedbackType::Positive,
+                );
+            }
+            Err(e) => self.set_feedback(
+                format!("Failed to enter {}: {}", directory.display(), e),
+                FeedbackType::Negative,
+            ),
+        }
+    }
+
+    fn remember_directory(&mut self, index: usize) {
+        let conversation = &mut self.conversations[index];
+        if conversation.directory.is_some() {
+            return;
+        }
+        let Ok(cwd) = env::current_dir() else {
+            return;
+        };
+        let mut directories = store::load(CONVERSATION_DIRS_FILE);
+        if !directories.is_object() {
+            directories = Value::Object(Default::default());
+        }
+        directories[conversation.id.as_str()] = Value::String(cwd.to_string_lossy().into_owned());
+        conversation.directory = Some(cwd);
+        if let Err(e) = store::save(CONVERSATION_DIRS_FILE, &directories) {
+            self.set_feedback(
+                format!("Failed to save conversation directory: {}", e),
+                FeedbackType::Negative,
+            );
+        }
+    }
 
-            let tx = self.tx.clone();
-            let model_alias = self.models[self.model_list_state.selected().unwrap_or(0)]
-                .alias
-                .clone();
+    // Indices into `models` of the ones the model filter leaves, best matches first.
+    fn visible_models(&self) -> Vec<usize> {
+        let mut scored: Vec<(i64, usize)> = self
+            .models
+            .iter()
+            .enumerate()
+            .filter_map(|(i, m)| {
+                let score = fuzzy::score(&self.model_query, &m.full_name)
+                    .max(fuzzy::score(&self.model_query, &m.alias))?;
+                Some((score, i))
+            })
+            .collect();
+        scored.sort_by_key(|&(score, _)| Reverse(score));
+        scored.into_iter().map(|(_, i)| i).collect()
+    }
 
-            thread::spawn(move || {
-                let response = run_llm(&prompt, &model_alias);
-                tx.send(response).unwrap();
-            });
+    // Narrows the model selector to `query` and selects the best match.
+    fn set_model_query(&mut self, query: String) {
+        self.model_query = query;
+        if let Some(&best) = self.visible_models().first() {
+            self.model_list_state.select(Some(best));
         }
     }
 
-    fn check_for_response(&mut self) {
-        if let Ok(response) = self.rx.try_recv() {
+    // Moves the selection `step` places through the visible models, wrapping around.
+    fn step_model(&mut self, step: isize) {
+        let visible = self.visible_models();
+        if visible.is_empty() {
+            return;
+        }
+        let i = match self
+            .model_list_state
+            .selected()
+            .and_then(|selected| visible.iter().position(|&i| i == selected))
+        {
+            Some(position) => {
+                (position as isize + step).rem_euclid(visible.len() as isize) as usize
+            }
+            None => 0,
+        };
+        self.model_list_state.select(Some(visible[i]));
+    }
+
+    fn next_model(&mut self) {
+        self.step_model(1);
+    }
+
+    fn previous_model(&mut self) {
+        self.step_model(-1);
+    }
+
+    fn send_message(&mut self) {
+        if self.kiosk {
+            if self.input.starts_with('/') {
+                self.set_feedback(
+                    "Commands are disabled in kiosk mode".to_string(),
+                    FeedbackType::Negative,
+                );
+                return;
+            }
+            if let Some(word) = self.banned_word() {
+                self.set_feedback(
+                    format!("'{}' isn't allowed here", word),
+                    FeedbackType::Negative,
+                );
+                return;
+            }
             if let Some(index) = self.current_conversation_index {
-                let conversation = &mut self.conversations[index];
-                conversation.messages.push(Message {
-                    role: "assistant".to_string(),
-                    content: response,
-                });
-                self.state = AppState::Normal;
-                self.scroll_to_bottom();
+                self.send_input(index);
+            }
+            return;
+        }
+        if let Some(command) = self.input.strip_prefix('/') {
+            let command = command.to_string();
+            self.input.clear();
+            self.run_slash_command(&command);
+            return;
+        }
+        if let Some(index) = self.current_conversation_index {
+            if self.config.duplicate_threshold.is_some() && self.offer_earlier_answer(index) {
+                return;
             }
+            self.send_input(index);
         }
     }
 
-    fn scroll_to_bottom(&mut self) {
+    fn send_input(&mut self, index: usize) {
+        let prompt = self.expanded_input();
+        self.input.clear();
+        let attachments = std::mem::take(&mut self.pending_fragments);
+        self.send_prompt(index, prompt, attachments, None);
+    }
+
+    // The input as it's sent: with `@path` mentions inlined, except in kiosk mode,
+    // which keeps the user away from files.
+    fn expanded_input(&self) -> String {
+        if self.kiosk {
+            self.input.to_string()
+        } else {
+            mentions::inline(self.input.as_str())
+        }
+    }
+
+    // Shows what sending the input would send: the prompt with mentioned files
+    // inlined, its size, the system prompt and whatever goes along with it.
+    fn preview_input(&mut self) {
+        let prompt = self.config.redact(&self.expanded_input());
+        let model = self.selected_model().unwrap_or_default();
+        let counter = tokens::TokenCounter::for_model(&model, &self.config.tokenizers);
+        let approximate = if counter.is_exact() { "" } else { "~" };
+        let mut lines = vec![format!("Model: {}", model), String::new()];
+        let system = match self.current_conversation_index {
+            Some(index) => self.system_prompt(index),
+            None => self.config.system_prompt.clone(),
+        };
+        match system {
+            None => lines.push("System prompt: none".to_string()),
+            Some(system) => {
+                lines.push(format!(
+                    "System prompt ({}{} tokens):",
+                    approximate,
+                    counter.count(&system)
+                ));
+                lines.extend(system.lines().map(|line| format!("  {}", line)));
+            }
+        }
+        lines.push(String::new());
+        lines.push(format!(
+            "Prompt ({} chars, {}{} tokens):",
+            prompt.chars().count(),
+            approximate,
+            counter.count(&prompt)
+        ));
+        lines.extend(prompt.lines().map(|line| format!("  {}", line)));
+        let files: Vec<String> = self
+            .project_fragments(&prompt)
+            .into_iter()
+            .chain(self.pending_fragments.iter().cloned())
+            .map(|(label, _)| label)
+            .chain(self.pending_attachments.iter().map(|a| attachment_label(a)))
+            .collect();
+        if !files.is_empty() {
+            lines.push(String::new());
+            lines.push("With:".to_string());
+            lines.extend(files.iter().map(|file| format!("  {}", file)));
+        }
         if let Some(index) = self.current_conversation_index {
-            let message_count = self.conversations[index].messages.len();
-            if message_count > 0 {
-                self.chat_state.list_state.select(Some(message_count - 1));
+            let history = self.history_context(index).len();
+            let pinned = self.pinned_context(index).len();
+            if history + pinned > 0 {
+                lines.push(String::new());
+                lines.push(format!(
+                    "Context: {} earlier message(s), {} pinned item(s)",
+                    history, pinned
+                ));
             }
         }
+        self.panel = Some(Panel {
+            title: "Prompt preview | j/k: Scroll | Esc: Close".to_string(),
+            lines,
+            scroll: 0,
+        });
     }
 
-    fn start_new_conversation(&mut self) {
-        let new_id = self.conversations.len().to_string();
-        let new_conversation = Conversation {
-            id: new_id.clone(),
-            name: format!("New Conversation {}", new_id),
-            messages: Vec::new(),
+    // Looks for an earlier, answered prompt similar to the input and, if there is
+    // one, shows its answer with the option to send anyway. The input is kept until
+    // then so cancelling leaves it to edit.
+    fn offer_earlier_answer(&mut self, index: usize) -> bool {
+        let threshold = self.config.duplicate_threshold.unwrap_or(1.0);
+        let candidates = self
+            .conversations
+            .iter()
+            .enumerate()
+            .flat_map(|(c, conversation)| {
+                conversation
+                    .messages
+                    .windows(2)
+                    .enumerate()
+                    .filter(|(_, pair)| {
+                        pair[0].role == "user"
+                            && pair[1].role == "assistant"
+                            && !pair[0].hidden
+                            && !pair[1].failed
+                    })
+                    .map(move |(m, pair)| (c, m, pair[0].content.as_str()))
+            });
+        let Some(duplicate) = duplicates::find(&self.input, candidates, threshold) else {
+            return false;
         };
-        self.conversations.push(new_conversation);
-        self.current_conversation_index = Some(self.conversations.len() - 1);
-        self.conversation_list_state
-            .select(Some(self.conversations.len() - 1));
+        let conversation = &self.conversations[duplicate.conversation];
+        let answer = &conversation.messages[duplicate.message + 1].content;
+        let theme = &self.config.theme;
+        let mut lines = vec![
+            format!(
+                "{}{}",
+                theme.prefix("user", None),
+                preview(&conversation.messages[duplicate.message].content, 80)
+            ),
+            String::new(),
+        ];
+        let assistant = theme.prefix("assistant", conversation.model.as_deref());
+        lines.extend(
+            answer
+                .lines()
+                .take(EARLIER_ANSWER_LINES)
+                .map(|line| format!("{}{}", assistant, line)),
+        );
+        if answer.lines().count() > EARLIER_ANSWER_LINES {
+            lines.push("...".to_string());
+        }
+        self.confirm = Some(Confirm {
+            title: format!(
+                "Asked before in '{}' ({:.0}% similar). y/Enter: Send anyway | n/Esc: Cancel",
+                conversation.name,
+                duplicate.score * 100.0
+            ),
+            lines,
+            action: ConfirmAction::SendAnyway(index),
+        });
+        true
     }
 
-    fn toggle_conversation_list(&mut self) {
-        self.show_conversation_list = !self.show_conversation_list;
-        if !self.show_conversation_list
-            && matches!(self.focused_block, FocusedBlock::ConversationList)
-        {
-            self.next_focus();
+    // Sends `prompt` in the given conversation, which needn't be the one on screen.
+    // `attachments` are extra (label, path) fragments; `reply` also receives the
+    // response.
+    fn send_prompt(
+        &mut self,
+        index: usize,
+        prompt: String,
+        attachments: Vec<(String, String)>,
+        reply: Option<Sender<String>>,
+    ) {
+        // One request per conversation: a second would stream into the same message.
+        if self.running.contains_key(&index) {
+            self.held.push_back(HeldPrompt {
+                index,
+                prompt,
+                attachments,
+                reply,
+                template: self.pending_template.take(),
+            });
+            self.set_feedback(
+                format!(
+                    "'{}' is still being answered; the prompt is sent after that",
+                    self.conversations[index].name
+                ),
+                FeedbackType::Positive,
+            );
+            return;
+        }
+        let prompt = self.config.redact(&prompt);
+        // Oldest first, which is the order they're dropped in if they don't fit.
+        let parts: Vec<ContextPart> = self
+            .history_context(index)
+            .into_iter()
+            .chain(
+                self.pinned_context(index)
+                    .into_iter()
+                    .map(|text| ContextPart {
+                        label: format!("pinned: {}", preview(&text, 40)),
+                        source: PartSource::Pinned(text),
+                    }),
+            )
+            .chain(
+                self.project_fragments(&prompt)
+                    .into_iter()
+                    .chain(attachments)
+                    .map(|(label, path)| ContextPart {
+                        label,
+                        source: PartSource::File(path),
+                    }),
+            )
+            .collect();
+        self.dispatch_prompt(index, prompt, parts, reply);
+    }
+
+    // Sends the next prompt held for the conversation, once nothing runs in it.
+    fn send_held(&mut self, index: usize) {
+        if self.running.contains_key(&index) {
+            return;
+        }
+        let Some(position) = self.held.iter().position(|held| held.index == index) else {
+            return;
+        };
+        if let Some(held) = self.held.remove(position) {
+            self.pending_template = held.template;
+            self.send_prompt(held.index, held.prompt, held.attachments, held.reply);
+        }
+    }
+
+    fn dispatch_prompt(
+        &mut self,
+        index: usize,
+        prompt: String,
+        parts: Vec<ContextPart>,
+        reply: Option<Sender<String>>,
+    ) {
+        let Some(model_alias) = self.model_or_selected(None) else {
+            return;
+        };
+        if self.suggestions.as_ref().is_some_and(|(c, _)| *c == index) {
+            self.suggestions = None;
+        }
+        let mut fragments = Vec::new();
+        let mut history = Vec::new();
+        let mut pinned = Vec::new();
+        for part in &parts {
+            match &part.source {
+                PartSource::History(role, text) => history.push((role.as_str(), text.as_str())),
+                PartSource::Pinned(text) => pinned.push(text.as_str()),
+                PartSource::File(path) => fragments.push((part.label.clone(), path.clone())),
+            }
+        }
+        let mut temporary = TempFiles(Vec::new());
+        if !pinned.is_empty() {
+            match save_fragment("pinned", &pinned.join("\n\n---\n\n")) {
+                Ok(path) => {
+                    fragments.insert(
+                        0,
+                        (
+                            format!("{} pinned item(s)", pinned.len()),
+                            path.to_string_lossy().into_owned(),
+                        ),
+                    );
+                    temporary.0.push(path);
+                }
+                Err(e) => self.set_feedback(
+                    format!("Failed to attach pinned context: {}", e),
+                    FeedbackType::Negative,
+                ),
+            }
+        }
+        if !history.is_empty() {
+            let count = history.len();
+            match save_fragment("history", &context::transcript(history)) {
+                Ok(path) => {
+                    fragments.insert(
+                        0,
+                        (
+                            format!("{} earlier message(s)", count),
+                            path.to_string_lossy().into_owned(),
+                        ),
+                    );
+                    temporary.0.push(path);
+                }
+                Err(e) => self.set_feedback(
+                    format!("Failed to attach conversation history: {}", e),
+                    FeedbackType::Negative,
+                ),
+            }
+        }
+        let attachments = std::mem::take(&mut self.pending_attachments);
+        let mut message = Message::new("user", prompt.clone());
+        message.fragments = fragments
+            .iter()
+            .map(|(shown, _)| shown.clone())
+            .chain(attachments.iter().map(|path| attachment_label(path)))
+            .collect();
+        self.conversations[index].messages.push(message);
+        self.remember_directory(index);
+        if Some(index) == self.current_conversation_index {
+            self.scroll_to_bottom();
+        }
+
+        self.state = AppState::Thinking;
+
+        self.conversations[index].model = Some(model_alias.clone());
+        self.conversations[index].last_active = Some(unix_now());
+        if self.list_sort != SortKey::Log {
+            self.sync_conversation_list();
+        }
+
+        let mut preferences = self.conversations[index].preferences.clone();
+        if let Some(seed) = self.pending_seed.take() {
+            preferences.seed = Some(seed);
+        }
+        let template = self.pending_template.take();
+        let prompt = template
+            .as_ref()
+            .map_or(prompt, |template| template.input.clone());
+        let request = LlmRequest {
+            system: self.system_prompt(index),
+            fragments: self
+                .config
+                .fragments
+                .iter()
+                .cloned()
+                .chain(fragments.into_iter().map(|(_, path)| path))
+                .collect(),
+            env: self.command_env(index),
+            options: preferences.model_options(
+                &self.config.stop_option,
+                &self.config.prefix_option,
+                &self.config.seed_option,
+            ),
+            template,
+            attachments,
+            ..LlmRequest::new(prompt, model_alias)
+        };
+        let counter = tokens::TokenCounter::for_model(&request.model, &self.config.tokenizers);
+        let sent_tokens = counter.count(&request.prompt)
+            + request
+                .system
+                .as_deref()
+                .map_or(0, |system| counter.count(system))
+            + parts
+                .iter()
+                .map(|part| part_tokens(part, &counter))
+                .sum::<usize>();
+        self.session
+            .record_prompt(&self.conversations[index].name, &request.model, sent_tokens);
+        self.last_sent.insert(
+            index,
+            SentPrompt {
+                prompt: request.prompt.clone(),
+                parts,
+                seed: preferences.seed,
+            },
+        );
+        let limit = self
+            .concurrency_limit(&request.model)
+            .filter(|&limit| self.requests_in_flight(&request.model) >= limit);
+        let running = RunningChild::default();
+        self.running.insert(index, Arc::clone(&running));
+        let waiting = WaitingRequest {
+            index,
+            request,
+            reply,
+            running,
+            temporary,
+        };
+        if limit.is_some() {
+            self.waiting.push_back(waiting);
+            if let Some(reason) = self.waiting_reason(index) {
+                self.set_feedback(reason, FeedbackType::Positive);
+            }
+        } else {
+            self.start_request(waiting);
+        }
+    }
+
+    fn start_request(&self, waiting: WaitingRequest) {
+        let WaitingRequest {
+            index,
+            request,
+            reply,
+            running,
+            temporary,
+        } = waiting;
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            let _temporary = temporary;
+            let response = stream_llm_cancellable(&request, &running, |chunk| {
+                let _ = tx.send(ResponseEvent::Chunk(index, chunk.to_string()));
+            });
+            let Some(response) = response else {
+                if let Some(reply) = reply {
+                    let _ = reply.send("\nError: Cancelled".to_string());
+                }
+                let _ = tx.send(ResponseEvent::Cancelled(index));
+                return;
+            };
+            if let Some(reply) = reply {
+                let _ = reply.send(
+                    response
+                        .as_ref()
+                        .map_or_else(LlmFailure::transcript, String::clone),
+                );
+            }
+            let answer = response.as_ref().ok().cloned();
+            let _ = tx.send(ResponseEvent::Done(index, response));
+            if let Some((conversation_id, log_id)) = answer.as_deref().and_then(find_logged) {
+                let _ = tx.send(ResponseEvent::Logged(index, conversation_id, log_id));
+            }
+        });
+    }
+
+    // From `[concurrency]`, which may name the model by its alias or full name.
+    fn concurrency_limit(&self, alias: &str) -> Option<usize> {
+        let full_name = self
+            .models
+            .iter()
+            .find(|m| m.alias == alias)
+            .map(|m| m.full_name.as_str());
+        self.config
+            .concurrency
+            .iter()
+            .find(|(name, _)| name == alias || Some(name.as_str()) == full_name)
+            .map(|(_, limit)| *limit)
+    }
+
+    // Requests to the model that have been sent and not answered yet.
+    fn requests_in_flight(&self, alias: &str) -> usize {
+        self.running
+            .keys()
+            .filter(|&&index| !self.waiting.iter().any(|w| w.index == index))
+            .filter(|&&index| self.conversations[index].model.as_deref() == Some(alias))
+            .count()
+    }
+
+    // Sends the waiting requests whose model has a free slot, oldest first.
+    fn start_waiting_requests(&mut self) {
+        let mut i = 0;
+        while i < self.waiting.len() {
+            let model = &self.waiting[i].request.model;
+            if self
+                .concurrency_limit(model)
+                .is_some_and(|limit| self.requests_in_flight(model) >= limit)
+            {
+                i += 1;
+                continue;
+            }
+            if let Some(waiting) = self.waiting.remove(i) {
+                self.start_request(waiting);
+            }
+        }
+    }
+
+    // Which limit the conversation's request is waiting on, if it's waiting.
+    fn waiting_reason(&self, index: usize) -> Option<String> {
+        let position = self.waiting.iter().position(|w| w.index == index)?;
+        let model = &self.waiting[position].request.model;
+        let ahead = self
+            .waiting
+            .iter()
+            .take(position)
+            .filter(|w| &w.request.model == model)
+            .count();
+        let limit = self.concurrency_limit(model).unwrap_or(1);
+        let mut reason = format!("Waiting for {}: {} request(s) at a time", model, limit);
+        if ahead > 0 {
+            reason.push_str(&format!(", {} queued ahead", ahead));
+        }
+        Some(reason)
+    }
+
+    // Kills the request in flight in the open conversation, drops what it streamed
+    // so far and puts the prompt back in the input for another try.
+    fn cancel_request(&mut self) -> bool {
+        let Some(index) = self.current_conversation_index else {
+            return false;
+        };
+        let Some(running) = self.running.remove(&index) else {
+            return false;
+        };
+        if let Some(mut child) = running.lock().unwrap().take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        self.waiting.retain(|w| w.index != index);
+        self.start_waiting_requests();
+        let messages = &mut self.conversations[index].messages;
+        if messages.last().is_some_and(|m| m.streaming) {
+            messages.pop();
+        }
+        if messages.last().is_some_and(|m| m.role == "user") {
+            let prompt = messages.pop().map(|m| m.content).unwrap_or_default();
+            if self.input.is_empty() {
+                self.input.set(prompt);
+            }
+        }
+        if self.running.is_empty() {
+            self.state = AppState::Normal;
+        }
+        self.set_feedback("Request cancelled".to_string(), FeedbackType::Positive);
+        self.send_held(index);
+        true
+    }
+
+    fn run_slash_command(&mut self, command: &str) {
+        let (name, argument) = command
+            .split_once(char::is_whitespace)
+            .map(|(name, argument)| (name, argument.trim()))
+            .unwrap_or((command, ""));
+        match name {
+            "fetch" if !argument.is_empty() => self.fetch_url(argument.to_string()),
+            "fetch" => self.set_feedback("Usage: /fetch <url>".to_string(), FeedbackType::Negative),
+            "attach" => self.attach(argument),
+            "digest" => self.run_digest_now(),
+            "explain-last" => self.explain_last_failure(),
+            "pin" if !argument.is_empty() => self.pin_snippet(argument.to_string()),
+            "pin" => self.set_feedback("Usage: /pin <text>".to_string(), FeedbackType::Negative),
+            "unpin" => self.unpin_snippet(argument),
+            "filters" => self.set_filters(argument),
+            "env" => self.set_env(argument),
+            "prefs" => self.open_preferences(),
+            "options" => self.open_model_options(),
+            "plain" => self.toggle_plain(),
+            "template" => self.use_template(argument),
+            "snippet" => self.insert_snippet(argument),
+            "persona" => self.set_persona(argument),
+            "system" => self.set_system_prompt(argument),
+            "library" => self.show_library(argument),
+            "translate" => self.translate_selected(argument),
+            "improve" => self.improve_prompt(argument),
+            "gen-tests" => self.generate_tests(argument),
+            "eval" => self.run_eval(argument),
+            "summarize" => self.summarize_conversation(),
+            "reactions" => self.show_reactions(argument),
+            "note" => self.append_note(),
+            "autotag" => self.auto_tag(),
+            "python" => self.reset_python(argument),
+            "server" => self.show_server_status(),
+            "search" => self.open_global_search(argument),
+            _ => self.set_feedback(
+                format!("Unknown command: /{}", name),
+                FeedbackType::Negative,
+            ),
+        }
+    }
+
+    // Tab after `@path` in the input completes the file name; several matches are
+    // listed in the feedback.
+    fn complete_mention(&mut self) -> bool {
+        if self.kiosk {
+            return false;
+        }
+        let Some((suffix, names)) = mentions::complete(self.input.as_str()) else {
+            return false;
+        };
+        self.input.push_str(&suffix);
+        match names.len() {
+            0 => self.set_feedback("No matching files".to_string(), FeedbackType::Negative),
+            1 => {}
+            count => {
+                let mut shown = names[..count.min(MENTION_MATCHES_SHOWN)].join("  ");
+                if count > MENTION_MATCHES_SHOWN {
+                    shown.push_str(&format!("  (+{} more)", count - MENTION_MATCHES_SHOWN));
+                }
+                self.set_feedback(shown, FeedbackType::Positive);
+            }
+        }
+        true
+    }
+
+    // `/attach <path|url>` adds an attachment (an image for a vision model, say) to
+    // the next prompt; `/attach off` drops them.
+    fn attach(&mut self, argument: &str) {
+        match argument {
+            "" => self.set_feedback(
+                "Usage: /attach <path|url> (/attach off: remove attachments)".to_string(),
+                FeedbackType::Negative,
+            ),
+            "off" => {
+                self.pending_attachments.clear();
+                self.set_feedback("Attachments removed".to_string(), FeedbackType::Positive);
+            }
+            url if url.starts_with("http://") || url.starts_with("https://") => {
+                self.pending_attachments.push(url.to_string());
+                self.set_feedback(
+                    format!("Attached to next prompt: {}", url),
+                    FeedbackType::Positive,
+                );
+            }
+            path => {
+                let path = config::expand_home(path);
+                match fs::canonicalize(&path) {
+                    Ok(path) if path.is_file() => {
+                        let path = path.to_string_lossy().into_owned();
+                        self.set_feedback(
+                            format!("Attached to next prompt: {}", attachment_label(&path)),
+                            FeedbackType::Positive,
+                        );
+                        self.pending_attachments.push(path);
+                    }
+                    _ => self.set_feedback(
+                        format!("No file at {}", path.display()),
+                        FeedbackType::Negative,
+                    ),
+                }
+            }
+        }
+    }
+
+    // Downloads a page in the background and queues its readable text as a fragment
+    // for the next prompt.
+    fn fetch_url(&mut self, url: String) {
+        self.set_feedback(format!("Fetching {}...", url), FeedbackType::Positive);
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let result = fetch::fetch_readable(&url).and_then(|text| {
+                let path = save_fragment("fetch", &text).map_err(|e| e.to_string())?;
+                let label = format!("{} ({})", url, format_size(text.len()));
+                Ok((label, path.to_string_lossy().into_owned()))
+            });
+            let _ = background_tx.send(BackgroundEvent::Fetched(result));
+        });
+    }
+
+    fn check_background_events(&mut self) {
+        while let Ok(event) = self.background_rx.try_recv() {
+            match event {
+                BackgroundEvent::ProjectIndexed(root, result) => self.project_indexed(root, result),
+                BackgroundEvent::LibrarySynced(Ok(())) => self.reload_library(),
+                BackgroundEvent::LibrarySynced(Err(e)) => {
+                    self.reload_library();
+                    self.set_feedback(
+                        format!("Failed to sync library: {}", e),
+                        FeedbackType::Negative,
+                    )
+                }
+                BackgroundEvent::Fetched(Ok((label, path))) => {
+                    self.set_feedback(
+                        format!("Attached to next prompt: {}", label),
+                        FeedbackType::Positive,
+                    );
+                    self.pending_fragments.push((label, path));
+                }
+                BackgroundEvent::Fetched(Err(e)) => {
+                    self.set_feedback(format!("Fetch failed: {}", e), FeedbackType::Negative)
+                }
+                BackgroundEvent::Digest(Ok(digest)) => self.append_digest(digest),
+                BackgroundEvent::Digest(Err(e)) => {
+                    self.set_feedback(format!("Digest failed: {}", e), FeedbackType::Negative)
+                }
+                BackgroundEvent::Summarized(id, Ok(response)) => {
+                    if let Err(e) = self.store_summary(&id, &response, true) {
+                        self.set_feedback(e, FeedbackType::Negative);
+                    }
+                }
+                BackgroundEvent::Summarized(_, Err(e)) => {
+                    self.set_feedback(format!("Summary failed: {}", e), FeedbackType::Negative)
+                }
+                BackgroundEvent::AutoTagged(id, result) => self.on_auto_tagged(&id, result),
+                BackgroundEvent::Suggested(id, suggestions) => {
+                    // Drop suggestions for an answer that's no longer the latest.
+                    let latest =
+                        self.conversations
+                            .iter()
+                            .position(|c| c.id == id)
+                            .filter(|&index| {
+                                self.conversations[index]
+                                    .messages
+                                    .last()
+                                    .is_some_and(|m| m.role == "assistant" && !m.streaming)
+                            });
+                    if let Some(index) = latest.filter(|_| !suggestions.is_empty()) {
+                        self.suggestions = Some((index, suggestions));
+                    }
+                }
+                BackgroundEvent::Exported(Ok(message)) => {
+                    self.set_feedback(message, FeedbackType::Positive)
+                }
+                BackgroundEvent::Exported(Err(e)) => {
+                    self.set_feedback(format!("Failed to export: {}", e), FeedbackType::Negative)
+                }
+                BackgroundEvent::Translated(language, Ok(translation)) => {
+                    self.panel = Some(Panel {
+                        title: format!("Translation ({}) | j/k: Scroll | Esc: Close", language),
+                        lines: translation.lines().map(str::to_string).collect(),
+                        scroll: 0,
+                    })
+                }
+                BackgroundEvent::Improved(draft, Ok(improved)) => {
+                    let mut lines = improve::side_by_side("Your prompt", "Suggested", 42);
+                    lines.push(format!("{}┼{}", "─".repeat(43), "─".repeat(44)));
+                    lines.extend(improve::side_by_side(&draft, improved.trim(), 42));
+                    self.confirm = Some(Confirm {
+                        title: "Improved prompt. y/Enter: Use it | n/Esc: Keep yours".to_string(),
+                        lines,
+                        action: ConfirmAction::UseImprovedPrompt(improved.trim().to_string()),
+                    });
+                }
+                BackgroundEvent::Improved(_, Err(e)) => {
+                    self.set_feedback(format!("Improving failed: {}", e), FeedbackType::Negative)
+                }
+                BackgroundEvent::Translated(_, Err(e)) => {
+                    self.set_feedback(format!("Translation failed: {}", e), FeedbackType::Negative)
+                }
+                BackgroundEvent::Ran(language, Ok((status, output))) => {
+                    let mut lines: Vec<String> = output.lines().map(str::to_string).collect();
+                    if lines.is_empty() {
+                        lines.push("(no output)".to_string());
+                    }
+                    self.panel = Some(Panel {
+                        title: format!(
+                            "{} output ({}) | j/k: Scroll | Esc: Close",
+                            language, status
+                        ),
+                        lines,
+                        scroll: 0,
+                    })
+                }
+                BackgroundEvent::PythonRan(id, result) => self.on_python_ran(&id, result),
+                BackgroundEvent::TestsGenerated(path, Ok(response)) => {
+                    let contents = gen_tests::extract(&response);
+                    self.set_feedback(
+                        format!("Generated {} lines of tests", contents.lines().count()),
+                        FeedbackType::Positive,
+                    );
+                    self.open_prompt(PromptKind::SaveTests(contents), path);
+                }
+                BackgroundEvent::Completed(text, reply) => {
+                    if text == self.completion.seen && self.completion.pending.take().is_some() {
+                        self.completion.suggestion =
+                            reply.and_then(|reply| completion::clean(&text, &reply));
+                        self.needs_redraw = true;
+                    }
+                }
+                BackgroundEvent::EvalProgress(done, total) => {
+                    self.evaluating = Some((done, total));
+                    self.set_feedback(
+                        format!("Evaluating... {}/{}", done, total),
+                        FeedbackType::Positive,
+                    );
+                }
+                BackgroundEvent::OlderLogs(count, Ok((older, entries))) => {
+                    self.add_older_conversations(count, older, entries)
+                }
+                BackgroundEvent::OlderLogs(_, Err(e)) => {
+                    self.loading_older = false;
+                    self.set_feedback(
+                        format!("Failed to load older conversations: {}", e),
+                        FeedbackType::Negative,
+                    );
+                }
+                BackgroundEvent::Evaluated(report) => {
+                    self.evaluating = None;
+                    self.set_feedback(
+                        format!(
+                            "{}: {} of {} passed",
+                            report.name,
+                            report.passed(),
+                            report.total()
+                        ),
+                        if report.passed() == report.total() {
+                            FeedbackType::Positive
+                        } else {
+                            FeedbackType::Negative
+                        },
+                    );
+                    self.panel = Some(Panel {
+                        title: format!(
+                            "Eval: {} | /eval export <file.csv> | j/k: Scroll | Esc: Close",
+                            report.name
+                        ),
+                        lines: report.matrix_lines(),
+                        scroll: 0,
+                    });
+                    self.eval_report = Some(report);
+                }
+                BackgroundEvent::TestsGenerated(_, Err(e)) => self.set_feedback(
+                    format!("Generating tests failed: {}", e),
+                    FeedbackType::Negative,
+                ),
+                BackgroundEvent::Ran(_, Err(e)) => {
+                    self.set_feedback(format!("Run failed: {}", e), FeedbackType::Negative)
+                }
+                BackgroundEvent::Copied(Ok(message)) => {
+                    self.set_feedback(message, FeedbackType::Positive)
+                }
+                BackgroundEvent::Copied(Err(e)) => {
+                    self.set_feedback(format!("Failed to copy: {}", e), FeedbackType::Negative)
+                }
+                BackgroundEvent::ClipboardRead(Ok(text)) => self.paste(&text),
+                BackgroundEvent::ClipboardRead(Err(e)) => {
+                    self.set_feedback(format!("Failed to paste: {}", e), FeedbackType::Negative)
+                }
+            }
+        }
+    }
+
+    // Sends the last failed shell command recorded by the shell hook (see
+    // `--shell-hook`) with an explain-and-fix prompt.
+    fn explain_last_failure(&mut self) {
+        // The shell hook doesn't know about profiles.
+        let report = store::base_dir()
+            .map(|dir| dir.join(LAST_FAILURE_FILE))
+            .and_then(|path| fs::read_to_string(path).ok());
+        let Some(report) = report.filter(|r| !r.trim().is_empty()) else {
+            self.set_feedback(
+                "No failed command recorded; enable the shell hook with: eval \"$(llm-tui --shell-hook)\""
+                    .to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        if self.current_conversation_index.is_none() {
+            self.start_new_conversation();
+        }
+        self.input.set(explain_failure_prompt(&report));
+        self.send_message();
+    }
+
+    fn digest_job(&self) -> Option<DigestJob> {
+        if self.config.digest_feeds.is_empty() {
+            return None;
+        }
+        Some(DigestJob {
+            feeds: self.config.digest_feeds.clone(),
+            model: self
+                .config
+                .digest_model
+                .clone()
+                .or_else(|| self.config.model.clone()),
+            secrets: self.config.redact.clone(),
+            background_tx: self.background_tx.clone(),
+            paused: Arc::clone(&self.background_paused),
+            idle: Arc::clone(&self.idle),
+        })
+    }
+
+    fn run_digest_now(&mut self) {
+        match self.digest_job() {
+            Some(job) => {
+                self.set_feedback("Building digest...".to_string(), FeedbackType::Positive);
+                thread::spawn(move || job.run());
+            }
+            None => self.set_feedback(
+                "No digest feeds configured".to_string(),
+                FeedbackType::Negative,
+            ),
+        }
+    }
+
+    fn append_digest(&mut self, digest: String) {
+        let mut history = store::load(DIGEST_FILE);
+        if let Some(entries) = history.as_array_mut() {
+            entries.push(Value::String(digest.clone()));
+        } else {
+            history = Value::Array(vec![Value::String(digest.clone())]);
+        }
+        if let Err(e) = store::save(DIGEST_FILE, &history) {
+            self.set_feedback(
+                format!("Failed to save digest: {}", e),
+                FeedbackType::Negative,
+            );
+        }
+        self.push_digest_message(digest);
+        self.set_feedback(
+            format!("New digest in '{}'", self.config.digest_conversation),
+            FeedbackType::Positive,
+        );
+    }
+
+    fn push_digest_message(&mut self, digest: String) {
+        let index = match self.conversations.iter().position(|c| c.id == DIGEST_ID) {
+            Some(index) => index,
+            None => {
+                self.conversations.push(Conversation {
+                    id: DIGEST_ID.to_string(),
+                    name: self.config.digest_conversation.clone(),
+                    messages: Vec::new(),
+                    directory: None,
+                    snippets: Vec::new(),
+                    filter_overrides: Value::Null,
+                    env: BTreeMap::new(),
+                    preferences: Preferences::default(),
+                    summary: None,
+                    tags: Vec::new(),
+                    started: None,
+                    model: None,
+                    last_active: None,
+                    unread: 0,
+                });
+                self.conversations.len() - 1
+            }
+        };
+        self.conversations[index]
+            .messages
+            .push(Message::new("assistant", digest));
+        self.on_new_content(index, true);
+    }
+
+    // Picks the project files to attach to `prompt`, as (shown path, full path) pairs.
+    fn project_fragments(&self, prompt: &str) -> Vec<(String, String)> {
+        let Some(project) = &self.project else {
+            return Vec::new();
+        };
+        project
+            .select(prompt, self.config.project_top_k)
+            .into_iter()
+            .map(|relative| {
+                let full = project.root.join(&relative);
+                (
+                    relative.to_string_lossy().into_owned(),
+                    full.to_string_lossy().into_owned(),
+                )
+            })
+            .collect()
+    }
+
+    // Turning the mode on indexes the working directory on a helper thread; the
+    // index arrives as `BackgroundEvent::ProjectIndexed`.
+    fn toggle_project_mode(&mut self) {
+        let on = self.project.is_some() || self.indexing.is_some();
+        self.project = None;
+        self.indexing = None;
+        if on {
+            self.set_feedback(
+                "Project context mode off".to_string(),
+                FeedbackType::Positive,
+            );
+            return;
+        }
+        let root = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        self.indexing = Some(root.clone());
+        self.set_feedback(
+            format!("Indexing {}...", root.display()),
+            FeedbackType::Positive,
+        );
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let result = ProjectIndex::build(&root).map_err(|e| e.to_string());
+            let _ = background_tx.send(BackgroundEvent::ProjectIndexed(root, result));
+        });
+    }
+
+    fn project_indexed(&mut self, root: PathBuf, result: Result<ProjectIndex, String>) {
+        // Dropped if the mode was turned off, or restarted elsewhere, meanwhile.
+        if self.indexing.as_ref() != Some(&root) {
+            return;
+        }
+        self.indexing = None;
+        match result {
+            Ok(index) => {
+                self.set_feedback(
+                    format!(
+                        "Project context mode on: indexed {} files in {}",
+                        index.len(),
+                        root.display()
+                    ),
+                    FeedbackType::Positive,
+                );
+                self.project = Some(index);
+            }
+            Err(e) => self.set_feedback(
+                format!("Failed to index project: {}", e),
+                FeedbackType::Negative,
+            ),
+        }
+    }
+
+    // Applies every pending chunk before the next frame, so a fast stream costs one
+    // redraw per frame rather than one per chunk.
+    fn check_for_response(&mut self) {
+        let mut length_error = None;
+        let mut answered = Vec::new();
+        let mut finished = Vec::new();
+        while let Ok(event) = self.rx.try_recv() {
+            let index = match event {
+                ResponseEvent::Logged(index, conversation_id, log_id) => {
+                    self.link_logged(index, &conversation_id, log_id);
+                    continue;
+                }
+                ResponseEvent::Chunk(index, _)
+                | ResponseEvent::Done(index, _)
+                | ResponseEvent::Cancelled(index) => index,
+            };
+            let filters = self.filters(index);
+            let seed = self.last_sent.get(&index).and_then(|sent| sent.seed);
+            let model = self.conversations[index].model.clone();
+            let messages = &mut self.conversations[index].messages;
+            let streaming = messages.last().is_some_and(|m| m.streaming);
+            let count = messages.len();
+            match event {
+                ResponseEvent::Chunk(_, chunk) => {
+                    if streaming {
+                        if let Some(message) = messages.last_mut() {
+                            message.content.push_str(&chunk);
+                        }
+                    } else {
+                        let mut message = Message::new("assistant", chunk);
+                        message.streaming = true;
+                        message.seed = seed;
+                        messages.push(message);
+                    }
+                }
+                // Handled above.
+                ResponseEvent::Logged(..) => {}
+                ResponseEvent::Cancelled(_) => {
+                    // Chunks may have arrived after `cancel_request` tidied up.
+                    if streaming {
+                        messages.pop();
+                    }
+                }
+                ResponseEvent::Done(_, response) => {
+                    self.running.remove(&index);
+                    self.start_waiting_requests();
+                    let messages = &mut self.conversations[index].messages;
+                    if streaming {
+                        messages.pop();
+                    }
+                    match &response {
+                        Err(failure) if context::is_length_error(&failure.error) => {
+                            length_error = Some(index);
+                        }
+                        Err(_) => {}
+                        Ok(response) => {
+                            answered.push(index);
+                            if let Some(model) = &model {
+                                let counter =
+                                    tokens::TokenCounter::for_model(model, &self.config.tokenizers);
+                                self.session.record_response(model, counter.count(response));
+                            }
+                        }
+                    }
+                    let failed = response.is_err();
+                    let response = response.unwrap_or_else(|failure| failure.transcript());
+                    let filtered = filters.run(&response);
+                    let mut message = Message::new("assistant", filtered);
+                    if message.content != response {
+                        message.raw = Some(response);
+                    }
+                    message.seed = seed;
+                    message.failed = failed;
+                    messages.push(message);
+                    if self.running.is_empty() {
+                        self.state = AppState::Normal;
+                    }
+                    finished.push(index);
+                }
+            }
+            let added = self.conversations[index].messages.len() > count;
+            self.on_new_content(index, added);
+            self.needs_redraw = true;
+        }
+        if let Some(index) = length_error {
+            self.offer_context_retry(index);
+        }
+        for index in finished {
+            self.send_held(index);
+        }
+        if self.config.suggestions {
+            for index in answered {
+                self.request_suggestions(index);
+            }
+        }
+    }
+
+    // Asks the `[suggestions]` model in the background for follow-up questions to
+    // the latest answer in a conversation.
+    fn request_suggestions(&mut self, index: usize) {
+        if self.background_paused() {
+            return;
+        }
+        let conversation = &self.conversations[index];
+        let [.., question, answer] = conversation.messages.as_slice() else {
+            return;
+        };
+        if question.role != "user" {
+            return;
+        }
+        let Some(model) = self
+            .config
+            .suggestions_model
+            .clone()
+            .or_else(|| self.selected_model())
+        else {
+            return;
+        };
+        let request = self.request(
+            &suggestions::build_prompt(&question.content, &answer.content),
+            model,
+        );
+        let id = conversation.id.clone();
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            if let Ok(response) = run_llm_checked(&request) {
+                let suggestions = suggestions::parse(&response);
+                let _ = background_tx.send(BackgroundEvent::Suggested(id, suggestions));
+            }
+        });
+    }
+
+    // Puts the n-th suggestion (1-based) in the input, or sends it.
+    fn pick_suggestion(&mut self, n: usize) {
+        let Some((index, suggestions)) = &self.suggestions else {
+            return;
+        };
+        let (index, Some(suggestion)) = (*index, suggestions.get(n.wrapping_sub(1)).cloned())
+        else {
+            return;
+        };
+        if Some(index) != self.current_conversation_index {
+            return;
+        }
+        self.input.set(suggestion);
+        if self.config.suggestions_send {
+            self.send_input(index);
+        } else {
+            self.focused_block = FocusedBlock::Input;
+            self.input_mode = InputMode::Editing;
+        }
+    }
+
+    // After a context-length error, works out which of the oldest context parts to
+    // leave out and retries without them, asking first unless `auto_retry_context`.
+    fn offer_context_retry(&mut self, index: usize) {
+        let Some(sent) = self.last_sent.get(&index) else {
+            return;
+        };
+        let counter = self.token_counter();
+        let part_tokens: Vec<usize> = sent
+            .parts
+            .iter()
+            .map(|part| part_tokens(part, &counter))
+            .collect();
+        let drop = context::parts_to_drop(
+            counter.count(&sent.prompt),
+            &part_tokens,
+            self.context_limit(),
+        );
+        if drop == 0 {
+            self.set_feedback(
+                "The prompt alone is too long for this model's context window".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        let omitted: Vec<String> = sent.parts[..drop]
+            .iter()
+            .zip(&part_tokens)
+            .map(|(part, tokens)| format!("{} (~{} tokens)", part.label, tokens))
+            .collect();
+        let prompt = sent.prompt.clone();
+        let kept = sent.parts[drop..].to_vec();
+        if self.config.auto_retry_context {
+            self.retry_with_less_context(index, prompt, kept, omitted);
+            return;
+        }
+        let mut lines = vec!["Leaving out, oldest first:".to_string()];
+        lines.extend(omitted.iter().map(|label| format!("  - {}", label)));
+        lines.push(format!("Keeping {} context item(s)", kept.len()));
+        self.confirm = Some(Confirm {
+            title: "Context too long. Retry with less? y/Enter: Retry | n/Esc: Cancel".to_string(),
+            lines,
+            action: ConfirmAction::RetryWithLessContext(index, prompt, kept, omitted),
+        });
+    }
+
+    fn retry_with_less_context(
+        &mut self,
+        index: usize,
+        prompt: String,
+        parts: Vec<ContextPart>,
+        omitted: Vec<String>,
+    ) {
+        if self.running.contains_key(&index) {
+            self.set_feedback(
+                "Not retried: the conversation is being answered".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        self.dispatch_prompt(index, prompt, parts, None);
+        self.set_feedback(
+            format!("Retrying without: {}", omitted.join(", ")),
+            FeedbackType::Positive,
+        );
+    }
+
+    // Gives the exchange just answered the id llm logged it under, and links the
+    // llm conversation it went into to this one so they're shown as one after a
+    // restart.
+    fn link_logged(&mut self, index: usize, conversation_id: &str, log_id: String) {
+        let conversation = &mut self.conversations[index];
+        let messages = &mut conversation.messages;
+        if let Some(answer) = messages
+            .iter()
+            .rposition(|m| m.role == "assistant" && m.log_id.is_none())
+        {
+            messages[answer].log_id = Some(log_id.clone());
+            if let Some(prompt) = answer.checked_sub(1).map(|prompt| &mut messages[prompt]) {
+                if prompt.role == "user" && prompt.log_id.is_none() {
+                    prompt.log_id = Some(log_id);
+                }
+            }
+        }
+        if conversation.id == conversation_id {
+            return;
+        }
+        if let Err(e) = save_link(conversation_id, &conversation.id) {
+            self.set_feedback(
+                format!("Failed to save conversation link: {}", e),
+                FeedbackType::Negative,
+            );
+        }
+    }
+
+    // Keeps the chat at the bottom in follow mode, otherwise counts what was missed.
+    fn on_new_content(&mut self, conversation: usize, new_message: bool) {
+        if new_message {
+            self.conversations[conversation].last_active = Some(unix_now());
+            if self.list_sort != SortKey::Log {
+                self.sync_conversation_list();
+            }
+        }
+        if Some(conversation) != self.current_conversation_index {
+            if new_message {
+                self.conversations[conversation].unread += 1;
+                self.note_activity(conversation);
+            }
+            return;
+        }
+        if self.follow {
+            self.scroll_to_bottom();
+        } else if new_message {
+            self.unseen_messages += 1;
+        } else {
+            self.new_content_below = true;
+        }
+    }
+
+    fn note_activity(&mut self, conversation: usize) {
+        let conversation = &self.conversations[conversation];
+        let response = conversation
+            .messages
+            .last()
+            .is_some_and(|m| m.role == "assistant");
+        if !self.config.follow_activity || !response {
+            return;
+        }
+        self.activity = Some((conversation.id.clone(), Instant::now()));
+        self.set_feedback(
+            format!("Response in '{}' | Ctrl+O: Jump to it", conversation.name),
+            FeedbackType::Positive,
+        );
+    }
+
+    // Whether the list entry of `conversation` is in the lit phase of its flash.
+    fn flashing(&self, conversation: usize) -> bool {
+        self.activity.as_ref().is_some_and(|(id, at)| {
+            *id == self.conversations[conversation].id
+                && at.elapsed() < ACTIVITY_FLASH
+                && (at.elapsed().as_millis() / IDLE_REDRAW_INTERVAL.as_millis()).is_multiple_of(2)
+        })
+    }
+
+    // Ctrl+O: opens the conversation that last got a response, remembering the
+    // current one for Ctrl+B.
+    fn jump_to_activity(&mut self) {
+        let Some((id, _)) = self.activity.take() else {
+            self.set_feedback(
+                "No new responses elsewhere".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        self.jump_to(&id);
+    }
+
+    // Ctrl+B: returns to where the last jump came from; pressing it again goes back.
+    fn jump_back(&mut self) {
+        let Some(id) = self.jump_back.take() else {
+            self.set_feedback(
+                "Nowhere to jump back to".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        self.jump_to(&id);
+    }
+
+    fn jump_to(&mut self, id: &str) {
+        let Some(index) = self.conversations.iter().position(|c| c.id == id) else {
+            self.set_feedback(
+                "That conversation is gone".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        if Some(index) == self.current_conversation_index {
+            return;
+        }
+        self.jump_back = self
+            .current_conversation_index
+            .map(|i| self.conversations[i].id.clone());
+        let position = self
+            .visible_conversations()
+            .iter()
+            .position(|&visible| visible == index);
+        self.chat_state.marked.clear();
+        self.conversation_list_state.select(position);
+        self.current_conversation_index = Some(index);
+        self.conversations[index].unread = 0;
+        self.scroll_to_bottom();
+        let back = if self.jump_back.is_some() {
+            " | Ctrl+B: Jump back"
+        } else {
+            ""
+        };
+        self.set_feedback(
+            format!("Opened '{}'{}", self.conversations[index].name, back),
+            FeedbackType::Positive,
+        );
+    }
+
+    fn resume_follow(&mut self) {
+        self.follow = true;
+        self.unseen_messages = 0;
+        self.new_content_below = false;
+    }
+
+    // Follow mode turns off when scrolling away from the last message and back on
+    // when reaching it.
+    fn update_follow(&mut self) {
+        let Some(index) = self.current_conversation_index else {
+            return;
+        };
+        let count = self.conversations[index].messages.len();
+        if self
+            .selected_message()
+            .is_none_or(|selected| selected + 1 >= count)
+        {
+            self.resume_follow();
+        } else {
+            self.follow = false;
+        }
+    }
+
+    fn scroll_to_bottom(&mut self) {
+        self.resume_follow();
+        if let Some(index) = self.current_conversation_index {
+            let message_count = self.conversations[index].messages.len();
+            if message_count > 0 {
+                self.chat_state.list_state.select(Some(message_count - 1));
+            }
+        }
+    }
+
+    fn start_new_conversation(&mut self) {
+        let index =
+            self.create_conversation(format!("New Conversation {}", self.conversations.len()));
+        self.remember_directory(index);
+        self.chat_state.marked.clear();
+        self.resume_follow();
+        self.current_conversation_index = Some(index);
+        let position = self
+            .visible_conversations()
+            .iter()
+            .position(|&visible| visible == index);
+        self.conversation_list_state.select(position);
+    }
+
+    // Adds an empty conversation without switching to it.
+    fn create_conversation(&mut self, name: String) -> usize {
+        // Unique across sessions too, since the exchanges llm logs are linked to it
+        // (see `CONVERSATION_LINKS_FILE`), and such a conversation may not be loaded.
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let new_id = (stamp..)
+            .map(|n| format!("local-{}", n))
+            .find(|id| self.conversations.iter().all(|c| &c.id != id))
+            .unwrap_or_default();
+        let new_conversation = Conversation {
+            id: new_id,
+            name,
+            messages: Vec::new(),
+            directory: None,
+            snippets: Vec::new(),
+            filter_overrides: Value::Null,
+            env: BTreeMap::new(),
+            preferences: Preferences::default(),
+            summary: None,
+            tags: Vec::new(),
+            started: None,
+            model: None,
+            last_active: None,
+            unread: 0,
+        };
+        self.conversations.push(new_conversation);
+        self.conversations.len() - 1
+    }
+
+    fn toggle_conversation_list(&mut self) {
+        self.show_conversation_list = !self.show_conversation_list;
+        if !self.show_conversation_list
+            && matches!(self.focused_block, FocusedBlock::ConversationList)
+        {
+            self.next_focus();
+        }
+    }
+    fn selected_message(&self) -> Option<usize> {
+        self.chat_state.list_state.selected()
+    }
+
+    fn next_message(&mut self) {
+        if let Some(index) = self.current_conversation_index {
+            let messages = &self.conversations[index].messages;
+            let i = match self.chat_state.list_state.selected() {
+                Some(i) => {
+                    if i >= messages.len() - 1 {
+                        0
+                    } else {
+                        i + 1
+                    }
+                }
+                None => 0,
+            };
+            self.chat_state.list_state.select(Some(i));
+            self.update_follow();
+        }
+    }
+
+    fn previous_message(&mut self) {
+        if let Some(index) = self.current_conversation_index {
+            let messages = &self.conversations[index].messages;
+            let i = match self.chat_state.list_state.selected() {
+                Some(i) => {
+                    if i == 0 {
+                        messages.len() - 1
+                    } else {
+                        i - 1
+                    }
+                }
+                None => 0,
+            };
+            self.chat_state.list_state.select(Some(i));
+            self.update_follow();
+        }
+    }
+
+    fn selected_message_mut(&mut self) -> Option<&mut Message> {
+        let conversation_index = self.current_conversation_index?;
+        let message_index = self.chat_state.list_state.selected()?;
+        self.conversations[conversation_index]
+            .messages
+            .get_mut(message_index)
+    }
+
+    fn toggle_raw(&mut self) {
+        let default = self.show_raw;
+        if let Some(message) = self.selected_message_mut() {
+            message.show_raw = Some(!message.show_raw.unwrap_or(default));
+        }
+    }
+
+    // Switches every message to the raw (or rendered) view.
+    fn toggle_raw_all(&mut self) {
+        self.show_raw = !self.show_raw;
+        for conversation in &mut self.conversations {
+            for message in &mut conversation.messages {
+                message.show_raw = None;
+            }
+        }
+    }
+
+    fn toggle_code_wrap(&mut self) {
+        let default = self.config.wrap_code;
+        if let Some(message) = self.selected_message_mut() {
+            let wrap = !message.wrap_code.unwrap_or(default);
+            message.wrap_code = Some(wrap);
+            message.code_scroll = 0;
+        }
+    }
+
+    // True when the selected message has code blocks shown in horizontal-scroll mode,
+    // in which case h/l pan the code instead of their usual bindings.
+    fn is_panning_code(&self) -> bool {
+        let default = self.config.wrap_code;
+        self.selected_message_ref().is_some_and(|message| {
+            !message.wrap_code.unwrap_or(default)
+                && !code_blocks::code_blocks(&message.content).is_empty()
+        })
+    }
+
+    fn pan_code(&mut self, delta: isize) {
+        if let Some(message) = self.selected_message_mut() {
+            let max_width = code_blocks::code_blocks(&message.content)
+                .iter()
+                .flat_map(|block| block.lines.iter())
+                .map(|line| line.width())
+                .max()
+                .unwrap_or(0);
+            message.code_scroll = message
+                .code_scroll
+                .saturating_add_signed(delta)
+                .min(max_width.saturating_sub(1));
+        }
+    }
+
+    fn copy_selected_message_to_clipboard(&mut self) {
+        let text = self.selected_message_ref().map(|message| {
+            if message.show_raw.unwrap_or(self.show_raw) {
+                message.raw_content().to_string()
+            } else {
+                message.content.clone()
+            }
+        });
+        match text {
+            Some(text) => self.copy_async(text, "Message copied successfully!".to_string()),
+            None => self.set_feedback(
+                "Failed to copy: No message selected".to_string(),
+                FeedbackType::Negative,
+            ),
+        }
+    }
+
+    // Copies on a worker thread, since some clipboard managers block for seconds,
+    // and reports the outcome through a background event.
+    fn copy_async(&self, text: String, success: String) {
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let (tx, rx) = crossbeam_channel::bounded(1);
+            thread::spawn(move || {
+                let _ = tx.send(copy_to_clipboard(text).map_err(|e| e.to_string()));
+            });
+            let result = match rx.recv_timeout(CLIPBOARD_TIMEOUT) {
+                Ok(result) => result.map(|()| success),
+                Err(_) => Err("Clipboard timed out".to_string()),
+            };
+            let _ = background_tx.send(BackgroundEvent::Copied(result));
+        });
+    }
+
+    // Reads the clipboard the same way, for terminals that don't paste on their own.
+    fn paste_from_clipboard(&self) {
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let (tx, rx) = crossbeam_channel::bounded(1);
+            thread::spawn(move || {
+                let _ = tx.send(read_clipboard().map_err(|e| e.to_string()));
+            });
+            let result = rx
+                .recv_timeout(CLIPBOARD_TIMEOUT)
+                .unwrap_or_else(|_| Err("Clipboard timed out".to_string()));
+            let _ = background_tx.send(BackgroundEvent::ClipboardRead(result));
+        });
+    }
+
+    fn selected_message_ref(&self) -> Option<&Message> {
+        self.current_conversation_index
+            .zip(self.selected_message())
+            .and_then(|(c, m)| self.conversations[c].messages.get(m))
+    }
+
+    // Actions that make sense for the selected message, in menu order.
+    fn applicable_actions(&self) -> Vec<MessageAction> {
+        if !self.chat_state.marked.is_empty() {
+            return vec![
+                MessageAction::CopySelection,
+                MessageAction::ExportSelection,
+                MessageAction::ExportFlashcards,
+                MessageAction::HideSelection,
+                MessageAction::DeleteSelection,
+                MessageAction::SendSelection,
+                MessageAction::ClearSelection,
+            ];
+        }
+        let Some(message) = self.selected_message_ref() else {
+            return Vec::new();
+        };
+        let has_code = !code_blocks::code_blocks(&message.content).is_empty();
+        let mut actions = vec![MessageAction::Copy];
+        if has_code {
+            actions.extend([
+                MessageAction::CopyCode,
+                MessageAction::CopyLines,
+                MessageAction::SaveCode,
+            ]);
+        }
+        if code_blocks::code_blocks(&message.content)
+            .iter()
+            .any(sandbox::runnable)
+        {
+            actions.push(MessageAction::RunCode);
+        }
+        if !python_cells(&message.content).is_empty() {
+            actions.push(MessageAction::RunPython);
+        }
+        actions.extend([
+            MessageAction::Quote,
+            MessageAction::OpenInPager,
+            MessageAction::Translate,
+            MessageAction::AppendNote,
+        ]);
+        if message.role == "assistant" {
+            actions.push(MessageAction::Regenerate);
+            if message.seed.is_some() {
+                actions.push(MessageAction::ReplaySeed);
+            }
+            actions.push(MessageAction::Provenance);
+        }
+        if has_code {
+            actions.push(MessageAction::ToggleWrap);
+        }
+        actions.extend([
+            MessageAction::ToggleRaw,
+            MessageAction::TogglePin,
+            MessageAction::ToggleHidden,
+            MessageAction::Delete,
+        ]);
+        actions
+    }
+
+    fn open_action_menu(&mut self) {
+        let actions = self.applicable_actions();
+        if actions.is_empty() {
+            return;
+        }
+        let mut state = ListState::default();
+        state.select(Some(0));
+        self.action_menu = Some(ActionMenu { actions, state });
+    }
+
+    fn run_message_action(&mut self, action: MessageAction) {
+        match action {
+            MessageAction::Copy => self.copy_selected_message_to_clipboard(),
+            MessageAction::CopyCode => {
+                let code = self
+                    .selected_message_ref()
+                    .map(|message| {
+                        code_blocks::code_blocks(&message.content)
+                            .iter()
+                            .map(|block| block.lines.join("\n"))
+                            .collect::<Vec<_>>()
+                            .join("\n\n")
+                    })
+                    .unwrap_or_default();
+                self.copy_async(code, "Code copied successfully!".to_string());
+            }
+            MessageAction::CopyLines => self.open_prompt(PromptKind::CopyLines, String::new()),
+            MessageAction::SaveCode => {
+                self.open_prompt(PromptKind::SaveCodeBlocks, ".".to_string())
+            }
+            MessageAction::RunCode => self.confirm_run_code(),
+            MessageAction::RunPython => self.confirm_run_python(),
+            MessageAction::Quote => {
+                if let Some(message) = self.selected_message_ref() {
+                    let quoted: Vec<String> = message
+                        .content
+                        .lines()
+                        .map(|line| format!("> {}", line))
+                        .collect();
+                    let quoted = quoted.join("\n");
+                    if !self.input.is_empty() {
+                        self.input.push('\n');
+                    }
+                    self.input.push_str(&quoted);
+                    self.input.push('\n');
+                    self.focused_block = FocusedBlock::Input;
+                    self.input_mode = InputMode::Editing;
+                }
+            }
+            MessageAction::Regenerate => self.regenerate_selected(),
+            MessageAction::ReplaySeed => {
+                self.pending_seed = self.selected_message_ref().and_then(|m| m.seed);
+                self.regenerate_selected();
+                // Left over if there was nothing to regenerate from.
+                self.pending_seed = None;
+            }
+            MessageAction::Provenance => self.show_provenance(),
+            MessageAction::AppendNote => self.append_note(),
+            MessageAction::Translate => {
+                let language = self.config.translate_language.clone().unwrap_or_default();
+                self.open_prompt(PromptKind::Translate, language)
+            }
+            MessageAction::OpenInPager => {
+                self.pager_request = self.selected_message_ref().map(|message| {
+                    if message.show_raw.unwrap_or(self.show_raw) {
+                        message.raw_content().to_string()
+                    } else {
+                        message.content.clone()
+                    }
+                })
+            }
+            MessageAction::ToggleWrap => self.toggle_code_wrap(),
+            MessageAction::ToggleRaw => self.toggle_raw(),
+            MessageAction::TogglePin => {
+                if let Some(selected) = self.selected_message() {
+                    self.toggle_pin(selected);
+                }
+            }
+            MessageAction::ToggleHidden => {
+                if let Some(selected) = self.selected_message() {
+                    let hidden = !self.selected_message_ref().is_some_and(|m| m.hidden);
+                    self.set_hidden(&[selected], hidden);
+                }
+            }
+            MessageAction::Delete => {
+                if let Some(selected) = self.selected_message() {
+                    self.confirm_delete(vec![selected]);
+                }
+            }
+            MessageAction::HideSelection => {
+                let indices: Vec<usize> = self.chat_state.marked.iter().copied().collect();
+                self.set_hidden(&indices, true);
+                self.chat_state.marked.clear();
+            }
+            MessageAction::CopySelection => self.copy_async(
+                self.marked_messages_markdown(),
+                format!("Copied {} message(s)!", self.chat_state.marked.len()),
+            ),
+            MessageAction::ExportSelection => {
+                self.open_prompt(PromptKind::ExportSelection, "selection.md".to_string())
+            }
+            MessageAction::ExportFlashcards => {
+                self.open_prompt(PromptKind::ExportFlashcards, "flashcards.tsv".to_string())
+            }
+            MessageAction::DeleteSelection => {
+                self.confirm_delete(self.chat_state.marked.iter().copied().collect())
+            }
+            MessageAction::SendSelection => {
+                self.open_prompt(PromptKind::SendSelection, String::new())
+            }
+            MessageAction::ClearSelection => self.chat_state.marked.clear(),
+        }
+    }
+
+    // Builds the request summarizing a conversation, unless it has nothing to
+    // summarize.
+    fn summary_request(&self, index: usize, model: &str) -> Option<LlmRequest> {
+        let messages: Vec<&Message> = self.conversations[index]
+            .messages
+            .iter()
+            .filter(|m| !m.hidden)
+            .collect();
+        if messages.is_empty() {
+            return None;
+        }
+        Some(self.request(
+            &summary::build_prompt(
+                &self.config.summary_prompt,
+                // The stock labels, which the model can't mistake for anything else.
+                &messages_to_markdown(&messages, &Theme::default(), None),
+            ),
+            model.to_string(),
+        ))
+    }
+
+    // Sends the open conversation's transcript to the `[summarize]` model.
+    fn summarize_conversation(&mut self) {
+        let Some(index) = self.current_conversation_index else {
+            return;
+        };
+        let Some(model) = self.model_or_selected(self.config.summary_model.clone()) else {
+            return;
+        };
+        let Some(request) = self.summary_request(index, &model) else {
+            self.set_feedback(
+                "Nothing to summarize yet".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        let conversation = &self.conversations[index];
+        let id = conversation.id.clone();
+        self.set_feedback(
+            format!("Summarizing '{}'...", conversation.name),
+            FeedbackType::Positive,
+        );
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let result = run_llm_checked(&request);
+            let _ = background_tx.send(BackgroundEvent::Summarized(id, result));
+        });
+    }
+
+    // `/autotag`: summarizes and tags every untagged conversation, one at a time in
+    // the background. Unlike `s`, the summaries aren't pinned, so what's sent in
+    // those conversations doesn't change.
+    fn auto_tag(&mut self) {
+        if self.tagging.is_some() {
+            self.set_feedback(
+                "Tagging is already running".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        let Some(model) = self.model_or_selected(self.config.summary_model.clone()) else {
+            return;
+        };
+        let jobs: Vec<(String, LlmRequest)> = (0..self.conversations.len())
+            .filter(|&i| self.conversations[i].tags.is_empty())
+            .filter_map(|i| {
+                Some((
+                    self.conversations[i].id.clone(),
+                    self.summary_request(i, &model)?,
+                ))
+            })
+            .collect();
+        if jobs.is_empty() {
+            self.set_feedback(
+                "Every conversation is tagged already".to_string(),
+                FeedbackType::Positive,
+            );
+            return;
+        }
+        self.tagging = Some((0, jobs.len()));
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            for (id, request) in jobs {
+                let result = run_llm_checked(&request);
+                if background_tx
+                    .send(BackgroundEvent::AutoTagged(id, result))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        });
+    }
+
+    fn on_auto_tagged(&mut self, id: &str, result: Result<String, String>) {
+        let Some((done, total)) = self.tagging.as_mut() else {
+            return;
+        };
+        *done += 1;
+        let (done, total) = (*done, *total);
+        let stored = result.and_then(|response| self.store_summary(id, &response, false));
+        if let Err(e) = stored {
+            self.tagging_errors += 1;
+            self.set_feedback(format!("Tagging failed: {}", e), FeedbackType::Negative);
+        }
+        if done == total {
+            self.tagging = None;
+            let failed = std::mem::take(&mut self.tagging_errors);
+            self.set_feedback(
+                format!(
+                    "Tagged {} conversation(s), {} failed",
+                    total - failed,
+                    failed
+                ),
+                if failed == 0 {
+                    FeedbackType::Positive
+                } else {
+                    FeedbackType::Negative
+                },
+            );
+        }
+    }
+
+    // Keeps the summary and tags for the conversation list and, when `pin` is set,
+    // pins the summary to the conversation in place of an earlier one.
+    fn store_summary(&mut self, id: &str, response: &str, pin: bool) -> Result<(), String> {
+        let Some(index) = self.conversations.iter().position(|c| c.id == id) else {
+            return Ok(());
+        };
+        let (summary, tags) = summary::parse(response);
+        let conversation = &mut self.conversations[index];
+        conversation.summary = Some(summary.clone());
+        conversation.tags = tags.clone();
+
+        for (file, value) in [
+            (CONVERSATION_SUMMARIES_FILE, Value::String(summary.clone())),
+            (CONVERSATION_TAGS_FILE, Value::from(tags.clone())),
+        ] {
+            let mut saved = store::load(file);
+            if !saved.is_object() {
+                saved = Value::Object(Default::default());
+            }
+            if let Some(map) = saved.as_object_mut() {
+                map.insert(id.to_string(), value);
+            }
+            store::save(file, &saved).map_err(|e| format!("Failed to save summary: {}", e))?;
+        }
+
+        if pin {
+            let snippets = &mut self.conversations[index].snippets;
+            snippets.retain(|snippet| !snippet.starts_with(SUMMARY_PREFIX));
+            snippets.push(format!("{}{}", SUMMARY_PREFIX, summary));
+            self.save_snippets(index);
+            let tags: Vec<String> = tags.iter().map(|tag| format!("#{}", tag)).collect();
+            self.set_feedback(
+                format!(
+                    "Summarized '{}' {}",
+                    self.conversations[index].name,
+                    tags.join(" ")
+                ),
+                FeedbackType::Positive,
+            );
+        }
+        Ok(())
+    }
+
+    fn filter_by_tag(&mut self, tag: &str) {
+        let tag = summary::normalize_tag(tag);
+        self.tag_filter = Some(tag).filter(|tag| !tag.is_empty());
+        self.sync_conversation_list();
+    }
+
+    // Appends the selected message to the `[notes]` file with where it came from.
+    fn append_note(&mut self) {
+        let Some(path) = self.config.notes_path.clone() else {
+            self.set_feedback(
+                "Set [notes] path in config.toml to keep notes".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        let (Some(index), Some(message)) =
+            (self.current_conversation_index, self.selected_message_ref())
+        else {
+            self.set_feedback(
+                "Select a message in the chat to note it".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        let conversation = &self.conversations[index];
+        let role = self
+            .config
+            .theme
+            .label(&message.role, conversation.model.as_deref());
+        let note = notes::Note {
+            content: &message.content,
+            role: &role,
+            conversation: &conversation.name,
+            model: self.selected_model_name(),
+            log_id: message.log_id.as_deref().unwrap_or_default(),
+        };
+        match notes::append(&path, &self.config.notes_template, &note) {
+            Ok(path) => self.set_feedback(
+                format!("Appended to {}", path.display()),
+                FeedbackType::Positive,
+            ),
+            Err(e) => self.set_feedback(
+                format!("Failed to save note: {}", e),
+                FeedbackType::Negative,
+            ),
+        }
+    }
+
+    // Sends the selected message through the `[translate]` prompt and shows the
+    // result in a popup, leaving the conversation untouched.
+    fn translate_selected(&mut self, language: &str) {
+        let language = Some(language)
+            .filter(|language| !language.is_empty())
+            .map(str::to_string)
+            .or_else(|| self.config.translate_language.clone());
+        let Some(language) = language else {
+            self.set_feedback(
+                "Usage: /translate <language>".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        let Some(text) = self.selected_message_ref().map(|m| m.content.clone()) else {
+            self.set_feedback(
+                "Select a message in the chat to translate".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        let template = &self.config.translate_prompt;
+        let mut prompt = template.replace("{language}", &language);
+        if template.contains("{text}") {
+            prompt = prompt.replace("{text}", &text);
+        } else {
+            prompt.push_str("\n\n");
+            prompt.push_str(&text);
+        }
+        let Some(model) = self.model_or_selected(self.config.translate_model.clone()) else {
+            return;
+        };
+        let request = self.request(&prompt, model);
+        self.set_feedback(
+            format!("Translating into {}...", language),
+            FeedbackType::Positive,
+        );
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let result = run_llm_checked(&request);
+            let _ = background_tx.send(BackgroundEvent::Translated(language, result));
+        });
+    }
+
+    // Sends a drafted prompt through the `[improve]` template. The draft goes back in
+    // the input meanwhile, and the rewrite is offered next to it to accept or reject.
+    fn improve_prompt(&mut self, draft: &str) {
+        if draft.is_empty() {
+            self.set_feedback(
+                "Usage: /improve <prompt>".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        let Some(model) = self.model_or_selected(self.config.improve_model.clone()) else {
+            return;
+        };
+        let draft = draft.to_string();
+        self.input.set(draft.clone());
+        let request = self.request(
+            &improve::build_prompt(&self.config.improve_prompt, &draft),
+            model,
+        );
+        self.set_feedback("Improving prompt...".to_string(), FeedbackType::Positive);
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let result = run_llm_checked(&request);
+            let _ = background_tx.send(BackgroundEvent::Improved(draft, result));
+        });
+    }
+
+    // Sends a code block of the selected message (`/gen-tests [n]`, the first by
+    // default) or a file (`/gen-tests @path`) through the `[gen_tests]` template,
+    // then offers to save the tests it gets back.
+    fn generate_tests(&mut self, argument: &str) {
+        let source = match argument.strip_prefix('@') {
+            Some(path) => {
+                let path = config::expand_home(path.trim());
+                fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+                    .map(|code| {
+                        let extension = path
+                            .extension()
+                            .and_then(|extension| extension.to_str())
+                            .unwrap_or("txt")
+                            .to_lowercase();
+                        (extension.clone(), extension, path, code)
+                    })
+            }
+            None => self.test_source_block(argument),
+        };
+        let (language, extension, path, code) = match source {
+            Ok(source) => source,
+            Err(e) => {
+                self.set_feedback(e, FeedbackType::Negative);
+                return;
+            }
+        };
+        let suggested = gen_tests::suggested_path(&path, &extension);
+        let Some(model) = self.model_or_selected(self.config.gen_tests_model.clone()) else {
+            return;
+        };
+        let request = self.request(
+            &gen_tests::build_prompt(
+                &self.config.gen_tests_prompt,
+                &language,
+                &path.display().to_string(),
+                &code,
+            ),
+            model,
+        );
+        self.set_feedback(
+            format!("Generating tests for {}...", path.display()),
+            FeedbackType::Positive,
+        );
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let result = run_llm_checked(&request);
+            let _ = background_tx.send(BackgroundEvent::TestsGenerated(suggested, result));
+        });
+    }
+
+    // `/eval <file.yaml> [model,...]`: runs the suite's cases on each model (those
+    // given, else the file's `models`, else the selected one) in the background and
+    // shows the results as a matrix. `/eval export <file.csv>` saves the last results.
+    fn run_eval(&mut self, argument: &str) {
+        if let Some(path) = argument.strip_prefix("export ") {
+            let path = config::expand_home(path.trim());
+            let result = match &self.eval_report {
+                Some(report) => fs::write(&path, report.to_csv()).map_err(|e| e.to_string()),
+                None => Err("Nothing to export yet; run /eval first".to_string()),
+            };
+            match result {
+                Ok(()) => self.set_feedback(
+                    format!("Exported results to {}", path.display()),
+                    FeedbackType::Positive,
+                ),
+                Err(e) => {
+                    self.set_feedback(format!("Failed to export: {}", e), FeedbackType::Negative)
+                }
+            }
+            return;
+        }
+        if self.evaluating.is_some() {
+            self.set_feedback(
+                "An eval is already running".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        let (path, models) = argument
+            .split_once(char::is_whitespace)
+            .unwrap_or((argument, ""));
+        if path.is_empty() {
+            self.set_feedback(
+                "Usage: /eval <file.yaml> [model,...] | /eval export <file.csv>".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        let suite = match eval::Suite::load(&config::expand_home(path)) {
+            Ok(suite) => suite,
+            Err(e) => {
+                self.set_feedback(e, FeedbackType::Negative);
+                return;
+            }
+        };
+        let Some(selected) = self.model_or_selected(None) else {
+            return;
+        };
+        let mut models: Vec<String> = models
+            .split(',')
+            .map(str::trim)
+            .filter(|model| !model.is_empty())
+            .map(str::to_string)
+            .collect();
+        if models.is_empty() {
+            models = suite.models.clone();
+        }
+        if models.is_empty() {
+            models.push(selected.clone());
+        }
+        let judge = suite.judge.clone().unwrap_or(selected);
+
+        let total = suite.cases.len() * models.len();
+        self.evaluating = Some((0, total));
+        self.set_feedback(
+            format!("Evaluating {}... 0/{}", suite.name, total),
+            FeedbackType::Positive,
+        );
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let report = eval::run(suite, models, &judge, |done, total| {
+                let _ = background_tx.send(BackgroundEvent::EvalProgress(done, total));
+            });
+            let _ = background_tx.send(BackgroundEvent::Evaluated(report));
+        });
+    }
+
+    // The language, file extension, a stand-in path and the code of the n-th
+    // (1-based) code block of the selected message.
+    fn test_source_block(&self, number: &str) -> Result<(String, String, PathBuf, String), String> {
+        let number = if number.is_empty() {
+            1
+        } else {
+            number
+                .parse::<usize>()
+                .map_err(|_| "Usage: /gen-tests [block number | @file]".to_string())?
+        };
+        let content = self
+            .selected_message_ref()
+            .map(|message| message.content.clone())
+            .ok_or("Select a message with code, or use /gen-tests @file")?;
+        let blocks = code_blocks::code_blocks(&content);
+        let block = number
+            .checked_sub(1)
+            .and_then(|i| blocks.get(i))
+            .ok_or_else(|| format!("The selected message has {} code block(s)", blocks.len()))?;
+        let language = block.language().unwrap_or("text").to_lowercase();
+        let extension = language::extension(&language).to_string();
+        let path = PathBuf::from(format!("snippet.{}", extension));
+        Ok((language, extension, path, block.lines.join("\n")))
+    }
+
+    // Confirms writing generated tests, showing the start of the file.
+    fn plan_tests_save(&mut self, path: &str, contents: String) {
+        let Some(path) = safe_join(Path::new("."), path.trim()) else {
+            self.set_feedback(
+                format!("Refusing to write outside the current directory: {}", path),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        let note = if path.exists() { " (overwrite)" } else { "" };
+        let mut lines = vec![
+            format!(
+                "{} ({} lines){}",
+                path.display(),
+                contents.lines().count(),
+                note
+            ),
+            String::new(),
+        ];
+        lines.extend(
+            contents
+                .lines()
+                .take(TESTS_PREVIEW_LINES)
+                .map(str::to_string),
+        );
+        if contents.lines().count() > TESTS_PREVIEW_LINES {
+            lines.push("...".to_string());
+        }
+        self.confirm = Some(Confirm {
+            title: "Save generated tests? y/Enter: Save | n/Esc: Cancel".to_string(),
+            lines,
+            action: ConfirmAction::SaveFiles(vec![(path, contents)]),
+        });
+    }
+
+    // Explains where the selected response came from, using its llm log entry.
+    fn show_provenance(&mut self) {
+        let (Some(index), Some(selected)) =
+            (self.current_conversation_index, self.selected_message())
+        else {
+            return;
+        };
+        let conversation = &self.conversations[index];
+        let message = &conversation.messages[selected];
+        let Some(log_id) = message.log_id.clone() else {
+            self.set_feedback(
+                "This response isn't in llm's logs yet; restart to load it".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        let exchange = match history::find(&logged_conversation_ids(&conversation.id), &log_id) {
+            Ok(Some(exchange)) => exchange,
+            Ok(None) => {
+                self.set_feedback(
+                    format!("Log entry {} not found", log_id),
+                    FeedbackType::Negative,
+                );
+                return;
+            }
+            Err(e) => {
+                self.set_feedback(
+                    format!("Failed to load the log entry: {}", e),
+                    FeedbackType::Negative,
+                );
+                return;
+            }
+        };
+
+        let mut lines = vec![
+            format!("Model:        {}", exchange.model),
+            format!("Time:         {} UTC", format_log_time(&exchange.datetime)),
+        ];
+        let options: Vec<String> = exchange
+            .options
+            .as_object()
+            .map(|options| {
+                options
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect()
+            })
+            .unwrap_or_default();
+        lines.push(format!(
+            "Options:      {}",
+            if options.is_empty() {
+                "defaults".to_string()
+            } else {
+                options.join(", ")
+            }
+        ));
+        let mut usage = Vec::new();
+        if let Some(tokens) = exchange.input_tokens {
+            usage.push(format!("{} in", tokens));
+        }
+        if let Some(tokens) = exchange.output_tokens {
+            usage.push(format!("{} out", tokens));
+        }
+        if let Some(ms) = exchange.duration_ms {
+            usage.push(format!("{:.1}s", ms as f64 / 1000.0));
+        }
+        if !usage.is_empty() {
+            lines.push(format!("Usage:        {}", usage.join(", ")));
+        }
+        lines.push(String::new());
+        match &exchange.system {
+            Some(system) => {
+                lines.push("System prompt:".to_string());
+                lines.extend(system.lines().map(|line| format!("  {}", line)));
+            }
+            None => lines.push("System prompt: none".to_string()),
+        }
+        // Labels recorded when the prompt was sent here are clearer than the
+        // fragment hashes llm logs.
+        let attached = conversation.messages[..selected]
+            .iter()
+            .rev()
+            .find(|message| message.role == "user")
+            .map(|message| message.fragments.clone())
+            .filter(|fragments| !fragments.is_empty())
+            .unwrap_or(exchange.fragments);
+        for (heading, items) in [
+            ("Fragments", attached),
+            ("Attachments", exchange.attachments),
+            ("Tool calls", exchange.tool_calls),
+        ] {
+            if items.is_empty() {
+                lines.push(format!("{}: none", heading));
+            } else {
+                lines.push(format!("{}:", heading));
+                lines.extend(items.iter().map(|item| format!("  - {}", item)));
+            }
+        }
+        lines.push(String::new());
+        lines.push(format!("Log id:       {}", log_id));
+
+        self.panel = Some(Panel {
+            title: "Provenance | j/k: Scroll | Esc: Close".to_string(),
+            lines,
+            scroll: 0,
+        });
+    }
+
+    // Re-sends the prompt that produced the selected response.
+    fn regenerate_selected(&mut self) {
+        let prompt = self
+            .current_conversation_index
+            .zip(self.selected_message())
+            .and_then(|(c, m)| {
+                self.conversations[c].messages[..m]
+                    .iter()
+                    .rev()
+                    .find(|message| message.role == "user")
+            })
+            .map(|message| message.content.clone());
+        match prompt.zip(self.current_conversation_index) {
+            Some((prompt, index)) => {
+                self.input.set(prompt);
+                self.send_input(index);
+            }
+            None => self.set_feedback(
+                "No prompt to regenerate from".to_string(),
+                FeedbackType::Negative,
+            ),
+        }
+    }
+
+    fn copy_code_lines(&mut self, spec: &str) -> Result<(), String> {
+        let range = code_blocks::parse_line_range(spec)?;
+        let content = self
+            .selected_message_ref()
+            .map(|message| message.content.clone())
+            .ok_or("No message selected")?;
+        let lines = code_blocks::extract_lines(&content, &range)?;
+        let count = lines.lines().count();
+        self.copy_async(lines, format!("Copied {} line(s) to clipboard!", count));
+        Ok(())
+    }
+
+    fn plan_code_block_save(&mut self, root: &str) -> Result<(), String> {
+        let content = self
+            .selected_message_ref()
+            .map(|message| message.content.clone())
+            .ok_or("No message selected")?;
+        let planned = code_blocks::planned_files(&content);
+        if planned.is_empty() {
+            return Err("No code blocks in the selected message".to_string());
+        }
+
+        let root = Path::new(if root.trim().is_empty() {
+            "."
+        } else {
+            root.trim()
+        });
+        let mut files = Vec::new();
+        let mut lines = Vec::new();
+        for file in planned {
+            let path = safe_join(root, &file.path).ok_or_else(|| {
+                format!(
+                    "Refusing to write outside {}: {}",
+                    root.display(),
+                    file.path
+                )
+            })?;
+            let note = if path.exists() { " (overwrite)" } else { "" };
+            lines.push(format!(
+                "{} ({} lines){}",
+                path.display(),
+                file.contents.lines().count(),
+                note
+            ));
+            files.push((path, file.contents));
+        }
+
+        self.confirm = Some(Confirm {
+            title: format!(
+                "Save {} file(s)? y/Enter: Save | n/Esc: Cancel",
+                files.len()
+            ),
+            lines,
+            action: ConfirmAction::SaveFiles(files),
+        });
+        Ok(())
+    }
+
+    // Asks before running the first runnable code block of the selected message,
+    // showing the command and whether it's sandboxed.
+    fn confirm_run_code(&mut self) {
+        let blocks = self
+            .selected_message_ref()
+            .map(|message| code_blocks::code_blocks(&message.content))
+            .unwrap_or_default();
+        let Some(position) = blocks.iter().position(sandbox::runnable) else {
+            self.set_feedback(
+                "No runnable code block in this message".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        let env = self
+            .current_conversation_index
+            .map(|index| self.command_env(index))
+            .unwrap_or_default();
+        let run = match sandbox::Run::prepare(&blocks[position], self.config.sandbox.as_ref(), env)
+        {
+            Ok(run) => run,
+            Err(e) => {
+                self.set_feedback(e, FeedbackType::Negative);
+                return;
+            }
+        };
+
+        let mut lines = vec![
+            match &run.sandbox {
+                Some(name) => format!("Sandbox: {}", name),
+                None => "Sandbox: none, the code runs with your permissions".to_string(),
+            },
+            format!("$ {}", run.argv.join(" ")),
+            String::new(),
+        ];
+        let code_lines = run.code.lines().count();
+        lines.extend(run.code.lines().take(RUN_PREVIEW_LINES).map(str::to_string));
+        if code_lines > RUN_PREVIEW_LINES {
+            lines.push(format!(
+                "... {} more line(s)",
+                code_lines - RUN_PREVIEW_LINES
+            ));
+        }
+        let title = if blocks.len() > 1 {
+            format!(
+                "Run block {} of {} ({})? y/Enter: Run | n/Esc: Cancel",
+                position + 1,
+                blocks.len(),
+                run.language
+            )
+        } else {
+            format!("Run {} code? y/Enter: Run | n/Esc: Cancel", run.language)
+        };
+        self.confirm = Some(Confirm {
+            title,
+            lines,
+            action: ConfirmAction::RunCode(run),
+        });
+    }
+
+    fn run_code(&mut self, run: sandbox::Run) {
+        self.set_feedback(
+            format!("Running {} code...", run.language),
+            FeedbackType::Positive,
+        );
+        let timeout = Duration::from_secs(self.config.run_timeout_seconds);
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let language = run.language.clone();
+            let _ = background_tx.send(BackgroundEvent::Ran(language, run.execute(timeout)));
+        });
+    }
+
+    // Asks before running the Python blocks of the selected message, in order, in
+    // the conversation's Python session.
+    fn confirm_run_python(&mut self) {
+        let (Some(index), Some(message)) =
+            (self.current_conversation_index, self.selected_message_ref())
+        else {
+            return;
+        };
+        let cells = python_cells(&message.content);
+        if cells.is_empty() {
+            return;
+        }
+        let id = self.conversations[index].id.clone();
+        // The sessions are locked while cells run.
+        let running = self
+            .python_sessions
+            .try_lock()
+            .ok()
+            .map(|sessions| sessions.contains_key(&id));
+        let Some(running) = running else {
+            self.set_feedback(
+                "Python is still running".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+
+        let mut lines = vec![
+            match &self.config.sandbox {
+                Some(sandbox) => format!("Sandbox: {}", sandbox.name),
+                None => "Sandbox: none, the code runs with your permissions".to_string(),
+            },
+            format!(
+                "Python: {} ({})",
+                self.config.python_interpreter.join(" "),
+                if running {
+                    "session running"
+                } else {
+                    "new session"
+                }
+            ),
+        ];
+        for (i, cell) in cells.iter().enumerate() {
+            lines.push(String::new());
+            if cells.len() > 1 {
+                lines.push(format!("# Cell {}", i + 1));
+            }
+            let cell_lines = cell.lines().count();
+            lines.extend(cell.lines().take(RUN_PREVIEW_LINES).map(str::to_string));
+            if cell_lines > RUN_PREVIEW_LINES {
+                lines.push(format!(
+                    "... {} more line(s)",
+                    cell_lines - RUN_PREVIEW_LINES
+                ));
+            }
+        }
+        self.confirm = Some(Confirm {
+            title: format!(
+                "Run {} cell(s) in Python? y/Enter: Run | n/Esc: Cancel",
+                cells.len()
+            ),
+            lines,
+            action: ConfirmAction::RunPython(id, cells),
+        });
+    }
+
+    fn run_python(&mut self, id: String, cells: Vec<String>) {
+        self.set_feedback("Running Python...".to_string(), FeedbackType::Positive);
+        let sessions = Arc::clone(&self.python_sessions);
+        let interpreter = self.config.python_interpreter.clone();
+        let sandbox = self.config.sandbox.clone();
+        let env = self
+            .conversations
+            .iter()
+            .position(|c| c.id == id)
+            .map(|index| self.command_env(index))
+            .unwrap_or_default();
+        let timeout = Duration::from_secs(self.config.run_timeout_seconds);
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let mut sessions = sessions.lock().unwrap();
+            let result = (|| {
+                if !sessions.contains_key(&id) {
+                    let session = python::Session::start(&interpreter, sandbox.as_ref(), &env)?;
+                    sessions.insert(id.clone(), session);
+                }
+                let session = sessions.get_mut(&id).unwrap();
+                let mut output = String::new();
+                for cell in &cells {
+                    output.push_str(&session.run(cell, timeout)?);
+                }
+                Ok(output)
+            })();
+            if result.is_err() {
+                sessions.remove(&id);
+            }
+            let _ = background_tx.send(BackgroundEvent::PythonRan(id, result));
+        });
+    }
+
+    // Appends the output of Python cells to the conversation they came from, where
+    // it's sent along with later prompts like any other message.
+    fn on_python_ran(&mut self, id: &str, result: Result<String, String>) {
+        let Some(index) = self.conversations.iter().position(|c| c.id == id) else {
+            return;
+        };
+        let output = match result {
+            Ok(output) if output.trim().is_empty() => "(no output)".to_string(),
+            Ok(output) => output.trim_end().to_string(),
+            Err(e) => {
+                self.set_feedback(format!("Python failed: {}", e), FeedbackType::Negative);
+                return;
+            }
+        };
+        self.conversations[index].messages.push(Message::new(
+            "user",
+            format!("Python output:\n```\n{}\n```", output),
+        ));
+        self.on_new_content(index, true);
+        self.set_feedback("Python output added".to_string(), FeedbackType::Positive);
+    }
+
+    // `/python reset`: stops the conversation's Python session, dropping its state.
+    fn reset_python(&mut self, argument: &str) {
+        if argument != "reset" {
+            self.set_feedback("Usage: /python reset".to_string(), FeedbackType::Negative);
+            return;
+        }
+        let Some(index) = self.current_conversation_index else {
+            return;
+        };
+        let id = &self.conversations[index].id;
+        let removed = self
+            .python_sessions
+            .try_lock()
+            .ok()
+            .map(|mut sessions| sessions.remove(id).is_some());
+        match removed {
+            Some(true) => {
+                self.set_feedback("Python session reset".to_string(), FeedbackType::Positive)
+            }
+            Some(false) => self.set_feedback(
+                "No Python session to reset".to_string(),
+                FeedbackType::Positive,
+            ),
+            None => self.set_feedback(
+                "Python is still running".to_string(),
+                FeedbackType::Negative,
+            ),
+        }
+    }
+
+    fn confirm_action(&mut self) {
+        let Some(confirm) = self.confirm.take() else {
+            return;
+        };
+        match confirm.action {
+            ConfirmAction::SaveFiles(files) => match write_files(&files) {
+                Ok(()) => self.set_feedback(
+                    format!("Saved {} file(s)!", files.len()),
+                    FeedbackType::Positive,
+                ),
+                Err(e) => {
+                    self.set_feedback(format!("Failed to save: {}", e), FeedbackType::Negative)
+                }
+            },
+            ConfirmAction::RetryWithLessContext(index, prompt, parts, omitted) => {
+                self.retry_with_less_context(index, prompt, parts, omitted)
+            }
+            ConfirmAction::SendAnyway(index) => self.send_input(index),
+            ConfirmAction::RunCode(run) => self.run_code(run),
+            ConfirmAction::RunPython(id, cells) => self.run_python(id, cells),
+            ConfirmAction::SmartPaste(text) => self.smart_paste(&text, None),
+            ConfirmAction::UseImprovedPrompt(prompt) => {
+                self.input.set(prompt);
+                self.focused_block = FocusedBlock::Input;
+                self.input_mode = InputMode::Editing;
+            }
+            ConfirmAction::SendRemote(command) => {
+                self.send_remote_command(command);
+                self.review_remote();
+            }
+            ConfirmAction::RestoreDraft(draft) => {
+                self.input.set(draft);
+                self.focused_block = FocusedBlock::Input;
+                self.set_feedback("Draft restored".to_string(), FeedbackType::Positive);
+            }
+            ConfirmAction::DeleteConversation(index) => self.delete_conversation(index),
+            ConfirmAction::DeleteMessages(conversation_index, indices) => {
+                let messages = &mut self.conversations[conversation_index].messages;
+                let mut keys = Vec::new();
+                for &index in indices.iter().rev() {
+                    if index < messages.len() {
+                        keys.extend(messages.remove(index).override_key());
+                    }
+                }
+                if let Err(e) = save_message_overrides(&keys, Some("deleted")) {
+                    self.set_feedback(
+                        format!("Failed to save deletions: {}", e),
+                        FeedbackType::Negative,
+                    );
+                    return;
+                }
+                self.chat_state.marked.clear();
+                self.chat_state.list_state.select(None);
+                self.set_feedback(
+                    format!("Deleted {} message(s) locally", indices.len()),
+                    FeedbackType::Positive,
+                );
+            }
+        }
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind, input: String) {
+        self.prompt = Some(Prompt { kind, input });
+    }
+
+    fn submit_prompt(&mut self) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        match prompt.kind {
+            PromptKind::CopyLines => {
+                if let Err(e) = self.copy_code_lines(&prompt.input) {
+                    self.set_feedback(e, FeedbackType::Negative);
+                }
+            }
+            PromptKind::SaveCodeBlocks => {
+                if let Err(e) = self.plan_code_block_save(&prompt.input) {
+                    self.set_feedback(e, FeedbackType::Negative);
+                }
+            }
+            PromptKind::ExportSelection => {
+                let markdown = self.marked_messages_markdown();
+                let path = config::expand_home(prompt.input.trim());
+                match fs::write(&path, markdown) {
+                    Ok(()) => self.set_feedback(
+                        format!("Exported selection to {}", path.display()),
+                        FeedbackType::Positive,
+                    ),
+                    Err(e) => self
+                        .set_feedback(format!("Failed to export: {}", e), FeedbackType::Negative),
+                }
+            }
+            PromptKind::ExportFlashcards => self.export_flashcards(prompt.input.trim()),
+            PromptKind::SendSelection => {
+                if let Err(e) = self.send_selection_to(prompt.input.trim()) {
+                    self.set_feedback(e, FeedbackType::Negative);
+                }
+            }
+            PromptKind::Translate => self.translate_selected(prompt.input.trim()),
+            PromptKind::TagFilter => self.filter_by_tag(&prompt.input),
+            PromptKind::SaveTests(contents) => self.plan_tests_save(&prompt.input, contents),
+            PromptKind::RenameConversation(index) => {
+                self.rename_conversation(index, prompt.input.trim())
+            }
+            PromptKind::Preference(field) => self.set_preference(field, &prompt.input),
+            PromptKind::ModelOption(replacing) => {
+                self.set_model_option(replacing.as_deref(), &prompt.input)
+            }
+            PromptKind::TemplateVariable(name) => {
+                if let Some((_, values)) = &mut self.template_fill {
+                    values.push((name, prompt.input));
+                }
+                self.fill_template();
+            }
+            PromptKind::Find { regex } => {
+                match search::count_matches(&self.input, &prompt.input, regex) {
+                    Ok(0) => self.set_feedback("No matches".to_string(), FeedbackType::Negative),
+                    Ok(matches) => self.open_prompt(
+                        PromptKind::Replace {
+                            pattern: prompt.input,
+                            regex,
+                            matches,
+                        },
+                        String::new(),
+                    ),
+                    Err(e) => self.set_feedback(e, FeedbackType::Negative),
+                }
+            }
+            PromptKind::Replace { pattern, regex, .. } => {
+                match search::replace_all(&self.input, &pattern, &prompt.input, regex) {
+                    Ok((replaced, count)) => {
+                        self.input.set(replaced);
+                        self.set_feedback(
+                            format!("Replaced {} occurrence(s)", count),
+                            FeedbackType::Positive,
+                        );
+                    }
+                    Err(e) => self.set_feedback(e, FeedbackType::Negative),
+                }
+            }
+        }
+    }
+
+    fn confirm_delete(&mut self, indices: Vec<usize>) {
+        if let Some(index) = self.current_conversation_index {
+            self.confirm = Some(Confirm {
+                title: format!(
+                    "Delete {} message(s) from this view? y/Enter: Delete | n/Esc: Cancel",
+                    indices.len()
+                ),
+                lines: vec![
+                    "Messages are only removed locally; llm's logs are not changed.".to_string(),
+                ],
+                action: ConfirmAction::DeleteMessages(index, indices),
+            });
+        }
+    }
+
+    fn open_rename_prompt(&mut self) {
+        if let Some(index) = self.highlighted_conversation() {
+            let name = self.conversations[index].name.clone();
+            self.open_prompt(PromptKind::RenameConversation(index), name);
+        }
+    }
+
+    // Keeps the name locally and, when the `sqlite3` command is available, in llm's
+    // log database too.
+    fn rename_conversation(&mut self, index: usize, name: &str) {
+        if name.is_empty() || index >= self.conversations.len() {
+            return;
+        }
+        let id = self.conversations[index].id.clone();
+        self.conversations[index].name = name.to_string();
+        let mut names = store::load(CONVERSATION_NAMES_FILE);
+        if !names.is_object() {
+            names = Value::Object(Default::default());
+        }
+        if let Some(map) = names.as_object_mut() {
+            map.insert(id.clone(), Value::String(name.to_string()));
+        }
+        if let Err(e) = store::save(CONVERSATION_NAMES_FILE, &names) {
+            self.set_feedback(
+                format!("Failed to save the name: {}", e),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        match rename_in_logs(&id, name) {
+            Ok(()) => self.set_feedback(format!("Renamed to '{}'", name), FeedbackType::Positive),
+            Err(e) => self.set_feedback(
+                format!("Renamed to '{}' here only ({})", name, e),
+                FeedbackType::Positive,
+            ),
+        }
+    }
+
+    // Asks before deleting the conversation selected in the list.
+    fn confirm_delete_conversation(&mut self) {
+        let Some(index) = self.highlighted_conversation() else {
+            return;
+        };
+        if self.refuse_delete_while_busy() {
+            return;
+        }
+        let conversation = &self.conversations[index];
+        self.confirm = Some(Confirm {
+            title: format!(
+                "Delete '{}'? y/Enter: Delete | n/Esc: Cancel",
+                conversation.name
+            ),
+            lines: vec![
+                format!("{} message(s)", conversation.messages.len()),
+                "It's only removed from this list; llm's logs are not changed.".to_string(),
+            ],
+            action: ConfirmAction::DeleteConversation(index),
+        });
+    }
+
+    // Responses on their way, and prompts waiting or held to be sent, are
+    // delivered by index, so nothing is deleted until they're through. Checked again
+    // on deleting, as a request may have started while the popup was open.
+    fn refuse_delete_while_busy(&mut self) -> bool {
+        let busy = !self.running.is_empty() || !self.waiting.is_empty() || !self.held.is_empty();
+        if busy {
+            self.set_feedback(
+                "Wait for the responses in progress before deleting".to_string(),
+                FeedbackType::Negative,
+            );
+        }
+        busy
+    }
+
+    fn delete_conversation(&mut self, index: usize) {
+        if index >= self.conversations.len() || self.refuse_delete_while_busy() {
+            return;
+        }
+        let id = self.conversations[index].id.clone();
+        let mut deleted = store::load(DELETED_CONVERSATIONS_FILE);
+        if !deleted.is_array() {
+            deleted = Value::Array(Vec::new());
+        }
+        if let Some(ids) = deleted.as_array_mut() {
+            ids.push(Value::String(id.clone()));
+        }
+        if let Err(e) = store::save(DELETED_CONVERSATIONS_FILE, &deleted) {
+            self.set_feedback(
+                format!("Failed to save deletion: {}", e),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        let conversation = self.conversations.remove(index);
+        if let Ok(mut sessions) = self.python_sessions.try_lock() {
+            sessions.remove(&id);
+        }
+
+        // Later conversations move up one place.
+        let shift = |i: usize| if i > index { i - 1 } else { i };
+        self.last_sent = std::mem::take(&mut self.last_sent)
+            .into_iter()
+            .filter(|(i, _)| *i != index)
+            .map(|(i, sent)| (shift(i), sent))
+            .collect();
+        self.suggestions = self
+            .suggestions
+            .take()
+            .filter(|(i, _)| *i != index)
+            .map(|(i, suggestions)| (shift(i), suggestions));
+        self.health = None;
+
+        let len = self.visible_conversations().len();
+        let position = self
+            .conversation_list_state
+            .selected()
+            .map(|position| position.min(len.saturating_sub(1)))
+            .filter(|_| len > 0);
+        match self.current_conversation_index {
+            Some(current) if current == index => self.select_visible_conversation(position),
+            current => {
+                self.current_conversation_index = current.map(shift);
+                self.conversation_list_state.select(position);
+            }
+        }
+        self.set_feedback(
+            format!("Deleted '{}'", conversation.name),
+            FeedbackType::Positive,
+        );
+    }
+
+    fn toggle_reaction(&mut self, name: &str) {
+        let Some(message) = self
+            .current_conversation_index
+            .zip(self.selected_message())
+            .and_then(|(c, m)| self.conversations[c].messages.get_mut(m))
+        else {
+            return;
+        };
+        match message.reactions.iter().position(|r| r == name) {
+            Some(position) => {
+                message.reactions.remove(position);
+            }
+            None => {
+                message.reactions.push(name.to_string());
+                message
+                    .reactions
+                    .sort_by_key(|r| REACTIONS.iter().position(|(_, name, _)| name == r));
+            }
+        }
+        let Some(key) = message.override_key() else {
+            return;
+        };
+        let mut saved = store::load(MESSAGE_REACTIONS_FILE);
+        if !saved.is_object() {
+            saved = Value::Object(Default::default());
+        }
+        if let Some(map) = saved.as_object_mut() {
+            if message.reactions.is_empty() {
+                map.remove(&key);
+            } else {
+                map.insert(key, Value::from(message.reactions.clone()));
+            }
+        }
+        if let Err(e) = store::save(MESSAGE_REACTIONS_FILE, &saved) {
+            self.set_feedback(
+                format!("Failed to save reaction: {}", e),
+                FeedbackType::Negative,
+            );
+        }
+    }
+
+    // `/reactions [name or emoji]` lists the messages with that reaction (or any) in
+    // every conversation.
+    fn show_reactions(&mut self, argument: &str) {
+        let wanted = if argument.is_empty() {
+            None
+        } else {
+            match REACTIONS
+                .iter()
+                .find(|(_, name, emoji)| *name == argument || *emoji == argument)
+            {
+                Some((_, name, _)) => Some(*name),
+                None => {
+                    let names: Vec<&str> = REACTIONS.iter().map(|(_, name, _)| *name).collect();
+                    self.set_feedback(
+                        format!("Unknown reaction; use one of {}", names.join(", ")),
+                        FeedbackType::Negative,
+                    );
+                    return;
+                }
+            }
+        };
+        let mut lines = Vec::new();
+        for conversation in &self.conversations {
+            for message in &conversation.messages {
+                let matches = match wanted {
+                    Some(name) => message.reactions.iter().any(|r| r == name),
+                    None => !message.reactions.is_empty(),
+                };
+                if matches {
+                    lines.push(format!(
+                        "{} {} · {}{}",
+                        message.reaction_emojis(),
+                        conversation.name,
+                        self.config
+                            .theme
+                            .prefix(&message.role, conversation.model.as_deref()),
+                        preview(&message.content, 100)
+                    ));
+                }
+            }
+        }
+        if lines.is_empty() {
+            self.set_feedback(
+                "No messages with that reaction".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        self.panel = Some(Panel {
+            title: format!(
+                "{} message(s) with reactions | j/k: Scroll | Esc: Close",
+                lines.len()
+            ),
+            lines,
+            scroll: 0,
+        });
+    }
+
+    fn open_global_search(&mut self, query: &str) {
+        self.global_search = Some(GlobalSearch {
+            query: query.to_string(),
+            hits: Vec::new(),
+            selected: 0,
+        });
+        self.update_global_search();
+    }
+
+    // Searches again after the query changed, most recently active conversations
+    // and their latest messages first.
+    fn update_global_search(&mut self) {
+        let Some(search) = self.global_search.as_mut() else {
+            return;
+        };
+        let mut order: Vec<usize> = (0..self.conversations.len()).collect();
+        order.sort_by_key(|&i| Reverse(self.conversations[i].last_active));
+        search.hits = order
+            .into_iter()
+            .flat_map(|c| {
+                let query = &search.query;
+                self.conversations[c]
+                    .messages
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .filter_map(move |(m, message)| {
+                        let (excerpt, matched) =
+                            search::excerpt(&message.content, query, SEARCH_EXCERPT_CHARS)?;
+                        Some(SearchHit {
+                            conversation: c,
+                            message: m,
+                            excerpt,
+                            matched,
+                        })
+                    })
+            })
+            .take(SEARCH_MAX_HITS)
+            .collect();
+        search.selected = 0;
+    }
+
+    // Opens the conversation of the highlighted hit with its message selected.
+    fn open_search_hit(&mut self) {
+        let Some(search) = self.global_search.take() else {
+            return;
+        };
+        let Some(hit) = search.hits.get(search.selected) else {
+            return;
+        };
+        let Some(conversation) = self.conversations.get(hit.conversation) else {
+            return;
+        };
+        let id = conversation.id.clone();
+        self.jump_to(&id);
+        if Some(hit.conversation) == self.current_conversation_index {
+            self.chat_state.list_state.select(Some(hit.message));
+            self.update_follow();
+            self.focused_block = FocusedBlock::Chat;
+            self.input_mode = InputMode::Normal;
+        }
+    }
+
+    // Messages of the open conversation containing the chat search text.
+    fn chat_matches(&self) -> Vec<usize> {
+        let Some(index) = self.current_conversation_index else {
+            return Vec::new();
+        };
+        self.conversations[index]
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, message)| {
+                !search::find_ignoring_case(&message.content, &self.chat_query).is_empty()
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn set_chat_query(&mut self, query: String) {
+        self.chat_query = query;
+        self.step_chat_match(true, true);
+    }
+
+    // Selects the next (or previous) matching message after the selected one, or
+    // from it when `inclusive`, wrapping around.
+    fn step_chat_match(&mut self, forward: bool, inclusive: bool) {
+        if self.chat_query.is_empty() {
+            return;
+        }
+        let matches = self.chat_matches();
+        let current = self.selected_message().unwrap_or(0);
+        let found = if forward {
+            matches
+                .iter()
+                .find(|&&i| i > current || (inclusive && i == current))
+                .or(matches.first())
+        } else {
+            matches
+                .iter()
+                .rev()
+                .find(|&&i| i < current)
+                .or(matches.last())
+        };
+        let Some(&found) = found else {
+            self.set_feedback(
+                format!("No message contains '{}'", self.chat_query),
+                FeedbackType::Negative,
+            );
+            return;
+        };
+        self.chat_state.list_state.select(Some(found));
+        self.update_follow();
+        if !self.chat_searching {
+            let position = matches.iter().position(|&i| i == found).unwrap_or(0);
+            self.set_feedback(
+                format!(
+                    "Match {} of {} message(s) with '{}'",
+                    position + 1,
+                    matches.len(),
+                    self.chat_query
+                ),
+                FeedbackType::Positive,
+            );
+        }
+    }
+
+    fn set_hidden(&mut self, indices: &[usize], hidden: bool) {
+        let Some(index) = self.current_conversation_index else {
+            return;
+        };
+        let mut keys = Vec::new();
+        for &i in indices {
+            if let Some(message) = self.conversations[index].messages.get_mut(i) {
+                message.hidden = hidden;
+                keys.extend(message.override_key());
+            }
+        }
+        let value = if hidden { Some("hidden") } else { None };
+        if let Err(e) = save_message_overrides(&keys, value) {
+            self.set_feedback(
+                format!("Failed to save hidden messages: {}", e),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        let verb = if hidden { "Hid" } else { "Restored" };
+        self.set_feedback(
+            format!("{} {} message(s) in context", verb, indices.len()),
+            FeedbackType::Positive,
+        );
+    }
+
+    // A request sending `prompt` as it is, once the configured secrets are out.
+    // Conversation prompts are redacted in `send_prompt` instead.
+    fn request(&self, prompt: &str, model: String) -> LlmRequest {
+        LlmRequest::new(self.config.redact(prompt), model)
+    }
+
+    // The alias of the model picked in the model list, unless there are none.
+    fn selected_model(&self) -> Option<String> {
+        self.models
+            .get(self.model_list_state.selected().unwrap_or(0))
+            .map(|m| m.alias.clone())
+    }
+
+    // A feature's own model from the config, else the selected one. Without either
+    // the user is told, and the feature has nothing to send to.
+    fn model_or_selected(&mut self, configured: Option<String>) -> Option<String> {
+        let model = configured.or_else(|| self.selected_model());
+        if model.is_none() {
+            self.set_feedback(
+                "No model is available; check `llm models`".to_string(),
+                FeedbackType::Negative,
+            );
+        }
+        model
+    }
+
+    fn selected_model_name(&self) -> &str {
+        self.model_list_state
+            .selected()
+            .and_then(|i| self.models.get(i))
+            .map_or("", |m| m.full_name.as_str())
+    }
+
+    // From `[prices]`, which may name the model by its alias or full name.
+    fn model_price(&self, alias: &str) -> Option<(f64, f64)> {
+        let full_name = self
+            .models
+            .iter()
+            .find(|m| m.alias == alias)
+            .map(|m| m.full_name.as_str());
+        self.config
+            .prices
+            .iter()
+            .find(|(name, _)| name == alias || Some(name.as_str()) == full_name)
+            .map(|(_, price)| *price)
+    }
+
+    fn token_counter(&self) -> tokens::TokenCounter {
+        tokens::TokenCounter::for_model(self.selected_model_name(), &self.config.tokenizers)
+    }
+
+    // The tokens the conversation's history takes up and a warning when it has grown
+    // too long or old to answer well, cached because counting tokens can be slow.
+    fn conversation_health(&mut self, index: usize) -> (usize, Option<String>) {
+        let messages = &self.conversations[index].messages;
+        let key = (
+            index,
+            messages.len(),
+            messages.last().map_or(0, |m| m.content.len()),
+            self.model_list_state.selected(),
+        );
+        if let Some((cached, tokens, warning)) = &self.health {
+            if *cached == key {
+                return (*tokens, warning.clone());
+            }
+        }
+        let counter = self.token_counter();
+        let tokens = messages
+            .iter()
+            .filter(|m| !m.hidden)
+            .map(|m| counter.count(&m.content))
+            .sum();
+        let now = unix_now();
+        let age = self.conversations[index]
+            .started
+            .as_deref()
+            .and_then(|started| health::age_days(started, now));
+        let warning = health::warning(tokens, self.context_limit(), age);
+        self.health = Some((key, tokens, warning.clone()));
+        (tokens, warning)
+    }
+
+    fn context_limit(&self) -> usize {
+        self.config
+            .context_tokens
+            .unwrap_or_else(|| context::model_limit(self.selected_model_name()))
+    }
+
+    fn open_composer(&mut self) {
+        let Some(index) = self.current_conversation_index else {
+            return;
+        };
+        if self.conversations[index].messages.is_empty() {
+            return;
+        }
+        let mut state = ListState::default();
+        state.select(Some(self.selected_message().unwrap_or(0)));
+        self.composer = Some(state);
+    }
+
+    fn open_time_travel(&mut self) {
+        let Some(index) = self.current_conversation_index else {
+            return;
+        };
+        let conversation = &self.conversations[index];
+        match history::load(&logged_conversation_ids(&conversation.id)) {
+            Ok(exchanges) if !exchanges.is_empty() => {
+                // Start at the exchange the selected message belongs to.
+                let position = self
+                    .selected_message()
+                    .and_then(|selected| conversation.messages[selected].log_id.as_ref())
+                    .and_then(|log_id| {
+                        exchanges
+                            .iter()
+                            .position(|e| e.log_id.as_ref() == Some(log_id))
+                    })
+                    .map_or(exchanges.len(), |i| i + 1);
+                self.time_travel = Some(TimeTravel {
+                    name: conversation.name.clone(),
+                    exchanges,
+                    position,
+                    scroll: 0,
+                    overrides: store::load(MESSAGE_OVERRIDES_FILE),
+                });
+            }
+            Ok(_) => self.set_feedback(
+                "No logged history for this conversation yet".to_string(),
+                FeedbackType::Negative,
+            ),
+            Err(e) => self.set_feedback(
+                format!("Failed to load history: {}", e),
+                FeedbackType::Negative,
+            ),
+        }
+    }
+
+    fn move_time_travel(&mut self, delta: isize) {
+        if let Some(travel) = self.time_travel.as_mut() {
+            travel.position = travel
+                .position
+                .saturating_add_signed(delta)
+                .clamp(1, travel.exchanges.len());
+            travel.scroll = 0;
+        }
+    }
+
+    fn scroll_time_travel(&mut self, delta: isize) {
+        if let Some(travel) = self.time_travel.as_mut() {
+            travel.scroll = travel.scroll.saturating_add_signed(delta);
+        }
+    }
+
+    fn composer_selected(&self) -> Option<usize> {
+        self.composer.as_ref().and_then(|state| state.selected())
+    }
+
+    fn move_composer(&mut self, delta: isize) {
+        let Some(len) = self
+            .current_conversation_index
+            .map(|i| self.conversations[i].messages.len())
+            .filter(|&len| len > 0)
+        else {
+            return;
+        };
+        if let Some(state) = self.composer.as_mut() {
+            let i = state.selected().unwrap_or(0) as isize + delta;
+            state.select(Some(i.rem_euclid(len as isize) as usize));
+        }
+    }
+
+    fn toggle_composer_hidden(&mut self) {
+        let Some((conversation, selected)) = self
+            .current_conversation_index
+            .zip(self.composer_selected())
+        else {
+            return;
+        };
+        let hidden = !self.conversations[conversation].messages[selected].hidden;
+        if hidden
+            && self.conversations[conversation].messages[selected]
+                .pin
+                .is_some()
+        {
+            self.conversations[conversation].messages[selected].pin = None;
+            self.save_pins(conversation);
+        }
+        self.set_hidden(&[selected], hidden);
+    }
+
+    fn toggle_pin(&mut self, selected: usize) {
+        let Some(conversation) = self.current_conversation_index else {
+            return;
+        };
+        let messages = &mut self.conversations[conversation].messages;
+        let next = messages
+            .iter()
+            .filter_map(|m| m.pin)
+            .max()
+            .map_or(0, |p| p + 1);
+        let message = &mut messages[selected];
+        message.pin = match message.pin {
+            Some(_) => None,
+            None => Some(next),
+        };
+        let unhide = message.pin.is_some() && message.hidden;
+        self.save_pins(conversation);
+        if unhide {
+            self.set_hidden(&[selected], false);
+        }
+    }
+
+    // Swaps the selected pinned message with its neighbour in pin order.
+    fn move_pin(&mut self, delta: isize) {
+        let Some((conversation, selected)) = self
+            .current_conversation_index
+            .zip(self.composer_selected())
+        else {
+            return;
+        };
+        let messages = &mut self.conversations[conversation].messages;
+        let mut pinned: Vec<usize> = (0..messages.len())
+            .filter(|&i| messages[i].pin.is_some())
+            .collect();
+        pinned.sort_by_key(|&i| messages[i].pin);
+        let Some(position) = pinned.iter().position(|&i| i == selected) else {
+            return;
+        };
+        let Some(&other) = position
+            .checked_add_signed(delta)
+            .and_then(|p| pinned.get(p))
+        else {
+            return;
+        };
+        let pin = messages[selected].pin;
+        messages[selected].pin = messages[other].pin;
+        messages[other].pin = pin;
+        self.save_pins(conversation);
+    }
+
+    fn save_pins(&mut self, conversation: usize) {
+        let pins: Vec<(String, Option<usize>)> = self.conversations[conversation]
+            .messages
+            .iter()
+            .filter_map(|m| m.override_key().map(|key| (key, m.pin)))
+            .collect();
+        let mut saved = store::load(CONTEXT_PINS_FILE);
+        if !saved.is_object() {
+            saved = Value::Object(Default::default());
+        }
+        if let Some(map) = saved.as_object_mut() {
+            for (key, pin) in pins {
+                match pin {
+                    Some(pin) => {
+                        map.insert(key, Value::from(pin));
+                    }
+                    None => {
+                        map.remove(&key);
+                    }
+                }
+            }
+        }
+        if let Err(e) = store::save(CONTEXT_PINS_FILE, &saved) {
+            self.set_feedback(
+                format!("Failed to save pinned messages: {}", e),
+                FeedbackType::Negative,
+            );
+        }
+    }
+
+    fn filters(&self, conversation: usize) -> filters::Filters {
+        let mut filters = self.config.filters.clone();
+        filters.apply(&self.conversations[conversation].filter_overrides);
+        filters
+    }
+
+    // `/filters` shows the active filters, `/filters key=value ...` overrides them
+    // for this conversation and `/filters reset` goes back to the config.
+    fn set_filters(&mut self, argument: &str) {
+        let Some(index) = self.current_conversation_index else {
+            return;
+        };
+        if argument == "reset" {
+            self.conversations[index].filter_overrides = Value::Null;
+        } else if !argument.is_empty() {
+            let mut overrides = match &self.conversations[index].filter_overrides {
+                Value::Object(map) => map.clone(),
+                _ => Default::default(),
+            };
+            for setting in argument.split_whitespace() {
+                let parsed = setting.split_once('=').and_then(|(key, value)| {
+                    let value = match value {
+                        "on" | "true" => Value::Bool(true),
+                        "off" | "false" => Value::Bool(false),
+                        number => Value::from(number.parse::<u64>().ok()?),
+                    };
+                    Some((key.to_string(), value))
+                });
+                match parsed {
+                    Some((key, value)) => {
+                        overrides.insert(key, value);
+                    }
+                    None => {
+                        self.set_feedback(
+                            format!("Invalid filter setting '{}'", setting),
+                            FeedbackType::Negative,
+                        );
+                        return;
+                    }
+                }
+            }
+            self.conversations[index].filter_overrides = Value::Object(overrides);
+        }
+
+        let conversation = &self.conversations[index];
+        let mut saved = store::load(CONVERSATION_FILTERS_FILE);
+        if !saved.is_object() {
+            saved = Value::Object(Default::default());
+        }
+        if let Some(map) = saved.as_object_mut() {
+            if conversation.filter_overrides.is_null() {
+                map.remove(&conversation.id);
+            } else {
+                map.insert(
+                    conversation.id.clone(),
+                    conversation.filter_overrides.clone(),
+                );
+            }
+        }
+        if let Err(e) = store::save(CONVERSATION_FILTERS_FILE, &saved) {
+            self.set_feedback(
+                format!("Failed to save filters: {}", e),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        self.set_feedback(
+            format!("Response filters: {}", self.filters(index).describe()),
+            FeedbackType::Positive,
+        );
+    }
+
+    // Environment for commands run on behalf of a conversation: its `/env`
+    // variables plus `LLM_TUI_CONVERSATION` naming it.
+    fn command_env(&self, conversation: usize) -> Vec<(String, String)> {
+        let conversation = &self.conversations[conversation];
+        std::iter::once((
+            "LLM_TUI_CONVERSATION".to_string(),
+            conversation.name.clone(),
+        ))
+        .chain(conversation.env.clone())
+        .collect()
+    }
+
+    // Pasted text arrives in one piece, so its newlines don't send the input. A
+    // popup prompt is single-line and gets them as spaces.
+    fn paste(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        if let Some(prompt) = self.prompt.as_mut() {
+            prompt.input.push_str(&text.replace('\n', " "));
+        } else if let (FocusedBlock::Input, InputMode::Editing) =
+            (&self.focused_block, &self.input_mode)
+        {
+            match paste::detect(&text).filter(|_| self.config.smart_paste) {
+                Some(kind) => self.offer_smart_paste(kind, text),
+                None => self.input.push_str(&text),
+            }
+        } else {
+            return;
+        }
+        self.needs_redraw = true;
+    }
+
+    fn offer_smart_paste(&mut self, kind: &str, text: String) {
+        let mut lines = vec![
+            format!(
+                "This looks like a {} ({} lines).",
+                kind,
+                text.lines().count()
+            ),
+            String::new(),
+        ];
+        lines.extend(
+            self.config
+                .paste_prompts
+                .iter()
+                .take(9)
+                .enumerate()
+                .map(|(i, prompt)| format!("  {}  {}", i + 1, prompt)),
+        );
+        lines.push("  y  Only put it in a code block".to_string());
+        lines.push("  n  Paste it as it is".to_string());
+        self.confirm = Some(Confirm {
+            title: "Smart paste | 1-9: Add prompt | y/Enter: Fence | n/Esc: As is".to_string(),
+            lines,
+            action: ConfirmAction::SmartPaste(text),
+        });
+    }
+
+    // Puts the pasted text in a code block under the `choice`th paste prompt (none
+    // for `None`) in the input, after anything typed there already.
+    fn smart_paste(&mut self, text: &str, choice: Option<usize>) {
+        let prompt = choice.and_then(|choice| self.config.paste_prompts.get(choice));
+        let mut wrapped = paste::wrap(text, prompt.map(String::as_str));
+        if !self.input.is_empty() && !self.input.as_str().ends_with('\n') {
+            wrapped.insert_str(0, "\n\n");
+        }
+        self.input.push_str(&wrapped);
+    }
+
+    // Copies the code block of the selected message, asking which one when it has
+    // several.
+    fn copy_code_block(&mut self) {
+        let count = self.selected_message_ref().map_or(0, |message| {
+            code_blocks::code_blocks(&message.content).len()
+        });
+        match count {
+            0 => self.set_feedback(
+                "No code blocks in this message".to_string(),
+                FeedbackType::Negative,
+            ),
+            1 => self.copy_picked_code_block(0),
+            _ => self.code_picker = Some(0),
+        }
+    }
+
+    fn copy_picked_code_block(&mut self, index: usize) {
+        self.code_picker = None;
+        let code = self.selected_message_ref().and_then(|message| {
+            code_blocks::code_blocks(&message.content)
+                .get(index)
+                .map(|block| block.lines.join("\n"))
+        });
+        if let Some(code) = code {
+            self.copy_async(code, format!("Code block {} copied!", index + 1));
+        }
+    }
+
+    // Opens the response preferences popup for the open conversation.
+    fn open_preferences(&mut self) {
+        if self.current_conversation_index.is_some() {
+            self.preferences_row = Some(0);
+        }
+    }
+
+    // Edits the selected preference: text fields in a prompt, "code only" in place.
+    fn edit_preference(&mut self) {
+        let (Some(index), Some(row)) = (self.current_conversation_index, self.preferences_row)
+        else {
+            return;
+        };
+        let field = preferences::FIELDS[row];
+        let preferences = &mut self.conversations[index].preferences;
+        if field == preferences::Field::CodeOnly {
+            preferences.code_only = !preferences.code_only;
+            self.save_preferences(index);
+        } else {
+            let value = preferences.value(field);
+            self.open_prompt(PromptKind::Preference(field), value);
+        }
+    }
+
+    fn set_preference(&mut self, field: preferences::Field, text: &str) {
+        let Some(index) = self.current_conversation_index else {
+            return;
+        };
+        match self.conversations[index].preferences.set(field, text) {
+            Ok(()) => self.save_preferences(index),
+            Err(e) => self.set_feedback(e, FeedbackType::Negative),
+        }
+    }
+
+    // Opens the model options popup for the open conversation.
+    fn open_model_options(&mut self) {
+        if self.current_conversation_index.is_some() {
+            self.model_options_row = Some(0);
+        } else {
+            self.set_feedback("No conversation open".to_string(), FeedbackType::Negative);
+        }
+    }
+
+    // Edits the selected model option, or adds one when `add` is set or there are none.
+    fn edit_model_option(&mut self, add: bool) {
+        let (Some(index), Some(row)) = (self.current_conversation_index, self.model_options_row)
+        else {
+            return;
+        };
+        match self.conversations[index].preferences.options.get(row) {
+            Some((name, value)) if !add => {
+                let prefill = format!("{} {}", name, value);
+                self.open_prompt(PromptKind::ModelOption(Some(name.clone())), prefill);
+            }
+            _ => self.open_prompt(PromptKind::ModelOption(None), String::new()),
+        }
+    }
+
+    fn remove_model_option(&mut self) {
+        let (Some(index), Some(row)) = (self.current_conversation_index, self.model_options_row)
+        else {
+            return;
+        };
+        let options = &mut self.conversations[index].preferences.options;
+        if row < options.len() {
+            options.remove(row);
+            self.model_options_row = Some(row.min(options.len().saturating_sub(1)));
+            self.save_preferences(index);
+        }
+    }
+
+    fn set_model_option(&mut self, replacing: Option<&str>, text: &str) {
+        let Some(index) = self.current_conversation_index else {
+            return;
+        };
+        match self.conversations[index]
+            .preferences
+            .set_option(replacing, text)
+        {
+            Ok(()) => self.save_preferences(index),
+            Err(e) => self.set_feedback(e, FeedbackType::Negative),
+        }
+    }
+
+    fn save_preferences(&mut self, index: usize) {
+        let conversation = &self.conversations[index];
+        let mut saved = store::load(CONVERSATION_PREFERENCES_FILE);
+        if !saved.is_object() {
+            saved = Value::Object(Default::default());
+        }
+        if let Some(map) = saved.as_object_mut() {
+            if conversation.preferences.is_empty() {
+                map.remove(&conversation.id);
+            } else {
+                map.insert(conversation.id.clone(), conversation.preferences.to_json());
+            }
+        }
+        if let Err(e) = store::save(CONVERSATION_PREFERENCES_FILE, &saved) {
+            self.set_feedback(
+                format!("Failed to save preferences: {}", e),
+                FeedbackType::Negative,
+            );
+        }
+    }
+
+    // `/env` lists the conversation's variables, `/env KEY=value ...` sets them and
+    // `/env unset KEY ...` removes them.
+    fn set_env(&mut self, argument: &str) {
+        let Some(index) = self.current_conversation_index else {
+            return;
+        };
+        if let Some(keys) = argument
+            .strip_prefix("unset")
+            .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+        {
+            for key in keys.split_whitespace() {
+                self.conversations[index].env.remove(key);
+            }
+        } else if !argument.is_empty() {
+            let mut env = self.conversations[index].env.clone();
+            for setting in argument.split_whitespace() {
+                match setting.split_once('=') {
+                    Some((key, value)) if is_env_name(key) => {
+                        env.insert(key.to_string(), value.to_string());
+                    }
+                    _ => {
+                        self.set_feedback(
+                            format!("Invalid variable '{}', expected KEY=value", setting),
+                            FeedbackType::Negative,
+                        );
+                        return;
+                    }
+                }
+            }
+            self.conversations[index].env = env;
+        }
+
+        let conversation = &self.conversations[index];
+        let mut saved = store::load(CONVERSATION_ENV_FILE);
+        if !saved.is_object() {
+            saved = Value::Object(Default::default());
+        }
+        if let Some(map) = saved.as_object_mut() {
+            if conversation.env.is_empty() {
+                map.remove(&conversation.id);
+            } else {
+                map.insert(conversation.id.clone(), serde_json::json!(conversation.env));
+            }
+        }
+        if let Err(e) = store::save(CONVERSATION_ENV_FILE, &saved) {
+            self.set_feedback(
+                format!("Failed to save environment: {}", e),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        let described = if conversation.env.is_empty() {
+            "none".to_string()
+        } else {
+            conversation
+                .env
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        self.set_feedback(
+            format!("Environment: {}", described),
+            FeedbackType::Positive,
+        );
+    }
+
+    fn pin_snippet(&mut self, text: String) {
+        let Some(index) = self.current_conversation_index else {
+            return;
+        };
+        self.conversations[index].snippets.push(text);
+        self.save_snippets(index);
+    }
+
+    // `/unpin <n>` removes the n-th item of the pinned header; `/unpin all` removes
+    // everything.
+    fn unpin_snippet(&mut self, argument: &str) {
+        let Some(index) = self.current_conversation_index else {
+            return;
+        };
+        let messages = &mut self.conversations[index].messages;
+        let mut pinned: Vec<usize> = (0..messages.len())
+            .filter(|&i| messages[i].pin.is_some())
+            .collect();
+        pinned.sort_by_key(|&i| messages[i].pin);
+        let snippets = self.conversations[index].snippets.len();
+        match argument.parse::<usize>() {
+            _ if argument == "all" => {
+                for message in &mut self.conversations[index].messages {
+                    message.pin = None;
+                }
+                self.conversations[index].snippets.clear();
+                self.save_pins(index);
+            }
+            Ok(n) if (1..=pinned.len()).contains(&n) => {
+                self.conversations[index].messages[pinned[n - 1]].pin = None;
+                self.save_pins(index);
+                return;
+            }
+            Ok(n) if n > pinned.len() && n - pinned.len() <= snippets => {
+                self.conversations[index]
+                    .snippets
+                    .remove(n - pinned.len() - 1);
+            }
+            _ => {
+                self.set_feedback(
+                    "Usage: /unpin <number> | all".to_string(),
+                    FeedbackType::Negative,
+                );
+                return;
+            }
+        }
+        self.save_snippets(index);
+    }
+
+    fn save_snippets(&mut self, conversation: usize) {
+        let conversation = &self.conversations[conversation];
+        let mut saved = store::load(PINNED_SNIPPETS_FILE);
+        if !saved.is_object() {
+            saved = Value::Object(Default::default());
+        }
+        if let Some(map) = saved.as_object_mut() {
+            if conversation.snippets.is_empty() {
+                map.remove(&conversation.id);
+            } else {
+                map.insert(
+                    conversation.id.clone(),
+                    Value::from(conversation.snippets.clone()),
+                );
+            }
+        }
+        let count = conversation.snippets.len();
+        match store::save(PINNED_SNIPPETS_FILE, &saved) {
+            Ok(()) => self.set_feedback(
+                format!("{} pinned snippet(s)", count),
+                FeedbackType::Positive,
+            ),
+            Err(e) => self.set_feedback(
+                format!("Failed to save pinned snippets: {}", e),
+                FeedbackType::Negative,
+            ),
+        }
+    }
+
+    // The earlier messages the context composer shows as sent: the most recent
+    // unhidden, unpinned ones that fit in the context window.
+    fn history_context(&self, conversation: usize) -> Vec<ContextPart> {
+        let conversation = &self.conversations[conversation];
+        let messages = &conversation.messages;
+        let plan = context::plan(messages, self.context_limit(), &self.token_counter());
+        messages
+            .iter()
+            .zip(&plan.inclusion)
+            .filter(|(message, inclusion)| {
+                **inclusion == context::Inclusion::Included && !message.streaming
+            })
+            .map(|(message, _)| ContextPart {
+                label: format!(
+                    "history: {}{}",
+                    self.config
+                        .theme
+                        .prefix(&message.role, conversation.model.as_deref()),
+                    preview(&message.content, 40)
+                ),
+                source: PartSource::History(message.role.clone(), message.content.clone()),
+            })
+            .collect()
+    }
+
+    // Pinned messages (in pin order) followed by pinned snippets.
+    fn pinned_context(&self, conversation: usize) -> Vec<String> {
+        let conversation = &self.conversations[conversation];
+        let mut pinned: Vec<&Message> = conversation
+            .messages
+            .iter()
+            .filter(|m| m.pin.is_some())
+            .collect();
+        pinned.sort_by_key(|m| m.pin);
+        pinned
+            .into_iter()
+            .map(|m| m.content.clone())
+            .chain(conversation.snippets.iter().cloned())
+            .collect()
+    }
+
+    fn marked_messages(&self) -> Vec<&Message> {
+        let Some(index) = self.current_conversation_index else {
+            return Vec::new();
+        };
+        let messages = &self.conversations[index].messages;
+        self.chat_state
+            .marked
+            .iter()
+            .filter_map(|&i| messages.get(i))
+            .collect()
+    }
+
+    // The question/answer pairs the marked messages belong to: a marked prompt
+    // brings its answer and a marked answer brings its prompt.
+    fn marked_question_answers(&self) -> Vec<(String, String)> {
+        let Some(index) = self.current_conversation_index else {
+            return Vec::new();
+        };
+        let messages = &self.conversations[index].messages;
+        let questions: BTreeSet<usize> = self
+            .chat_state
+            .marked
+            .iter()
+            .filter_map(|&i| messages.get(..=i)?.iter().rposition(|m| m.role == "user"))
+            .collect();
+        questions
+            .into_iter()
+            .filter_map(|q| {
+                let answer = messages.get(q + 1).filter(|m| m.role == "assistant")?;
+                Some((messages[q].content.clone(), answer.content.clone()))
+            })
+            .collect()
+    }
+
+    // Writes the marked Q&A pairs as Anki flashcards, condensing the answers with a
+    // model first when `[anki] condense` is set.
+    fn export_flashcards(&mut self, path: &str) {
+        let cards = self.marked_question_answers();
+        if cards.is_empty() {
+            self.set_feedback(
+                "Mark a prompt or an answer to export it as a flashcard".to_string(),
+                FeedbackType::Negative,
+            );
+            return;
+        }
+        let path = config::expand_home(path);
+        if !self.config.anki_condense {
+            match fs::write(&path, anki::to_tsv(&cards)) {
+                Ok(()) => self.set_feedback(
+                    format!(
+                        "Exported {} flashcard(s) to {}",
+                        cards.len(),
+                        path.display()
+                    ),
+                    FeedbackType::Positive,
+                ),
+                Err(e) => {
+                    self.set_feedback(format!("Failed to export: {}", e), FeedbackType::Negative)
+                }
+            }
+            return;
+        }
+
+        let Some(model) = self.model_or_selected(self.config.anki_model.clone()) else {
+            return;
+        };
+        self.set_feedback(
+            format!("Condensing {} flashcard(s)...", cards.len()),
+            FeedbackType::Positive,
+        );
+        let jobs: Vec<(String, String, LlmRequest)> = cards
+            .into_iter()
+            .map(|(question, answer)| {
+                let request = self.request(
+                    &anki::condense_prompt(anki::CONDENSE_PROMPT, &question, &answer),
+                    model.clone(),
+                );
+                (question, answer, request)
+            })
+            .collect();
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            let count = jobs.len();
+            // An answer that fails to condense is exported as it is.
+            let cards: Vec<(String, String)> = jobs
+                .into_iter()
+                .map(|(question, answer, request)| {
+                    let condensed = run_llm_checked(&request).unwrap_or(answer);
+                    (question, condensed)
+                })
+                .collect();
+            let result = fs::write(&path, anki::to_tsv(&cards))
+                .map(|()| format!("Exported {} flashcard(s) to {}", count, path.display()))
+                .map_err(|e| e.to_string());
+            let _ = background_tx.send(BackgroundEvent::Exported(result));
+        });
+    }
+
+    // The open conversation as the exporter writes it, for the plain view.
+    fn transcript(&self, index: usize) -> String {
+        let conversation = &self.conversations[index];
+        let messages: Vec<&Message> = conversation.messages.iter().collect();
+        messages_to_markdown(&messages, &self.config.theme, conversation.model.as_deref())
+    }
+
+    fn toggle_plain(&mut self) {
+        if self.plain.is_some() {
+            self.plain = None;
+        } else if self.current_conversation_index.is_some() {
+            self.plain = Some(0);
+        } else {
+            self.set_feedback("No conversation open".to_string(), FeedbackType::Negative);
+        }
+    }
+
+    fn marked_messages_markdown(&self) -> String {
+        let model = self
+            .current_conversation_index
+            .and_then(|index| self.conversations[index].model.as_deref());
+        messages_to_markdown(&self.marked_messages(), &self.config.theme, model)
+    }
+
+    fn toggle_mark(&mut self) {
+        if let Some(selected) = self.selected_message() {
+            if !self.chat_state.marked.remove(&selected) {
+                self.chat_state.marked.insert(selected);
+            }
+        }
+    }
+
+    // Attaches the marked messages as a fragment to the next prompt in another
+    // conversation, found by name or by its number in the list.
+    fn send_selection_to(&mut self, target: &str) -> Result<(), String> {
+        let target_index = target
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .and_then(|position| self.visible_conversations().get(position).copied())
+            .or_else(|| {
+                let target = target.to_lowercase();
+                self.conversations
+                    .iter()
+                    .position(|c| c.name.to_lowercase().contains(&target))
+            })
+            .ok_or_else(|| format!("No conversation matching '{}'", target))?;
+
+        let markdown = self.marked_messages_markdown();
+        let count = self.chat_state.marked.len();
+        let path = save_fragment("selection", &markdown).map_err(|e| e.to_string())?;
+        let source = self
+            .current_conversation_index
+            .map(|i| self.conversations[i].name.clone())
+            .unwrap_or_default();
+        self.pending_fragments.push((
+            format!("{} message(s) from '{}'", count, source),
+            path.to_string_lossy().into_owned(),
+        ));
+
+        let position = self
+            .visible_conversations()
+            .iter()
+            .position(|&visible| visible == target_index);
+        self.chat_state.marked.clear();
+        self.conversation_list_state.select(position);
+        self.current_conversation_index = Some(target_index);
+        self.conversations[target_index].unread = 0;
+        self.focused_block = FocusedBlock::Input;
+        self.input_mode = InputMode::Editing;
+        self.set_feedback(
+            format!(
+                "Selection attached to next prompt in '{}'",
+                self.conversations[target_index].name
+            ),
+            FeedbackType::Positive,
+        );
+        Ok(())
+    }
+
+    // Remote prompts go to the conversation they name (created if missing), or the
+    // open one, without touching the input the user may be composing.
+    fn handle_remote_command(&mut self) {
+        if let Ok(mut command) = self.remote_command_rx.try_recv() {
+            if self.models.is_empty() {
+                let error =
+                    RemoteError::new("no_model", "No model is available; check `llm models`");
+                match command.accepted {
+                    // The server reports it along with its own errors.
+                    Some(accepted) => {
+                        let _ = accepted.send(Err(error));
+                    }
+                    None => self.set_feedback(
+                        format!("Remote prompt rejected: {}", error.message),
+                        FeedbackType::Negative,
+                    ),
+                }
+                return;
+            }
+            if let Some(accepted) = command.accepted.take() {
+                let _ = accepted.send(Ok(()));
+            }
+            if self.do_not_disturb {
+                self.pending_remote.push_back(command);
+                self.set_feedback(
+                    format!(
+                        "Remote prompt queued ({} pending, A to review)",
+                        self.pending_remote.len()
+                    ),
+                    FeedbackType::Positive,
+                );
+            } else {
+                self.send_remote_command(command);
+            }
+        }
+    }
+
+    fn send_remote_command(&mut self, command: RemoteCommand) {
+        let index = match &command.conversation {
+            Some(target) => self.find_or_create_conversation(target),
+            None => match self.current_conversation_index {
+                Some(index) => index,
+                None => {
+                    self.start_new_conversation();
+                    self.conversations.len() - 1
+                }
+            },
+        };
+        self.send_prompt(index, command.prompt, Vec::new(), command.reply);
+        self.set_feedback(
+            format!(
+                "Remote message received and sent to '{}'!",
+                self.conversations[index].name
+            ),
+            FeedbackType::Positive,
+        );
+    }
+
+    fn toggle_do_not_disturb(&mut self) {
+        self.do_not_disturb = !self.do_not_disturb;
+        let message = if self.do_not_disturb {
+            "Do not disturb: remote prompts will be queued for review".to_string()
+        } else if self.pending_remote.is_empty() {
+            "Do not disturb off".to_string()
+        } else {
+            format!(
+                "Do not disturb off ({} queued prompt(s) still need review, A)",
+                self.pending_remote.len()
+            )
+        };
+        self.set_feedback(message, FeedbackType::Positive);
+    }
+
+    // The remote server's address and what it has handled, with the last error in full.
+    fn show_server_status(&mut self) {
+        let stats = &self.server_stats;
+        let state = if self.server_running.load(Ordering::SeqCst) {
+            "listening"
+        } else if self.config.server_enabled && !self.kiosk {
+            "stopped"
+        } else {
+            "off ([server] enabled = false)"
+        };
+        let last_command = match stats.last_command.load(Ordering::SeqCst) {
+            0 => "never".to_string(),
+            time => match listing::ago(time, unix_now()).as_str() {
+                "now" => "just now".to_string(),
+                ago => format!("{} ago", ago),
+            },
+        };
+        let mut lines = vec![
+            format!("Address: {} ({})", self.config.server_address(), state),
+            "Auth: none; anyone who can reach the address can send prompts".to_string(),
+            format!("Connections: {}", stats.connections.load(Ordering::SeqCst)),
+            format!("Last remote prompt: {}", last_command),
+            format!("Errors: {}", stats.errors.load(Ordering::SeqCst)),
+        ];
+        if let Some(error) = stats.last_error.lock().unwrap().as_ref() {
+            lines.push(format!("Last error: {}", error));
+        }
+        self.panel = Some(Panel {
+            title: "Remote server | Esc: Close".to_string(),
+            lines,
+            scroll: 0,
+        });
+    }
+
+    fn background_paused(&self) -> bool {
+        self.background_paused.load(Ordering::SeqCst)
+    }
+
+    fn toggle_background_pause(&mut self) {
+        let paused = !self.background_paused();
+        self.background_paused.store(paused, Ordering::SeqCst);
+        let message = if paused {
+            "Background jobs paused: no digests, follow-up suggestions or inline completions"
+        } else {
+            "Background jobs resumed"
+        };
+        self.set_feedback(message.to_string(), FeedbackType::Positive);
+    }
+
+    // Asks about the oldest queued remote prompt. Declining drops it, and a client
+    // waiting for the response is told it wasn't sent.
+    fn review_remote(&mut self) {
+        if self.confirm.is_some() {
+            return;
+        }
+        let Some(command) = self.pending_remote.pop_front() else {
+            return;
+        };
+        let target = command.conversation.clone().unwrap_or_else(|| {
+            self.current_conversation_index
+                .map_or("a new conversation".to_string(), |index| {
+                    self.conversations[index].name.clone()
+                })
+        });
+        let mut lines: Vec<String> = command
+            .prompt
+            .lines()
+            .take(REMOTE_PREVIEW_LINES)
+            .map(String::from)
+            .collect();
+        let more = command.prompt.lines().count().saturating_sub(lines.len());
+        if more > 0 {
+            lines.push(format!("... {} more line(s)", more));
+        }
+        self.confirm = Some(Confirm {
+            title: format!(
+                "Send remote prompt to '{}'? ({} more queued) y/Enter: Send | n/Esc: Discard",
+                target,
+                self.pending_remote.len()
+            ),
+            lines,
+            action: ConfirmAction::SendRemote(command),
+        });
+    }
+
+    // Matches a conversation by id, then by name (ignoring case).
+    fn find_or_create_conversation(&mut self, target: &str) -> usize {
+        let target_lower = target.to_lowercase();
+        self.conversations
+            .iter()
+            .position(|c| c.id == target)
+            .or_else(|| {
+                self.conversations
+                    .iter()
+                    .position(|c| c.name.to_lowercase() == target_lower)
+            })
+            .unwrap_or_else(|| self.create_conversation(target.to_string()))
+    }
+
+    fn set_feedback(&mut self, message: String, feedback_type: FeedbackType) {
+        self.feedback = Some(FeedbackMessage {
+            message,
+            feedback_type,
+            expires_at: Instant::now() + Duration::from_secs(5), // Display for 5 seconds
+        });
+        self.needs_redraw = true;
+    }
+
+    // Writes the input to disk when it changed since the last save, at most every
+    // `DRAFT_SAVE_INTERVAL`. An empty input removes the draft.
+    fn autosave_draft(&mut self) {
+        if self.draft_checked_at.elapsed() < DRAFT_SAVE_INTERVAL
+            || self.input.as_str() == self.saved_draft
+        {
+            return;
+        }
+        self.draft_checked_at = Instant::now();
+        let result = if self.input.trim().is_empty() {
+            store::remove(DRAFT_FILE)
+        } else {
+            store::save(
+                DRAFT_FILE,
+                &serde_json::json!({"text": self.input.as_str()}),
+            )
+        };
+        match result {
+            Ok(()) => self.saved_draft = self.input.to_string(),
+            Err(e) => self.set_feedback(
+                format!("Failed to save draft: {}", e),
+                FeedbackType::Negative,
+            ),
         }
     }
-    fn selected_message(&self) -> Option<usize> {
-        self.chat_state.list_state.selected()
-    }
 
-    fn next_message(&mut self) {
-        if let Some(index) = self.current_conversation_index {
-            let messages = &self.conversations[index].messages;
-            let i = match self.chat_state.list_state.selected() {
-                Some(i) => {
-                    if i >= messages.len() - 1 {
-                        0
-                    } else {
-                        i + 1
-                    }
-                }
-                None => 0,
-            };
-            self.chat_state.list_state.select(Some(i));
+    // Keeps the ghost-text completion in step with the input: an edit drops the
+    // suggestion (unless it was typed along) and any request in flight, and a pause
+    // in typing asks for a new one. Nothing is asked while a prompt is answered.
+    fn update_completion(&mut self) {
+        let Some(model) = self.config.completion_model.clone() else {
+            return;
+        };
+        if self.input.as_str() != self.completion.seen {
+            let typed = self.input.strip_prefix(self.completion.seen.as_str());
+            self.completion.suggestion =
+                self.completion
+                    .suggestion
+                    .take()
+                    .zip(typed)
+                    .and_then(|(suggestion, typed)| {
+                        suggestion
+                            .strip_prefix(typed)
+                            .filter(|rest| !rest.is_empty())
+                            .map(str::to_string)
+                    });
+            self.completion.seen = self.input.to_string();
+            self.completion.edited_at = Some(Instant::now());
+            self.cancel_completion();
         }
-    }
 
-    fn previous_message(&mut self) {
-        if let Some(index) = self.current_conversation_index {
-            let messages = &self.conversations[index].messages;
-            let i = match self.chat_state.list_state.selected() {
-                Some(i) => {
-                    if i == 0 {
-                        messages.len() - 1
-                    } else {
-                        i - 1
-                    }
-                }
-                None => 0,
-            };
-            self.chat_state.list_state.select(Some(i));
+        let editing = matches!(
+            (&self.focused_block, &self.input_mode),
+            (FocusedBlock::Input, InputMode::Editing)
+        );
+        if !editing || self.kiosk || !self.running.is_empty() || self.background_paused() {
+            self.cancel_completion();
+            return;
+        }
+        let due = self.completion.edited_at.is_some_and(|edited_at| {
+            edited_at.elapsed() >= Duration::from_millis(self.config.completion_delay_ms)
+        });
+        if !due
+            || self.completion.suggestion.is_some()
+            || self.input.trim().is_empty()
+            || self.input.starts_with('/')
+        {
+            return;
         }
+        self.completion.edited_at = None;
+
+        let text = self.input.to_string();
+        let request = LlmRequest {
+            no_log: true,
+            ..self.request(&completion::build_prompt(&text), model)
+        };
+        let running = RunningChild::default();
+        self.completion.pending = Some(Arc::clone(&running));
+        let background_tx = self.background_tx.clone();
+        thread::spawn(move || {
+            // Cancelling already cleared `pending`; anything else, failures too, is
+            // reported so it's cleared and the app can go idle.
+            let Some(reply) = stream_llm_cancellable(&request, &running, |_| {}) else {
+                return;
+            };
+            let _ = background_tx.send(BackgroundEvent::Completed(text, reply.ok()));
+        });
     }
 
-    fn copy_selected_message_to_clipboard(&mut self) -> io::Result<()> {
-        if let Some(conversation_index) = self.current_conversation_index {
-            if let Some(message_index) = self.chat_state.list_state.selected() {
-                let conversation = &self.conversations[conversation_index];
-                if let Some(message) = conversation.messages.get(message_index) {
-                    let mut ctx = ClipboardContext::new()
-                        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-                    ctx.set_contents(message.content.clone())
-                        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-                    return Ok(());
-                }
+    fn cancel_completion(&mut self) {
+        if let Some(running) = self.completion.pending.take() {
+            if let Some(mut child) = running.lock().unwrap().take() {
+                let _ = child.kill();
+                let _ = child.wait();
             }
         }
-        Err(io::Error::new(io::ErrorKind::Other, "No message selected"))
     }
 
-    fn handle_remote_command(&mut self) {
-        if let Ok(input) = self.remote_command_rx.try_recv() {
-            self.input = input;
-            self.send_message();
-            self.set_feedback(
-                "Remote message received and sent!".to_string(),
-                FeedbackType::Positive,
-            );
+    fn accept_completion(&mut self) {
+        if let Some(suggestion) = self.completion.suggestion.take() {
+            self.input.push_str(&suggestion);
         }
     }
 
-    fn set_feedback(&mut self, message: String, feedback_type: FeedbackType) {
-        self.feedback = Some(FeedbackMessage {
-            message,
-            feedback_type,
-            expires_at: Instant::now() + Duration::from_secs(5), // Display for 5 seconds
+    // A draft left on disk means the last session ended without a clean exit.
+    fn offer_draft_restore(&mut self) {
+        let Some(draft) = store::load(DRAFT_FILE)["text"]
+            .as_str()
+            .filter(|text| !text.trim().is_empty())
+            .map(str::to_string)
+        else {
+            return;
+        };
+        let mut lines: Vec<String> = draft.lines().take(8).map(str::to_string).collect();
+        if draft.lines().count() > lines.len() {
+            lines.push("...".to_string());
+        }
+        self.saved_draft = draft.clone();
+        self.confirm = Some(Confirm {
+            title: "Restore unsent draft from last session? y/Enter: Restore | n/Esc: Discard"
+                .to_string(),
+            lines,
+            action: ConfirmAction::RestoreDraft(draft),
         });
     }
 
@@ -378,77 +6121,893 @@ impl App {
         if let Some(feedback) = &self.feedback {
             if Instant::now() > feedback.expires_at {
                 self.feedback = None;
+                self.needs_redraw = true;
             }
         }
     }
+
+    // Whether the app has been left alone (see `IDLE_AFTER`): no recent input, no
+    // response or completion on its way, nothing on screen about to change.
+    fn update_idle(&mut self) -> bool {
+        let flashing = self
+            .activity
+            .as_ref()
+            .is_some_and(|(_, at)| at.elapsed() < ACTIVITY_FLASH);
+        let idle = self.last_input.elapsed() >= IDLE_AFTER
+            && self.running.is_empty()
+            && self.completion.pending.is_none()
+            && !self.loading_older
+            && self.feedback.is_none()
+            && !flashing;
+        self.idle.store(idle, Ordering::SeqCst);
+        idle
+    }
+
+    // How long the main loop waits for input: a frame while drawing or streaming,
+    // longer when nothing is going on.
+    fn poll_interval(&self, idle: bool) -> Duration {
+        if self.needs_redraw || !self.running.is_empty() || self.completion.pending.is_some() {
+            FRAME_INTERVAL
+        } else if idle {
+            IDLE_POLL_INTERVAL
+        } else {
+            IDLE_REDRAW_INTERVAL
+        }
+    }
 }
 
-fn load_models() -> Vec<ModelInfo> {
-    let output = Command::new("llm")
-        .args(["aliases"])
-        .output()
-        .expect("Failed to execute llm aliases command");
+// Joins a relative path suggested by a model onto `root`, rejecting absolute paths
+// and `..` components so a response can't write outside the chosen directory.
+fn safe_join(root: &Path, relative: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative);
+    let is_safe = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    is_safe.then(|| root.join(relative))
+}
+
+// The Python code blocks of a message, each run as one cell.
+fn python_cells(content: &str) -> Vec<String> {
+    code_blocks::code_blocks(content)
+        .iter()
+        .filter(|block| {
+            block.language().is_some_and(|language| {
+                matches!(
+                    language.to_lowercase().as_str(),
+                    "python" | "python3" | "py"
+                )
+            })
+        })
+        .map(|block| block.lines.join("\n"))
+        .collect()
+}
+
+fn write_files(files: &[(PathBuf, String)]) -> io::Result<()> {
+    for (path, contents) in files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)?;
+    }
+    Ok(())
+}
+
+fn explain_failure_prompt(report: &str) -> String {
+    let (header, stderr) = report.split_once("--- stderr ---").unwrap_or((report, ""));
+    let field = |name: &str| {
+        header
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .map(str::trim)
+            .unwrap_or_default()
+            .to_string()
+    };
+    format!(
+        "This shell command failed. Explain what went wrong and how to fix it.\n\n\
+         Command: {}\nExit status: {}\nWorking directory: {}\n\nStderr:\n```\n{}\n```",
+        field("command:"),
+        field("exit_status:"),
+        field("cwd:"),
+        stderr.trim()
+    )
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// Writes generated text (fetched pages, etc.) where `llm -f` can read it.
+fn save_fragment(kind: &str, text: &str) -> io::Result<PathBuf> {
+    let dir = store::data_dir()
+        .ok_or_else(|| io::Error::other("No home directory"))?
+        .join("fragments");
+    fs::create_dir_all(&dir)?;
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let path = dir.join(format!("{}-{}.md", kind, stamp));
+    fs::write(&path, text)?;
+    Ok(path)
+}
 
-    String::from_utf8_lossy(&output.stdout)
+fn format_size(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+}
+
+fn copy_to_clipboard(text: String) -> io::Result<()> {
+    let mut ctx = ClipboardContext::new().map_err(io::Error::other)?;
+    ctx.set_contents(text).map_err(io::Error::other)
+}
+
+fn read_clipboard() -> io::Result<String> {
+    let mut ctx = ClipboardContext::new().map_err(io::Error::other)?;
+    ctx.get_contents().map_err(io::Error::other)
+}
+
+// Every model llm knows about, from `llm models list` (lines such as
+// `Ollama: llama3:8b (aliases: llama3)`, ending with `Default: <model>`), with the
+// aliases from `llm aliases` merged in. Also returns the position of the default.
+fn load_models() -> Result<(Vec<ModelInfo>, Option<usize>), String> {
+    let output = llm_output(&["aliases"])?;
+    // Model ids (as in `llama3:8b`) may contain colons, aliases don't.
+    let aliases: Vec<(String, String)> = String::from_utf8_lossy(&output)
         .lines()
         .filter_map(|line| {
-            let parts: Vec<&str> = line.split(':').collect();
-            if parts.len() == 2 {
-                Some(ModelInfo {
-                    alias: parts[0].trim().to_string(),
-                    full_name: parts[1].trim().to_string(),
+            let (alias, model) = line.split_once(':')?;
+            Some((alias.trim().to_string(), model.trim().to_string()))
+        })
+        .filter(|(alias, model)| !alias.is_empty() && !model.is_empty())
+        .collect();
+
+    let mut models: Vec<ModelInfo> = Vec::new();
+    let mut default = None;
+    if let Ok(output) = llm_output(&["models", "list"]) {
+        for line in String::from_utf8_lossy(&output).lines() {
+            // Indented lines are option descriptions.
+            if line.starts_with(char::is_whitespace) {
+                continue;
+            }
+            let Some((kind, rest)) = line.split_once(": ") else {
+                continue;
+            };
+            let (id, listed_aliases) = match rest.split_once(" (aliases: ") {
+                Some((id, aliases)) => (id, aliases.trim_end_matches(')')),
+                None => (rest, ""),
+            };
+            let id = id.trim().to_string();
+            if kind == "Default" {
+                default = Some(id);
+                continue;
+            }
+            let alias = aliases
+                .iter()
+                .find(|(_, model)| *model == id)
+                .map(|(alias, _)| alias.clone())
+                .or_else(|| {
+                    listed_aliases
+                        .split(',')
+                        .map(str::trim)
+                        .find(|alias| !alias.is_empty())
+                        .map(str::to_string)
                 })
-            } else {
-                None
+                .unwrap_or_else(|| id.clone());
+            if !models.iter().any(|m| m.full_name == id) {
+                models.push(ModelInfo {
+                    alias,
+                    full_name: id,
+                });
             }
-        })
-        .collect()
+        }
+    }
+    // Aliases for models the list doesn't show, e.g. from an older llm.
+    for (alias, model) in aliases {
+        if !models.iter().any(|m| m.full_name == model) {
+            models.push(ModelInfo {
+                alias,
+                full_name: model,
+            });
+        }
+    }
+    let default = default.and_then(|default| {
+        models
+            .iter()
+            .position(|m| m.full_name == default || m.alias == default)
+    });
+    Ok((models, default))
+}
+
+// Takes `--profile <name>` out of `args` and activates that profile, or the one in
+// `LLM_TUI_PROFILE`. Returns whether either named one.
+fn activate_requested_profile(args: &mut Vec<String>) -> io::Result<bool> {
+    let requested = match args.iter().position(|arg| arg == "--profile") {
+        Some(i) if i + 1 < args.len() => {
+            let name = args.remove(i + 1);
+            args.remove(i);
+            Some(name)
+        }
+        Some(_) => return Err(io::Error::other("Usage: llm-tui --profile <name>")),
+        None => env::var(store::PROFILE_ENV)
+            .ok()
+            .filter(|name| !name.is_empty()),
+    };
+    match requested {
+        Some(name) => profile::activate(&name).map(|()| true),
+        None => Ok(false),
+    }
+}
+
+// Asks which profile to use when profiles exist, for the TUI started without one.
+fn choose_profile() -> io::Result<()> {
+    let profiles = profile::list();
+    if profiles.is_empty() {
+        return Ok(());
+    }
+    match profile::choose(&profiles)? {
+        Some(name) => profile::activate(&name),
+        None => Ok(()),
+    }
+}
+
+// `llm-tui print [NAME]`: writes a conversation (the most recent one, or the one
+// named, in part) to stdout as `/plain` shows it, for `tee` or a printer.
+fn print_transcript(args: Vec<String>) -> io::Result<()> {
+    let name = args.join(" ").to_lowercase();
+    let conversations = load_conversations().map_err(io::Error::other)?;
+    let conversation = if name.is_empty() {
+        conversations.iter().max_by_key(|c| c.last_active)
+    } else {
+        conversations
+            .iter()
+            .find(|c| c.name.to_lowercase() == name)
+            .or_else(|| {
+                conversations
+                    .iter()
+                    .find(|c| c.name.to_lowercase().contains(&name))
+            })
+    };
+    let Some(conversation) = conversation else {
+        return Err(io::Error::other(format!(
+            "No conversation named '{}'",
+            args.join(" ")
+        )));
+    };
+    let messages: Vec<&Message> = conversation.messages.iter().collect();
+    let theme = Config::load().theme;
+    println!(
+        "{}",
+        messages_to_markdown(&messages, &theme, conversation.model.as_deref())
+    );
+    Ok(())
 }
 
 fn main() -> Result<(), io::Error> {
+    // Subcommands use the profile too, so it's switched to before they run.
+    let mut args: Vec<String> = env::args().collect();
+    let has_profile = activate_requested_profile(&mut args)?;
+    let rest = || args.iter().skip(2).cloned().collect();
+    match args.get(1).map(String::as_str) {
+        Some("--shell-hook") => {
+            print!("{}", SHELL_HOOK);
+            return Ok(());
+        }
+        Some("--stdio") => return rpc::serve_stdio(),
+        Some("send") => return client::send(rest()),
+        Some("capture-pane") => return client::capture_pane(rest()),
+        Some("config") => return bundle::run(rest()),
+        Some("batch") => return batch::run(rest()),
+        Some("print") => return print_transcript(rest()),
+        _ => {}
+    }
+    if !has_profile {
+        choose_profile()?;
+    }
+    let kiosk = env::args().any(|arg| arg == "--kiosk");
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
+    // Lets terminals that support it report Shift+Enter apart from Enter.
+    let keyboard_enhancement = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if keyboard_enhancement {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+        )?;
+    }
+    // A panic would otherwise leave the terminal in raw mode on the alternate
+    // screen, with the message lost. Panics on helper threads don't end the app
+    // (though a poisoned lock soon takes the main thread down too).
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if thread::current().name() == Some("main") {
+            let mut stdout = io::stdout();
+            if keyboard_enhancement {
+                let _ = execute!(stdout, PopKeyboardEnhancementFlags);
+            }
+            let _ = disable_raw_mode();
+            let _ = execute!(
+                stdout,
+                LeaveAlternateScreen,
+                DisableMouseCapture,
+                DisableBracketedPaste,
+                Show
+            );
+        }
+        default_hook(info);
+    }));
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let app = Arc::new(Mutex::new(App::new()));
+    // Errors end the app too, but only once the terminal is usable again.
+    let result = run_app(&mut terminal, kiosk);
+    restore_terminal(&mut terminal, keyboard_enhancement)?;
+    match result {
+        Ok(summary) => {
+            for line in summary {
+                println!("{}", line);
+            }
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Runs the TUI until quit; returns what to print once the terminal is restored.
+fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    kiosk: bool,
+) -> io::Result<Vec<String>> {
+    let app = loop {
+        match App::new() {
+            Ok(app) => break app,
+            Err(e) => {
+                if !llm_setup_screen(terminal, &e)? {
+                    return Err(io::Error::other(e));
+                }
+            }
+        }
+    };
+    let app = Arc::new(Mutex::new(app));
+    let fifo_app = Arc::clone(&app);
+    {
+        let mut app = app.lock().unwrap();
+        if kiosk {
+            app.enter_kiosk_mode();
+        } else {
+            app.offer_draft_restore();
+            if !app.background_paused() {
+                app.sync_library();
+            }
+            if let Some(job) = app.digest_job() {
+                job.spawn_periodic(Duration::from_secs(app.config.digest_interval_minutes * 60));
+            }
+            if let Some(path) = app.config.fifo.clone() {
+                let tx = app.remote_command_tx.clone();
+                thread::spawn(move || {
+                    if let Err(e) = remote::listen_on_fifo(&path, tx) {
+                        fifo_app
+                            .lock()
+                            .unwrap()
+                            .set_feedback(e, FeedbackType::Negative);
+                    }
+                });
+            }
+        }
+    }
     let app_clone = Arc::clone(&app);
     let server_running = Arc::clone(&app.lock().unwrap().server_running);
+    let server_stats = Arc::clone(&app.lock().unwrap().server_stats);
 
-    thread::spawn(move || {
-        let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
-        server_running.store(true, Ordering::SeqCst);
+    let (server_address, allow_network) = {
+        let app = app.lock().unwrap();
+        (
+            Some(app.config.server_address()).filter(|_| app.config.server_enabled),
+            app.config.server_allow_network,
+        )
+    };
+    if let Some(address) = server_address.filter(|_| !kiosk) {
+        thread::spawn(move || {
+            // Anyone who can connect can send prompts, so only this machine may
+            // unless the config says otherwise.
+            let loopback = remote::is_loopback(&address);
+            if !loopback && !allow_network {
+                let error = format!(
+                    "Remote server not started: {} is reachable from other machines; set allow_network = true under [server] to allow it",
+                    address
+                );
+                server_stats.record_error(error.clone());
+                app_clone
+                    .lock()
+                    .unwrap()
+                    .set_feedback(error, FeedbackType::Negative);
+                return;
+            }
+            let listener = match TcpListener::bind(&address) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    let error = format!("Remote server couldn't listen on {}: {}", address, e);
+                    server_stats.record_error(error.clone());
+                    app_clone
+                        .lock()
+                        .unwrap()
+                        .set_feedback(error, FeedbackType::Negative);
+                    return;
+                }
+            };
+            server_running.store(true, Ordering::SeqCst);
+            if !loopback {
+                app_clone.lock().unwrap().set_feedback(
+                    format!(
+                        "Warning: remote server listening on {}; anyone who can reach it can send prompts without authentication",
+                        address
+                    ),
+                    FeedbackType::Negative,
+                );
+            }
 
-        for stream in listener.incoming() {
-            let stream = stream.unwrap();
-            let tx = app_clone.lock().unwrap().remote_command_tx.clone();
-            thread::spawn(move || {
-                handle_client(stream, tx);
-            });
-        }
-    });
+            for stream in listener.incoming().flatten() {
+                let tx = app_clone.lock().unwrap().remote_command_tx.clone();
+                let stats = Arc::clone(&server_stats);
+                let app = Arc::clone(&app_clone);
+                thread::spawn(move || {
+                    remote::handle_client(stream, tx, &stats, |e| {
+                        app.lock().unwrap().set_feedback(
+                            format!("Remote request failed: {}", e),
+                            FeedbackType::Negative,
+                        )
+                    });
+                });
+            }
+        });
+    }
 
     loop {
-        {
+        let pager_request = app.lock().unwrap().pager_request.take();
+        if let Some(text) = pager_request {
+            let result = run_pager(terminal, &text);
+            let mut app = app.lock().unwrap();
+            if let Err(e) = result {
+                app.set_feedback(
+                    format!("Failed to open pager: {}", e),
+                    FeedbackType::Negative,
+                );
+            }
+            app.needs_redraw = true;
+        }
+        let poll_interval = {
             let mut app = app.lock().unwrap();
             app.update_feedback();
+            app.autosave_draft();
+            app.update_completion();
             app.check_for_response();
+            app.check_background_events();
             app.handle_remote_command();
-            terminal.draw(|f| ui(f, &mut *app))?;
-        }
+            let idle = app.update_idle();
+            let refresh = if idle {
+                IDLE_REFRESH_INTERVAL
+            } else {
+                IDLE_REDRAW_INTERVAL
+            };
+            let since_draw = app.last_draw.elapsed();
+            if (app.needs_redraw && since_draw >= FRAME_INTERVAL) || since_draw >= refresh {
+                terminal.draw(|f| ui(f, &mut *app))?;
+                app.needs_redraw = false;
+                app.last_draw = Instant::now();
+            }
+            app.poll_interval(idle)
+        };
 
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
+        if event::poll(poll_interval)? {
+            let event = event::read()?;
+            app.lock().unwrap().last_input = Instant::now();
+            if let Event::Paste(text) = &event {
+                app.lock().unwrap().paste(text);
+            }
+            if let Event::Key(key) = event {
+                // Windows reports key releases too; only presses are handled.
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
                 let mut app = app.lock().unwrap();
+                app.needs_redraw = true;
+                if let Some(menu) = app.action_menu.as_mut() {
+                    match key.code {
+                        KeyCode::Char('j') | KeyCode::Down => menu.next(),
+                        KeyCode::Char('k') | KeyCode::Up => menu.previous(),
+                        KeyCode::Enter => {
+                            let action = menu.selected();
+                            app.action_menu = None;
+                            app.run_message_action(action);
+                        }
+                        KeyCode::Esc | KeyCode::Char('q') => app.action_menu = None,
+                        KeyCode::Char(c) => {
+                            if let Some(action) = menu.actions.iter().find(|a| a.key() == c) {
+                                let action = *action;
+                                app.action_menu = None;
+                                app.run_message_action(action);
+                            }
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let Some(panel) = app.panel.as_mut() {
+                    match key.code {
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            panel.scroll = panel.scroll.saturating_add(1)
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            panel.scroll = panel.scroll.saturating_sub(1)
+                        }
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Enter => app.panel = None,
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.time_travel.is_some() {
+                    match key.code {
+                        KeyCode::Char('[') | KeyCode::Left => app.move_time_travel(-1),
+                        KeyCode::Char(']') | KeyCode::Right => app.move_time_travel(1),
+                        KeyCode::Char('k') | KeyCode::Up => app.scroll_time_travel(1),
+                        KeyCode::Char('j') | KeyCode::Down => app.scroll_time_travel(-1),
+                        KeyCode::Home => app.move_time_travel(isize::MIN),
+                        KeyCode::End => app.move_time_travel(isize::MAX),
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('T') => {
+                            app.time_travel = None
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.composer.is_some() {
+                    match key.code {
+                        KeyCode::Char('j') | KeyCode::Down => app.move_composer(1),
+                        KeyCode::Char('k') | KeyCode::Up => app.move_composer(-1),
+                        KeyCode::Char(' ') => app.toggle_composer_hidden(),
+                        KeyCode::Char('p') => {
+                            if let Some(selected) = app.composer_selected() {
+                                app.toggle_pin(selected);
+                            }
+                        }
+                        KeyCode::Char('J') => app.move_pin(1),
+                        KeyCode::Char('K') => app.move_pin(-1),
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('C') => {
+                            app.composer = None
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.confirm.is_some() {
+                    match key.code {
+                        KeyCode::Enter | KeyCode::Char('y') => app.confirm_action(),
+                        KeyCode::Esc | KeyCode::Char('n') => {
+                            match app.confirm.take().map(|confirm| confirm.action) {
+                                Some(ConfirmAction::SendRemote(_)) => app.review_remote(),
+                                Some(ConfirmAction::SmartPaste(text)) => app.input.push_str(&text),
+                                _ => {}
+                            }
+                        }
+                        KeyCode::Char(c @ '1'..='9') => {
+                            if let Some(Confirm {
+                                action: ConfirmAction::SmartPaste(text),
+                                ..
+                            }) = app.confirm.take()
+                            {
+                                app.smart_paste(&text, Some(c as usize - '1' as usize));
+                            }
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let Some(prompt) = app.prompt.as_mut() {
+                    match key.code {
+                        KeyCode::Enter => app.submit_prompt(),
+                        KeyCode::Esc => app.prompt = None,
+                        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            if let PromptKind::Find { regex } = &mut prompt.kind {
+                                *regex = !*regex;
+                            }
+                        }
+                        KeyCode::Char(c) => prompt.input.push(c),
+                        KeyCode::Backspace => {
+                            prompt.input.pop();
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let Some(selected) = app.code_picker {
+                    let count = app.selected_message_ref().map_or(0, |message| {
+                        code_blocks::code_blocks(&message.content).len()
+                    });
+                    match key.code {
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            app.code_picker = Some((selected + 1) % count.max(1))
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            app.code_picker = Some((selected + count.max(1) - 1) % count.max(1))
+                        }
+                        KeyCode::Enter | KeyCode::Char('c') => app.copy_picked_code_block(selected),
+                        KeyCode::Char(c @ '1'..='9') if (c as usize - '0' as usize) <= count => {
+                            app.copy_picked_code_block(c as usize - '1' as usize)
+                        }
+                        KeyCode::Esc | KeyCode::Char('q') => app.code_picker = None,
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let Some(search) = app.global_search.as_mut() {
+                    let count = search.hits.len();
+                    match key.code {
+                        KeyCode::Down if count > 0 => {
+                            search.selected = (search.selected + 1) % count
+                        }
+                        KeyCode::Up if count > 0 => {
+                            search.selected = (search.selected + count - 1) % count
+                        }
+                        KeyCode::Enter => app.open_search_hit(),
+                        KeyCode::Esc => app.global_search = None,
+                        KeyCode::Backspace => {
+                            search.query.pop();
+                            app.update_global_search();
+                        }
+                        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            search.query.push(c);
+                            app.update_global_search();
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let Some((templates, selected)) = &mut app.template_picker {
+                    let count = templates.len();
+                    match key.code {
+                        KeyCode::Char('j') | KeyCode::Down => *selected = (*selected + 1) % count,
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            *selected = (*selected + count - 1) % count
+                        }
+                        KeyCode::Enter => {
+                            let name = templates[*selected].0.clone();
+                            app.use_template(&name);
+                        }
+                        KeyCode::Esc | KeyCode::Char('q') => app.template_picker = None,
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let (Some(scroll), Some(index)) = (app.plain, app.current_conversation_index) {
+                    let page = terminal.size()?.height.saturating_sub(1);
+                    app.plain = Some(match key.code {
+                        KeyCode::Char('j') | KeyCode::Down => scroll.saturating_add(1),
+                        KeyCode::Char('k') | KeyCode::Up => scroll.saturating_sub(1),
+                        KeyCode::PageDown | KeyCode::Char(' ') => scroll.saturating_add(page),
+                        KeyCode::PageUp => scroll.saturating_sub(page),
+                        KeyCode::Char('g') | KeyCode::Home => 0,
+                        KeyCode::Char('G') | KeyCode::End => u16::MAX,
+                        KeyCode::Char('y') => {
+                            app.copy_async(app.transcript(index), "Transcript copied!".to_string());
+                            scroll
+                        }
+                        KeyCode::Esc | KeyCode::Char('q') => {
+                            app.plain = None;
+                            continue;
+                        }
+                        _ => scroll,
+                    });
+                    continue;
+                }
+                if let Some(row) = app.model_options_row {
+                    let count = app.current_conversation_index.map_or(0, |index| {
+                        app.conversations[index].preferences.options.len()
+                    });
+                    match key.code {
+                        KeyCode::Char('j') | KeyCode::Down if count > 0 => {
+                            app.model_options_row = Some((row + 1) % count)
+                        }
+                        KeyCode::Char('k') | KeyCode::Up if count > 0 => {
+                            app.model_options_row = Some((row + count - 1) % count)
+                        }
+                        KeyCode::Enter => app.edit_model_option(false),
+                        KeyCode::Char('a') => app.edit_model_option(true),
+                        KeyCode::Char('d') | KeyCode::Backspace => app.remove_model_option(),
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('o') => {
+                            app.model_options_row = None
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if let Some(row) = app.preferences_row {
+                    match key.code {
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            app.preferences_row = Some((row + 1) % preferences::FIELDS.len())
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            app.preferences_row = Some(
+                                (row + preferences::FIELDS.len() - 1) % preferences::FIELDS.len(),
+                            )
+                        }
+                        KeyCode::Enter | KeyCode::Char(' ') => app.edit_preference(),
+                        KeyCode::Char('d') | KeyCode::Backspace => {
+                            app.set_preference(preferences::FIELDS[row], "")
+                        }
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('L') => {
+                            app.preferences_row = None
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.list_searching && matches!(app.focused_block, FocusedBlock::ConversationList)
+                {
+                    match key.code {
+                        KeyCode::Down => app.next_conversation(),
+                        KeyCode::Up => app.previous_conversation(),
+                        KeyCode::Enter => {
+                            app.list_searching = false;
+                            if let Some(position) = app.conversation_list_state.selected() {
+                                app.select_visible_conversation(Some(position));
+                                app.focused_block = FocusedBlock::Chat;
+                            }
+                        }
+                        KeyCode::Esc => {
+                            app.list_searching = false;
+                            app.list_query.clear();
+                            app.sync_conversation_list();
+                        }
+                        KeyCode::Backspace => {
+                            let mut query = app.list_query.clone();
+                            query.pop();
+                            app.set_list_query(query);
+                        }
+                        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            let query = format!("{}{}", app.list_query, c);
+                            app.set_list_query(query);
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.chat_searching && matches!(app.focused_block, FocusedBlock::Chat) {
+                    match key.code {
+                        KeyCode::Enter => {
+                            app.chat_searching = false;
+                            app.step_chat_match(true, true);
+                        }
+                        KeyCode::Esc => {
+                            app.chat_searching = false;
+                            app.chat_query.clear();
+                        }
+                        KeyCode::Backspace => {
+                            let mut query = app.chat_query.clone();
+                            query.pop();
+                            app.set_chat_query(query);
+                        }
+                        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            let query = format!("{}{}", app.chat_query, c);
+                            app.set_chat_query(query);
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.model_searching && matches!(app.focused_block, FocusedBlock::ModelSelect) {
+                    match key.code {
+                        KeyCode::Down => app.next_model(),
+                        KeyCode::Up => app.previous_model(),
+                        KeyCode::Enter => app.model_searching = false,
+                        KeyCode::Esc => {
+                            app.model_searching = false;
+                            app.model_query.clear();
+                        }
+                        KeyCode::Backspace => {
+                            let mut query = app.model_query.clone();
+                            query.pop();
+                            app.set_model_query(query);
+                        }
+                        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            let query = format!("{}{}", app.model_query, c);
+                            app.set_model_query(query);
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                let cancel = match key.code {
+                    KeyCode::Char('c') => key.modifiers.contains(KeyModifiers::CONTROL),
+                    KeyCode::Esc => {
+                        !matches!(app.input_mode, InputMode::Editing)
+                            && app.chat_state.marked.is_empty()
+                    }
+                    _ => false,
+                };
+                if cancel && app.cancel_request() {
+                    continue;
+                }
+                if app.kiosk {
+                    if key.code == KeyCode::Char('q')
+                        && key.modifiers.contains(KeyModifiers::CONTROL)
+                    {
+                        break;
+                    }
+                    if !app.kiosk_allows(&key) {
+                        continue;
+                    }
+                }
+                if key.code == KeyCode::Char('f') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    app.open_global_search("");
+                    continue;
+                }
+                if key.modifiers.contains(KeyModifiers::CONTROL) && app.config.follow_activity {
+                    match key.code {
+                        KeyCode::Char('o') => {
+                            app.jump_to_activity();
+                            continue;
+                        }
+                        KeyCode::Char('b') => {
+                            app.jump_back();
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
+                let typing = matches!(app.focused_block, FocusedBlock::Input)
+                    && matches!(app.input_mode, InputMode::Editing);
+                let key = if typing || app.kiosk {
+                    key
+                } else {
+                    match app.config.keys.translate(key) {
+                        Some(key) => key,
+                        None => continue,
+                    }
+                };
                 match app.focused_block {
                     FocusedBlock::ConversationList => match key.code {
                         KeyCode::Char('j') | KeyCode::Down => app.next_conversation(),
                         KeyCode::Char('k') | KeyCode::Up => app.previous_conversation(),
                         KeyCode::Enter => {
-                            app.current_conversation_index = app.conversation_list_state.selected();
+                            let position = app.conversation_list_state.selected();
+                            app.select_visible_conversation(position);
                             app.focused_block = FocusedBlock::Chat;
                         }
+                        KeyCode::Char('/') => app.list_searching = true,
+                        KeyCode::Esc if !app.list_query.is_empty() => {
+                            app.list_query.clear();
+                            app.sync_conversation_list();
+                        }
+                        KeyCode::Char('f') => app.toggle_project_filter(),
+                        KeyCode::Char('s') => {
+                            app.open_highlighted_conversation();
+                            app.summarize_conversation()
+                        }
+                        KeyCode::Char('t') => {
+                            let tag = app.tag_filter.clone().unwrap_or_default();
+                            app.open_prompt(PromptKind::TagFilter, tag)
+                        }
+                        KeyCode::Char('c') => {
+                            app.open_highlighted_conversation();
+                            app.enter_conversation_directory()
+                        }
+                        KeyCode::Char('d') => app.confirm_delete_conversation(),
+                        KeyCode::Char('r') => app.open_rename_prompt(),
+                        KeyCode::Char('o') => app.cycle_list_sort(),
                         KeyCode::Char('n') => {
                             app.start_new_conversation();
                             app.focused_block = FocusedBlock::Input;
@@ -456,63 +7015,145 @@ fn main() -> Result<(), io::Error> {
                         KeyCode::Tab => app.next_focus(),
                         KeyCode::Char('h') => app.toggle_conversation_list(),
                         KeyCode::Char('i') => {
+                            app.sync_conversation_list();
                             app.focused_block = FocusedBlock::Input;
                             app.input_mode = InputMode::Editing;
                         }
+                        KeyCode::Char('P') => app.toggle_project_mode(),
+                        KeyCode::Char('D') => app.toggle_do_not_disturb(),
+                        KeyCode::Char('A') => app.review_remote(),
+                        KeyCode::Char('Z') => app.toggle_background_pause(),
                         KeyCode::Char('q') => break,
                         _ => {}
                     },
                     FocusedBlock::ModelSelect => match key.code {
                         KeyCode::Char('j') | KeyCode::Down => app.next_model(),
                         KeyCode::Char('k') | KeyCode::Up => app.previous_model(),
+                        KeyCode::Char('o') => app.open_model_options(),
+                        KeyCode::Char('/') => app.model_searching = true,
+                        KeyCode::Esc if !app.model_query.is_empty() => app.model_query.clear(),
                         KeyCode::Tab => app.next_focus(),
                         KeyCode::Char('h') => app.toggle_conversation_list(),
                         KeyCode::Char('i') => {
                             app.focused_block = FocusedBlock::Input;
                             app.input_mode = InputMode::Editing;
                         }
+                        KeyCode::Char('P') => app.toggle_project_mode(),
+                        KeyCode::Char('D') => app.toggle_do_not_disturb(),
+                        KeyCode::Char('A') => app.review_remote(),
+                        KeyCode::Char('Z') => app.toggle_background_pause(),
                         KeyCode::Char('q') => break,
                         _ => {}
                     },
                     FocusedBlock::Chat => match key.code {
                         KeyCode::Tab => app.next_focus(),
+                        KeyCode::Char('h') | KeyCode::Left if app.is_panning_code() => {
+                            app.pan_code(-CODE_PAN_STEP)
+                        }
+                        KeyCode::Char('l') | KeyCode::Right if app.is_panning_code() => {
+                            app.pan_code(CODE_PAN_STEP)
+                        }
                         KeyCode::Char('h') => app.toggle_conversation_list(),
                         KeyCode::Char('j') | KeyCode::Down => app.next_message(),
                         KeyCode::Char('k') | KeyCode::Up => app.previous_message(),
+                        KeyCode::Char('w') => app.toggle_code_wrap(),
+                        KeyCode::Char('#') => app.show_line_numbers = !app.show_line_numbers,
+                        KeyCode::Char('Y') => app.open_prompt(PromptKind::CopyLines, String::new()),
+                        KeyCode::Char('S') => {
+                            app.open_prompt(PromptKind::SaveCodeBlocks, ".".to_string())
+                        }
+                        KeyCode::Char('x') => app.confirm_run_code(),
+                        KeyCode::Char('c') => app.copy_code_block(),
                         KeyCode::Char('i') => {
                             app.focused_block = FocusedBlock::Input;
                             app.input_mode = InputMode::Editing;
                         }
-                        KeyCode::Char('y') => match app.copy_selected_message_to_clipboard() {
-                            Ok(_) => {
-                                app.set_feedback(
-                                    "Message copied successfully!".to_string(),
-                                    FeedbackType::Positive,
-                                );
-                            }
-                            Err(e) => {
-                                app.set_feedback(
-                                    format!("Failed to copy: {}", e),
-                                    FeedbackType::Negative,
-                                );
+                        KeyCode::Char('y') if !app.chat_state.marked.is_empty() => {
+                            app.run_message_action(MessageAction::CopySelection)
+                        }
+                        KeyCode::Char('y') => app.run_message_action(MessageAction::Copy),
+                        KeyCode::Char(' ') => app.toggle_mark(),
+                        KeyCode::Char('C') => app.open_composer(),
+                        KeyCode::Char('T') => app.open_time_travel(),
+                        KeyCode::Char('L') => app.open_preferences(),
+                        KeyCode::Char(c @ '1'..='9') => {
+                            app.pick_suggestion(c as usize - '0' as usize)
+                        }
+                        KeyCode::Char(c) if REACTIONS.iter().any(|(key, _, _)| *key == c) => {
+                            if let Some((_, name, _)) =
+                                REACTIONS.iter().find(|(key, _, _)| *key == c)
+                            {
+                                app.toggle_reaction(name);
                             }
-                        },
+                        }
+                        KeyCode::Char('o') => app.run_message_action(MessageAction::OpenInPager),
+                        KeyCode::Char('z') => app.pinned_expanded = !app.pinned_expanded,
+                        KeyCode::Char('G') => app.scroll_to_bottom(),
+                        KeyCode::Char('R') => app.toggle_raw(),
+                        KeyCode::Char('M') => app.toggle_raw_all(),
+                        KeyCode::Char('V') => app.compact = !app.compact,
+                        KeyCode::Char('/') => {
+                            app.chat_query.clear();
+                            app.chat_searching = true;
+                        }
+                        KeyCode::Char('n') => app.step_chat_match(true, false),
+                        KeyCode::Char('N') => app.step_chat_match(false, false),
+                        KeyCode::Esc if !app.chat_query.is_empty() => app.chat_query.clear(),
+                        KeyCode::Esc => app.chat_state.marked.clear(),
+                        KeyCode::Enter | KeyCode::Char('.') => app.open_action_menu(),
+                        KeyCode::Char('P') => app.toggle_project_mode(),
+                        KeyCode::Char('D') => app.toggle_do_not_disturb(),
+                        KeyCode::Char('A') => app.review_remote(),
+                        KeyCode::Char('Z') => app.toggle_background_pause(),
                         KeyCode::Char('q') => break,
                         _ => {}
                     },
                     FocusedBlock::Input => match app.input_mode {
                         InputMode::Normal => match key.code {
                             KeyCode::Char('i') => app.input_mode = InputMode::Editing,
+                            KeyCode::Char('u') => {
+                                app.input.undo();
+                            }
+                            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.input.redo();
+                            }
                             KeyCode::Tab => app.next_focus(),
                             KeyCode::Char('h') => app.toggle_conversation_list(),
+                            KeyCode::Char('P') => app.toggle_project_mode(),
+                            KeyCode::Char('D') => app.toggle_do_not_disturb(),
+                            KeyCode::Char('A') => app.review_remote(),
+                            KeyCode::Char('Z') => app.toggle_background_pause(),
                             KeyCode::Char('q') => break,
                             _ => {}
                         },
                         InputMode::Editing => match key.code {
+                            // Shift+Enter only reaches us from terminals that report it.
+                            KeyCode::Enter
+                                if key
+                                    .modifiers
+                                    .intersects(KeyModifiers::ALT | KeyModifiers::SHIFT) =>
+                            {
+                                app.input.push('\n');
+                            }
                             KeyCode::Enter => {
                                 app.send_message();
                                 app.input_mode = InputMode::Normal;
                             }
+                            KeyCode::Char('h') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.open_prompt(PromptKind::Find { regex: false }, String::new())
+                            }
+                            KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.input.undo();
+                            }
+                            KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.input.redo();
+                            }
+                            KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.paste_from_clipboard();
+                            }
+                            KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                                app.preview_input();
+                            }
                             KeyCode::Char(c) => {
                                 app.input.push(c);
                             }
@@ -522,6 +7163,10 @@ fn main() -> Result<(), io::Error> {
                             KeyCode::Esc => {
                                 app.input_mode = InputMode::Normal;
                             }
+                            KeyCode::Tab if app.complete_mention() => {}
+                            KeyCode::Tab if app.completion.suggestion.is_some() => {
+                                app.accept_completion();
+                            }
                             KeyCode::Tab => {
                                 app.input_mode = InputMode::Normal;
                                 app.next_focus();
@@ -534,18 +7179,128 @@ fn main() -> Result<(), io::Error> {
         }
     }
 
+    // A clean exit doesn't need crash recovery.
+    let _ = store::remove(DRAFT_FILE);
+    let app = app.lock().unwrap();
+    if !app.config.quit_summary || app.kiosk {
+        return Ok(Vec::new());
+    }
+    Ok(app.session.summary(|model| app.model_price(model)))
+}
+
+fn restore_terminal(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    keyboard_enhancement: bool,
+) -> io::Result<()> {
+    if keyboard_enhancement {
+        execute!(terminal.backend_mut(), PopKeyboardEnhancementFlags)?;
+    }
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
-    terminal.show_cursor()?;
+    terminal.show_cursor()
+}
 
-    Ok(())
+// Explains how to set up llm when it can't be run at startup. Returns whether to
+// try again (`r`) rather than quit (`q`).
+fn llm_setup_screen(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    error: &str,
+) -> io::Result<bool> {
+    let text = vec![
+        Line::styled(
+            "llm-tui couldn't start llm",
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Line::default(),
+        Line::styled(format!("  {}", error), Style::default().fg(Color::Red)),
+        Line::default(),
+        Line::raw("llm-tui talks to models through the llm command (https://llm.datasette.io)."),
+        Line::raw("To set it up:"),
+        Line::default(),
+        Line::raw("  1. Install it:             pipx install llm   (or pip install llm, brew install llm)"),
+        Line::raw("  2. Add an API key:         llm keys set openai"),
+        Line::raw("     or a local model:       llm install llm-ollama"),
+        Line::raw("  3. Check that it answers:  llm 'Say hello'"),
+        Line::default(),
+        Line::raw("If llm is installed outside the PATH, set LLM_TUI_LLM to its full path."),
+        Line::default(),
+        Line::styled("r: Retry | q: Quit", Style::default().fg(Color::Cyan)),
+    ];
+    loop {
+        terminal.draw(|f| {
+            let widget = Paragraph::new(text.clone())
+                .wrap(Wrap { trim: false })
+                .block(Block::default().borders(Borders::ALL).title("Setup"));
+            f.render_widget(widget, f.area());
+        })?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('r') | KeyCode::Enter => return Ok(true),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(false)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// `more` is a `.com` program, which `Command` doesn't find by its bare name.
+const DEFAULT_PAGER: &str = if cfg!(windows) { "more.com" } else { "less" };
+
+// Hands the terminal to `$PAGER` (or `less`, `more` on Windows) to show `text`, then
+// takes it back.
+fn run_pager(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, text: &str) -> io::Result<()> {
+    let pager = env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string());
+    let mut words = pager.split_whitespace();
+    let program = words.next().unwrap_or(DEFAULT_PAGER);
+
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste
+    )?;
+    let result = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            if let Some(mut stdin) = child.stdin.take() {
+                // The pager may quit before reading everything.
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            child.wait()
+        });
+    enable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
+    terminal.clear()?;
+    result.map(|_| ())
 }
 
 fn ui(f: &mut Frame, app: &mut App) {
+    if let (Some(scroll), Some(index)) = (app.plain, app.current_conversation_index) {
+        app.plain = Some(render_plain(f, &app.transcript(index), scroll));
+        return;
+    }
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
@@ -579,9 +7334,14 @@ fn ui(f: &mut Frame, app: &mut App) {
         main_chunks[0]
     };
 
+    // The input grows with its text, up to `MAX_INPUT_LINES`.
+    let input_height = input_lines(&app.input, right_area.width.saturating_sub(2) as usize)
+        .len()
+        .min(MAX_INPUT_LINES) as u16
+        + 2;
     let right_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(0), Constraint::Length(3)].as_ref())
+        .constraints([Constraint::Min(0), Constraint::Length(input_height)].as_ref())
         .split(right_area);
 
     render_chat(f, app, right_chunks[0]);
@@ -589,81 +7349,804 @@ fn ui(f: &mut Frame, app: &mut App) {
     render_status(f, app, chunks[1]);
 
     if let Some(feedback) = &app.feedback {
-        let feedback_color = match feedback.feedback_type {
-            FeedbackType::Positive => Color::Green,
-            FeedbackType::Negative => Color::Red,
+        let feedback_style = match feedback.feedback_type {
+            FeedbackType::Positive => app.config.theme.positive,
+            FeedbackType::Negative => app.config.theme.negative,
         };
         let feedback_widget = Paragraph::new(feedback.message.as_str())
-            .style(Style::default().fg(feedback_color))
+            .style(feedback_style)
             .block(Block::default().borders(Borders::ALL).title("Feedback"));
+        f.render_widget(Clear, chunks[1]);
         f.render_widget(feedback_widget, chunks[1]);
     }
+
+    if let (Some(row), Some(index)) = (app.preferences_row, app.current_conversation_index) {
+        render_preferences(
+            f,
+            &app.conversations[index].preferences,
+            row,
+            &app.config.theme,
+            f.area(),
+        );
+    }
+    if let Some((templates, selected)) = &app.template_picker {
+        render_template_picker(f, templates, *selected, &app.config.theme, f.area());
+    }
+    if let Some(search) = &app.global_search {
+        render_global_search(f, app, search, f.area());
+    }
+    if let (Some(row), Some(index)) = (app.model_options_row, app.current_conversation_index) {
+        render_model_options(
+            f,
+            &app.conversations[index].preferences.options,
+            row,
+            &app.config.theme,
+            f.area(),
+        );
+    }
+    if let Some(prompt) = &app.prompt {
+        render_prompt(f, prompt, &app.config.theme, f.area());
+    }
+    if let Some(confirm) = &app.confirm {
+        render_confirm(f, confirm, &app.config.theme, f.area());
+    }
+    if let Some(menu) = &mut app.action_menu {
+        render_action_menu(f, menu, &app.config.theme, f.area());
+    }
+    if let (Some(selected), Some(message)) = (app.code_picker, app.selected_message_ref()) {
+        render_code_picker(
+            f,
+            &code_blocks::code_blocks(&message.content),
+            selected,
+            &app.config.theme,
+            f.area(),
+        );
+    }
+    if app.composer.is_some() {
+        render_composer(f, app, f.area());
+    }
+    if let Some(travel) = &app.time_travel {
+        render_time_travel(
+            f,
+            travel,
+            app.show_line_numbers,
+            app.config.highlight_theme.as_deref(),
+            &app.config.theme,
+            f.area(),
+        );
+    }
+    if let Some(panel) = &app.panel {
+        render_panel(f, panel, f.area());
+    }
+}
+
+fn render_panel(f: &mut Frame, panel: &Panel, area: Rect) {
+    let height = (panel.lines.len() as u16 + 2).min(area.height.saturating_sub(4));
+    let area = centered_rect(area.width.saturating_sub(4).min(100), height, area);
+    let text: Vec<Line> = panel
+        .lines
+        .iter()
+        .map(|line| Line::raw(line.as_str()))
+        .collect();
+    let widget = Paragraph::new(text)
+        .wrap(Wrap { trim: false })
+        .scroll((panel.scroll, 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(panel.title.as_str())
+                .border_style(Style::default().fg(Color::Cyan)),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(widget, area);
+}
+
+// Shows the logged exchanges up to the chosen one, with the model and system prompt
+// each answer was produced with.
+fn render_time_travel(
+    f: &mut Frame,
+    travel: &TimeTravel,
+    line_numbers: bool,
+    highlight_theme: Option<&str>,
+    theme: &Theme,
+    area: Rect,
+) {
+    let area = centered_rect(
+        area.width.saturating_sub(8).min(120),
+        area.height.saturating_sub(4),
+        area,
+    );
+    let width = (area.width as usize).saturating_sub(4).max(1);
+    let meta_style = Style::default().fg(Color::DarkGray);
+    let mut lines: Vec<Line> = Vec::new();
+    for (i, exchange) in travel.exchanges[..travel.position].iter().enumerate() {
+        let mut header = format!(
+            "── #{} {} · {}",
+            i + 1,
+            format_log_time(&exchange.datetime),
+            exchange.model
+        );
+        let key = |role: &str| {
+            exchange
+                .log_id
+                .as_ref()
+                .map_or(String::new(), |id| format!("{}/{}", id, role))
+        };
+        let now: Vec<&str> = ["user", "assistant"]
+            .iter()
+            .filter_map(|role| travel.overrides[key(role).as_str()].as_str())
+            .collect();
+        if !now.is_empty() {
+            header.push_str(&format!(" · {} now", now.join("/")));
+        }
+        lines.push(Line::styled(header, meta_style));
+        if let Some(system) = &exchange.system {
+            lines.push(Line::styled(format!("System: {}", system), meta_style));
+        }
+        for (role, content, style) in [
+            ("user", &exchange.prompt, theme.user),
+            ("assistant", &exchange.response, theme.assistant),
+        ] {
+            let message = Message::new(role, content.clone());
+            lines.extend(message_lines(
+                &message,
+                &theme.prefix(role, Some(&exchange.model)),
+                style,
+                width,
+                MessageView {
+                    wrap_code: true,
+                    line_numbers,
+                    raw: false,
+                    highlight_theme,
+                },
+            ));
+        }
+        lines.push(Line::default());
+    }
+
+    let height = area.height.saturating_sub(2) as usize;
+    let bottom = lines.len().saturating_sub(height);
+    let offset = bottom.saturating_sub(travel.scroll);
+    let current = &travel.exchanges[travel.position - 1];
+    let title = format!(
+        "Time travel: '{}' as of {} ({}/{}) | [/]: Earlier/Later | j/k: Scroll | Esc: Close",
+        travel.name,
+        format_log_time(&current.datetime),
+        travel.position,
+        travel.exchanges.len()
+    );
+    let widget = Paragraph::new(lines).scroll((offset as u16, 0)).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_style(Style::default().fg(Color::Magenta)),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(widget, area);
+}
+
+// `2024-08-12T10:11:12.123456` as `2024-08-12 10:11:12`.
+fn format_log_time(datetime: &str) -> String {
+    datetime
+        .chars()
+        .take(19)
+        .collect::<String>()
+        .replace('T', " ")
+}
+
+// Lists every message of the conversation with whether it will be sent with the
+// next prompt, given hidden/pinned messages and the model's context window.
+fn render_composer(f: &mut Frame, app: &mut App, area: Rect) {
+    let Some(index) = app.current_conversation_index else {
+        return;
+    };
+    let messages = &app.conversations[index].messages;
+    let model = app.conversations[index].model.as_deref();
+    let counter = app.token_counter();
+    let plan = context::plan(messages, app.context_limit(), &counter);
+    let approx = if counter.is_exact() { "" } else { "~" };
+    let width = area.width.saturating_sub(8).min(110);
+    let area = centered_rect(width, area.height.saturating_sub(4), area);
+    let preview_width = (width as usize).saturating_sub(32);
+
+    let items: Vec<ListItem> = messages
+        .iter()
+        .enumerate()
+        .map(|(i, message)| {
+            let (label, color) = match plan.inclusion[i] {
+                context::Inclusion::Pinned => (
+                    format!("pin {}", message.pin.unwrap_or(0) + 1),
+                    Color::Magenta,
+                ),
+                context::Inclusion::Included => ("sent".to_string(), Color::Green),
+                context::Inclusion::Hidden => ("hidden".to_string(), Color::DarkGray),
+                context::Inclusion::Dropped => ("dropped".to_string(), Color::Red),
+            };
+            let speaker = app.config.theme.prefix(&message.role, model);
+            let preview: String = message
+                .content
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .chars()
+                .take(preview_width)
+                .collect();
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:<8}", label), Style::default().fg(color)),
+                Span::raw(format!("{:>7} tok  ", plan.tokens[i])),
+                Span::styled(speaker, Style::default().fg(Color::Yellow)),
+                Span::raw(preview),
+            ]))
+        })
+        .collect();
+
+    let title = format!(
+        "Context: {}{} / {} tokens | Space: Include/Exclude | p: Pin | J/K: Move Pin | Esc: Close",
+        approx, plan.used, plan.limit
+    );
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(app.config.theme.focus),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_widget(Clear, area);
+    if let Some(state) = app.composer.as_mut() {
+        f.render_stateful_widget(list, area, state);
+    }
+}
+
+fn render_action_menu(f: &mut Frame, menu: &mut ActionMenu, theme: &Theme, area: Rect) {
+    let area = centered_rect(40, menu.actions.len() as u16 + 2, area);
+    let items: Vec<ListItem> = menu
+        .actions
+        .iter()
+        .map(|action| ListItem::new(format!("{}  {}", action.key(), action.label())))
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Actions")
+                .border_style(theme.focus),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut menu.state);
+}
+
+fn render_code_picker(
+    f: &mut Frame,
+    blocks: &[code_blocks::CodeBlock],
+    selected: usize,
+    theme: &Theme,
+    area: Rect,
+) {
+    let area = centered_rect(
+        area.width.saturating_sub(4).min(70),
+        blocks.len() as u16 + 2,
+        area,
+    );
+    let items: Vec<ListItem> = blocks
+        .iter()
+        .enumerate()
+        .map(|(i, block)| {
+            let first = block
+                .lines
+                .iter()
+                .map(|line| line.trim())
+                .find(|line| !line.is_empty())
+                .unwrap_or_default();
+            ListItem::new(format!(
+                "{}  {:<12}{} ({} lines)",
+                i + 1,
+                block.language().unwrap_or("-"),
+                first,
+                block.lines.len()
+            ))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Copy code block | Enter/1-9: Copy | Esc: Close")
+                .border_style(theme.focus),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_template_picker(
+    f: &mut Frame,
+    templates: &[(String, String)],
+    selected: usize,
+    theme: &Theme,
+    area: Rect,
+) {
+    let area = centered_rect(
+        area.width.saturating_sub(4).min(80),
+        (templates.len() as u16 + 2).min(area.height.saturating_sub(2)),
+        area,
+    );
+    let width = templates
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let items: Vec<ListItem> = templates
+        .iter()
+        .map(|(name, summary)| ListItem::new(format!("{:<width$}  {}", name, summary)))
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Templates | Enter: Use | Esc: Close")
+                .border_style(theme.focus),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_global_search(f: &mut Frame, app: &App, search: &GlobalSearch, area: Rect) {
+    let area = centered_rect(
+        area.width.saturating_sub(4).min(120),
+        area.height.saturating_sub(4),
+        area,
+    );
+    let theme = &app.config.theme;
+    let dim = Style::default().fg(Color::DarkGray);
+    let items: Vec<ListItem> = search
+        .hits
+        .iter()
+        .map(|hit| {
+            let conversation = &app.conversations[hit.conversation];
+            let speaker = theme.prefix(
+                &conversation.messages[hit.message].role,
+                conversation.model.as_deref(),
+            );
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} · {}", conversation.name, speaker), dim),
+                Span::raw(hit.excerpt[..hit.matched.start].to_string()),
+                Span::styled(
+                    hit.excerpt[hit.matched.clone()].to_string(),
+                    theme.focus.add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(hit.excerpt[hit.matched.end..].to_string()),
+            ]))
+        })
+        .collect();
+    let count = if search.hits.len() >= SEARCH_MAX_HITS {
+        format!("{}+ matches", SEARCH_MAX_HITS)
+    } else {
+        format!("{} match(es)", search.hits.len())
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(
+                    "Search all conversations: {}_ | {} | ↑↓ Enter: Open | Esc: Close",
+                    search.query, count
+                ))
+                .border_style(theme.focus),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(Some(search.selected));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_preferences(
+    f: &mut Frame,
+    preferences: &Preferences,
+    row: usize,
+    theme: &Theme,
+    area: Rect,
+) {
+    let area = centered_rect(50, preferences::FIELDS.len() as u16 + 2, area);
+    let items: Vec<ListItem> = preferences::FIELDS
+        .iter()
+        .map(|&field| {
+            let value = preferences.value(field);
+            ListItem::new(format!(
+                "{:<20}{}",
+                field.label(),
+                if value.is_empty() { "-" } else { &value }
+            ))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Response preferences | Enter: Edit | d: Clear | Esc: Close")
+                .border_style(theme.focus),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default();
+    state.select(Some(row));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_model_options(
+    f: &mut Frame,
+    options: &[(String, String)],
+    row: usize,
+    theme: &Theme,
+    area: Rect,
+) {
+    let area = centered_rect(60, options.len().max(1) as u16 + 2, area);
+    let items: Vec<ListItem> = if options.is_empty() {
+        vec![ListItem::new("No options (a: Add, e.g. temperature 0.7)")]
+    } else {
+        options
+            .iter()
+            .map(|(name, value)| ListItem::new(format!("{:<20}{}", name, value)))
+            .collect()
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Model options | Enter: Edit | a: Add | d: Remove | Esc: Close")
+                .border_style(theme.focus),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default();
+    state.select((!options.is_empty()).then_some(row));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+// The transcript as unstyled text filling the screen, for reading, printing or
+// capturing the terminal. Returns `scroll` kept within the text.
+fn render_plain(f: &mut Frame, transcript: &str, scroll: u16) -> u16 {
+    let area = f.area();
+    let width = (area.width as usize).max(1);
+    // Close enough to the wrapped height to stop at the end.
+    let height: usize = transcript
+        .lines()
+        .map(|line| line.chars().count().div_ceil(width).max(1))
+        .sum();
+    let scroll = scroll.min(height.saturating_sub(area.height as usize) as u16);
+    f.render_widget(
+        Paragraph::new(transcript.to_string())
+            .wrap(Wrap { trim: false })
+            .scroll((scroll, 0)),
+        area,
+    );
+    scroll
+}
+
+// A `-a` attachment as shown in the input title and the chat: its file name, or the
+// whole URL.
+fn attachment_label(attachment: &str) -> String {
+    let name = if attachment.contains("://") {
+        attachment
+    } else {
+        Path::new(attachment)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(attachment)
+    };
+    format!("📎 {}", name)
+}
+
+// Messages under their labels from `theme`; one without a label starts with its
+// content (or reactions).
+fn messages_to_markdown(messages: &[&Message], theme: &Theme, model: Option<&str>) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let speaker = match theme.label(&message.role, model) {
+                label if label.is_empty() => String::new(),
+                label => format!("**{}:**", label),
+            };
+            let header = [speaker, message.reaction_emojis()]
+                .into_iter()
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            if header.is_empty() {
+                message.content.clone()
+            } else {
+                format!("{}\n\n{}", header, message.content)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n---\n\n")
+}
+
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+fn render_confirm(f: &mut Frame, confirm: &Confirm, theme: &Theme, area: Rect) {
+    let height = confirm.lines.len() as u16 + 2;
+    let area = centered_rect(area.width.saturating_sub(4).min(90), height, area);
+    let items: Vec<ListItem> = confirm
+        .lines
+        .iter()
+        .map(|line| ListItem::new(line.as_str()))
+        .collect();
+    let list = List::new(items).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(confirm.title.as_str())
+            .border_style(theme.focus),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(list, area);
+}
+
+fn render_prompt(f: &mut Frame, prompt: &Prompt, theme: &Theme, area: Rect) {
+    let area = centered_rect(area.width.saturating_sub(4).min(70), 3, area);
+    let widget = Paragraph::new(prompt.input.as_str())
+        .style(theme.focus)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(prompt.kind.title())
+                .border_style(theme.focus),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(widget, area);
+    f.set_cursor_position(ratatui::layout::Position {
+        x: area.x + prompt.input.width() as u16 + 1,
+        y: area.y + 1,
+    });
 }
 
 fn render_status(f: &mut Frame, app: &App, area: Rect) {
+    let server = server_line(app);
+    let server_width = (server.width() as u16 + 2).clamp(20, area.width / 2);
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(80), Constraint::Percentage(20)].as_ref())
+        .constraints([Constraint::Min(0), Constraint::Length(server_width)].as_ref())
         .split(area);
 
     let status = if let Some(feedback) = &app.feedback {
         // When feedback is present, show only the feedback message
-        let feedback_color = match feedback.feedback_type {
-            FeedbackType::Positive => Color::Green,
-            FeedbackType::Negative => Color::Red,
+        let feedback_style = match feedback.feedback_type {
+            FeedbackType::Positive => app.config.theme.positive,
+            FeedbackType::Negative => app.config.theme.negative,
         };
-        Span::styled(&feedback.message, Style::default().fg(feedback_color))
+        Span::styled(&feedback.message, feedback_style)
+    } else if let Some(reason) = app
+        .current_conversation_index
+        .and_then(|index| app.waiting_reason(index))
+    {
+        Span::styled(
+            format!("{}... (Esc/Ctrl+C: cancel)", reason),
+            Style::default().fg(Color::Yellow),
+        )
     } else if matches!(app.state, AppState::Thinking) {
-        Span::styled("Thinking...", Style::default().fg(Color::Yellow))
+        Span::styled(
+            "Thinking... (Esc/Ctrl+C: cancel)",
+            Style::default().fg(Color::Yellow),
+        )
     } else {
         // When no feedback is present, show the normal status
+        let keys = &app.config.keys;
+        let common = format!(
+            "{}: Edit Input | {}: Next Focus | {}: Toggle List",
+            keys.label(Action::EditInput),
+            keys.label(Action::NextFocus),
+            keys.label(Action::ToggleList)
+        );
         let status_text = match app.focused_block {
-            FocusedBlock::ConversationList => "Conversation List | j/k or ↑↓: Navigate | Enter: Select | n: New Conversation | i: Edit Input | Tab: Next Focus | h: Toggle List",
-            FocusedBlock::ModelSelect => "Model Select | j/k or ↑↓: Change Model | i: Edit Input | Tab: Next Focus | h: Toggle List",
-            FocusedBlock::Chat => "Chat | j/k or ↑↓: Scroll | y: Copy Message | i: Edit Input | Tab: Next Focus | h: Toggle List",
+            FocusedBlock::ConversationList => format!("Conversation List | j/k or ↑↓: Navigate | Enter: Select | n: New Conversation | s: Summarize | t: Filter by Tag | f: This Project Only | o: Sort | c: Enter Directory | {}", common),
+            FocusedBlock::ModelSelect => format!("Model Select | j/k or ↑↓: Change Model | /: Filter | o: Model Options | {}", common),
+            FocusedBlock::Chat if app.is_panning_code() => format!("Chat | j/k or ↑↓: Scroll | h/l or ←→: Pan Code | w: Wrap Code | #: Line Numbers | y: Copy Message | Y: Copy Lines | S: Save Code | Enter/.: Actions | Space: Mark | {}: Edit Input | {}: Next Focus", keys.label(Action::EditInput), keys.label(Action::NextFocus)),
+            FocusedBlock::Chat => format!("Chat | j/k or ↑↓: Scroll | /: Search | w: Wrap/Scroll Code | #: Line Numbers | y: Copy Message | Y: Copy Lines | S: Save Code | Enter/.: Actions | Space: Mark | {}", common),
             FocusedBlock::Input => match app.input_mode {
-                InputMode::Normal => "Input | i: Start Editing | Tab: Next Focus | h: Toggle List",
-                InputMode::Editing => "Input (Editing) | Enter: Send | Esc: Stop Editing | h: Toggle List",
+                InputMode::Normal => format!("Input | {}: Start Editing | {}: Next Focus | {}: Toggle List", keys.label(Action::EditInput), keys.label(Action::NextFocus), keys.label(Action::ToggleList)),
+                InputMode::Editing => "Input (Editing) | Enter: Send | Ctrl+L: Preview | Esc: Stop Editing".to_string(),
             },
         };
-        Span::styled(status_text, Style::default().fg(Color::Cyan))
+        Span::styled(status_text, app.config.theme.status)
     };
 
-    let status_widget = Paragraph::new(status)
-        .style(Style::default())
-        .block(Block::default().borders(Borders::ALL).title("Status"));
+    let mut title = vec![Span::raw("Status")];
+    if app.kiosk {
+        title.push(Span::styled(
+            " [KIOSK]",
+            Style::default().fg(Color::Magenta),
+        ));
+    }
+    if app.do_not_disturb {
+        title.push(Span::styled(" [DND]", Style::default().fg(Color::Magenta)));
+    }
+    if app.background_paused() {
+        title.push(Span::styled(
+            format!(
+                " [PAUSED ({})]",
+                app.config.keys.label(Action::PauseBackground)
+            ),
+            Style::default().fg(Color::Magenta),
+        ));
+    }
+    if !app.pending_remote.is_empty() {
+        title.push(Span::styled(
+            format!(" ● {} remote pending (A)", app.pending_remote.len()),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    let status_widget = Paragraph::new(status).style(Style::default()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(Line::from(title)),
+    );
 
     f.render_widget(status_widget, chunks[0]);
 
-    // Render server status gauge
-    let server_status = if app.server_running.load(Ordering::SeqCst) {
-        "Server Running"
+    let server_widget = Paragraph::new(server).block(
+        Block::default()
+            .title("Server (/server)")
+            .borders(Borders::ALL),
+    );
+    f.render_widget(server_widget, chunks[1]);
+}
+
+// The remote server at a glance: whether it listens and where, the connections it
+// handled, when the last prompt came and how many errors there were.
+fn server_line(app: &App) -> Line<'static> {
+    let stats = &app.server_stats;
+    let errors = stats.errors.load(Ordering::SeqCst);
+    let dim = Style::default().fg(Color::DarkGray);
+    let mut spans = if app.server_running.load(Ordering::SeqCst) {
+        let last = match stats.last_command.load(Ordering::SeqCst) {
+            0 => "never".to_string(),
+            time => listing::ago(time, unix_now()),
+        };
+        vec![
+            Span::styled("● ", Style::default().fg(Color::Green)),
+            Span::raw(app.config.server_address()),
+            Span::styled(" no auth", dim),
+            Span::raw(format!(
+                " | {} conn | last {}",
+                stats.connections.load(Ordering::SeqCst),
+                last
+            )),
+        ]
+    } else if app.config.server_enabled && !app.kiosk {
+        vec![
+            Span::styled("● ", Style::default().fg(Color::Red)),
+            Span::raw(format!("{} stopped", app.config.server_address())),
+        ]
     } else {
-        "Server Stopped"
+        vec![Span::styled("Off", dim)]
     };
-
-    let gauge = Gauge::default()
-        .block(Block::default().title("Server").borders(Borders::ALL))
-        .gauge_style(Style::default().fg(Color::Green))
-        .ratio(if app.server_running.load(Ordering::SeqCst) {
-            1.0
-        } else {
-            0.0
-        })
-        .label(server_status);
-
-    f.render_widget(gauge, chunks[1]);
+    if errors > 0 {
+        spans.push(Span::styled(
+            format!(" | {} err", errors),
+            Style::default().fg(Color::Red),
+        ));
+    }
+    Line::from(spans)
 }
 
 fn render_conversation_list(f: &mut Frame, app: &App, area: Rect) {
+    let now = unix_now();
+    // Less the borders and the highlight symbol.
+    let (columns, name_width) = listing::fit_columns(
+        &app.config.list_columns,
+        (area.width as usize).saturating_sub(4),
+    );
     let items: Vec<ListItem> = app
-        .conversations
-        .iter()
-        .map(|c| ListItem::new(c.name.clone()))
+        .visible_conversations()
+        .into_iter()
+        .map(|i| {
+            let conversation = &app.conversations[i];
+            let mut spans = Vec::new();
+            for (n, &column) in columns.iter().enumerate() {
+                if n > 0 {
+                    spans.push(Span::raw(" "));
+                }
+                let width = column.width().unwrap_or(name_width);
+                let dim = Style::default().fg(Color::DarkGray);
+                match column {
+                    Column::Name => spans.extend(name_spans(conversation, width)),
+                    Column::Model => spans.push(Span::styled(
+                        listing::pad(conversation.model.as_deref().unwrap_or(""), width),
+                        Style::default().fg(Color::Blue),
+                    )),
+                    Column::Messages => spans.push(Span::styled(
+                        format!("{:>width$}", conversation.messages.len(), width = width),
+                        dim,
+                    )),
+                    Column::Activity => {
+                        let ago = conversation
+                            .last_active
+                            .map_or_else(String::new, |secs| listing::ago(secs, now));
+                        spans.push(Span::styled(format!("{:>width$}", ago, width = width), dim))
+                    }
+                    Column::Markers => {
+                        let pinned = !conversation.snippets.is_empty()
+                            || conversation.messages.iter().any(|m| m.pin.is_some());
+                        let mut markers = String::from(if pinned { "◆" } else { " " });
+                        if conversation.unread > 0 {
+                            markers.push_str(&format!("●{}", conversation.unread));
+                        }
+                        spans.push(Span::styled(
+                            listing::pad(&markers, width),
+                            Style::default()
+                                .fg(Color::Magenta)
+                                .add_modifier(Modifier::BOLD),
+                        ));
+                    }
+                }
+            }
+            let mut lines = vec![Line::from(spans)];
+            // The summary acts as a tooltip for the highlighted conversation.
+            if let Some(summary) = conversation
+                .summary
+                .as_ref()
+                .filter(|_| Some(i) == app.highlighted_conversation())
+            {
+                let width = (area.width as usize).saturating_sub(6).max(10);
+                lines.extend(
+                    textwrap::wrap(summary, width)
+                        .into_iter()
+                        .take(SUMMARY_TOOLTIP_LINES)
+                        .map(|line| {
+                            Line::styled(
+                                format!("  {}", line),
+                                Style::default().fg(Color::DarkGray),
+                            )
+                        }),
+                );
+            }
+            let item = ListItem::new(lines);
+            if app.flashing(i) {
+                item.style(app.config.theme.focus.add_modifier(Modifier::REVERSED))
+            } else {
+                item
+            }
+        })
         .collect();
+    let mut title = "Conversations".to_string();
+    if app.project_filter {
+        title.push_str(" (this project)");
+    }
+    if let Some(tag) = &app.tag_filter {
+        title.push_str(&format!(" #{}", tag));
+    }
+    if app.list_searching || !app.list_query.is_empty() {
+        let cursor = if app.list_searching { "_" } else { "" };
+        title.push_str(&format!(" /{}{}", app.list_query, cursor));
+    } else if app.list_sort != SortKey::Log {
+        title.push_str(&format!(" | by {}", app.list_sort.name()));
+    }
+    if let Some((done, total)) = app.tagging {
+        title.push_str(&format!(" | tagging {}/{}", done, total));
+    }
+    if app.loading_older {
+        title.push_str(" | loading older...");
+    }
 
     let border_style = if matches!(app.focused_block, FocusedBlock::ConversationList) {
-        Style::default().fg(Color::Yellow)
+        app.config.theme.focus
     } else {
         Style::default()
     };
@@ -671,7 +8154,7 @@ fn render_conversation_list(f: &mut Frame, app: &App, area: Rect) {
     let list = List::new(items)
         .block(
             Block::default()
-                .title("Conversations")
+                .title(title)
                 .borders(Borders::ALL)
                 .border_style(border_style),
         )
@@ -681,66 +8164,228 @@ fn render_conversation_list(f: &mut Frame, app: &App, area: Rect) {
     f.render_stateful_widget(list, area, &mut app.conversation_list_state.clone());
 }
 
+// The name and then the tags, as much as fits in `width` columns.
+fn name_spans(conversation: &Conversation, width: usize) -> Vec<Span<'static>> {
+    let room = width.saturating_sub(conversation.name.width());
+    if room == 0 {
+        return vec![Span::raw(listing::pad(&conversation.name, width))];
+    }
+    let tags: String = conversation
+        .tags
+        .iter()
+        .map(|tag| format!(" #{}", tag))
+        .collect();
+    vec![
+        Span::raw(conversation.name.clone()),
+        Span::styled(listing::pad(&tags, room), Style::default().fg(Color::Cyan)),
+    ]
+}
+
 fn render_model_select(f: &mut Frame, app: &App, area: Rect) {
-    let items: Vec<ListItem> = app
-        .models
+    let visible = app.visible_models();
+    let items: Vec<ListItem> = visible
         .iter()
-        .map(|m| ListItem::new(format!("{} ({})", m.full_name, m.alias)))
+        .map(|&i| &app.models[i])
+        .map(|m| {
+            if m.alias == m.full_name {
+                ListItem::new(m.full_name.as_str())
+            } else {
+                ListItem::new(format!("{} ({})", m.full_name, m.alias))
+            }
+        })
         .collect();
 
     let border_style = if matches!(app.focused_block, FocusedBlock::ModelSelect) {
-        Style::default().fg(Color::Yellow)
+        app.config.theme.focus
     } else {
         Style::default()
     };
 
+    let mut title = match app.current_conversation_index.map_or(0, |index| {
+        app.conversations[index].preferences.options.len()
+    }) {
+        0 => "Model".to_string(),
+        count => format!("Model | {} option(s)", count),
+    };
+    if app.model_searching || !app.model_query.is_empty() {
+        let cursor = if app.model_searching { "_" } else { "" };
+        title.push_str(&format!(
+            " /{}{} ({})",
+            app.model_query,
+            cursor,
+            visible.len()
+        ));
+    }
+    let mut state = ListState::default().with_selected(
+        app.model_list_state
+            .selected()
+            .and_then(|selected| visible.iter().position(|&i| i == selected)),
+    );
     let list = List::new(items)
         .block(
             Block::default()
-                .title("Model")
+                .title(title)
                 .borders(Borders::ALL)
                 .border_style(border_style),
         )
         .highlight_style(Style::default().add_modifier(Modifier::BOLD))
         .highlight_symbol("> ");
 
-    f.render_stateful_widget(list, area, &mut app.model_list_state.clone());
+    f.render_stateful_widget(list, area, &mut state);
 }
 
 fn render_chat(f: &mut Frame, app: &mut App, area: Rect) {
+    if let Some(index) = app.previewed_conversation() {
+        render_preview(f, app, index, area);
+        return;
+    }
     let border_style = if matches!(app.focused_block, FocusedBlock::Chat) {
-        Style::default().fg(Color::Yellow)
+        app.config.theme.focus
     } else {
         Style::default()
     };
 
+    let mut title = match app.current_conversation_index {
+        Some(index) => chat_breadcrumb(app, index),
+        None => Line::raw("Chat"),
+    };
+    if app.chat_searching || !app.chat_query.is_empty() {
+        let cursor = if app.chat_searching { "_" } else { " (n/N)" };
+        title.spans.push(Span::styled(
+            format!(" /{}{}", app.chat_query, cursor),
+            app.config.theme.focus,
+        ));
+    }
     let block = Block::default()
-        .title("Chat")
+        .title(title)
         .borders(Borders::ALL)
         .border_style(border_style);
 
-    let inner_area = block.inner(area);
+    let mut inner_area = block.inner(area);
     f.render_widget(block, area);
 
     if let Some(index) = app.current_conversation_index {
+        let width = inner_area.width as usize;
+        let mut header = Vec::new();
+        if let Some(system) = &app.conversations[index].preferences.system {
+            header.extend(system_header(system, app.pinned_expanded, width));
+        }
+        let pinned = app.pinned_context(index);
+        if !pinned.is_empty() {
+            header.extend(pinned_header(&pinned, app.pinned_expanded, width));
+        }
+        if !header.is_empty() {
+            let height = (header.len() as u16).min(inner_area.height / 2);
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(height), Constraint::Min(0)])
+                .split(inner_area);
+            f.render_widget(Paragraph::new(header), chunks[0]);
+            inner_area = chunks[1];
+        }
+
         let conversation = &app.conversations[index];
+        let width = (inner_area.width as usize).saturating_sub(2).max(1);
+        let waiting = app.waiting_reason(index);
         let messages: Vec<ListItem> = conversation
             .messages
             .iter()
             .enumerate()
-            .map(|(_msg_index, msg)| {
-                let (style, prefix) = match msg.role.as_str() {
-                    "user" => (Style::default().fg(Color::Green), "You: "),
-                    "assistant" => (Style::default().fg(Color::Blue), "AI: "),
-                    _ => (Style::default(), ""),
+            .map(|(msg_index, msg)| {
+                let style = match msg.role.as_str() {
+                    "user" => app.config.theme.user,
+                    "assistant" => app.config.theme.assistant,
+                    _ => Style::default(),
                 };
+                let prefix = app
+                    .config
+                    .theme
+                    .prefix(&msg.role, conversation.model.as_deref());
 
-                let content = format!("{}{}", prefix, msg.content);
-                let wrapped_content = textwrap::wrap(&content, inner_area.width as usize - 2);
-                let lines: Vec<Line> = wrapped_content
-                    .into_iter()
-                    .map(|line| Line::from(vec![Span::styled(line.to_string(), style)]))
-                    .collect();
+                let style = if msg.hidden {
+                    style.add_modifier(Modifier::DIM)
+                } else {
+                    style
+                };
+
+                let wrap_code = msg.wrap_code.unwrap_or(app.config.wrap_code);
+                let raw = msg.show_raw.unwrap_or(app.show_raw);
+                let truncated = truncate_for_display(msg, raw, app.config.max_display_lines);
+                let collapsed =
+                    app.compact && msg.role == "user" && app.selected_message() != Some(msg_index);
+                let mut lines = if collapsed {
+                    vec![collapsed_line(msg, &prefix, style, width)]
+                } else {
+                    message_lines(
+                        truncated.as_ref().map_or(msg, |(shown, _)| shown),
+                        &prefix,
+                        style,
+                        width,
+                        MessageView {
+                            wrap_code,
+                            line_numbers: app.show_line_numbers,
+                            raw,
+                            highlight_theme: app.config.highlight_theme.as_deref(),
+                        },
+                    )
+                };
+                if !app.chat_query.is_empty() {
+                    highlight_matches(&mut lines, &app.chat_query, prefix.len());
+                }
+                let reactions = msg.reaction_emojis();
+                if !reactions.is_empty() {
+                    if let Some(first) = lines.first_mut() {
+                        first.spans.push(Span::raw(format!(" {}", reactions)));
+                    }
+                }
+                if let (Some(seed), Some(first)) = (msg.seed, lines.first_mut()) {
+                    first.spans.push(Span::styled(
+                        format!(" seed {}", seed),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                let suggestions = app
+                    .suggestions
+                    .as_ref()
+                    .filter(|(c, _)| *c == index && msg_index + 1 == conversation.messages.len());
+                if let Some(reason) = waiting
+                    .as_ref()
+                    .filter(|_| msg_index + 1 == conversation.messages.len())
+                {
+                    lines.push(Line::from(Span::styled(
+                        format!("  {}", reason),
+                        Style::default().fg(Color::DarkGray),
+                    )));
+                }
+                if let Some((_, suggestions)) = suggestions {
+                    for (n, suggestion) in suggestions.iter().enumerate() {
+                        lines.push(Line::from(Span::styled(
+                            format!("  {}. {}", n + 1, suggestion),
+                            Style::default().fg(Color::Cyan),
+                        )));
+                    }
+                }
+                if let Some((_, left_out)) = truncated.filter(|_| !collapsed) {
+                    lines.push(Line::from(Span::styled(
+                        format!("… {} more line(s), o: open in pager", left_out),
+                        Style::default().fg(Color::DarkGray),
+                    )));
+                }
+                if msg.hidden {
+                    if let Some(first) = lines.first_mut() {
+                        first.spans.insert(
+                            0,
+                            Span::styled("(hidden) ", Style::default().fg(Color::DarkGray)),
+                        );
+                    }
+                }
+                if app.chat_state.marked.contains(&msg_index) {
+                    if let Some(first) = lines.first_mut() {
+                        first
+                            .spans
+                            .insert(0, Span::styled("● ", Style::default().fg(Color::Magenta)));
+                    }
+                }
 
                 ListItem::new(lines).style(style)
             })
@@ -774,22 +8419,525 @@ fn render_chat(f: &mut Frame, app: &mut App, area: Rect) {
     }
 }
 
+// Marks where `query` occurs in the lines, span by span, except in the first
+// `skip` bytes of the first line (the speaker prefix).
+fn highlight_matches(lines: &mut [Line], query: &str, mut skip: usize) {
+    let style = Style::default().fg(Color::Black).bg(Color::Yellow);
+    for line in lines {
+        for span in std::mem::take(&mut line.spans) {
+            let before = skip;
+            skip = skip.saturating_sub(span.content.len());
+            let found: Vec<_> = search::find_ignoring_case(&span.content, query)
+                .into_iter()
+                .filter(|range| range.start >= before)
+                .collect();
+            if found.is_empty() {
+                line.spans.push(span);
+                continue;
+            }
+            let mut last = 0;
+            for range in found {
+                if range.start > last {
+                    line.spans.push(Span::styled(
+                        span.content[last..range.start].to_string(),
+                        span.style,
+                    ));
+                }
+                line.spans.push(Span::styled(
+                    span.content[range.clone()].to_string(),
+                    span.style.patch(style),
+                ));
+                last = range.end;
+            }
+            if last < span.content.len() {
+                line.spans
+                    .push(Span::styled(span.content[last..].to_string(), span.style));
+            }
+        }
+        skip = 0;
+    }
+}
+
+// The conversation's system prompt as one line, or in full when expanded.
+fn system_header(system: &str, expanded: bool, width: usize) -> Vec<Line<'static>> {
+    let style = Style::default().fg(Color::Cyan);
+    if expanded {
+        let mut lines = vec![Line::from(Span::styled(
+            "System prompt (z: collapse)".to_string(),
+            style.add_modifier(Modifier::BOLD),
+        ))];
+        let width = width.saturating_sub(2).max(1);
+        for line in system.lines() {
+            let chars: Vec<char> = line.chars().collect();
+            for part in chars.chunks(width) {
+                let part: String = part.iter().collect();
+                lines.push(Line::from(Span::styled(format!("  {}", part), style)));
+            }
+        }
+        return lines;
+    }
+    let hint = " (z: expand)";
+    let preview: String = system
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(width.saturating_sub(8 + hint.len()))
+        .collect();
+    vec![Line::from(vec![
+        Span::styled("System: ", style.add_modifier(Modifier::BOLD)),
+        Span::styled(preview, style),
+        Span::styled(hint, style),
+    ])]
+}
+
+// The collapsible area above the chat listing what's pinned to the conversation.
+fn pinned_header(pinned: &[String], expanded: bool, width: usize) -> Vec<Line<'static>> {
+    let style = Style::default().fg(Color::Magenta);
+    let hint = if expanded { "z: collapse" } else { "z: expand" };
+    let mut lines = vec![Line::from(Span::styled(
+        format!("Pinned context: {} item(s) ({})", pinned.len(), hint),
+        style.add_modifier(Modifier::BOLD),
+    ))];
+    if expanded {
+        for (i, text) in pinned.iter().enumerate() {
+            let preview: String = text
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .chars()
+                .take(width.saturating_sub(6))
+                .collect();
+            lines.push(Line::from(Span::styled(
+                format!("{:>3}. {}", i + 1, preview),
+                style,
+            )));
+        }
+    }
+    lines
+}
+
+// A message as one dim line, cut to `width` with an ellipsis, for the compact view.
+fn collapsed_line(msg: &Message, prefix: &str, style: Style, width: usize) -> Line<'static> {
+    let text = msg.content.split_whitespace().collect::<Vec<_>>().join(" ");
+    let room = width.saturating_sub(prefix.chars().count() + 2).max(1);
+    let mut preview: String = text.chars().take(room).collect();
+    if text.chars().count() > room {
+        preview.push('…');
+    }
+    Line::from(Span::styled(
+        format!("{}{}", prefix, preview),
+        style.add_modifier(Modifier::DIM),
+    ))
+}
+
+// Cuts a message to its first `max` lines so giant responses (generated datasets,
+// logs) don't slow rendering down. Returns the shortened copy and how many lines
+// were left out.
+fn truncate_for_display(msg: &Message, raw: bool, max: Option<usize>) -> Option<(Message, usize)> {
+    let max = max?;
+    let text = if raw { msg.raw_content() } else { &msg.content };
+    let total = text.lines().count();
+    if total <= max {
+        return None;
+    }
+    let mut shown = Message::new(
+        &msg.role,
+        text.lines().take(max).collect::<Vec<_>>().join("\n"),
+    );
+    shown.wrap_code = msg.wrap_code;
+    shown.code_scroll = msg.code_scroll;
+    shown.fragments = msg.fragments.clone();
+    Some((shown, total - max))
+}
+
+// The chat title: where you are (conversation › model › persona), how full the
+// context is, then badges and notices.
+fn chat_breadcrumb(app: &mut App, index: usize) -> Line<'static> {
+    let (tokens, warning) = app.conversation_health(index);
+    let limit = app.context_limit();
+    let model = app
+        .selected_model()
+        .unwrap_or_else(|| "no model".to_string());
+    let pinned = app.pinned_context(index).len();
+    let conversation = &app.conversations[index];
+    let hidden = conversation.messages.iter().filter(|m| m.hidden).count();
+
+    let separator = || Span::styled(" › ", Style::default().fg(Color::DarkGray));
+    let mut spans = vec![
+        Span::styled(
+            conversation.name.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        separator(),
+        Span::styled(model, Style::default().fg(Color::Blue)),
+    ];
+    if let Some(persona) = &conversation.preferences.persona {
+        spans.push(separator());
+        spans.push(Span::styled(
+            format!("@{}", persona),
+            Style::default().fg(Color::Magenta),
+        ));
+    }
+    let usage_color = if tokens * 10 >= limit * 9 {
+        Color::Red
+    } else if tokens * 10 >= limit * 6 {
+        Color::Yellow
+    } else {
+        Color::DarkGray
+    };
+    spans.push(Span::styled(
+        format!(" | {}/{} tokens", format_count(tokens), format_count(limit)),
+        Style::default().fg(usage_color),
+    ));
+    let mut badges = conversation.preferences.badges();
+    if pinned > 0 {
+        badges.insert(0, format!("◆ {} pinned", pinned));
+    }
+    if app.compact {
+        badges.push("compact".to_string());
+    }
+    for badge in badges {
+        spans.push(Span::styled(
+            format!(" [{}]", badge),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+    if hidden > 0 {
+        spans.push(Span::raw(format!(" ({} hidden from context)", hidden)));
+    }
+    if let Some(warning) = warning {
+        spans.push(Span::styled(
+            format!(" | ⚠ {}: consider s (summarize) or n (new)", warning),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+    if app.unseen_messages > 0 {
+        spans.push(Span::raw(format!(" | {} new ↓ (G)", app.unseen_messages)));
+    } else if app.new_content_below {
+        spans.push(Span::raw(" | new content ↓ (G)"));
+    }
+    Line::from(spans)
+}
+
+// `950`, `12.3k`, `128k`, `1.0M`.
+fn format_count(count: usize) -> String {
+    match count {
+        0..1000 => count.to_string(),
+        1000..100_000 => format!("{:.1}k", count as f64 / 1000.0),
+        100_000..1_000_000 => format!("{}k", count / 1000),
+        _ => format!("{:.1}M", count as f64 / 1_000_000.0),
+    }
+}
+
+// The end of a conversation, dimmed, in place of the open one.
+fn render_preview(f: &mut Frame, app: &App, index: usize, area: Rect) {
+    let conversation = &app.conversations[index];
+    let width = area.width.saturating_sub(2) as usize;
+    let start = conversation.messages.len().saturating_sub(PREVIEW_MESSAGES);
+    let mut lines = Vec::new();
+    for message in &conversation.messages[start..] {
+        let prefix = app
+            .config
+            .theme
+            .prefix(&message.role, conversation.model.as_deref());
+        lines.extend(message_lines(
+            message,
+            &prefix,
+            Style::default(),
+            width,
+            MessageView {
+                wrap_code: true,
+                line_numbers: false,
+                raw: false,
+                highlight_theme: None,
+            },
+        ));
+    }
+    if lines.is_empty() {
+        lines.push(Line::raw("(no messages)"));
+    }
+    for line in &mut lines {
+        for span in &mut line.spans {
+            span.style = span.style.fg(Color::DarkGray);
+        }
+    }
+    // The newest messages are the ones that fit.
+    let height = area.height.saturating_sub(2) as usize;
+    let offset = lines.len().saturating_sub(height);
+    let title = format!(
+        "Preview: {} ({} message(s)) | Enter: Open",
+        conversation.name,
+        conversation.messages.len()
+    );
+    let widget = Paragraph::new(lines)
+        .scroll((offset as u16, 0))
+        .block(Block::default().title(title).borders(Borders::ALL));
+    f.render_widget(widget, area);
+}
+
+// How a message is laid out in the chat.
+struct MessageView<'a> {
+    wrap_code: bool,
+    line_numbers: bool,
+    raw: bool,
+    // The syntax highlighting theme for code blocks; `None` shows them plain.
+    highlight_theme: Option<&'a str>,
+}
+
+fn message_lines(
+    msg: &Message,
+    prefix: &str,
+    style: Style,
+    width: usize,
+    view: MessageView,
+) -> Vec<Line<'static>> {
+    let MessageView {
+        wrap_code,
+        line_numbers,
+        raw,
+        highlight_theme,
+    } = view;
+    let code_style = style.add_modifier(Modifier::DIM);
+    let gutter_style = Style::default().fg(Color::DarkGray);
+    let mut lines = Vec::new();
+
+    // The raw view hard-wraps the text as returned, with no markdown handling.
+    if raw {
+        let text = format!("{}{}", prefix, msg.raw_content());
+        for line in text.lines() {
+            for chunk in split_at_width(&line.replace('\t', "    "), width) {
+                lines.push(Line::from(Span::styled(chunk, style)));
+            }
+        }
+        lines.push(Line::from(Span::styled("[raw]", gutter_style)));
+        return lines;
+    }
+
+    let mut prefix = Some(prefix);
+    let mut block_number = 0;
+
+    for segment in code_blocks::parse_segments(&msg.content) {
+        match segment {
+            Segment::Text(text) if msg.role == "assistant" => {
+                let prefix = prefix.take().unwrap_or_default();
+                lines.extend(markdown::render(&text, prefix, width, style));
+            }
+            Segment::Text(text) => {
+                let text = format!("{}{}", prefix.take().unwrap_or_default(), text);
+                for line in text.lines() {
+                    for wrapped in textwrap::wrap(line, width) {
+                        lines.push(Line::from(Span::styled(wrapped.into_owned(), style)));
+                    }
+                }
+            }
+            Segment::Code(block) => {
+                block_number += 1;
+                if let Some(prefix) = prefix.take() {
+                    lines.push(Line::from(Span::styled(prefix.to_string(), style)));
+                }
+                let mut header = vec![Span::styled(format!("```{}", block.info), code_style)];
+                if block.tagged_language().is_none() {
+                    if let Some(detected) = block.language() {
+                        header.push(Span::styled(format!("({})", detected), gutter_style));
+                    }
+                }
+                if line_numbers {
+                    header.push(Span::styled(
+                        format!("  [block {}]", block_number),
+                        gutter_style,
+                    ));
+                }
+                lines.push(Line::from(header));
+
+                let number_width = block.lines.len().to_string().len();
+                let gutter_width = if line_numbers { number_width + 3 } else { 0 };
+                let code_width = width.saturating_sub(gutter_width).max(1);
+                let background = style.bg(CODE_BACKGROUND);
+                let highlighted = highlight_theme
+                    .and_then(|theme| highlight::highlight(&block.lines, block.language(), theme));
+                for (i, code_line) in block.lines.iter().enumerate() {
+                    let runs = match highlighted.as_ref().and_then(|styled| styled.get(i)) {
+                        Some(runs) => runs
+                            .iter()
+                            .map(|(run_style, text)| (background.patch(*run_style), text.clone()))
+                            .collect(),
+                        None => vec![(
+                            code_style.bg(CODE_BACKGROUND),
+                            code_line.replace('\t', "    "),
+                        )],
+                    };
+                    let chunks = if wrap_code {
+                        split_runs_at_width(&runs, code_width)
+                    } else {
+                        vec![slice_run_columns(&runs, msg.code_scroll, code_width)]
+                    };
+                    for (j, mut chunk) in chunks.into_iter().enumerate() {
+                        // Pad to the full width so the background forms a block.
+                        let used: usize = chunk.iter().map(|span| span.content.width()).sum();
+                        if used < code_width {
+                            chunk.push(Span::styled(" ".repeat(code_width - used), background));
+                        }
+                        let mut spans = Vec::new();
+                        if line_numbers {
+                            let gutter = if j == 0 {
+                                format!("{:>w$} │ ", i + 1, w = number_width)
+                            } else {
+                                format!("{:>w$} │ ", "", w = number_width)
+                            };
+                            spans.push(Span::styled(gutter, gutter_style));
+                        }
+                        spans.extend(chunk);
+                        lines.push(Line::from(spans));
+                    }
+                }
+                lines.push(Line::from(Span::styled("```", code_style)));
+            }
+        }
+    }
+
+    if let Some(prefix) = prefix {
+        lines.push(Line::from(Span::styled(prefix.to_string(), style)));
+    }
+    if !msg.fragments.is_empty() {
+        let attached = format!("Attached: {}", msg.fragments.join(", "));
+        for wrapped in textwrap::wrap(&attached, width) {
+            lines.push(Line::from(Span::styled(wrapped.into_owned(), gutter_style)));
+        }
+    }
+    lines
+}
+
+// Hard-wraps a line every `width` columns, keeping indentation and whitespace intact
+// (unlike `textwrap`, which reflows on word boundaries).
+fn split_at_width(line: &str, width: usize) -> Vec<String> {
+    let mut chunks = vec![String::new()];
+    let mut current_width = 0;
+    for c in line.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if current_width + char_width > width && current_width > 0 {
+            chunks.push(String::new());
+            current_width = 0;
+        }
+        chunks.last_mut().unwrap().push(c);
+        current_width += char_width;
+    }
+    chunks
+}
+
+// `split_at_width` for styled runs of text.
+fn split_runs_at_width(runs: &[(Style, String)], width: usize) -> Vec<Vec<Span<'static>>> {
+    let mut chunks: Vec<Vec<Span<'static>>> = vec![Vec::new()];
+    let mut current_width = 0;
+    for (style, text) in runs {
+        let mut piece = String::new();
+        for c in text.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if current_width + char_width > width && current_width > 0 {
+                if !piece.is_empty() {
+                    chunks
+                        .last_mut()
+                        .unwrap()
+                        .push(Span::styled(std::mem::take(&mut piece), *style));
+                }
+                chunks.push(Vec::new());
+                current_width = 0;
+            }
+            piece.push(c);
+            current_width += char_width;
+        }
+        if !piece.is_empty() {
+            chunks.last_mut().unwrap().push(Span::styled(piece, *style));
+        }
+    }
+    chunks
+}
+
+// The styled runs within `width` columns after the first `skip`, for code blocks
+// scrolled horizontally.
+fn slice_run_columns(runs: &[(Style, String)], skip: usize, width: usize) -> Vec<Span<'static>> {
+    let mut column = 0;
+    let mut spans = Vec::new();
+    for (style, text) in runs {
+        let mut visible = String::new();
+        for c in text.chars() {
+            let char_width = c.width().unwrap_or(0);
+            if column >= skip && column + char_width <= skip + width {
+                visible.push(c);
+            }
+            column += char_width;
+        }
+        if !visible.is_empty() {
+            spans.push(Span::styled(visible, *style));
+        }
+    }
+    spans
+}
+
 fn render_input(f: &mut Frame, app: &mut App, area: Rect) {
     let border_style = if matches!(app.focused_block, FocusedBlock::Input) {
-        Style::default().fg(Color::Yellow)
+        app.config.theme.focus
     } else {
         Style::default()
     };
 
-    let input = Paragraph::new(app.input.as_str())
+    let mut title = match &app.project {
+        Some(project) => format!(
+            "Input (project context: top {} of {} files)",
+            app.config.project_top_k,
+            project.len()
+        ),
+        None => "Input".to_string(),
+    };
+    if !app.pending_fragments.is_empty() {
+        let labels: Vec<&str> = app
+            .pending_fragments
+            .iter()
+            .map(|(label, _)| label.as_str())
+            .collect();
+        title.push_str(&format!(" [+ {}]", labels.join(", ")));
+    }
+    for attachment in &app.pending_attachments {
+        title.push_str(&format!(" [{}]", attachment_label(attachment)));
+    }
+    let suggestion = match app.input_mode {
+        InputMode::Editing => app
+            .completion
+            .suggestion
+            .as_deref()
+            .filter(|_| app.completion.seen == app.input.as_str()),
+        InputMode::Normal => None,
+    };
+    match (&app.input_mode, suggestion) {
+        (InputMode::Editing, Some(_)) => title.push_str(" (Tab: accept suggestion)"),
+        (InputMode::Editing, None) => title.push_str(" (Alt+Enter: new line)"),
+        (InputMode::Normal, _) => {}
+    }
+
+    // Only the last lines fit, so the cursor (always at the end) stays in view.
+    let lines = input_lines(&app.input, area.width.saturating_sub(2) as usize);
+    let visible = area.height.saturating_sub(2) as usize;
+    let shown = &lines[lines.len().saturating_sub(visible)..];
+    let cursor = shown.last().map_or(0, |line| line.width());
+
+    let mut text: Vec<Line> = shown.iter().map(|line| Line::from(line.clone())).collect();
+    if let (Some(suggestion), Some(last)) = (suggestion, text.last_mut()) {
+        let room = (area.width as usize).saturating_sub(cursor + 2);
+        last.spans.push(Span::styled(
+            completion::fit(suggestion, room).to_string(),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+
+    let input = Paragraph::new(text)
         .style(match app.input_mode {
             InputMode::Normal => Style::default(),
-            InputMode::Editing => Style::default().fg(Color::Yellow),
+            InputMode::Editing => app.config.theme.focus,
         })
         .block(
             Block::default()
                 .borders(Borders::ALL)
-                .title("Input")
+                .title(title)
                 .border_style(border_style),
         );
 
@@ -798,105 +8946,515 @@ fn render_input(f: &mut Frame, app: &mut App, area: Rect) {
     if let FocusedBlock::Input = app.focused_block {
         if let InputMode::Editing = app.input_mode {
             f.set_cursor_position(ratatui::layout::Position {
-                x: area.x + app.input.len() as u16 + 1,
-                y: area.y + 1,
+                x: area.x + cursor as u16 + 1,
+                y: area.y + shown.len().max(1) as u16,
             });
         }
     }
 }
 
-fn load_conversations() -> Vec<Conversation> {
-    let output = Command::new("llm")
-        .args(["logs", "list", "--json"])
-        .output()
-        .expect("Failed to execute llm logs list command");
+// The input as it's displayed: hard-wrapped to `width`, with an empty line to
+// hold the cursor once the last one is full.
+fn input_lines(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines: Vec<String> = text
+        .split('\n')
+        .flat_map(|line| split_at_width(line, width))
+        .collect();
+    if lines.last().is_some_and(|line| line.width() >= width) {
+        lines.push(String::new());
+    }
+    lines
+}
+
+fn load_conversations() -> Result<Vec<Conversation>, String> {
+    load_logs(None, None).map(|(conversations, _)| conversations)
+}
 
-    let json: Value = serde_json::from_slice(&output.stdout).expect("Failed to parse JSON output");
+// The conversations in the `count` most recent log entries (all of them if `None`),
+// leaving out entries from `older_than` on, and how many entries llm returned.
+fn load_logs(
+    count: Option<usize>,
+    older_than: Option<&str>,
+) -> Result<(Vec<Conversation>, usize), String> {
+    let count = count.unwrap_or(0).to_string();
+    let output = llm_output(&["logs", "list", "--json", "-n", &count])?;
+    let json: Value = serde_json::from_slice(&output)
+        .map_err(|e| format!("`llm logs list` didn't return JSON: {}", e))?;
+    let entries = json.as_array().map_or(0, Vec::len);
 
-    let mut conversations = Vec::new();
-    let mut current_conversation: Option<Conversation> = None;
+    let directories = store::load(CONVERSATION_DIRS_FILE);
+    let overrides = store::load(MESSAGE_OVERRIDES_FILE);
+    let pins = store::load(CONTEXT_PINS_FILE);
+    let reactions = store::load(MESSAGE_REACTIONS_FILE);
+    let snippets = store::load(PINNED_SNIPPETS_FILE);
+    let filter_overrides = store::load(CONVERSATION_FILTERS_FILE);
+    let env = store::load(CONVERSATION_ENV_FILE);
+    let preferences = store::load(CONVERSATION_PREFERENCES_FILE);
+    let summaries = store::load(CONVERSATION_SUMMARIES_FILE);
+    let names = store::load(CONVERSATION_NAMES_FILE);
+    let tags = store::load(CONVERSATION_TAGS_FILE);
+    let links = store::load(CONVERSATION_LINKS_FILE);
+    let deleted = store::load(DELETED_CONVERSATIONS_FILE);
+    let deleted: BTreeSet<&str> = deleted
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    let mut conversations: Vec<Conversation> = Vec::new();
 
     if let Some(logs) = json.as_array() {
         for log in logs.iter().rev() {
-            let conversation_id = log["conversation_id"]
+            let logged_id = log["conversation_id"].as_str().unwrap_or_default();
+            let conversation_id = links[logged_id].as_str().unwrap_or(logged_id).to_string();
+            if deleted.contains(conversation_id.as_str()) {
+                continue;
+            }
+            let datetime = log["datetime_utc"].as_str().map(str::to_string);
+            if older_than.is_some() && datetime.as_deref() >= older_than {
+                continue;
+            }
+            // An exchange linked to a conversation is logged under a name llm made
+            // up for it, which shouldn't name the conversation.
+            let logged_name = log["conversation_name"]
                 .as_str()
-                .unwrap_or_default()
-                .to_string();
-            let conversation_name = log["conversation_name"]
+                .filter(|_| logged_id == conversation_id);
+            let conversation_name = names[conversation_id.as_str()]
                 .as_str()
+                .or(logged_name)
                 .unwrap_or_default()
                 .to_string();
             let prompt = log["prompt"].as_str().unwrap_or_default().to_string();
             let response = log["response"].as_str().unwrap_or_default().to_string();
+            let log_id = log["id"].as_str().map(str::to_string);
+            let seed = logged_seed(log);
+            let model = log["model"].as_str().map(str::to_string);
+            let last_active = datetime.as_deref().and_then(listing::timestamp_secs);
+            let exchange: Vec<Message> = [("user", prompt), ("assistant", response)]
+                .into_iter()
+                .filter_map(|(role, content)| {
+                    let mut message = Message::new(role, content);
+                    message.log_id = log_id.clone();
+                    let key = message.override_key().unwrap_or_default();
+                    match overrides[key.as_str()].as_str() {
+                        Some("deleted") => return None,
+                        Some("hidden") => message.hidden = true,
+                        _ => {}
+                    }
+                    message.pin = pins[key.as_str()].as_u64().map(|pin| pin as usize);
+                    message.reactions = string_array(&reactions[key.as_str()]);
+                    if role == "assistant" {
+                        message.seed = seed;
+                    }
+                    Some(message)
+                })
+                .collect();
 
-            match current_conversation {
-                Some(ref mut conv) if conv.id == conversation_id => {
-                    conv.messages.insert(
-                        0,
-                        Message {
-                            role: "user".to_string(),
-                            content: prompt,
-                        },
-                    );
-                    conv.messages.insert(
-                        1,
-                        Message {
-                            role: "assistant".to_string(),
-                            content: response,
-                        },
-                    );
-                }
-                _ => {
-                    if let Some(conv) = current_conversation.take() {
-                        conversations.push(conv);
+            // Exchanges of one conversation needn't be next to each other in the log,
+            // e.g. when several were answered at once.
+            match conversations.iter_mut().find(|c| c.id == conversation_id) {
+                Some(conv) => {
+                    conv.messages.splice(0..0, exchange);
+                    if conv.name.is_empty() {
+                        conv.name = conversation_name;
                     }
-                    current_conversation = Some(Conversation {
+                    if conv.last_active.is_none() || last_active > conv.last_active {
+                        conv.last_active = last_active;
+                        conv.model = model;
+                    }
+                    if datetime.is_some() && (conv.started.is_none() || datetime < conv.started) {
+                        conv.started = datetime;
+                    }
+                }
+                None => {
+                    conversations.push(Conversation {
+                        directory: directories[conversation_id.as_str()]
+                            .as_str()
+                            .map(PathBuf::from),
+                        snippets: string_array(&snippets[conversation_id.as_str()]),
+                        filter_overrides: filter_overrides[conversation_id.as_str()].clone(),
+                        env: string_map(&env[conversation_id.as_str()]),
+                        preferences: Preferences::from_json(&preferences[conversation_id.as_str()]),
+                        summary: summaries[conversation_id.as_str()]
+                            .as_str()
+                            .map(str::to_string),
+                        tags: string_array(&tags[conversation_id.as_str()]),
+                        started: datetime,
+                        model,
+                        last_active,
+                        unread: 0,
                         id: conversation_id,
                         name: conversation_name,
-                        messages: vec![
-                            Message {
-                                role: "user".to_string(),
-                                content: prompt,
-                            },
-                            Message {
-                                role: "assistant".to_string(),
-                                content: response,
-                            },
-                        ],
+                        messages: exchange,
                     });
                 }
             }
         }
     }
 
-    if let Some(conv) = current_conversation {
-        conversations.push(conv);
+    Ok((conversations, entries))
+}
+
+// Records that exchanges llm logged under `logged_id` belong to the conversation
+// `conversation_id`.
+fn save_link(logged_id: &str, conversation_id: &str) -> io::Result<()> {
+    let mut links = store::load(CONVERSATION_LINKS_FILE);
+    if !links.is_object() {
+        links = Value::Object(Default::default());
+    }
+    links[logged_id] = Value::String(conversation_id.to_string());
+    store::save(CONVERSATION_LINKS_FILE, &links)
+}
+
+// The llm conversations a conversation's exchanges were logged under: its own id
+// and those linked to it (see `CONVERSATION_LINKS_FILE`).
+fn logged_conversation_ids(id: &str) -> Vec<String> {
+    let links = store::load(CONVERSATION_LINKS_FILE);
+    let mut ids = vec![id.to_string()];
+    ids.extend(
+        links
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(_, linked)| linked.as_str() == Some(id))
+            .map(|(logged, _)| logged.clone()),
+    );
+    ids
+}
+
+// The conversation and entry ids llm logged `response` under, looked up among its
+// latest entries.
+fn find_logged(response: &str) -> Option<(String, String)> {
+    let output = llm_output(&["logs", "list", "--json", "-n", "10"]).ok()?;
+    let logs: Value = serde_json::from_slice(&output).ok()?;
+    let log = logs
+        .as_array()?
+        .iter()
+        .rev()
+        .find(|log| log["response"].as_str().map(str::trim) == Some(response.trim()))?;
+    Some((
+        log["conversation_id"].as_str()?.to_string(),
+        log["id"].as_str()?.to_string(),
+    ))
+}
+
+// Renames a conversation in llm's log database, for `llm logs` and other tools.
+// Conversations started in this session aren't logged under their id, so for them
+// only the local name applies.
+fn rename_in_logs(id: &str, name: &str) -> Result<(), String> {
+    let output = llm_command()
+        .args(["logs", "path"])
+        .output()
+        .map_err(|e| e.to_string())?;
+    let database = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if database.is_empty() {
+        return Err("llm's log database wasn't found".to_string());
+    }
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+    let sql = format!(
+        "UPDATE conversations SET name = {} WHERE id = {}; SELECT changes();",
+        quote(name),
+        quote(id)
+    );
+    let output = Command::new("sqlite3")
+        .arg(&database)
+        .arg(sql)
+        .output()
+        .map_err(|_| "sqlite3 isn't installed to update llm's logs".to_string())?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "0" => Err("not in llm's logs yet".to_string()),
+        _ => Ok(()),
+    }
+}
+
+// The `seed` option a logged response was generated with.
+fn logged_seed(log: &Value) -> Option<u64> {
+    let options = match &log["options_json"] {
+        // Older llm versions log the options as a JSON string.
+        Value::String(json) => serde_json::from_str(json).unwrap_or(Value::Null),
+        options => options.clone(),
+    };
+    match &options["seed"] {
+        Value::String(seed) => seed.parse().ok(),
+        seed => seed.as_u64(),
+    }
+}
+
+// Records `value` ("hidden"/"deleted") for each message key, or clears it for `None`.
+fn save_message_overrides(keys: &[String], value: Option<&str>) -> io::Result<()> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+    let mut overrides = store::load(MESSAGE_OVERRIDES_FILE);
+    if !overrides.is_object() {
+        overrides = Value::Object(Default::default());
+    }
+    if let Some(map) = overrides.as_object_mut() {
+        for key in keys {
+            match value {
+                Some(value) => {
+                    map.insert(key.clone(), Value::String(value.to_string()));
+                }
+                None => {
+                    map.remove(key);
+                }
+            }
+        }
+    }
+    store::save(MESSAGE_OVERRIDES_FILE, &overrides)
+}
+
+// The start of `text` on one line, for labels.
+fn preview(text: &str, max_chars: usize) -> String {
+    let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() <= max_chars {
+        flat
+    } else {
+        format!("{}…", flat.chars().take(max_chars).collect::<String>())
+    }
+}
+
+fn is_env_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn string_map(value: &Value) -> BTreeMap<String, String> {
+    value
+        .as_object()
+        .map(|map| {
+            map.iter()
+                .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn string_array(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+struct LlmRequest {
+    prompt: String,
+    model: String,
+    system: Option<String>,
+    fragments: Vec<String>,
+    // llm conversation to continue (`--cid`), if any.
+    conversation_id: Option<String>,
+    env: Vec<(String, String)>,
+    // Model options, passed as `-o name value`.
+    options: Vec<(String, String)>,
+    // Keeps the exchange out of llm's log (and so out of the conversation list).
+    no_log: bool,
+    // Run an llm template (`-t`) with `prompt` as its `$input`.
+    template: Option<templates::TemplateCall>,
+    // Files or URLs passed as `-a`, e.g. images for vision models.
+    attachments: Vec<String>,
+}
+
+impl LlmRequest {
+    // Just the prompt, logged as usual: no system prompt, context or options.
+    fn new(prompt: String, model: String) -> Self {
+        LlmRequest {
+            prompt,
+            model,
+            system: None,
+            fragments: Vec::new(),
+            conversation_id: None,
+            env: Vec::new(),
+            options: Vec::new(),
+            no_log: false,
+            template: None,
+            attachments: Vec::new(),
+        }
+    }
+}
+
+// An `llm` run that exited with an error: what it printed before failing, and
+// what it said on stderr.
+struct LlmFailure {
+    output: String,
+    error: String,
+}
+
+impl LlmFailure {
+    fn new(output: String, error: impl Into<String>) -> Self {
+        LlmFailure {
+            output,
+            error: error.into(),
+        }
+    }
+
+    // The output with the error after it, as shown in the chat.
+    fn transcript(&self) -> String {
+        format!("{}\nError: {}", self.output, self.error)
+    }
+}
+
+// Runs `request` to completion, with the response trimmed or what llm said on failing.
+fn run_llm_checked(request: &LlmRequest) -> Result<String, String> {
+    match stream_llm(request, |_| {}) {
+        Ok(response) => Ok(response.trim().to_string()),
+        Err(failure) => Err(failure.error.trim().to_string()),
+    }
+}
+
+// The `llm` executable: `LLM_TUI_LLM` if set (a full path, or a wrapper such as a
+// `.cmd` file on Windows), otherwise `llm` from the `PATH`.
+fn llm_command() -> Command {
+    let program = env::var_os("LLM_TUI_LLM")
+        .filter(|program| !program.is_empty())
+        .unwrap_or_else(|| "llm".into());
+    let mut command = Command::new(program);
+    if cfg!(windows) {
+        // Python writes pipes in the ANSI code page otherwise, mangling non-ASCII text.
+        command.env("PYTHONUTF8", "1");
+    }
+    command
+}
+
+// The output of `llm <args>`, or why it couldn't be run or what it complained about.
+fn llm_output(args: &[&str]) -> Result<Vec<u8>, String> {
+    let mut command = llm_command();
+    let output = command.args(args).output().map_err(|e| {
+        if e.kind() == io::ErrorKind::NotFound {
+            format!("`{}` wasn't found", command.get_program().to_string_lossy())
+        } else {
+            format!("Failed to run llm: {}", e)
+        }
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.trim().lines().last().unwrap_or_default().to_string();
+        return Err(format!(
+            "`llm {}` failed ({}){}",
+            args.join(" "),
+            output.status,
+            if reason.is_empty() {
+                String::new()
+            } else {
+                format!(": {}", reason)
+            }
+        ));
     }
+    Ok(output.stdout)
+}
 
-    conversations
+// Runs `llm`, calling `on_chunk` with stdout text as it arrives, and returns the
+// full response.
+fn stream_llm(request: &LlmRequest, on_chunk: impl FnMut(&str)) -> Result<String, LlmFailure> {
+    stream_llm_cancellable(request, &RunningChild::default(), on_chunk)
+        .unwrap_or_else(|| Ok(String::new()))
 }
 
-fn run_llm(prompt: &str, model_alias: &str) -> String {
-    let mut command = Command::new("llm");
-    command.args(["-m", model_alias, prompt]);
+// Like `stream_llm`, but keeps the process in `running` while it streams; returns
+// `None` if it was taken from there (and killed) before finishing. Whether it
+// failed comes from llm's exit status, not from what the answer says.
+fn stream_llm_cancellable(
+    request: &LlmRequest,
+    running: &RunningChild,
+    mut on_chunk: impl FnMut(&str),
+) -> Option<Result<String, LlmFailure>> {
+    let mut command = llm_command();
+    command.args(["-m", &request.model]);
+    if let Some(conversation_id) = &request.conversation_id {
+        command.args(["--cid", conversation_id]);
+    }
+    // llm doesn't take `-s` along with `-t`; the template brings its own.
+    match &request.template {
+        Some(template) => {
+            command.args(["-t", &template.name]);
+            for (name, value) in &template.params {
+                command.args(["-p", name, value]);
+            }
+        }
+        None => {
+            if let Some(system) = &request.system {
+                command.args(["-s", system]);
+            }
+        }
+    }
+    for fragment in &request.fragments {
+        command.args(["-f", fragment]);
+    }
+    for attachment in &request.attachments {
+        command.args(["-a", attachment]);
+    }
+    for (name, value) in &request.options {
+        command.args(["-o", name, value]);
+    }
+    if request.no_log {
+        command.arg("--no-log");
+    }
+    command.arg(&request.prompt);
+    command.envs(request.env.iter().map(|(key, value)| (key, value)));
     command.stdout(Stdio::piped());
     command.stderr(Stdio::piped());
 
-    let mut child = command.spawn().expect("Failed to execute llm command");
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            return Some(Err(LlmFailure::new(
+                String::new(),
+                format!("Failed to run llm: {}", e),
+            )))
+        }
+    };
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    *running.lock().unwrap() = Some(child);
 
     let mut output = String::new();
     let mut error = String::new();
 
     // Read stdout
-    if let Some(stdout) = child.stdout.take() {
-        let mut stdout_reader = BufReader::new(stdout);
-        stdout_reader
-            .read_to_string(&mut output)
-            .expect("Failed to read stdout");
+    if let Some(mut stdout) = stdout {
+        let mut buffer = [0u8; 4096];
+        let mut pending: Vec<u8> = Vec::new();
+        loop {
+            let read = stdout.read(&mut buffer).expect("Failed to read stdout");
+            if read == 0 {
+                break;
+            }
+            pending.extend_from_slice(&buffer[..read]);
+            // Only hand out complete UTF-8 sequences; keep a split character for later.
+            let mut valid = match std::str::from_utf8(&pending) {
+                Ok(_) => pending.len(),
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                Err(_) => pending.len(),
+            };
+            // Likewise a `\r` that may start a Windows line ending.
+            if pending[..valid].ends_with(b"\r") {
+                valid -= 1;
+            }
+            let chunk = String::from_utf8_lossy(&pending[..valid]).replace("\r\n", "\n");
+            pending.drain(..valid);
+            on_chunk(&chunk);
+            output.push_str(&chunk);
+        }
+        if !pending.is_empty() {
+            let chunk = String::from_utf8_lossy(&pending).into_owned();
+            on_chunk(&chunk);
+            output.push_str(&chunk);
+        }
     }
 
     // Read stderr
-    if let Some(stderr) = child.stderr.take() {
+    if let Some(stderr) = stderr {
         let mut stderr_reader = BufReader::new(stderr);
         stderr_reader
             .read_to_string(&mut error)
@@ -904,26 +9462,12 @@ fn run_llm(prompt: &str, model_alias: &str) -> String {
     }
 
     // Wait for the command to finish
+    let mut child = running.lock().unwrap().take()?;
     let status = child.wait().expect("Failed to wait for llm command");
 
     if !status.success() {
-        // If the command failed, append the error to the output
-        output.push_str("\nError: ");
-        output.push_str(&error);
+        return Some(Err(LlmFailure::new(output, error)));
     }
 
-    output
-}
-
-fn handle_client(mut stream: TcpStream, tx: CrossbeamSender<String>) {
-    let mut reader = BufReader::new(&stream);
-    let mut command_output = String::new();
-
-    reader.read_line(&mut command_output).unwrap();
-
-    tx.send(command_output.trim().to_string()).unwrap();
-
-    stream
-        .write_all(b"Command received and processed.\n")
-        .unwrap();
+    Some(Ok(output))
 }
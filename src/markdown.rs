@@ -0,0 +1,241 @@
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use unicode_width::UnicodeWidthStr;
+
+// Renders the prose between code blocks: headings, emphasis, lists, blockquotes,
+// rules and inline code, wrapped to `width`. `prefix` starts the first line.
+pub fn render(text: &str, prefix: &str, width: usize, style: Style) -> Vec<Line<'static>> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut prefix = Some(prefix).filter(|prefix| !prefix.is_empty());
+    for line in text.lines() {
+        let first = prefix
+            .take()
+            .map(|prefix| Span::styled(prefix.to_string(), style));
+        let block = Block::parse(line);
+        if let Block::Rule = block {
+            lines.extend(first.map(Line::from));
+            lines.push(Line::from(Span::styled(
+                "─".repeat(width),
+                style.add_modifier(Modifier::DIM),
+            )));
+            continue;
+        }
+        let (marker, rest, line_style) = match block {
+            Block::Heading(level, rest) => {
+                let mut heading = style.add_modifier(Modifier::BOLD);
+                if level == 1 {
+                    heading = heading.add_modifier(Modifier::UNDERLINED);
+                }
+                (String::new(), rest, heading)
+            }
+            Block::Quote(rest) => ("│ ".to_string(), rest, style.add_modifier(Modifier::ITALIC)),
+            Block::Item(indent, bullet, rest) => (format!("{}{} ", indent, bullet), rest, style),
+            Block::Plain(rest) => (String::new(), rest, style),
+            Block::Rule => unreachable!(),
+        };
+        let runs = inline(rest, line_style);
+        lines.extend(wrap(first, &marker, runs, width, style));
+    }
+    lines
+}
+
+enum Block<'a> {
+    Heading(usize, &'a str),
+    Quote(&'a str),
+    // Indentation, bullet (`•` or the original number) and the item text.
+    Item(String, String, &'a str),
+    Rule,
+    Plain(&'a str),
+}
+
+impl<'a> Block<'a> {
+    fn parse(line: &'a str) -> Self {
+        let trimmed = line.trim_start();
+        let indent = " ".repeat(line.len() - trimmed.len());
+        let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+        if (1..=6).contains(&hashes) && trimmed[hashes..].starts_with(' ') {
+            return Block::Heading(hashes, trimmed[hashes..].trim());
+        }
+        if let Some(rest) = trimmed.strip_prefix('>') {
+            return Block::Quote(rest.strip_prefix(' ').unwrap_or(rest));
+        }
+        let compact: String = trimmed.chars().filter(|c| !c.is_whitespace()).collect();
+        if compact.len() >= 3
+            && ["-", "*", "_"]
+                .iter()
+                .any(|c| compact.chars().all(|x| x.to_string() == *c))
+        {
+            return Block::Rule;
+        }
+        for bullet in ["- ", "* ", "+ "] {
+            if let Some(rest) = trimmed.strip_prefix(bullet) {
+                return Block::Item(indent, "•".to_string(), rest);
+            }
+        }
+        let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+        if digits > 0 {
+            let rest = &trimmed[digits..];
+            if let Some(item) = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")) {
+                return Block::Item(indent, trimmed[..digits + 1].to_string(), item);
+            }
+        }
+        Block::Plain(line)
+    }
+}
+
+// Splits a line into runs of text styled by `**bold**`, `*italic*` and `` `code` ``
+// (and their underscore forms). Unclosed markers are kept as text.
+fn inline(text: &str, style: Style) -> Vec<(String, Style)> {
+    let mut runs = Vec::new();
+    let mut current = String::new();
+    let mut bold = false;
+    let mut italic = false;
+    let run_style = |bold: bool, italic: bool| {
+        let mut style = style;
+        if bold {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        if italic {
+            style = style.add_modifier(Modifier::ITALIC);
+        }
+        style
+    };
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '`' {
+            if let Some(end) = rest[1..].find('`') {
+                if !current.is_empty() {
+                    runs.push((std::mem::take(&mut current), run_style(bold, italic)));
+                }
+                runs.push((
+                    rest[1..end + 1].to_string(),
+                    style.fg(Color::Yellow).remove_modifier(Modifier::ITALIC),
+                ));
+                rest = &rest[end + 2..];
+                continue;
+            }
+        }
+        let double = rest.starts_with("**") || rest.starts_with("__");
+        if double && (bold || rest[2..].contains(&rest[..2])) {
+            if !current.is_empty() {
+                runs.push((std::mem::take(&mut current), run_style(bold, italic)));
+            }
+            bold = !bold;
+            rest = &rest[2..];
+            continue;
+        }
+        // A single `_` inside a word (snake_case) isn't emphasis.
+        let marker = c == '*' || (c == '_' && !current.ends_with(char::is_alphanumeric));
+        let opens = || {
+            let after = &rest[c.len_utf8()..];
+            !after.starts_with(' ') && after.contains(c)
+        };
+        if marker && (italic || opens()) {
+            if !current.is_empty() {
+                runs.push((std::mem::take(&mut current), run_style(bold, italic)));
+            }
+            italic = !italic;
+            rest = &rest[1..];
+            continue;
+        }
+        current.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    if !current.is_empty() {
+        runs.push((current, run_style(bold, italic)));
+    }
+    runs
+}
+
+// Wraps styled runs at word boundaries. Continuation lines are indented to line up
+// with the text after `marker`.
+fn wrap(
+    first: Option<Span<'static>>,
+    marker: &str,
+    runs: Vec<(String, Style)>,
+    width: usize,
+    style: Style,
+) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    let mut spans: Vec<Span<'static>> = first.into_iter().collect();
+    spans.push(Span::styled(marker.to_string(), style));
+    let hanging = " ".repeat(marker.width());
+    let mut used: usize = spans.iter().map(|span| span.content.width()).sum();
+    let mut empty = true;
+
+    for (text, run_style) in runs {
+        for word in text.split_inclusive(' ') {
+            let word_width = word.trim_end().width();
+            if used + word_width > width && !empty {
+                lines.push(Line::from(std::mem::take(&mut spans)));
+                spans.push(Span::styled(hanging.clone(), style));
+                used = hanging.len();
+            }
+            empty = false;
+            let mut word = word.to_string();
+            // Words longer than a whole line are broken up.
+            while used + word.width() > width && word.chars().count() > 1 {
+                let fit = width.saturating_sub(used).max(1);
+                let split: String = word.chars().take(fit).collect();
+                word = word.chars().skip(fit).collect();
+                spans.push(Span::styled(split, run_style));
+                lines.push(Line::from(std::mem::take(&mut spans)));
+                spans.push(Span::styled(hanging.clone(), style));
+                used = hanging.len();
+            }
+            used += word.width();
+            spans.push(Span::styled(word, run_style));
+        }
+    }
+    lines.push(Line::from(spans));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(runs: &[(String, Style)]) -> String {
+        runs.iter().map(|(text, _)| text.as_str()).collect()
+    }
+
+    #[test]
+    fn inline_keeps_non_ascii_text() {
+        let style = Style::default();
+        for input in [
+            "Café au lait",
+            "• point",
+            "😀 done",
+            "日本語のテキスト",
+            "é",
+        ] {
+            assert_eq!(text(&inline(input, style)), input);
+        }
+    }
+
+    #[test]
+    fn inline_emphasis_next_to_non_ascii() {
+        let runs = inline("*été* and **ça**", Style::default());
+        assert_eq!(runs[0].0, "été");
+        assert!(runs[0].1.add_modifier.contains(Modifier::ITALIC));
+        assert_eq!(runs[2].0, "ça");
+        assert!(runs[2].1.add_modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn render_non_ascii_lines() {
+        let lines = render(
+            "Café au lait\n- • item ünïcode\n日本語",
+            "",
+            20,
+            Style::default(),
+        );
+        let rendered: String = lines
+            .iter()
+            .flat_map(|line| line.spans.iter().map(|span| span.content.to_string()))
+            .collect();
+        assert!(rendered.contains("Café au lait"));
+        assert!(rendered.contains("日本語"));
+    }
+}
@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::Path;
+
+// Characters a mention can end with that belong to the sentence, as in "see
+// @src/main.rs, then".
+const TRAILING_PUNCTUATION: &[char] = &[',', '.', ';', ':', '!', '?', ')', '\'', '"'];
+// Larger files are left as `@path` rather than pasted into the prompt.
+const MAX_FILE_BYTES: u64 = 256 * 1024;
+
+// Replaces each `@path` naming a readable text file (a regular one, up to
+// `MAX_FILE_BYTES`) with the path and appends the
+// file, fenced and under its name. Other `@words` are left alone.
+pub fn inline(prompt: &str) -> String {
+    let mut text = String::new();
+    let mut files: Vec<(String, String)> = Vec::new();
+    let mut rest = prompt;
+    while let Some(at) = rest.find('@') {
+        let starts_word = rest[..at]
+            .chars()
+            .next_back()
+            .is_none_or(char::is_whitespace);
+        let after = &rest[at + 1..];
+        let end = after.find(char::is_whitespace).unwrap_or(after.len());
+        let word = &after[..end];
+        let path = word.trim_end_matches(TRAILING_PUNCTUATION);
+        let contents = starts_word
+            .then(|| read_file(path))
+            .flatten()
+            .filter(|_| !path.is_empty());
+        text.push_str(&rest[..at]);
+        match contents {
+            Some(contents) => {
+                text.push_str(word);
+                if !files.iter().any(|(known, _)| known == path) {
+                    files.push((path.to_string(), contents));
+                }
+            }
+            None => {
+                text.push('@');
+                text.push_str(word);
+            }
+        }
+        rest = &after[end..];
+    }
+    text.push_str(rest);
+    for (path, contents) in &files {
+        text.push_str(&format!("\n\n{}:\n{}", path, fenced(path, contents)));
+    }
+    text
+}
+
+// Devices and pipes such as `/dev/zero` or `/dev/stdin` would never finish
+// reading, so only regular files are read.
+fn read_file(path: &str) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    if !metadata.is_file() || metadata.len() > MAX_FILE_BYTES {
+        return None;
+    }
+    fs::read_to_string(path).ok()
+}
+
+// The contents in a code fence tagged with the file extension, longer than any
+// fence inside them.
+fn fenced(path: &str, contents: &str) -> String {
+    let mut fence = "```".to_string();
+    while contents.contains(&fence) {
+        fence.push('`');
+    }
+    let language = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    format!(
+        "{}{}\n{}\n{}",
+        fence,
+        language,
+        contents.trim_end_matches('\n'),
+        fence
+    )
+}
+
+// Completes the `@path` the input ends with: the text to append (the longest
+// common start of the matches, plus `/` for a single directory) and the matching
+// names. `None` when the input doesn't end with a mention.
+pub fn complete(input: &str) -> Option<(String, Vec<String>)> {
+    let word = input.rsplit(char::is_whitespace).next()?;
+    let partial = word.strip_prefix('@')?;
+    let (directory, name) = match partial.rfind('/') {
+        Some(slash) => (&partial[..=slash], &partial[slash + 1..]),
+        None => ("", partial),
+    };
+    let read = fs::read_dir(if directory.is_empty() { "." } else { directory }).ok()?;
+    let mut matches: Vec<(String, bool)> = read
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            let hidden = file_name.starts_with('.') && !name.starts_with('.');
+            (file_name.starts_with(name) && !hidden).then(|| (file_name, entry.path().is_dir()))
+        })
+        .collect();
+    matches.sort();
+    let mut suffix = match matches.as_slice() {
+        [] => String::new(),
+        [(only, _)] => only[name.len()..].to_string(),
+        [(first, _), rest @ ..] => {
+            let mut common = rest.iter().fold(first.len(), |common, (other, _)| {
+                first
+                    .bytes()
+                    .zip(other.bytes())
+                    .take(common)
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            });
+            while !first.is_char_boundary(common) {
+                common -= 1;
+            }
+            first[name.len()..common].to_string()
+        }
+    };
+    if let [(_, true)] = matches.as_slice() {
+        suffix.push('/');
+    }
+    let names = matches
+        .into_iter()
+        .map(|(name, dir)| if dir { format!("{}/", name) } else { name })
+        .collect();
+    Some((suffix, names))
+}
@@ -0,0 +1,89 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_TEMPLATE: &str =
+    "## {date} {time} · {conversation}\n\n{content}\n\n*{role}, {model}*\n";
+
+// What a note can refer to in the `[notes]` template and path.
+pub struct Note<'a> {
+    pub content: &'a str,
+    pub role: &'a str,
+    pub conversation: &'a str,
+    pub model: &'a str,
+    pub log_id: &'a str,
+}
+
+// Appends `note` to the notes file, e.g. `~/vault/Daily/{date}.md` for daily notes,
+// creating it if needed. Returns the file written to.
+pub fn append(path: &Path, template: &str, note: &Note) -> io::Result<PathBuf> {
+    let (date, time) = local_now();
+    let path = PathBuf::from(path.to_string_lossy().replace("{date}", &date));
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+    let text = template
+        .replace("{date}", &date)
+        .replace("{time}", &time)
+        .replace("{conversation}", note.conversation)
+        .replace("{role}", note.role)
+        .replace("{model}", note.model)
+        .replace("{log_id}", note.log_id)
+        .replace("{content}", note.content.trim_end());
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    if needs_separator(&path)? {
+        file.write_all(b"\n")?;
+    }
+    file.write_all(text.as_bytes())?;
+    if !text.ends_with('\n') {
+        file.write_all(b"\n")?;
+    }
+    Ok(path)
+}
+
+// Keeps notes apart when the file doesn't already end with a blank line.
+fn needs_separator(path: &Path) -> io::Result<bool> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(!text.is_empty() && !text.ends_with("\n\n"))
+}
+
+// The local date and time from `date`, falling back to UTC.
+fn local_now() -> (String, String) {
+    let local = Command::new("date")
+        .arg("+%Y-%m-%d %H:%M")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    if let Some((date, time)) = local.as_deref().and_then(|now| now.split_once(' ')) {
+        return (date.to_string(), time.to_string());
+    }
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        format!("{:02}:{:02} UTC", secs % 86_400 / 3600, secs % 3600 / 60),
+    )
+}
+
+// The inverse of `health::days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
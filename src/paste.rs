@@ -0,0 +1,77 @@
+use regex::Regex;
+use std::sync::OnceLock;
+
+// What smart paste offers to put pasted errors under, until `[paste] prompts` says
+// otherwise.
+pub const DEFAULT_PROMPTS: [&str; 3] = [
+    "Explain this error:",
+    "Find the root cause of this error:",
+    "Suggest a fix for this error:",
+];
+
+// Log lines need at least this many lines to tell them from a pasted sentence.
+const MIN_LOG_LINES: usize = 3;
+
+// Says what the pasted text looks like, "stack trace" or "log", when it's one of
+// them and isn't already fenced.
+pub fn detect(text: &str) -> Option<&'static str> {
+    if text.contains("```") {
+        return None;
+    }
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    if lines.len() < 2 {
+        return None;
+    }
+    let trace = trace_regex();
+    let frames = lines.iter().filter(|line| trace.is_match(line)).count();
+    if frames >= 2
+        || text.contains("Traceback (most recent call last)")
+        || text.contains("stack backtrace:")
+        || (text.contains("thread '") && text.contains("panicked at"))
+        || (text.contains("goroutine ") && text.contains("panic:"))
+    {
+        return Some("stack trace");
+    }
+    let log = log_regex();
+    let log_lines = lines.iter().filter(|line| log.is_match(line)).count();
+    (lines.len() >= MIN_LOG_LINES && log_lines * 2 >= lines.len()).then_some("log")
+}
+
+// The text in a code fence longer than any inside it, under `prompt` if given.
+pub fn wrap(text: &str, prompt: Option<&str>) -> String {
+    let mut fence = "```".to_string();
+    while text.contains(&fence) {
+        fence.push('`');
+    }
+    let block = format!("{}\n{}\n{}", fence, text.trim_end_matches('\n'), fence);
+    match prompt {
+        Some(prompt) => format!("{}\n\n{}", prompt, block),
+        None => block,
+    }
+}
+
+// Stack frames: Java/JS `at ...`, Python `File "..."`, Rust/Go numbered frames
+// and source locations.
+fn trace_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(
+            r#"^\s+(at\s+\S|File "[^"]+", line \d+)|^\s*\d+:\s+\S+::|^\s+\S+\.(go|rs|py|js|ts|java|rb):\d+"#,
+        )
+        .unwrap()
+    })
+}
+
+// Lines starting with a timestamp or a log level.
+fn log_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(
+            r"^\s*(\[?\d{4}-\d{2}-\d{2}[ T]\d{2}:\d{2}|\[?\d{2}:\d{2}:\d{2}|\[?(TRACE|DEBUG|INFO|WARN|WARNING|ERROR|FATAL|CRITICAL)\b)",
+        )
+        .unwrap()
+    })
+}
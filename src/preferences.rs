@@ -0,0 +1,287 @@
+use serde_json::{json, Value};
+
+// How a conversation's answers should look, added to the system prompt of every
+// request in it (stop sequences, the response prefix and the seed go as model
+// options).
+#[derive(Clone, Default)]
+pub struct Preferences {
+    // The conversation's own system prompt, set with `/system`.
+    pub system: Option<String>,
+    // A persona from the library, whose text goes before the other instructions.
+    pub persona: Option<String>,
+    pub language: Option<String>,
+    pub tone: Option<String>,
+    pub max_words: Option<usize>,
+    pub code_only: bool,
+    pub stop: Vec<String>,
+    // Text the answer is forced to start with, e.g. "```json".
+    pub prefix: Option<String>,
+    // For providers that can make generations reproducible.
+    pub seed: Option<u64>,
+    // Any other `-o` options (temperature, max_tokens, ...), in the order they were
+    // added; edited from the model pane rather than the popup.
+    pub options: Vec<(String, String)>,
+}
+
+// The rows of the settings popup, in order.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Field {
+    System,
+    Persona,
+    Language,
+    Tone,
+    MaxWords,
+    CodeOnly,
+    Stop,
+    Prefix,
+    Seed,
+}
+
+pub const FIELDS: [Field; 9] = [
+    Field::System,
+    Field::Persona,
+    Field::Language,
+    Field::Tone,
+    Field::MaxWords,
+    Field::CodeOnly,
+    Field::Stop,
+    Field::Prefix,
+    Field::Seed,
+];
+
+impl Field {
+    pub fn label(self) -> &'static str {
+        match self {
+            Field::System => "System prompt",
+            Field::Persona => "Persona",
+            Field::Language => "Language",
+            Field::Tone => "Tone",
+            Field::MaxWords => "Max length (words)",
+            Field::CodeOnly => "Code only",
+            Field::Stop => "Stop sequences",
+            Field::Prefix => "Response prefix",
+            Field::Seed => "Seed",
+        }
+    }
+}
+
+impl Preferences {
+    pub fn from_json(value: &Value) -> Self {
+        let text = |key: &str| {
+            value[key]
+                .as_str()
+                .filter(|text| !text.is_empty())
+                .map(str::to_string)
+        };
+        Preferences {
+            system: text("system"),
+            persona: text("persona"),
+            language: text("language"),
+            tone: text("tone"),
+            max_words: value["max_words"].as_u64().map(|words| words as usize),
+            code_only: value["code_only"].as_bool().unwrap_or(false),
+            stop: value["stop"]
+                .as_array()
+                .map(|stop| {
+                    stop.iter()
+                        .filter_map(Value::as_str)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            prefix: text("prefix"),
+            seed: value["seed"].as_u64(),
+            options: value["options"]
+                .as_array()
+                .map(|options| {
+                    options
+                        .iter()
+                        .filter_map(|pair| Some((pair[0].as_str()?, pair[1].as_str()?)))
+                        .map(|(name, value)| (name.to_string(), value.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "system": self.system,
+            "persona": self.persona,
+            "language": self.language,
+            "tone": self.tone,
+            "max_words": self.max_words,
+            "code_only": self.code_only,
+            "stop": self.stop,
+            "prefix": self.prefix,
+            "seed": self.seed,
+            "options": self.options,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.system.is_none()
+            && self.persona.is_none()
+            && self.language.is_none()
+            && self.tone.is_none()
+            && self.max_words.is_none()
+            && !self.code_only
+            && self.stop.is_empty()
+            && self.prefix.is_none()
+            && self.seed.is_none()
+            && self.options.is_empty()
+    }
+
+    pub fn value(&self, field: Field) -> String {
+        match field {
+            Field::System => self.system.clone().unwrap_or_default(),
+            Field::Persona => self.persona.clone().unwrap_or_default(),
+            Field::Language => self.language.clone().unwrap_or_default(),
+            Field::Tone => self.tone.clone().unwrap_or_default(),
+            Field::MaxWords => self.max_words.map(|w| w.to_string()).unwrap_or_default(),
+            Field::CodeOnly => if self.code_only { "yes" } else { "" }.to_string(),
+            Field::Stop => self.stop.join(", "),
+            Field::Prefix => self.prefix.clone().unwrap_or_default(),
+            Field::Seed => self.seed.map(|seed| seed.to_string()).unwrap_or_default(),
+        }
+    }
+
+    // Sets a field from text typed in the popup; empty text clears it.
+    pub fn set(&mut self, field: Field, text: &str) -> Result<(), String> {
+        let text = text.trim();
+        let optional = || Some(text.to_string()).filter(|text| !text.is_empty());
+        match field {
+            Field::System => self.system = optional(),
+            Field::Persona => self.persona = optional(),
+            Field::Language => self.language = optional(),
+            Field::Tone => self.tone = optional(),
+            Field::MaxWords if text.is_empty() => self.max_words = None,
+            Field::MaxWords => match text.parse() {
+                Ok(words) if words > 0 => self.max_words = Some(words),
+                _ => return Err(format!("Not a number of words: {}", text)),
+            },
+            Field::CodeOnly => self.code_only = !text.is_empty(),
+            Field::Stop => {
+                self.stop = text
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|stop| !stop.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            }
+            Field::Prefix => self.prefix = optional(),
+            Field::Seed if text.is_empty() => self.seed = None,
+            Field::Seed => match text.parse() {
+                Ok(seed) => self.seed = Some(seed),
+                Err(_) => return Err(format!("Not a seed: {}", text)),
+            },
+        }
+        Ok(())
+    }
+
+    // Sets a model option from `name value` or `name=value`, replacing `replacing`
+    // (the option being edited) if given; `name` alone removes it.
+    pub fn set_option(&mut self, replacing: Option<&str>, text: &str) -> Result<(), String> {
+        let text = text.trim();
+        let (name, value) = match text.split_once(|c: char| c == '=' || c.is_whitespace()) {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => (text, ""),
+        };
+        if name.is_empty() {
+            return Err("Expected an option name and value, e.g. temperature 0.7".to_string());
+        }
+        let position = self
+            .options
+            .iter()
+            .position(|(other, _)| Some(other.as_str()) == replacing || other == name);
+        self.options
+            .retain(|(other, _)| other != name && Some(other.as_str()) != replacing);
+        if !value.is_empty() {
+            let position = position
+                .unwrap_or(self.options.len())
+                .min(self.options.len());
+            self.options
+                .insert(position, (name.to_string(), value.to_string()));
+        }
+        Ok(())
+    }
+
+    // Short labels for the chat title, e.g. `French`, `formal`, `≤200w`, `code`, `stop`,
+    // `prefix`, `seed:42`; the system prompt and persona are shown on their own.
+    pub fn badges(&self) -> Vec<String> {
+        let mut badges: Vec<String> = self.language.iter().chain(&self.tone).cloned().collect();
+        if let Some(words) = self.max_words {
+            badges.push(format!("≤{}w", words));
+        }
+        if self.code_only {
+            badges.push("code".to_string());
+        }
+        if !self.stop.is_empty() {
+            badges.push("stop".to_string());
+        }
+        if self.prefix.is_some() {
+            badges.push("prefix".to_string());
+        }
+        if let Some(seed) = self.seed {
+            badges.push(format!("seed:{}", seed));
+        }
+        for (name, value) in &self.options {
+            badges.push(format!("{}={}", name, value));
+        }
+        badges
+    }
+
+    // The instructions appended to the system prompt.
+    pub fn instructions(&self) -> Option<String> {
+        let mut lines = Vec::new();
+        if let Some(language) = &self.language {
+            lines.push(format!("Always answer in {}.", language));
+        }
+        if let Some(tone) = &self.tone {
+            lines.push(format!("Use a {} tone.", tone));
+        }
+        if let Some(words) = self.max_words {
+            lines.push(format!("Keep answers under {} words.", words));
+        }
+        if self.code_only {
+            lines.push(
+                "Reply with code only, without explanations outside code comments.".to_string(),
+            );
+        }
+        Some(lines.join("\n")).filter(|lines| !lines.is_empty())
+    }
+
+    // The stop sequences, response prefix and seed as `-o` options with the given
+    // names, then the other options, which win over those with the same name.
+    // Several stop sequences are passed as a JSON list.
+    pub fn model_options(
+        &self,
+        stop_option: &str,
+        prefix_option: &str,
+        seed_option: &str,
+    ) -> Vec<(String, String)> {
+        let mut options = Vec::new();
+        match self.stop.as_slice() {
+            [] => {}
+            [stop] => options.push((stop_option.to_string(), stop.clone())),
+            stop => options.push((stop_option.to_string(), json!(stop).to_string())),
+        }
+        if let Some(prefix) = &self.prefix {
+            options.push((prefix_option.to_string(), prefix.clone()));
+        }
+        if let Some(seed) = self.seed {
+            options.push((seed_option.to_string(), seed.to_string()));
+        }
+        options.retain(|(name, _)| !self.options.iter().any(|(other, _)| other == name));
+        options.extend(self.options.iter().cloned());
+        options
+    }
+
+    // Adds the instructions to a system prompt, if there are any.
+    pub fn apply(&self, system: Option<String>) -> Option<String> {
+        match (system, self.instructions()) {
+            (Some(system), Some(instructions)) => Some(format!("{}\n\n{}", system, instructions)),
+            (system, instructions) => system.or(instructions),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{llm_command, store};
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+// Files in llm's directory that a new profile starts with a copy of.
+const SEEDED_FILES: [&str; 4] = [
+    "keys.json",
+    "aliases.json",
+    "default_model.txt",
+    "extra-openai-models.yaml",
+];
+
+// Names of the existing profiles, sorted.
+pub fn list() -> Vec<String> {
+    let mut names: Vec<String> = store::profiles_dir()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+// Switches this process, and every `llm` it runs, to the profile: llm-tui's state
+// moves to the profile's directory and llm logs to its own database through
+// `LLM_USER_PATH`. Creates the profile if it doesn't exist, seeded with llm's
+// settings. Must run before any threads are started.
+pub fn activate(name: &str) -> io::Result<()> {
+    if !is_valid_name(name) {
+        return Err(io::Error::other(format!("Invalid profile name: {}", name)));
+    }
+    let dir = store::profiles_dir()
+        .ok_or_else(|| io::Error::other("No home directory"))?
+        .join(name);
+    let llm_dir = dir.join("llm");
+    if !llm_dir.exists() {
+        fs::create_dir_all(&llm_dir)?;
+        seed(&llm_dir);
+    }
+    env::set_var(store::PROFILE_ENV, name);
+    env::set_var("LLM_USER_PATH", llm_dir);
+    Ok(())
+}
+
+// Copies llm's keys, aliases, default model and templates from its own directory
+// into a new profile's, so the profile can reach the same models. The logs stay
+// behind. Whatever can't be copied is left for the user to set up.
+fn seed(llm_dir: &Path) {
+    let Some(source) = default_llm_dir() else {
+        return;
+    };
+    for file in SEEDED_FILES {
+        let _ = fs::copy(source.join(file), llm_dir.join(file));
+    }
+    let Ok(templates) = fs::read_dir(source.join("templates")) else {
+        return;
+    };
+    if fs::create_dir_all(llm_dir.join("templates")).is_err() {
+        return;
+    }
+    for entry in templates.filter_map(Result::ok) {
+        if entry.path().is_file() {
+            let _ = fs::copy(
+                entry.path(),
+                llm_dir.join("templates").join(entry.file_name()),
+            );
+        }
+    }
+}
+
+// llm's directory before any profile is active, from where it keeps its keys.
+fn default_llm_dir() -> Option<PathBuf> {
+    let output = llm_command().args(["keys", "path"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let keys = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    keys.parent().map(Path::to_path_buf)
+}
+
+// Asks on the terminal which profile to use, before the TUI starts. Returns `None`
+// for the shared default state.
+pub fn choose(profiles: &[String]) -> io::Result<Option<String>> {
+    let mut stdout = io::stdout();
+    writeln!(stdout, "Profiles:")?;
+    for (i, name) in profiles.iter().enumerate() {
+        writeln!(stdout, "  {}. {}", i + 1, name)?;
+    }
+    loop {
+        write!(
+            stdout,
+            "Choose a profile (number or a new name, Enter for the default): "
+        )?;
+        stdout.flush()?;
+        let mut answer = String::new();
+        if io::stdin().lock().read_line(&mut answer)? == 0 {
+            return Ok(None);
+        }
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Ok(None);
+        }
+        if let Some(name) = answer
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| profiles.get(n.wrapping_sub(1)))
+        {
+            return Ok(Some(name.clone()));
+        }
+        if is_valid_name(answer) {
+            return Ok(Some(answer.to_string()));
+        }
+        writeln!(stdout, "Invalid profile name: {}", answer)?;
+    }
+}
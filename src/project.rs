@@ -0,0 +1,153 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const MAX_FILE_BYTES: u64 = 200 * 1024;
+const MAX_FILES: usize = 5000;
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "dist", "build", "__pycache__"];
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "with", "this", "that", "what", "how", "why", "can", "you", "does", "are",
+    "from", "into", "should", "would", "could", "please", "file", "code", "about",
+];
+
+struct IndexedFile {
+    path: PathBuf,
+    path_lower: String,
+    content_lower: String,
+}
+
+// An in-memory keyword index of the text files under a project root, used to pick
+// which files to attach as fragments to each prompt.
+pub struct ProjectIndex {
+    pub root: PathBuf,
+    files: Vec<IndexedFile>,
+}
+
+impl ProjectIndex {
+    pub fn build(root: &Path) -> io::Result<Self> {
+        let paths = match git_files(root) {
+            Some(paths) => paths,
+            None => {
+                let mut paths = Vec::new();
+                walk(root, root, &mut paths)?;
+                paths
+            }
+        };
+
+        let files = paths
+            .into_iter()
+            .take(MAX_FILES)
+            .filter_map(|relative| {
+                let full = root.join(&relative);
+                let metadata = fs::metadata(&full).ok()?;
+                if !metadata.is_file() || metadata.len() > MAX_FILE_BYTES {
+                    return None;
+                }
+                let bytes = fs::read(&full).ok()?;
+                if bytes.contains(&0) {
+                    return None;
+                }
+                Some(IndexedFile {
+                    path_lower: relative.to_string_lossy().to_lowercase(),
+                    content_lower: String::from_utf8_lossy(&bytes).to_lowercase(),
+                    path: relative,
+                })
+            })
+            .collect();
+
+        Ok(ProjectIndex {
+            root: root.to_path_buf(),
+            files,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    // Ranks files by how often the prompt's keywords appear in their path (weighted
+    // heavily) and contents, returning up to `k` paths relative to the root.
+    pub fn select(&self, prompt: &str, k: usize) -> Vec<PathBuf> {
+        let terms = keywords(prompt);
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut scored: Vec<(usize, &IndexedFile)> = self
+            .files
+            .iter()
+            .map(|file| {
+                let score = terms
+                    .iter()
+                    .map(|term| {
+                        file.path_lower.matches(term.as_str()).count() * 10
+                            + file.content_lower.matches(term.as_str()).count().min(20)
+                    })
+                    .sum();
+                (score, file)
+            })
+            .filter(|(score, _)| *score > 0)
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.path.cmp(&b.1.path)));
+        scored
+            .into_iter()
+            .take(k)
+            .map(|(_, file)| file.path.clone())
+            .collect()
+    }
+}
+
+pub fn keywords(prompt: &str) -> Vec<String> {
+    let mut terms: Vec<String> = prompt
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| word.chars().count() >= 3)
+        .map(str::to_lowercase)
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+        .collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+// Lists tracked and untracked-but-not-ignored files, so .gitignore is respected.
+fn git_files(root: &Path) -> Option<Vec<PathBuf>> {
+    let output = Command::new("git")
+        .args(["ls-files", "--cached", "--others", "--exclude-standard"])
+        .current_dir(root)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(PathBuf::from)
+            .collect(),
+    )
+}
+
+fn walk(root: &Path, dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        if paths.len() >= MAX_FILES {
+            break;
+        }
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_ref()) {
+            continue;
+        }
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk(root, &path, paths)?;
+        } else if file_type.is_file() {
+            if let Ok(relative) = path.strip_prefix(root) {
+                paths.push(relative.to_path_buf());
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use crate::sandbox::{self, Sandbox};
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Runs each cell (a JSON string per line on stdin) in one shared namespace and
+// answers with its output as a JSON string per line. Like a REPL, the value of a
+// trailing expression is printed; matplotlib figures are saved as PNG files and
+// reported by path.
+const DRIVER: &str = r#"import ast, contextlib, io, json, os, sys, traceback
+
+os.environ.setdefault("MPLBACKEND", "Agg")
+namespace = {"__name__": "__main__"}
+figure_dir = sys.argv[1]
+figures = 0
+
+
+def run(source):
+    global figures
+    out = io.StringIO()
+    with contextlib.redirect_stdout(out), contextlib.redirect_stderr(out):
+        try:
+            tree = ast.parse(source, "<cell>", "exec")
+            last = None
+            if tree.body and isinstance(tree.body[-1], ast.Expr):
+                last = tree.body.pop()
+            exec(compile(tree, "<cell>", "exec"), namespace)
+            if last is not None:
+                value = eval(compile(ast.Expression(last.value), "<cell>", "eval"), namespace)
+                if value is not None:
+                    print(repr(value))
+        except BaseException as e:
+            # Leave this driver's frame out of the traceback.
+            traceback.print_exception(type(e), e, e.__traceback__.tb_next)
+        plt = sys.modules.get("matplotlib.pyplot")
+        if plt is not None:
+            for number in plt.get_fignums():
+                figures += 1
+                path = os.path.join(figure_dir, "figure-%d.png" % figures)
+                try:
+                    plt.figure(number).savefig(path)
+                    print("[figure saved to %s]" % path)
+                except Exception as e:
+                    print("[figure %d not saved: %s]" % (figures, e))
+            plt.close("all")
+    return out.getvalue()
+
+
+for line in sys.stdin:
+    sys.stdout.write(json.dumps(run(json.loads(line))) + "\n")
+    sys.stdout.flush()
+"#;
+
+// A long-lived Python process whose variables carry over from one cell to the next.
+pub struct Session {
+    child: Child,
+    stdin: ChildStdin,
+    replies: Receiver<String>,
+    // Holds the driver, Python's stderr and saved figures; it's left in place so
+    // the figures outlive the session.
+    dir: PathBuf,
+}
+
+impl Session {
+    // `env` is the conversation's, as it is when the session starts.
+    pub fn start(
+        interpreter: &[String],
+        sandbox: Option<&Sandbox>,
+        env: &[(String, String)],
+    ) -> Result<Session, String> {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let dir = env::temp_dir().join(format!("llm-tui-python-{}-{}", std::process::id(), stamp));
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let driver = dir.join("driver.py");
+        fs::write(&driver, DRIVER).map_err(|e| e.to_string())?;
+
+        let mut command: Vec<String> = interpreter.to_vec();
+        if command.is_empty() {
+            return Err("No Python interpreter configured".to_string());
+        }
+        command.extend([
+            "-u".to_string(),
+            driver.to_string_lossy().into_owned(),
+            dir.to_string_lossy().into_owned(),
+        ]);
+        let argv = sandbox::wrap(sandbox, &command, &dir, env)?;
+        let stderr = fs::File::create(dir.join("stderr.txt")).map_err(|e| e.to_string())?;
+        let mut child = Command::new(&argv[0])
+            .args(&argv[1..])
+            .current_dir(&dir)
+            .envs(env.iter().map(|(name, value)| (name, value)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(stderr)
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", argv[0], e))?;
+        let stdin = child.stdin.take().ok_or("No stdin for Python")?;
+        let stdout = child.stdout.take().ok_or("No stdout for Python")?;
+
+        // Replies are read on their own thread so a cell can time out.
+        let (tx, replies) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Session {
+            child,
+            stdin,
+            replies,
+            dir,
+        })
+    }
+
+    // Runs one cell and returns its output. After an error the session is dead and
+    // should be dropped.
+    pub fn run(&mut self, code: &str, timeout: Duration) -> Result<String, String> {
+        let request = serde_json::to_string(code).map_err(|e| e.to_string())?;
+        writeln!(self.stdin, "{}", request)
+            .and_then(|()| self.stdin.flush())
+            .map_err(|_| self.exit_reason())?;
+        match self.replies.recv_timeout(timeout) {
+            Ok(reply) => serde_json::from_str(&reply).map_err(|e| e.to_string()),
+            Err(RecvTimeoutError::Timeout) => {
+                let _ = self.child.kill();
+                Err(format!(
+                    "Timed out after {}s; the session was stopped",
+                    timeout.as_secs()
+                ))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(self.exit_reason()),
+        }
+    }
+
+    // How the process ended, with the last line it wrote to stderr (e.g. a missing
+    // interpreter or module).
+    fn exit_reason(&mut self) -> String {
+        let status = match self.child.wait() {
+            Ok(status) => format!("Python exited ({})", status),
+            Err(e) => return e.to_string(),
+        };
+        let stderr = fs::read_to_string(self.dir.join("stderr.txt")).unwrap_or_default();
+        match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+            Some(line) => format!("{}: {}", status, line.trim()),
+            None => status,
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,323 @@
+use crossbeam_channel::Sender as CrossbeamSender;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+#[cfg(not(windows))]
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+pub const DEFAULT_PORT: u16 = 8080;
+// A TCP client that sends nothing for this long is disconnected.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+// Whether every address `address` resolves to is only reachable from this machine.
+pub fn is_loopback(address: &str) -> bool {
+    let Ok(resolved) = address.to_socket_addrs() else {
+        return false;
+    };
+    let mut any = false;
+    for resolved in resolved {
+        if !resolved.ip().is_loopback() {
+            return false;
+        }
+        any = true;
+    }
+    any
+}
+
+pub struct RemoteCommand {
+    pub prompt: String,
+    // The id or name of the conversation to send to; the open one if unset.
+    pub conversation: Option<String>,
+    // Set when the client waits for the model's response.
+    pub reply: Option<Sender<String>>,
+    // Told whether the app took the prompt, for TCP clients; the FIFO doesn't ask.
+    pub accepted: Option<Sender<Result<(), RemoteError>>>,
+}
+
+// Why a remote request failed: a stable `code` for scripts and a message for
+// people, sent as `{"error": message, "code": code}`.
+pub struct RemoteError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl RemoteError {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        RemoteError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({"error": self.message, "code": self.code})
+    }
+}
+
+// What the TCP server has done so far, for the status bar and `/server`.
+#[derive(Default)]
+pub struct ServerStats {
+    pub connections: AtomicUsize,
+    // Unix time of the last prompt a client sent; 0 before the first.
+    pub last_command: AtomicU64,
+    pub errors: AtomicUsize,
+    pub last_error: Mutex<Option<String>>,
+}
+
+impl ServerStats {
+    pub fn record_error(&self, error: String) {
+        self.errors.fetch_add(1, Ordering::SeqCst);
+        *self.last_error.lock().unwrap() = Some(error);
+    }
+}
+
+impl RemoteCommand {
+    fn fire_and_forget(prompt: String, conversation: Option<String>) -> Self {
+        RemoteCommand {
+            prompt,
+            conversation,
+            reply: None,
+            accepted: None,
+        }
+    }
+}
+
+// Reads prompts line by line from a named pipe, e.g. `echo "question" > ~/.llm-tui.fifo`.
+// The pipe is created if missing and reopened each time a writer closes it. Fails
+// when it can't be created or opened, or `path` is something else, such as a
+// regular file, whose lines would be sent over and over.
+#[cfg(not(windows))]
+pub fn listen_on_fifo(path: &Path, tx: CrossbeamSender<RemoteCommand>) -> Result<(), String> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if !path.exists() {
+        let created = Command::new("mkfifo")
+            .arg(path)
+            .status()
+            .is_ok_and(|status| status.success());
+        if !created {
+            return Err(format!("Couldn't create the pipe {}", path.display()));
+        }
+    }
+    let is_fifo = fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo());
+    if !is_fifo {
+        return Err(format!(
+            "{} isn't a named pipe; remote prompts won't be read from it",
+            path.display()
+        ));
+    }
+    loop {
+        let file = fs::File::open(path)
+            .map_err(|e| format!("Couldn't open the pipe {}: {}", path.display(), e))?;
+        if !forward_lines(file, &tx) {
+            return Ok(());
+        }
+    }
+}
+
+// Windows has no FIFOs in the file system; the pipe is `\\.\pipe\<file name>`
+// instead (or `path` itself when it already names one), so
+// `echo question > \\.\pipe\.llm-tui.fifo` works like the Unix version.
+#[cfg(windows)]
+pub fn listen_on_fifo(path: &Path, tx: CrossbeamSender<RemoteCommand>) -> Result<(), String> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::io::FromRawHandle;
+    use std::ptr;
+    use windows_sys::Win32::Foundation::{
+        GetLastError, ERROR_PIPE_CONNECTED, INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::Storage::FileSystem::PIPE_ACCESS_INBOUND;
+    use windows_sys::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+        PIPE_TYPE_BYTE, PIPE_WAIT,
+    };
+
+    let name: Vec<u16> = pipe_name(path)
+        .as_os_str()
+        .encode_wide()
+        .chain(Some(0))
+        .collect();
+    loop {
+        // SAFETY: `name` is NUL-terminated and outlives the call.
+        let handle = unsafe {
+            CreateNamedPipeW(
+                name.as_ptr(),
+                PIPE_ACCESS_INBOUND,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                1,
+                0,
+                4096,
+                0,
+                ptr::null(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(format!(
+                "Couldn't create the pipe {}",
+                pipe_name(path).display()
+            ));
+        }
+        // SAFETY: `handle` is a valid pipe we own; the `File` closes it when dropped.
+        let file = unsafe { fs::File::from_raw_handle(handle) };
+        // A writer that opened the pipe before `ConnectNamedPipe` counts as connected.
+        let connected = unsafe { ConnectNamedPipe(handle, ptr::null_mut()) } != 0
+            || unsafe { GetLastError() } == ERROR_PIPE_CONNECTED;
+        if !connected || !forward_lines(file, &tx) {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(windows)]
+fn pipe_name(path: &Path) -> std::path::PathBuf {
+    const PREFIX: &str = r"\\.\pipe\";
+    if path.to_string_lossy().starts_with(PREFIX) {
+        return path.to_path_buf();
+    }
+    let file_name = path
+        .file_name()
+        .map_or_else(|| "llm-tui".into(), |name| name.to_string_lossy());
+    format!("{}{}", PREFIX, file_name).into()
+}
+
+// Sends each non-empty line as a prompt until the writer closes its end. Returns
+// false once the app has stopped listening.
+fn forward_lines(reader: impl Read, tx: &CrossbeamSender<RemoteCommand>) -> bool {
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let line = line.trim();
+        if !line.is_empty()
+            && tx
+                .send(RemoteCommand::fire_and_forget(line.to_string(), None))
+                .is_err()
+        {
+            return false;
+        }
+    }
+    true
+}
+
+// Handles one TCP client, which may send any number of request lines and gets a
+// reply line for each, in order. A plain text line is sent as a prompt and
+// acknowledged; a JSON line (`{"prompt": "...", "wait": true, "conversation": "notes"}`)
+// may name the conversation to use and ask to wait for the response, which is
+// written back as `{"response": "..."}`. Failures are answered with a `RemoteError`
+// and passed to `report` for the app to show.
+pub fn handle_client(
+    stream: TcpStream,
+    tx: CrossbeamSender<RemoteCommand>,
+    stats: &ServerStats,
+    report: impl Fn(String),
+) {
+    stats.connections.fetch_add(1, Ordering::SeqCst);
+    let fail = |error: String| {
+        stats.record_error(error.clone());
+        report(error);
+    };
+    if let Err(e) = serve(stream, &tx, stats, &fail) {
+        fail(e);
+    }
+}
+
+// Answers lines until the client closes the connection or says nothing for
+// `IDLE_TIMEOUT`.
+fn serve(
+    stream: TcpStream,
+    tx: &CrossbeamSender<RemoteCommand>,
+    stats: &ServerStats,
+    fail: &impl Fn(String),
+) -> Result<(), String> {
+    let mut writer = stream
+        .try_clone()
+        .and_then(|writer| stream.set_read_timeout(Some(IDLE_TIMEOUT)).map(|_| writer))
+        .map_err(|e| format!("Couldn't set up a client connection: {}", e))?;
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return Ok(())
+            }
+            Err(e) => return Err(format!("Couldn't read from a client: {}", e)),
+        };
+        let reply = match respond(line.trim(), tx, stats) {
+            Ok(reply) => reply,
+            Err(error) => {
+                fail(error.message.clone());
+                error.to_json().to_string()
+            }
+        };
+        writeln!(writer, "{}", reply).map_err(|e| format!("Couldn't answer a client: {}", e))?;
+    }
+    Ok(())
+}
+
+// The reply to one request line.
+fn respond(
+    line: &str,
+    tx: &CrossbeamSender<RemoteCommand>,
+    stats: &ServerStats,
+) -> Result<String, RemoteError> {
+    if line.is_empty() {
+        return Err(RemoteError::new("empty_prompt", "The prompt is empty"));
+    }
+    if !line.starts_with('{') {
+        submit(tx, stats, line.to_string(), None, None)?;
+        return Ok("Command received and processed.".to_string());
+    }
+    let request = serde_json::from_str::<Value>(line)
+        .ok()
+        .filter(Value::is_object)
+        .ok_or_else(|| RemoteError::new("invalid_json", "The request isn't a JSON object"))?;
+    let prompt = match request["prompt"].as_str() {
+        Some(prompt) if prompt.trim().is_empty() => {
+            return Err(RemoteError::new("empty_prompt", "The prompt is empty"))
+        }
+        Some(prompt) => prompt.to_string(),
+        None => return Err(RemoteError::new("missing_prompt", "Missing 'prompt'")),
+    };
+    let conversation = request["conversation"].as_str().map(String::from);
+    if !request["wait"].as_bool().unwrap_or(false) {
+        submit(tx, stats, prompt, conversation, None)?;
+        return Ok(json!({"status": "queued"}).to_string());
+    }
+
+    let (reply_tx, reply_rx) = channel();
+    submit(tx, stats, prompt, conversation, Some(reply_tx))?;
+    match reply_rx.recv() {
+        Ok(response) => Ok(json!({"response": response}).to_string()),
+        Err(_) => Err(RemoteError::new("not_sent", "The prompt was not sent")),
+    }
+}
+
+// Hands the prompt to the app and waits for it to be taken (or queued for review).
+fn submit(
+    tx: &CrossbeamSender<RemoteCommand>,
+    stats: &ServerStats,
+    prompt: String,
+    conversation: Option<String>,
+    reply: Option<Sender<String>>,
+) -> Result<(), RemoteError> {
+    let (accepted_tx, accepted_rx) = channel();
+    tx.send(RemoteCommand {
+        prompt,
+        conversation,
+        reply,
+        accepted: Some(accepted_tx),
+    })
+    .map_err(|_| RemoteError::new("unavailable", "The app stopped taking remote prompts"))?;
+    stats
+        .last_command
+        .store(crate::unix_now(), Ordering::SeqCst);
+    accepted_rx
+        .recv()
+        .unwrap_or_else(|_| Err(RemoteError::new("not_sent", "The prompt was not sent")))
+}
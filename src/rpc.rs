@@ -0,0 +1,157 @@
+use crate::config::Config;
+use crate::{load_conversations, load_models, stream_llm, LlmRequest};
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+// An error response's `code`, `message` and, when there's more to it, `data`.
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl From<(i64, String)> for RpcError {
+    fn from((code, message): (i64, String)) -> Self {
+        RpcError {
+            code,
+            message,
+            data: None,
+        }
+    }
+}
+
+// `llm-tui --stdio`: a newline-delimited JSON-RPC 2.0 server on stdin/stdout for
+// editor plugins. `send_prompt` streams `response_chunk` notifications before
+// returning the full response as its result, or an error whose `data` holds what
+// was streamed when llm fails.
+//
+//   {"jsonrpc":"2.0","id":1,"method":"list_conversations"}
+//   {"jsonrpc":"2.0","id":2,"method":"send_prompt","params":{"prompt":"hi"}}
+pub fn serve_stdio() -> io::Result<()> {
+    let config = Config::load();
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Value = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                write_message(&error_response(Value::Null, PARSE_ERROR, &e.to_string()))?;
+                continue;
+            }
+        };
+        let id = request["id"].clone();
+        let Some(method) = request["method"].as_str() else {
+            write_message(&error_response(id, INVALID_REQUEST, "Missing method"))?;
+            continue;
+        };
+        let response = match handle(&config, method, &request["params"], &id) {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(error) => {
+                let mut response = error_response(id, error.code, &error.message);
+                if let Some(data) = error.data {
+                    response["error"]["data"] = data;
+                }
+                response
+            }
+        };
+        // Requests without an id are notifications and get no response.
+        if !request["id"].is_null() {
+            write_message(&response)?;
+        }
+    }
+    Ok(())
+}
+
+fn handle(config: &Config, method: &str, params: &Value, id: &Value) -> Result<Value, RpcError> {
+    match method {
+        "list_models" => Ok(load_models()
+            .map_err(|e| (INTERNAL_ERROR, e))?
+            .0
+            .iter()
+            .map(|m| json!({"alias": m.alias, "full_name": m.full_name}))
+            .collect()),
+        "list_conversations" => Ok(load_conversations()
+            .map_err(|e| (INTERNAL_ERROR, e))?
+            .iter()
+            .map(|c| json!({"id": c.id, "name": c.name, "messages": c.messages.len()}))
+            .collect()),
+        "get_conversation" => {
+            let conversation_id = params["id"]
+                .as_str()
+                .ok_or((INVALID_PARAMS, "Missing 'id'".to_string()))?;
+            let conversation = load_conversations()
+                .map_err(|e| (INTERNAL_ERROR, e))?
+                .into_iter()
+                .find(|c| c.id == conversation_id)
+                .ok_or((
+                    INVALID_PARAMS,
+                    format!("No conversation '{}'", conversation_id),
+                ))?;
+            Ok(json!({
+                "id": conversation.id,
+                "name": conversation.name,
+                "messages": conversation
+                    .messages
+                    .iter()
+                    .map(|m| json!({"role": m.role, "content": m.content}))
+                    .collect::<Vec<_>>(),
+            }))
+        }
+        "send_prompt" => {
+            let prompt = params["prompt"]
+                .as_str()
+                .ok_or((INVALID_PARAMS, "Missing 'prompt'".to_string()))?;
+            let model = params["model"]
+                .as_str()
+                .map(str::to_string)
+                .or_else(|| config.model.clone())
+                .or_else(|| {
+                    let (models, default) = load_models().ok()?;
+                    models.get(default.unwrap_or(0)).map(|m| m.alias.clone())
+                })
+                .ok_or((INVALID_PARAMS, "No model available".to_string()))?;
+            let request = LlmRequest {
+                system: params["system"]
+                    .as_str()
+                    .map(str::to_string)
+                    .or_else(|| config.system_prompt.clone()),
+                fragments: config.fragments.clone(),
+                conversation_id: params["conversation_id"].as_str().map(str::to_string),
+                ..LlmRequest::new(config.redact(prompt), model)
+            };
+            let response = stream_llm(&request, |chunk| {
+                let _ = write_message(&json!({
+                    "jsonrpc": "2.0",
+                    "method": "response_chunk",
+                    "params": {"id": id, "text": chunk},
+                }));
+            })
+            .map_err(|failure| RpcError {
+                code: INTERNAL_ERROR,
+                message: failure.error.trim().to_string(),
+                data: Some(json!({"response": failure.output})),
+            })?;
+            Ok(json!({"response": response}))
+        }
+        _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'", method)).into()),
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn write_message(message: &Value) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", message)?;
+    stdout.flush()
+}
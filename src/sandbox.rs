@@ -0,0 +1,210 @@
+use crate::code_blocks::CodeBlock;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_IMAGE: &str = "python:3-slim";
+pub const DEFAULT_TIMEOUT_SECONDS: u64 = 30;
+
+// Command templates for `[sandbox] preset`. `{command}` becomes the interpreter and
+// the snippet file, `{dir}` the directory holding the snippet and `{image}` the
+// docker image.
+const PRESETS: &[(&str, &str)] = &[
+    (
+        "firejail",
+        "firejail --quiet --noprofile --net=none --private --whitelist={dir} {command}",
+    ),
+    (
+        "bubblewrap",
+        "bwrap --ro-bind / / --dev /dev --proc /proc --tmpfs /tmp --bind {dir} {dir} \
+         --unshare-all --die-with-parent {command}",
+    ),
+    (
+        "docker",
+        "docker run --rm --interactive --network none --volume {dir}:{dir} --workdir {dir} \
+         {image} {command}",
+    ),
+];
+
+// What code run from the chat is wrapped in.
+#[derive(Clone)]
+pub struct Sandbox {
+    pub name: String,
+    // Empty for an unknown preset, which refuses to run rather than run unsandboxed.
+    template: String,
+}
+
+impl Sandbox {
+    pub fn preset(name: &str, image: &str) -> Self {
+        let template = PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .map_or_else(String::new, |(_, template)| {
+                template.replace("{image}", image)
+            });
+        Sandbox {
+            name: name.to_string(),
+            template,
+        }
+    }
+
+    // A template of its own; the first word names it.
+    pub fn custom(template: &str) -> Self {
+        Sandbox {
+            name: template
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            template: template.to_string(),
+        }
+    }
+}
+
+// A code block about to be run: written to its own directory and started there.
+pub struct Run {
+    pub language: String,
+    pub code: String,
+    pub argv: Vec<String>,
+    pub sandbox: Option<String>,
+    env: Vec<(String, String)>,
+    dir: PathBuf,
+    file: PathBuf,
+}
+
+impl Run {
+    pub fn prepare(
+        block: &CodeBlock,
+        sandbox: Option<&Sandbox>,
+        env: Vec<(String, String)>,
+    ) -> Result<Run, String> {
+        let language = block
+            .language()
+            .ok_or_else(|| "Can't tell what language this code is".to_string())?
+            .to_lowercase();
+        let (interpreter, extension) = interpreter(&language)
+            .ok_or_else(|| format!("Don't know how to run {} code", language))?;
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let dir = env::temp_dir().join(format!("llm-tui-run-{}-{}", std::process::id(), stamp));
+        let file = dir.join(format!("snippet.{}", extension));
+
+        let command = [interpreter.to_string(), file.to_string_lossy().into_owned()];
+        let argv = wrap(sandbox, &command, &dir, &env)?;
+
+        Ok(Run {
+            language,
+            code: block.lines.join("\n"),
+            argv,
+            sandbox: sandbox.map(|sandbox| sandbox.name.clone()),
+            env,
+            dir,
+            file,
+        })
+    }
+
+    // Runs the snippet, killing it after `timeout`, and returns how it ended along
+    // with its combined stdout and stderr.
+    pub fn execute(self, timeout: Duration) -> Result<(String, String), String> {
+        let result = self.execute_in_dir(timeout);
+        let _ = fs::remove_dir_all(&self.dir);
+        result
+    }
+
+    fn execute_in_dir(&self, timeout: Duration) -> Result<(String, String), String> {
+        fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        fs::write(&self.file, format!("{}\n", self.code)).map_err(|e| e.to_string())?;
+        // Output goes to a file so a chatty snippet can't fill a pipe and stall.
+        let output_path = self.dir.join("output.txt");
+        let output = fs::File::create(&output_path).map_err(|e| e.to_string())?;
+        let errors = output.try_clone().map_err(|e| e.to_string())?;
+
+        let mut child = Command::new(&self.argv[0])
+            .args(&self.argv[1..])
+            .current_dir(&self.dir)
+            .envs(self.env.iter().map(|(name, value)| (name, value)))
+            .stdin(Stdio::null())
+            .stdout(output)
+            .stderr(errors)
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", self.argv[0], e))?;
+
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+                break match status.code() {
+                    Some(code) => format!("exit {}", code),
+                    None => "killed".to_string(),
+                };
+            }
+            if started.elapsed() >= timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                break format!("timed out after {}s", timeout.as_secs());
+            }
+            thread::sleep(Duration::from_millis(50));
+        };
+        let output = fs::read(&output_path).map_err(|e| e.to_string())?;
+        Ok((status, String::from_utf8_lossy(&output).into_owned()))
+    }
+}
+
+// The command line that runs `command` in `sandbox`, with `dir` as its working
+// directory (the only one it may write to). `env` is set on the process started;
+// docker only hands it on to the container when named with `-e`.
+pub fn wrap(
+    sandbox: Option<&Sandbox>,
+    command: &[String],
+    dir: &Path,
+    env: &[(String, String)],
+) -> Result<Vec<String>, String> {
+    let Some(sandbox) = sandbox else {
+        return Ok(command.to_vec());
+    };
+    if sandbox.template.trim().is_empty() {
+        return Err(format!("Unknown sandbox preset '{}'", sandbox.name));
+    }
+    let dir = dir.to_string_lossy();
+    let mut argv = Vec::new();
+    for word in sandbox.template.split_whitespace() {
+        if word == "{command}" {
+            argv.extend(command.iter().cloned());
+        } else {
+            argv.push(word.replace("{dir}", &dir));
+        }
+        if sandbox.name == "docker" && argv == ["docker", "run"] {
+            for (name, _) in env {
+                argv.extend(["-e".to_string(), name.clone()]);
+            }
+        }
+    }
+    if !sandbox.template.contains("{command}") {
+        argv.extend(command.iter().cloned());
+    }
+    Ok(argv)
+}
+
+// The program and file extension for a fence's language tag.
+fn interpreter(language: &str) -> Option<(&'static str, &'static str)> {
+    let python = if cfg!(windows) { "python" } else { "python3" };
+    match language {
+        "sh" | "shell" => Some(("sh", "sh")),
+        "bash" => Some(("bash", "sh")),
+        "zsh" => Some(("zsh", "zsh")),
+        "python" | "python3" | "py" => Some((python, "py")),
+        "javascript" | "js" | "node" => Some(("node", "js")),
+        "ruby" | "rb" => Some(("ruby", "rb")),
+        "perl" | "pl" => Some(("perl", "pl")),
+        _ => None,
+    }
+}
+
+pub fn runnable(block: &CodeBlock) -> bool {
+    block
+        .language()
+        .is_some_and(|language| interpreter(&language.to_lowercase()).is_some())
+}
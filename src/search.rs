@@ -0,0 +1,112 @@
+use regex::Regex;
+use std::ops::Range;
+
+// Counts the matches of `pattern` in `text`, as a literal string or a regex.
+pub fn count_matches(text: &str, pattern: &str, regex: bool) -> Result<usize, String> {
+    if pattern.is_empty() {
+        return Ok(0);
+    }
+    if regex {
+        Ok(compile(pattern)?.find_iter(text).count())
+    } else {
+        Ok(text.matches(pattern).count())
+    }
+}
+
+// Replaces every match, returning the new text and how many were replaced. In regex
+// mode the replacement can refer to groups as `$1` or `${name}`.
+pub fn replace_all(
+    text: &str,
+    pattern: &str,
+    replacement: &str,
+    regex: bool,
+) -> Result<(String, usize), String> {
+    let count = count_matches(text, pattern, regex)?;
+    if count == 0 {
+        return Ok((text.to_string(), 0));
+    }
+    let replaced = if regex {
+        compile(pattern)?
+            .replace_all(text, replacement)
+            .into_owned()
+    } else {
+        text.replace(pattern, replacement)
+    };
+    Ok((replaced, count))
+}
+
+// A one-line excerpt of about `width` characters around the first word of `query`,
+// when `text` contains all its words (ignoring case), with where that word is in it.
+pub fn excerpt(text: &str, query: &str, width: usize) -> Option<(String, Range<usize>)> {
+    let terms: Vec<Vec<char>> = query
+        .split_whitespace()
+        .map(|term| term.chars().map(fold).collect())
+        .collect();
+    if terms.is_empty() {
+        return None;
+    }
+    let chars: Vec<char> = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .collect();
+    let folded: Vec<char> = chars.iter().map(|&c| fold(c)).collect();
+    let mut first = None;
+    for term in &terms {
+        let at = folded
+            .windows(term.len())
+            .position(|window| window == term.as_slice())?;
+        first.get_or_insert((at, term.len()));
+    }
+    let (at, len) = first?;
+    let start = at.saturating_sub(width / 3);
+    let end = (start + width).min(chars.len()).max(at + len);
+    let mut excerpt = String::new();
+    if start > 0 {
+        excerpt.push('…');
+    }
+    let before: String = chars[start..at].iter().collect();
+    excerpt.push_str(&before);
+    let matched_start = excerpt.len();
+    excerpt.extend(&chars[at..at + len]);
+    let matched = matched_start..excerpt.len();
+    excerpt.extend(&chars[at + len..end]);
+    if end < chars.len() {
+        excerpt.push('…');
+    }
+    Some((excerpt, matched))
+}
+
+// Where `query` occurs in `text`, ignoring case, as byte ranges.
+pub fn find_ignoring_case(text: &str, query: &str) -> Vec<Range<usize>> {
+    let query: Vec<char> = query.chars().map(fold).collect();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i + query.len() <= chars.len() {
+        let window = &chars[i..i + query.len()];
+        if window.iter().zip(&query).all(|(&(_, c), &q)| fold(c) == q) {
+            let end = chars
+                .get(i + query.len())
+                .map_or(text.len(), |&(byte, _)| byte);
+            found.push(chars[i].0..end);
+            i += query.len();
+        } else {
+            i += 1;
+        }
+    }
+    found
+}
+
+// One lowercase character for each character, so positions line up.
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn compile(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("Invalid regex: {}", e))
+}
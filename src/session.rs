@@ -0,0 +1,108 @@
+use crate::format_count;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct Usage {
+    prompts: usize,
+    input_tokens: usize,
+    output_tokens: usize,
+}
+
+// What this run sent and got back, for the summary printed on quit. Tokens are
+// counted locally, so they're estimates unless the model has a tokenizer.
+pub struct SessionStats {
+    started: Instant,
+    // By model alias.
+    usage: BTreeMap<String, Usage>,
+    // Names of the conversations prompts were sent in.
+    conversations: BTreeSet<String>,
+}
+
+impl SessionStats {
+    pub fn new() -> Self {
+        SessionStats {
+            started: Instant::now(),
+            usage: BTreeMap::new(),
+            conversations: BTreeSet::new(),
+        }
+    }
+
+    // A prompt and the context sent with it, `tokens` in all.
+    pub fn record_prompt(&mut self, conversation: &str, model: &str, tokens: usize) {
+        let usage = self.usage.entry(model.to_string()).or_default();
+        usage.prompts += 1;
+        usage.input_tokens += tokens;
+        self.conversations.insert(conversation.to_string());
+    }
+
+    pub fn record_response(&mut self, model: &str, tokens: usize) {
+        self.usage
+            .entry(model.to_string())
+            .or_default()
+            .output_tokens += tokens;
+    }
+
+    // `price` gives a model's USD per million input and output tokens, if known.
+    pub fn summary(&self, price: impl Fn(&str) -> Option<(f64, f64)>) -> Vec<String> {
+        let elapsed = format_duration(self.started.elapsed());
+        let prompts: usize = self.usage.values().map(|u| u.prompts).sum();
+        if prompts == 0 {
+            return vec![format!("llm-tui session: {}, no prompts sent", elapsed)];
+        }
+        let mut lines = vec![format!(
+            "llm-tui session: {}, {} prompt(s) in {} conversation(s)",
+            elapsed,
+            prompts,
+            self.conversations.len()
+        )];
+        let width = self
+            .usage
+            .keys()
+            .map(|m| m.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut cost = 0.0;
+        let mut unpriced = 0;
+        for (model, usage) in &self.usage {
+            let mut line = format!(
+                "  {:<width$}  {:>3} prompt(s)  ~{} in  ~{} out",
+                model,
+                usage.prompts,
+                format_count(usage.input_tokens),
+                format_count(usage.output_tokens),
+                width = width
+            );
+            match price(model) {
+                Some((input, output)) => {
+                    let model_cost = (usage.input_tokens as f64 * input
+                        + usage.output_tokens as f64 * output)
+                        / 1_000_000.0;
+                    cost += model_cost;
+                    line.push_str(&format!("  ${:.4}", model_cost));
+                }
+                None => unpriced += 1,
+            }
+            lines.push(line);
+        }
+        if unpriced < self.usage.len() {
+            let mut total = format!("  Estimated cost: ${:.4}", cost);
+            if unpriced > 0 {
+                total.push_str(&format!(" (without {} unpriced model(s))", unpriced));
+            }
+            lines.push(total);
+        }
+        let names: Vec<&str> = self.conversations.iter().map(String::as_str).collect();
+        lines.push(format!("  Conversations: {}", names.join(", ")));
+        lines
+    }
+}
+
+fn format_duration(elapsed: Duration) -> String {
+    let minutes = elapsed.as_secs() / 60;
+    match minutes {
+        0 => format!("{}s", elapsed.as_secs()),
+        1..60 => format!("{}m", minutes),
+        _ => format!("{}h {}m", minutes / 60, minutes % 60),
+    }
+}
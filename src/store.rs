@@ -0,0 +1,59 @@
+use crate::config;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+// Selects the profile whose state `data_dir` points to.
+pub const PROFILE_ENV: &str = "LLM_TUI_PROFILE";
+
+// Local state that llm's own logs don't track lives as JSON files here, in a
+// directory of its own for each profile.
+pub fn data_dir() -> Option<PathBuf> {
+    match env::var(PROFILE_ENV).ok().filter(|name| !name.is_empty()) {
+        Some(name) => profiles_dir().map(|dir| dir.join(name)),
+        None => base_dir(),
+    }
+}
+
+pub fn profiles_dir() -> Option<PathBuf> {
+    base_dir().map(|dir| dir.join("profiles"))
+}
+
+// The data directory shared by all profiles.
+pub fn base_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("llm-tui"));
+    }
+    if cfg!(windows) {
+        if let Some(dir) = env::var_os("LOCALAPPDATA").filter(|d| !d.is_empty()) {
+            return Some(PathBuf::from(dir).join("llm-tui"));
+        }
+    }
+    config::home_dir().map(|home| home.join(".local").join("share").join("llm-tui"))
+}
+
+pub fn load(name: &str) -> Value {
+    data_dir()
+        .and_then(|dir| fs::read(dir.join(name)).ok())
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .unwrap_or(Value::Null)
+}
+
+pub fn save(name: &str, value: &Value) -> io::Result<()> {
+    let dir = data_dir().ok_or_else(|| io::Error::other("No home directory"))?;
+    fs::create_dir_all(&dir)?;
+    let contents = serde_json::to_vec_pretty(value).map_err(io::Error::other)?;
+    fs::write(dir.join(name), contents)
+}
+
+pub fn remove(name: &str) -> io::Result<()> {
+    let Some(dir) = data_dir() else {
+        return Ok(());
+    };
+    match fs::remove_file(dir.join(name)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,30 @@
+const MAX_SUGGESTIONS: usize = 3;
+// Only the end of long answers is sent, which is enough to suggest what's next.
+const MAX_ANSWER_CHARS: usize = 4000;
+
+pub fn build_prompt(question: &str, answer: &str) -> String {
+    let skip = answer.chars().count().saturating_sub(MAX_ANSWER_CHARS);
+    let answer: String = answer.chars().skip(skip).collect();
+    format!(
+        "Suggest {} short follow-up questions the user might ask next, one per line, \
+         with no numbering or other text.\n\nQuestion:\n{}\n\nAnswer:\n{}",
+        MAX_SUGGESTIONS, question, answer
+    )
+}
+
+// One suggestion per line, without any list markers the model added anyway.
+pub fn parse(response: &str) -> Vec<String> {
+    response
+        .lines()
+        .map(|line| {
+            line.trim()
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .trim_start_matches(['.', ')', '-', '*', '•'])
+                .trim()
+                .trim_matches('"')
+                .to_string()
+        })
+        .filter(|line| !line.is_empty())
+        .take(MAX_SUGGESTIONS)
+        .collect()
+}
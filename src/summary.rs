@@ -0,0 +1,37 @@
+const MAX_TAGS: usize = 3;
+
+// Asks for the summary followed by a line of topic tags, which `parse` splits off.
+pub fn build_prompt(instructions: &str, transcript: &str) -> String {
+    format!(
+        "{}\n\nThen, on a last line, give 1 to {} short topic tags as `Tags: tag-one, tag-two`.\n\n{}",
+        instructions, MAX_TAGS, transcript
+    )
+}
+
+// Splits a response into the summary and its tags, normalised to lowercase words
+// joined by dashes.
+pub fn parse(response: &str) -> (String, Vec<String>) {
+    let response = response.trim();
+    let Some((summary, line)) = response
+        .rsplit_once('\n')
+        .filter(|(_, line)| line.trim().to_lowercase().starts_with("tags:"))
+    else {
+        return (response.to_string(), Vec::new());
+    };
+    let tags = line.trim()[5..]
+        .split(',')
+        .map(normalize_tag)
+        .filter(|tag| !tag.is_empty())
+        .take(MAX_TAGS)
+        .collect();
+    (summary.trim().to_string(), tags)
+}
+
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim()
+        .trim_matches(|c: char| c == '#' || c == '`' || c == '*')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
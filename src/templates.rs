@@ -0,0 +1,153 @@
+use crate::llm_output;
+use serde_json::Value;
+
+// What `llm -t` is asked to run: the template, values for its variables and the
+// text that stands for `$input`.
+pub struct TemplateCall {
+    pub name: String,
+    pub params: Vec<(String, String)>,
+    pub input: String,
+}
+
+// An llm template as `llm templates show` prints it.
+pub struct Template {
+    pub name: String,
+    pub prompt: Option<String>,
+    pub system: Option<String>,
+    pub defaults: Vec<(String, String)>,
+}
+
+// Names and one-line summaries from `llm templates list`.
+pub fn list() -> Result<Vec<(String, String)>, String> {
+    let output = llm_output(&["templates", "list"])?;
+    Ok(String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| {
+            let (name, summary) = line.split_once(" : ").unwrap_or((line, ""));
+            let name = name.trim();
+            (!name.is_empty()).then(|| (name.to_string(), summary.trim().to_string()))
+        })
+        .collect())
+}
+
+pub fn load(name: &str) -> Result<Template, String> {
+    let output = llm_output(&["templates", "show", name])?;
+    let value: Value = serde_yaml::from_slice(&output)
+        .map_err(|e| format!("Template '{}' isn't valid YAML: {}", name, e))?;
+    let text = |key: &str| value[key].as_str().map(str::to_string);
+    let defaults = value["defaults"]
+        .as_object()
+        .map(|defaults| {
+            defaults
+                .iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    };
+                    (key.clone(), value)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(Template {
+        name: name.to_string(),
+        prompt: text("prompt"),
+        system: text("system"),
+        defaults,
+    })
+}
+
+impl Template {
+    // The `$name` and `${name}` variables of the prompt and system prompt, in order,
+    // except `$input`.
+    pub fn variables(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for text in self.prompt.iter().chain(&self.system) {
+            interpolate(text, |name| {
+                if name != "input" && !names.iter().any(|known| known == name) {
+                    names.push(name.to_string());
+                }
+                None
+            });
+        }
+        names
+    }
+
+    // Whether the prompt has an `$input`, or there's no prompt and the input is it.
+    pub fn takes_input(&self) -> bool {
+        let Some(prompt) = &self.prompt else {
+            return true;
+        };
+        let mut found = false;
+        interpolate(prompt, |name| {
+            found |= name == "input";
+            None
+        });
+        found
+    }
+
+    pub fn default_value(&self, variable: &str) -> Option<&str> {
+        self.defaults
+            .iter()
+            .find(|(name, _)| name == variable)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // The prompt llm will send for `call`, to show in the conversation. Without a
+    // prompt in the template the input is sent as it is.
+    pub fn expand(&self, call: &TemplateCall) -> String {
+        let Some(prompt) = &self.prompt else {
+            return call.input.clone();
+        };
+        interpolate(prompt, |name| {
+            if name == "input" {
+                return Some(call.input.clone());
+            }
+            call.params
+                .iter()
+                .find(|(param, _)| param == name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| self.default_value(name).map(str::to_string))
+        })
+    }
+}
+
+// Python's `string.Template` rules, which llm uses: `$name` or `${name}`, with `$$`
+// for a dollar sign. Variables `value` doesn't know are left as they are.
+fn interpolate(text: &str, mut value: impl FnMut(&str) -> Option<String>) -> String {
+    let mut output = String::new();
+    let mut rest = text;
+    while let Some(dollar) = rest.find('$') {
+        output.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+        if let Some(after) = after.strip_prefix('$') {
+            output.push('$');
+            rest = after;
+            continue;
+        }
+        let (name, consumed) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        let valid = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+        match valid.then(|| value(name)).flatten() {
+            Some(replacement) => output.push_str(&replacement),
+            None => output.push_str(&rest[dollar..dollar + 1 + consumed]),
+        }
+        rest = &after[consumed..];
+    }
+    output.push_str(rest);
+    output
+}
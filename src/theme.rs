@@ -0,0 +1,139 @@
+use ratatui::style::{Color, Modifier, Style};
+use serde_json::Value;
+use std::str::FromStr;
+
+const DEFAULT_USER_LABEL: &str = "You";
+const DEFAULT_ASSISTANT_LABEL: &str = "AI";
+
+// Styles for what things are rather than how they look, set with `[theme]`.
+#[derive(Clone)]
+pub struct Theme {
+    pub user: Style,
+    pub assistant: Style,
+    // Borders of the focused pane and popups, and the input while editing.
+    pub focus: Style,
+    // Key hints in the status bar.
+    pub status: Style,
+    pub positive: Style,
+    pub negative: Style,
+    // What messages are prefixed with, e.g. `You` and `AI`; `{model}` in the
+    // assistant's stands for the model alias. Empty leaves the prefix out.
+    pub user_label: String,
+    pub assistant_label: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    fn dark() -> Self {
+        Theme {
+            user_label: DEFAULT_USER_LABEL.to_string(),
+            assistant_label: DEFAULT_ASSISTANT_LABEL.to_string(),
+            user: Style::default().fg(Color::Green),
+            assistant: Style::default().fg(Color::Blue),
+            focus: Style::default().fg(Color::Yellow),
+            status: Style::default().fg(Color::Cyan),
+            positive: Style::default().fg(Color::Green),
+            negative: Style::default().fg(Color::Red),
+        }
+    }
+
+    // Darker colors that stay readable on a white background.
+    fn light() -> Self {
+        Theme {
+            user: Style::default().fg(Color::Rgb(0, 110, 0)),
+            assistant: Style::default().fg(Color::Rgb(0, 70, 170)),
+            focus: Style::default()
+                .fg(Color::Rgb(150, 0, 150))
+                .add_modifier(Modifier::BOLD),
+            status: Style::default().fg(Color::Rgb(0, 100, 120)),
+            positive: Style::default().fg(Color::Rgb(0, 110, 0)),
+            negative: Style::default().fg(Color::Rgb(180, 0, 0)),
+            ..Theme::dark()
+        }
+    }
+
+    // Solarized's accent colors, which work on its dark and light backgrounds alike.
+    fn solarized() -> Self {
+        Theme {
+            user: Style::default().fg(Color::Rgb(0x85, 0x99, 0x00)),
+            assistant: Style::default().fg(Color::Rgb(0x26, 0x8b, 0xd2)),
+            focus: Style::default().fg(Color::Rgb(0xb5, 0x89, 0x00)),
+            status: Style::default().fg(Color::Rgb(0x2a, 0xa1, 0x98)),
+            positive: Style::default().fg(Color::Rgb(0x85, 0x99, 0x00)),
+            negative: Style::default().fg(Color::Rgb(0xdc, 0x32, 0x2f)),
+            ..Theme::dark()
+        }
+    }
+
+    // Starts from the built-in theme `name` (when given and known), then takes the
+    // roles the table sets, e.g. `user = "magenta bold"` or `focus = "#ff8800"`.
+    pub fn apply(&mut self, value: &Value) {
+        match value["name"].as_str() {
+            Some("dark") => *self = Theme::dark(),
+            Some("light") => *self = Theme::light(),
+            Some("solarized") => *self = Theme::solarized(),
+            _ => {}
+        }
+        if let Some(label) = value["user_label"].as_str() {
+            self.user_label = label.to_string();
+        }
+        if let Some(label) = value["assistant_label"].as_str() {
+            self.assistant_label = label.to_string();
+        }
+        for (role, style) in [
+            ("user", &mut self.user),
+            ("assistant", &mut self.assistant),
+            ("focus", &mut self.focus),
+            ("status", &mut self.status),
+            ("positive", &mut self.positive),
+            ("negative", &mut self.negative),
+        ] {
+            if let Some(parsed) = value[role].as_str().and_then(parse_style) {
+                *style = parsed;
+            }
+        }
+    }
+
+    // The label for a message from `role`, answered by `model` if known.
+    pub fn label(&self, role: &str, model: Option<&str>) -> String {
+        match role {
+            "user" => self.user_label.clone(),
+            "assistant" => self
+                .assistant_label
+                .replace("{model}", model.unwrap_or(DEFAULT_ASSISTANT_LABEL)),
+            _ => String::new(),
+        }
+    }
+
+    // The label followed by `: `, or nothing.
+    pub fn prefix(&self, role: &str, model: Option<&str>) -> String {
+        let label = self.label(role, model);
+        if label.is_empty() {
+            label
+        } else {
+            format!("{}: ", label)
+        }
+    }
+}
+
+// A color (a name such as `lightblue`, `#rrggbb` or a 0-255 index) followed by
+// any of `bold`, `italic`, `underlined`, `dim` and `reversed`.
+fn parse_style(text: &str) -> Option<Style> {
+    let mut style = Style::default();
+    for word in text.split_whitespace() {
+        style = match word.to_lowercase().as_str() {
+            "bold" => style.add_modifier(Modifier::BOLD),
+            "italic" => style.add_modifier(Modifier::ITALIC),
+            "underlined" => style.add_modifier(Modifier::UNDERLINED),
+            "dim" => style.add_modifier(Modifier::DIM),
+            "reversed" => style.add_modifier(Modifier::REVERSED),
+            color => style.fg(Color::from_str(color).ok()?),
+        };
+    }
+    Some(style)
+}
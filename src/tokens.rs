@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+// Counts tokens for a model. Without the `tokenizer` feature (or for models it has
+// no tokenizer for) counts are estimated at ~4 characters per token.
+#[derive(Clone)]
+pub enum TokenCounter {
+    Estimate,
+    #[cfg(feature = "tokenizer")]
+    Tiktoken(std::sync::Arc<tiktoken_rs::CoreBPE>),
+    #[cfg(feature = "tokenizer")]
+    HuggingFace(std::sync::Arc<tokenizers::Tokenizer>),
+}
+
+impl TokenCounter {
+    // `tokenizers` maps model name substrings to HuggingFace `tokenizer.json` files,
+    // from the `[tokenizers]` config table.
+    pub fn for_model(model: &str, tokenizers: &[(String, PathBuf)]) -> TokenCounter {
+        #[cfg(feature = "tokenizer")]
+        if let Some(counter) = exact::counter(model, tokenizers) {
+            return counter;
+        }
+        #[cfg(not(feature = "tokenizer"))]
+        let _ = (model, tokenizers);
+        TokenCounter::Estimate
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            TokenCounter::Estimate => estimate(text),
+            #[cfg(feature = "tokenizer")]
+            TokenCounter::Tiktoken(bpe) => bpe.encode_with_special_tokens(text).len(),
+            #[cfg(feature = "tokenizer")]
+            TokenCounter::HuggingFace(tokenizer) => tokenizer
+                .encode(text, false)
+                .map(|encoding| encoding.len())
+                .unwrap_or_else(|_| estimate(text)),
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        !matches!(self, TokenCounter::Estimate)
+    }
+}
+
+pub fn estimate(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+#[cfg(feature = "tokenizer")]
+mod exact {
+    use super::TokenCounter;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex, OnceLock};
+
+    // Loading a tokenizer takes a while, so each model's counter is built once.
+    static COUNTERS: OnceLock<Mutex<HashMap<String, Option<TokenCounter>>>> = OnceLock::new();
+
+    pub fn counter(model: &str, tokenizers: &[(String, PathBuf)]) -> Option<TokenCounter> {
+        let mut counters = COUNTERS.get_or_init(Default::default).lock().ok()?;
+        counters
+            .entry(model.to_string())
+            .or_insert_with(|| load(model, tokenizers))
+            .clone()
+    }
+
+    fn load(model: &str, tokenizers: &[(String, PathBuf)]) -> Option<TokenCounter> {
+        let lower = model.to_lowercase();
+        let configured = tokenizers
+            .iter()
+            .find(|(name, _)| lower.contains(&name.to_lowercase()))
+            .and_then(|(_, path)| tokenizers::Tokenizer::from_file(path).ok());
+        if let Some(tokenizer) = configured {
+            return Some(TokenCounter::HuggingFace(Arc::new(tokenizer)));
+        }
+        tiktoken_rs::get_bpe_from_model(model)
+            .ok()
+            .map(|bpe| TokenCounter::Tiktoken(Arc::new(bpe)))
+    }
+}